@@ -1,16 +1,40 @@
 use crate::curse;
 use crate::lockfile::AddonInfo;
+use chrono::{DateTime, Duration, Utc};
 use getset::{Getters, Setters};
+use std::collections::BTreeMap;
 
-#[derive(PartialEq, Getters, Setters)]
+#[derive(Clone, PartialEq, Getters, Setters)]
 #[getset(get = "pub", set = "pub")]
 pub struct Addon {
     name: String,
     addon_type: AddonType,
     addon_id: String,
+    /// Curse project slug, used in `curse:<slug>` identifiers
+    slug: Option<String>,
     /// Internal string used to check for updates
     version: String,
     dirs: Vec<String>,
+    /// Freeform notes from the user
+    notes: Option<String>,
+    /// How often to check for updates
+    frequency: UpdateFrequency,
+    /// When updates were last checked for, only kept for addons not checked every run
+    last_checked: Option<DateTime<Utc>>,
+    /// Updates that failed in a row since the last successful one
+    failures: Option<UpdateFailures>,
+    /// Dir the addon is installed in instead of `AddOns`, relative to the WoW dir,
+    /// e.g. `Interface/Sounds`
+    install_root: Option<String>,
+    /// Fingerprint of each dir when grunt last installed it, to notice local edits
+    fingerprints: BTreeMap<String, u32>,
+    /// Paths of the files in `dirs` as installed, relative to the dir the addon is
+    /// installed in and separated by `/`
+    files: Vec<String>,
+    /// Downloaded and installed before other addons when updating
+    priority: bool,
+    /// Kept at its installed version by `grunt pin`, update skips it
+    pinned: bool,
 }
 
 impl Addon {
@@ -20,8 +44,18 @@ impl Addon {
             name: info.name,
             addon_type: info.addon_type,
             addon_id: info.addon_id,
+            slug: info.slug,
             version: info.version,
             dirs: info.dirs,
+            notes: info.notes,
+            frequency: info.frequency,
+            last_checked: info.last_checked,
+            failures: info.failures,
+            install_root: info.install_root,
+            fingerprints: info.fingerprints,
+            files: info.files,
+            priority: info.priority,
+            pinned: info.pinned,
         }
     }
 
@@ -31,8 +65,18 @@ impl Addon {
             name: self.name.clone(),
             addon_type: self.addon_type.clone(),
             addon_id: self.addon_id.clone(),
+            slug: self.slug.clone(),
             version: self.version.clone(),
             dirs: self.dirs.clone(),
+            notes: self.notes.clone(),
+            frequency: self.frequency,
+            last_checked: self.last_checked,
+            failures: self.failures.clone(),
+            install_root: self.install_root.clone(),
+            fingerprints: self.fingerprints.clone(),
+            files: self.files.clone(),
+            priority: self.priority,
+            pinned: self.pinned,
         }
     }
 
@@ -48,8 +92,45 @@ impl Addon {
             name: dir_name,
             addon_type: AddonType::Curse,
             addon_id: info.id.to_string(),
+            slug: None,
             version: info.file.id.to_string(),
             dirs,
+            notes: None,
+            frequency: UpdateFrequency::default(),
+            last_checked: None,
+            failures: None,
+            install_root: None,
+            fingerprints: BTreeMap::new(),
+            files: Vec::new(),
+            priority: false,
+            pinned: false,
+        }
+    }
+
+    /// Initialize a newly installed Curse addon
+    pub fn from_curse_file(
+        name: String,
+        id: i64,
+        slug: Option<String>,
+        file_id: i64,
+        dirs: Vec<String>,
+    ) -> Self {
+        Addon {
+            name,
+            addon_type: AddonType::Curse,
+            addon_id: id.to_string(),
+            slug,
+            version: file_id.to_string(),
+            dirs,
+            notes: None,
+            frequency: UpdateFrequency::default(),
+            last_checked: None,
+            failures: None,
+            install_root: None,
+            fingerprints: BTreeMap::new(),
+            files: Vec::new(),
+            priority: false,
+            pinned: false,
         }
     }
 
@@ -59,39 +140,232 @@ impl Addon {
             name,
             addon_type: AddonType::Tukui,
             addon_id: id.to_string(),
+            slug: None,
             version,
             dirs,
+            notes: None,
+            frequency: UpdateFrequency::default(),
+            last_checked: None,
+            failures: None,
+            install_root: None,
+            fingerprints: BTreeMap::new(),
+            files: Vec::new(),
+            priority: false,
+            pinned: false,
         }
     }
 
-    /// Initialize using default values for addon `TradeSkillMaster`
-    pub fn init_tsm(version: String) -> Self {
-        let tsm_string = "TradeSkillMaster";
+    /// Initialize an addon installed from a custom source named `source`
+    pub fn from_custom(source: String, dirs: Vec<String>, version: String) -> Self {
         Addon {
-            name: tsm_string.to_string(),
-            addon_type: AddonType::TSM,
-            addon_id: "TradeSkillMaster".to_string(),
+            name: source.clone(),
+            addon_type: AddonType::Custom,
+            addon_id: source,
+            slug: None,
             version,
-            dirs: vec![tsm_string.to_string()],
+            dirs,
+            notes: None,
+            frequency: UpdateFrequency::default(),
+            last_checked: None,
+            failures: None,
+            install_root: None,
+            fingerprints: BTreeMap::new(),
+            files: Vec::new(),
+            priority: false,
+            pinned: false,
         }
     }
 
-    /// Initialize using default values for addon `TradeSkillMaster_AppHelper`
-    pub fn init_tsm_helper(version: String) -> Self {
-        let tsm_helper_string = "TradeSkillMaster_AppHelper";
+    /// Initialize an addon installed from a zip archive at `url`, named after its first dir
+    pub fn from_url(url: String, dirs: Vec<String>, version: String) -> Self {
+        Addon {
+            name: dirs[0].clone(),
+            addon_type: AddonType::Url,
+            addon_id: url,
+            slug: None,
+            version,
+            dirs,
+            notes: None,
+            frequency: UpdateFrequency::default(),
+            last_checked: None,
+            failures: None,
+            install_root: None,
+            fingerprints: BTreeMap::new(),
+            files: Vec::new(),
+            priority: false,
+            pinned: false,
+        }
+    }
+
+    /// Initialize an addon installed from a release of the GitHub repository `repo`,
+    /// named after its first dir
+    pub fn from_github(repo: String, dirs: Vec<String>, version: String) -> Self {
         Addon {
-            name: tsm_helper_string.to_string(),
+            name: dirs[0].clone(),
+            addon_type: AddonType::GitHub,
+            addon_id: repo,
+            slug: None,
+            version,
+            dirs,
+            notes: None,
+            frequency: UpdateFrequency::default(),
+            last_checked: None,
+            failures: None,
+            install_root: None,
+            fingerprints: BTreeMap::new(),
+            files: Vec::new(),
+            priority: false,
+            pinned: false,
+        }
+    }
+
+    /// Initialize an addon distributed by TSM, like `TradeSkillMaster_AppHelper`
+    /// Its id is the name without the `TradeSkillMaster_` prefix
+    pub fn from_tsm(name: String, version: String, dirs: Vec<String>) -> Self {
+        let addon_id = name
+            .strip_prefix("TradeSkillMaster_")
+            .unwrap_or(&name)
+            .to_string();
+        Addon {
+            name,
             addon_type: AddonType::TSM,
-            addon_id: "AppHelper".to_string(),
+            addon_id,
+            slug: None,
             version,
-            dirs: vec![tsm_helper_string.to_string()],
+            dirs,
+            notes: None,
+            frequency: UpdateFrequency::default(),
+            last_checked: None,
+            failures: None,
+            install_root: None,
+            fingerprints: BTreeMap::new(),
+            files: Vec::new(),
+            priority: false,
+            pinned: false,
         }
     }
 
+    /// Initialize using default values for addon `TradeSkillMaster`
+    pub fn init_tsm(version: String) -> Self {
+        let tsm_string = "TradeSkillMaster".to_string();
+        Addon::from_tsm(tsm_string.clone(), version, vec![tsm_string])
+    }
+
+    /// Initialize using default values for addon `TradeSkillMaster_AppHelper`
+    pub fn init_tsm_helper(version: String) -> Self {
+        let tsm_helper_string = "TradeSkillMaster_AppHelper".to_string();
+        Addon::from_tsm(tsm_helper_string.clone(), version, vec![tsm_helper_string])
+    }
+
     /// Returns a short type:id string
     pub fn desc_string(&self) -> String {
         format!("{:?}:{}", self.addon_type, self.addon_id)
     }
+
+    /// Unique `source:id` key of the addon, used to address it
+    /// Unlike the name, which is only for display, no two tracked addons share a key
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.addon_type.prefix(), self.addon_id)
+    }
+
+    /// Returns a readable `source:id` identifier, using the slug for Curse addons if known
+    pub fn identifier(&self) -> String {
+        let id = self.slug.as_ref().unwrap_or(&self.addon_id);
+        format!("{}:{}", self.addon_type.prefix(), id)
+    }
+
+    /// Checks if `query` is this addon's name or a `source:id` identifier for it
+    pub fn matches(&self, query: &str) -> bool {
+        if self.name == query {
+            return true;
+        }
+        match query.find(':') {
+            Some(index) => {
+                let (prefix, id) = (&query[..index], &query[index + 1..]);
+                prefix.eq_ignore_ascii_case(self.addon_type.prefix())
+                    && (self.addon_id == id || self.slug.as_deref() == Some(id))
+            }
+            None => false,
+        }
+    }
+}
+
+/// How often an addon is checked for updates
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateFrequency {
+    /// Every run
+    #[default]
+    Fast,
+    /// Once a week, for stable addons
+    Weekly,
+    /// Never, keeping the installed version
+    Frozen,
+}
+
+impl UpdateFrequency {
+    pub const NAMES: &'static [&'static str] = &["fast", "weekly", "frozen"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fast" => Some(UpdateFrequency::Fast),
+            "weekly" => Some(UpdateFrequency::Weekly),
+            "frozen" => Some(UpdateFrequency::Frozen),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            UpdateFrequency::Fast => "fast",
+            UpdateFrequency::Weekly => "weekly",
+            UpdateFrequency::Frozen => "frozen",
+        }
+    }
+
+    pub fn is_fast(&self) -> bool {
+        *self == UpdateFrequency::Fast
+    }
+
+    /// Checks if an addon last checked at `last_checked` should be checked at `now`
+    pub fn is_due(&self, last_checked: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+        match self {
+            UpdateFrequency::Fast => true,
+            UpdateFrequency::Weekly => {
+                last_checked.is_none_or(|checked| now - checked >= Duration::days(7))
+            }
+            UpdateFrequency::Frozen => false,
+        }
+    }
+}
+
+/// Consecutive update failures of an addon
+/// Once there are too many the addon isn't retried until `grunt retry` clears them
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+pub struct UpdateFailures {
+    pub count: u32,
+    pub last_error: String,
+}
+
+/// Why an addon won't get updates anymore
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Deprecation {
+    Inactive,
+    Abandoned,
+    Deleted,
+    Unavailable,
+}
+
+impl Deprecation {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Deprecation::Inactive => "is marked inactive on Curse",
+            Deprecation::Abandoned => "was abandoned by its author",
+            Deprecation::Deleted => "was deleted from Curse",
+            Deprecation::Unavailable => "isn't available to download anymore",
+        }
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
@@ -99,4 +373,78 @@ pub enum AddonType {
     Curse,
     Tukui,
     TSM,
+    /// A source from the settings' `custom_sources`
+    Custom,
+    /// A zip archive at a URL, the id is the URL
+    Url,
+    /// The latest release of a GitHub repository, the id is `owner/repo`
+    GitHub,
+}
+
+impl AddonType {
+    /// The prefix used in `source:id` identifiers
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            AddonType::Curse => "curse",
+            AddonType::Tukui => "tukui",
+            AddonType::TSM => "tsm",
+            AddonType::Custom => "custom",
+            AddonType::Url => "url",
+            AddonType::GitHub => "github",
+        }
+    }
+
+    /// Whether grunt was built with the cargo feature the source needs
+    pub fn is_built(&self) -> bool {
+        match self {
+            AddonType::Tukui => cfg!(feature = "tukui"),
+            AddonType::TSM => cfg!(feature = "tsm"),
+            AddonType::Curse | AddonType::Custom | AddonType::Url | AddonType::GitHub => true,
+        }
+    }
+
+    /// Whether `latest` is an update to the installed `current` version
+    /// Curse versions are file ids, Tukui versions are compared as text and URL versions
+    /// are `Last-Modified` dates. Other sources update whenever the version changes
+    pub fn is_newer(&self, current: &str, latest: &str) -> bool {
+        match self {
+            AddonType::Curse => match (current.parse::<i64>(), latest.parse::<i64>()) {
+                (Ok(current), Ok(latest)) => latest > current,
+                _ => latest != current,
+            },
+            AddonType::Tukui => latest > current,
+            AddonType::Url => match (
+                DateTime::parse_from_rfc2822(current),
+                DateTime::parse_from_rfc2822(latest),
+            ) {
+                (Ok(current), Ok(latest)) => latest > current,
+                // Installed when the server didn't send a date
+                (Err(_), Ok(_)) => true,
+                _ => false,
+            },
+            AddonType::Custom | AddonType::TSM | AddonType::GitHub => latest != current,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(AddonType::Curse.is_newer("3000", "3100"));
+        assert!(!AddonType::Curse.is_newer("3100", "3000"));
+        assert!(AddonType::Tukui.is_newer("12.01", "12.10"));
+        assert!(!AddonType::Tukui.is_newer("12.10", "12.10"));
+        assert!(AddonType::Custom.is_newer("v2", "v1"));
+        let (old, new) = (
+            "Tue, 01 Dec 2020 10:00:00 GMT",
+            "Wed, 02 Dec 2020 10:00:00 GMT",
+        );
+        assert!(AddonType::Url.is_newer(old, new));
+        assert!(!AddonType::Url.is_newer(new, old));
+        assert!(AddonType::Url.is_newer("", new));
+        assert!(!AddonType::Url.is_newer(old, ""));
+    }
 }
@@ -0,0 +1,630 @@
+use crate::addon::Addon;
+use crate::error::GruntError;
+use crate::flavor::Flavor;
+use crate::lockfile::Lockfile;
+use crate::{storage, wine, Grunt};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+/// Version of the archive layout, restoring archives made by newer versions fails
+const FORMAT: u32 = 1;
+const MANIFEST: &str = "manifest.json";
+const LOCKFILE: &str = "lockfile.json";
+/// Dirs of addons in `AddOns`
+const ADDONS: &str = "addons";
+/// Dirs of addons with an install root, under the index of the root in the manifest
+const ROOTS: &str = "roots";
+/// Account wide saved variables, under the name of the account
+const SAVED_VARIABLES: &str = "wtf";
+
+/// What a backup contains, the first file in the archive
+/// The lockfile follows it, then every tracked dir. Files are streamed through the tar
+/// and zstd encoders so backups of large AddOns dirs aren't held in memory
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Manifest {
+    pub format: u32,
+    pub created: DateTime<Utc>,
+    /// Version of grunt that made the backup
+    pub grunt_version: String,
+    pub flavor: Flavor,
+    /// The AddOns dir that was backed up
+    pub root_dir: PathBuf,
+    /// Install roots of addons outside of `AddOns`
+    pub install_roots: Vec<String>,
+    pub addons: Vec<BackedUpAddon>,
+    /// Whether `WTF/Account/*/SavedVariables` is included, after the addon dirs
+    #[serde(default)]
+    pub saved_variables: bool,
+}
+
+impl Manifest {
+    /// Finds a backed up addon by name or `source:id` identifier
+    pub fn find_addon(&self, query: &str) -> Option<&BackedUpAddon> {
+        self.addons.iter().find(|addon| {
+            addon.name.eq_ignore_ascii_case(query) || addon.identifier.eq_ignore_ascii_case(query)
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct BackedUpAddon {
+    pub name: String,
+    pub identifier: String,
+    pub version: String,
+    pub dirs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_root: Option<String>,
+}
+
+impl BackedUpAddon {
+    fn new(addon: &Addon) -> Self {
+        BackedUpAddon {
+            name: addon.name().clone(),
+            identifier: addon.identifier(),
+            version: addon.version().clone(),
+            dirs: addon.dirs().clone(),
+            install_root: addon.install_root().clone(),
+        }
+    }
+}
+
+/// Writes the lockfile and every tracked dir to a zstd compressed tar archive
+/// Dirs that are missing are left out
+/// With `saved_variables` the account wide saved variables of every addon are included
+/// if the WoW dir has any
+pub fn create<W: Write>(
+    grunt: &Grunt,
+    writer: W,
+    saved_variables: bool,
+) -> Result<Manifest, GruntError> {
+    let saved_variables_dirs = if saved_variables {
+        saved_variables_dirs(grunt.root_dir())
+    } else {
+        Vec::new()
+    };
+    let mut install_roots: Vec<String> = grunt
+        .addons()
+        .iter()
+        .filter_map(|addon| addon.install_root().clone())
+        .collect();
+    install_roots.sort();
+    install_roots.dedup();
+    let manifest = Manifest {
+        format: FORMAT,
+        created: Utc::now(),
+        grunt_version: env!("CARGO_PKG_VERSION").to_string(),
+        flavor: *grunt.flavor(),
+        root_dir: grunt.root_dir().clone(),
+        install_roots,
+        addons: grunt.addons().iter().map(BackedUpAddon::new).collect(),
+        saved_variables: !saved_variables_dirs.is_empty(),
+    };
+
+    let mut archive = tar::Builder::new(zstd::Encoder::new(writer, 0)?);
+    let manifest_json =
+        serde_json::to_vec_pretty(&manifest).expect("Error serializing backup manifest");
+    append_data(&mut archive, MANIFEST, &manifest_json)?;
+    append_data(
+        &mut archive,
+        LOCKFILE,
+        Lockfile::from_grunt(grunt).to_json().as_bytes(),
+    )?;
+    for addon in grunt.addons() {
+        let install_dir = grunt.install_dir(addon.install_root().as_deref())?;
+        let archive_dir = archive_dir(&manifest, addon.install_root().as_deref());
+        for dir in addon.dirs() {
+            if let Some(path) = wine::find_ignore_case(&install_dir, dir) {
+                archive.append_dir_all(archive_dir.join(dir), path)?;
+            }
+        }
+    }
+    for (account, dir) in saved_variables_dirs {
+        archive.append_dir_all(Path::new(SAVED_VARIABLES).join(account), dir)?;
+    }
+    archive.into_inner()?.finish()?;
+    Ok(manifest)
+}
+
+/// Reads only the manifest of a backup
+pub fn read_manifest<R: Read>(reader: R) -> Result<Manifest, GruntError> {
+    let mut archive = tar::Archive::new(zstd::Decoder::new(reader)?);
+    let mut entries = archive.entries()?;
+    next_json(&mut entries, MANIFEST)
+}
+
+/// Reads the lockfile saved in a backup, without restoring anything
+pub fn read_lockfile<R: Read>(reader: R) -> Result<Lockfile, GruntError> {
+    let mut archive = tar::Archive::new(zstd::Decoder::new(reader)?);
+    let mut entries = archive.entries()?;
+    let _: Manifest = next_json(&mut entries, MANIFEST)?;
+    next_json(&mut entries, LOCKFILE)
+}
+
+/// Restores a backup into the AddOns dir of `grunt`, replacing its lockfile
+/// Dirs in the backup replace the installed ones, other dirs are left alone and show up
+/// as untracked. Nothing is changed until the whole archive has been read
+/// Saved variables in the backup are restored over the current ones
+pub fn restore<R: Read>(grunt: &Grunt, reader: R) -> Result<Manifest, GruntError> {
+    restore_filtered(grunt, reader, None)
+}
+
+/// Restores only the saved variables of addons with one of `dirs`, leaving addon dirs
+/// and the lockfile alone. Every saved variable is restored if `dirs` is empty
+pub fn restore_saved_variables<R: Read>(
+    grunt: &Grunt,
+    reader: R,
+    dirs: &[String],
+) -> Result<Manifest, GruntError> {
+    restore_filtered(grunt, reader, Some(dirs))
+}
+
+/// Restores everything, or only saved variables of `saved_variables_of` if given
+fn restore_filtered<R: Read>(
+    grunt: &Grunt,
+    reader: R,
+    saved_variables_of: Option<&[String]>,
+) -> Result<Manifest, GruntError> {
+    let read = Rc::new(Cell::new(0));
+    let reader = CountingReader {
+        inner: reader,
+        read: read.clone(),
+    };
+    let mut archive = tar::Archive::new(zstd::Decoder::new(reader)?);
+    let mut entries = archive.entries()?;
+    let manifest: Manifest = next_json(&mut entries, MANIFEST)?;
+    if manifest.format > FORMAT {
+        return Err(GruntError::InvalidBackup(
+            "It was made by a newer version of grunt".to_string(),
+        ));
+    }
+    if saved_variables_of.is_some() && !manifest.saved_variables {
+        return Err(GruntError::InvalidBackup(
+            "It doesn't include saved variables".to_string(),
+        ));
+    }
+    let lockfile: Lockfile = next_json(&mut entries, LOCKFILE)?;
+
+    // A backup holds many addons, so the extract limits apply as if each was its own
+    // archive
+    let limits = grunt.extract_limits();
+    let addons = manifest.addons.len().max(1) as u64;
+    let max_files = limits.max_files.saturating_mul(addons as usize);
+    let max_read = (limits.max_archive_mib * 1024 * 1024).saturating_mul(addons);
+    let max_size = max_read.saturating_mul(limits.max_ratio);
+    let too_large = || {
+        GruntError::UnsafeArchive(format!(
+            "it expands to more than {} times its size",
+            limits.max_ratio
+        ))
+    };
+    let expands_too_much =
+        |extracted: u64| extracted > read.get().max(1).saturating_mul(limits.max_ratio);
+    let mut files = 0;
+    let mut extracted: u64 = 0;
+
+    // Everything is unpacked next to the AddOns dir first, so a truncated or invalid
+    // archive leaves the installed files alone
+    let staging_parent = grunt.root_dir().parent().unwrap_or(grunt.root_dir());
+    let staging = tempfile::Builder::new()
+        .prefix(".grunt-restore")
+        .tempdir_in(staging_parent)?;
+    let staging_path = staging.path().canonicalize()?;
+    // Addon dirs to move into their install dir and saved variables to move over the
+    // current ones
+    let mut staged_dirs = BTreeSet::new();
+    let mut staged_saved_variables = Vec::new();
+    for entry in entries {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        // Backups only have files and dirs, links could point outside of the WoW dir
+        let entry_type = entry.header().entry_type();
+        if !entry_type.is_file() && !entry_type.is_dir() {
+            return Err(GruntError::UnsafeArchive(format!(
+                "{} isn't a file or dir",
+                path.display()
+            )));
+        }
+        files += 1;
+        if files > max_files {
+            return Err(GruntError::UnsafeArchive(format!(
+                "it contains more than {} files",
+                max_files
+            )));
+        }
+        if read.get() > max_read {
+            return Err(GruntError::UnsafeArchive(format!(
+                "it's larger than {} MiB",
+                max_read / 1024 / 1024
+            )));
+        }
+        // Entries hold as many bytes as their header says, so the total is checked before
+        // writing. The ratio is checked after, once the compressed entry has been read
+        extracted = extracted.saturating_add(entry.size());
+        if extracted > max_size {
+            return Err(too_large());
+        }
+        // The path is checked by `saved_variables_path` or `restore_path` before
+        // anything is unpacked to it
+        let staged = staging_path.join(&path);
+        if path.starts_with(SAVED_VARIABLES) {
+            match saved_variables_path(grunt, &path, saved_variables_of)? {
+                Some(dest) => staged_saved_variables.push((staged.clone(), dest)),
+                None => continue,
+            }
+        } else if saved_variables_of.is_some() {
+            continue;
+        } else {
+            let (install_dir, relative) = restore_path(grunt, &manifest, &path)?;
+            // Replace the whole dir, not just the files in the backup
+            let dir = relative
+                .iter()
+                .next()
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            // The archive path ends with the path inside the install dir
+            let depth = relative.components().count();
+            let staged_dir = staged.ancestors().nth(depth - 1).unwrap().to_path_buf();
+            staged_dirs.insert((install_dir, dir, staged_dir));
+        }
+        if let Some(parent) = staged.parent() {
+            std::fs::create_dir_all(parent)?;
+            // Like `Entry::unpack_in`, which can't be used as the archive has different
+            // paths, refuse parents that lead out of the staging dir
+            if !parent.canonicalize()?.starts_with(&staging_path) {
+                return Err(GruntError::UnsafeArchive(path.display().to_string()));
+            }
+        }
+        entry.unpack(&staged)?;
+        if expands_too_much(extracted) {
+            return Err(too_large());
+        }
+    }
+
+    // The whole archive is valid, so the installed files can be replaced
+    let replaced = staging_path.join("replaced");
+    std::fs::create_dir(&replaced)?;
+    for (index, (install_dir, dir, staged_dir)) in staged_dirs.into_iter().enumerate() {
+        std::fs::create_dir_all(&install_dir)?;
+        let old = wine::find_ignore_case(&install_dir, &dir);
+        let aside = replaced.join(index.to_string());
+        if let Some(old) = &old {
+            std::fs::rename(old, &aside)?;
+        }
+        if let Err(err) = std::fs::rename(staged_dir, install_dir.join(&dir)) {
+            if let Some(old) = &old {
+                let _ = std::fs::rename(&aside, old);
+            }
+            return Err(err.into());
+        }
+    }
+    for (staged, dest) in staged_saved_variables {
+        if staged.is_dir() {
+            std::fs::create_dir_all(dest)?;
+        } else {
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(staged, dest)?;
+        }
+    }
+    if saved_variables_of.is_none() {
+        storage::open(*grunt.storage_backend(), grunt.root_dir())?.save(&lockfile)?;
+    }
+    Ok(manifest)
+}
+
+/// `WTF` of the WoW dir, two levels above `AddOns`
+fn wtf_dir(root_dir: &Path) -> Option<PathBuf> {
+    let wow_dir = root_dir.parent().and_then(Path::parent)?;
+    Some(wine::find_ignore_case(wow_dir, "WTF").unwrap_or_else(|| wow_dir.join("WTF")))
+}
+
+/// The `SavedVariables` dir of each account, by account name
+fn saved_variables_dirs(root_dir: &Path) -> Vec<(String, PathBuf)> {
+    let accounts = wtf_dir(root_dir).and_then(|wtf| wine::find_ignore_case(&wtf, "Account"));
+    let mut dirs: Vec<(String, PathBuf)> = accounts
+        .and_then(|accounts| std::fs::read_dir(accounts).ok())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let dir = wine::find_ignore_case(&entry.path(), "SavedVariables")?;
+            Some((entry.file_name().to_string_lossy().to_string(), dir))
+        })
+        .filter(|(_, dir)| dir.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Where a saved variables file in the archive is restored to
+/// `None` if it doesn't belong to an addon with one of `dirs`
+fn saved_variables_path(
+    grunt: &Grunt,
+    path: &Path,
+    dirs: Option<&[String]>,
+) -> Result<Option<PathBuf>, GruntError> {
+    let unsafe_path = || GruntError::UnsafeArchive(path.display().to_string());
+    let parts: Vec<&str> = path
+        .components()
+        .map(|component| match component {
+            Component::Normal(part) => part.to_str().ok_or_else(unsafe_path),
+            _ => Err(unsafe_path()),
+        })
+        .collect::<Result<_, _>>()?;
+    let (account, file) = match parts.as_slice() {
+        [_, account] => (account, None),
+        [_, account, file] => (account, Some(file)),
+        _ => return Err(unsafe_path()),
+    };
+    // Files are named after the addon's dir, with `.bak` for the previous session
+    let addon_dir = file.map(|file| {
+        let file = file.strip_suffix(".bak").unwrap_or(file);
+        file.strip_suffix(".lua").unwrap_or(file)
+    });
+    let wanted = match (dirs, addon_dir) {
+        (Some(dirs), Some(addon_dir)) if !dirs.is_empty() => {
+            dirs.iter().any(|dir| dir.eq_ignore_ascii_case(addon_dir))
+        }
+        _ => true,
+    };
+    if !wanted {
+        return Ok(None);
+    }
+    let wtf_dir =
+        wtf_dir(grunt.root_dir()).ok_or_else(|| GruntError::NotFound("WTF dir".to_string()))?;
+    let accounts_dir =
+        wine::find_ignore_case(&wtf_dir, "Account").unwrap_or_else(|| wtf_dir.join("Account"));
+    let account_dir = accounts_dir.join(account);
+    let saved_variables = wine::find_ignore_case(&account_dir, "SavedVariables")
+        .unwrap_or_else(|| account_dir.join("SavedVariables"));
+    Ok(Some(match file {
+        Some(file) => saved_variables.join(file),
+        None => saved_variables,
+    }))
+}
+
+/// Where the dirs of addons with an install root are in the archive
+fn archive_dir(manifest: &Manifest, install_root: Option<&str>) -> PathBuf {
+    match install_root {
+        Some(root) => {
+            let index = manifest
+                .install_roots
+                .iter()
+                .position(|other| other == root);
+            Path::new(ROOTS).join(index.unwrap().to_string())
+        }
+        None => PathBuf::from(ADDONS),
+    }
+}
+
+/// The dir a file in the archive is restored to and its path inside it
+fn restore_path(
+    grunt: &Grunt,
+    manifest: &Manifest,
+    path: &Path,
+) -> Result<(PathBuf, PathBuf), GruntError> {
+    let unsafe_path = || GruntError::UnsafeArchive(path.display().to_string());
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::CurDir => {}
+            _ => return Err(unsafe_path()),
+        }
+    }
+    let (install_root, skip) = match parts.first().and_then(|part| part.to_str()) {
+        Some(ADDONS) => (None, 1),
+        Some(ROOTS) => {
+            let root = parts
+                .get(1)
+                .and_then(|index| index.to_str()?.parse::<usize>().ok())
+                .and_then(|index| manifest.install_roots.get(index))
+                .ok_or_else(unsafe_path)?;
+            (Some(root.as_str()), 2)
+        }
+        _ => return Err(unsafe_path()),
+    };
+    if parts.len() <= skip {
+        return Err(unsafe_path());
+    }
+    let relative = parts[skip..].iter().collect();
+    Ok((grunt.install_dir(install_root)?, relative))
+}
+
+/// Counts the bytes read from `inner`, to compare with what they decompress to
+struct CountingReader<R> {
+    inner: R,
+    read: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read.set(self.read.get() + read as u64);
+        Ok(read)
+    }
+}
+
+fn append_data<W: Write>(
+    archive: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp() as u64);
+    archive.append_data(&mut header, path, data)
+}
+
+/// Parses the next file in the archive, which must be `name`
+fn next_json<R: Read, T: serde::de::DeserializeOwned>(
+    entries: &mut tar::Entries<R>,
+    name: &str,
+) -> Result<T, GruntError> {
+    let missing = || GruntError::InvalidBackup(format!("{} is missing", name));
+    // Files that aren't zstd or tar fail on the first read
+    let entry = entries
+        .next()
+        .ok_or_else(missing)?
+        .map_err(|err| GruntError::InvalidBackup(err.to_string()))?;
+    if entry.path()? != Path::new(name) {
+        return Err(missing());
+    }
+    serde_json::from_reader(entry).map_err(|err| GruntError::InvalidBackup(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::ExtractLimits;
+
+    #[test]
+    fn test_backup_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let root_dir = dir.path().join("_retail_/Interface/AddOns");
+        std::fs::create_dir_all(root_dir.join("A/Libs")).unwrap();
+        std::fs::write(root_dir.join("A/A.toc"), "## Title: A").unwrap();
+        std::fs::write(root_dir.join("A/Libs/Lib.lua"), "print()").unwrap();
+        let mut grunt = Grunt::new(&root_dir).unwrap();
+        let addon = Addon::from_custom("A".to_string(), vec!["A".to_string()], "1".to_string());
+        grunt.import_addons(vec![addon]);
+
+        let mut archive = Vec::new();
+        let manifest = create(&grunt, &mut archive, false).unwrap();
+        assert_eq!(manifest.addons[0].identifier, "custom:A");
+        assert_eq!(read_manifest(archive.as_slice()).unwrap(), manifest);
+
+        // Files that weren't backed up are removed with their dir
+        std::fs::write(root_dir.join("A/A.toc"), "## Title: Edited").unwrap();
+        std::fs::write(root_dir.join("A/New.lua"), "").unwrap();
+        restore(&grunt, archive.as_slice()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root_dir.join("A/A.toc")).unwrap(),
+            "## Title: A"
+        );
+        assert!(root_dir.join("A/Libs/Lib.lua").exists());
+        assert!(!root_dir.join("A/New.lua").exists());
+        assert_eq!(Grunt::new(&root_dir).unwrap().addons().len(), 1);
+
+        assert!(matches!(
+            restore(&grunt, &b"not a backup"[..]),
+            Err(GruntError::InvalidBackup(_))
+        ));
+        assert!(matches!(
+            restore_saved_variables(&grunt, archive.as_slice(), &[]),
+            Err(GruntError::InvalidBackup(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsafe_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let root_dir = dir.path().join("_retail_/Interface/AddOns");
+        std::fs::create_dir_all(root_dir.join("A")).unwrap();
+        std::fs::write(root_dir.join("A/A.toc"), "## Title: A").unwrap();
+        std::fs::write(root_dir.join("A/A.lua"), "print()").unwrap();
+        let mut grunt = Grunt::new(&root_dir).unwrap();
+        let addon = Addon::from_custom("A".to_string(), vec!["A".to_string()], "1".to_string());
+        grunt.import_addons(vec![addon]);
+
+        // Links are refused
+        let manifest = create(&grunt, std::io::sink(), false).unwrap();
+        let mut builder = tar::Builder::new(zstd::Encoder::new(Vec::new(), 0).unwrap());
+        let manifest_json = serde_json::to_vec(&manifest).unwrap();
+        append_data(&mut builder, MANIFEST, &manifest_json).unwrap();
+        let lockfile_json = Lockfile::from_grunt(&grunt).to_json();
+        append_data(&mut builder, LOCKFILE, lockfile_json.as_bytes()).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "addons/A", dir.path())
+            .unwrap();
+        let archive = builder.into_inner().unwrap().finish().unwrap();
+        assert!(matches!(
+            restore(&grunt, archive.as_slice()),
+            Err(GruntError::UnsafeArchive(_))
+        ));
+        assert!(root_dir.join("A/A.lua").is_file());
+
+        // So are backups over the extract limits
+        let mut archive = Vec::new();
+        create(&grunt, &mut archive, false).unwrap();
+        grunt.set_extract_limits(ExtractLimits {
+            max_files: 1,
+            ..Default::default()
+        });
+        assert!(matches!(
+            restore(&grunt, archive.as_slice()),
+            Err(GruntError::UnsafeArchive(_))
+        ));
+    }
+
+    #[test]
+    fn test_truncated_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let root_dir = dir.path().join("_retail_/Interface/AddOns");
+        std::fs::create_dir_all(root_dir.join("A")).unwrap();
+        std::fs::write(root_dir.join("A/A.toc"), "## Title: A").unwrap();
+        // Doesn't compress, so cutting the archive short cuts into it
+        let mut state: u32 = 1;
+        let noise: Vec<u8> = (0..1 << 20)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        std::fs::write(root_dir.join("A/Noise.bin"), noise).unwrap();
+        let mut grunt = Grunt::new(&root_dir).unwrap();
+        let addon = Addon::from_custom("A".to_string(), vec!["A".to_string()], "1".to_string());
+        grunt.import_addons(vec![addon]);
+        let mut archive = Vec::new();
+        create(&grunt, &mut archive, false).unwrap();
+
+        std::fs::write(root_dir.join("A/A.toc"), "## Title: Edited").unwrap();
+        archive.truncate(archive.len() / 2);
+        assert!(restore(&grunt, archive.as_slice()).is_err());
+        assert_eq!(
+            std::fs::read_to_string(root_dir.join("A/A.toc")).unwrap(),
+            "## Title: Edited"
+        );
+        assert!(root_dir.join("A/Noise.bin").is_file());
+        // The staging dir is cleaned up
+        let interface_dir = root_dir.parent().unwrap();
+        assert_eq!(std::fs::read_dir(interface_dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_restore_saved_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let root_dir = dir.path().join("_retail_/Interface/AddOns");
+        std::fs::create_dir_all(root_dir.join("A")).unwrap();
+        let saved_variables = dir
+            .path()
+            .join("_retail_/WTF/Account/ACCOUNT/SavedVariables");
+        std::fs::create_dir_all(&saved_variables).unwrap();
+        for file in ["A.lua", "A.lua.bak", "B.lua"].iter() {
+            std::fs::write(saved_variables.join(file), "Saved = true").unwrap();
+        }
+        let grunt = Grunt::new(&root_dir).unwrap();
+        let mut archive = Vec::new();
+        assert!(create(&grunt, &mut archive, true).unwrap().saved_variables);
+
+        for file in ["A.lua", "A.lua.bak", "B.lua"].iter() {
+            std::fs::write(saved_variables.join(file), "Saved = nil").unwrap();
+        }
+        restore_saved_variables(&grunt, archive.as_slice(), &["A".to_string()]).unwrap();
+        let read = |file: &str| std::fs::read_to_string(saved_variables.join(file)).unwrap();
+        assert_eq!(read("A.lua"), "Saved = true");
+        assert_eq!(read("A.lua.bak"), "Saved = true");
+        assert_eq!(read("B.lua"), "Saved = nil");
+    }
+}
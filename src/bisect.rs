@@ -0,0 +1,173 @@
+use crate::Grunt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A binary search for the addon breaking the UI
+/// Half of the suspects are disabled at a time by moving their dirs out of `AddOns`,
+/// into a dir next to it that the game doesn't load
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Bisect {
+    /// The AddOns dir being bisected
+    pub root_dir: PathBuf,
+    /// Dirs of every addon when the bisect started, by addon key
+    pub addons: BTreeMap<String, Vec<String>>,
+    /// Display names of the addons by key
+    #[serde(default)]
+    pub names: BTreeMap<String, String>,
+    /// Keys of the addons that could still be the culprit, the second half of them
+    /// is disabled
+    pub suspects: Vec<String>,
+}
+
+impl Bisect {
+    /// Starts with every addon as a suspect and disables half of them
+    pub fn start(grunt: &Grunt) -> std::io::Result<Self> {
+        let addons: BTreeMap<String, Vec<String>> = grunt
+            .addons()
+            .iter()
+            .map(|addon| (addon.key(), addon.dirs().clone()))
+            .collect();
+        let names = grunt
+            .addons()
+            .iter()
+            .map(|addon| (addon.key(), addon.name().clone()))
+            .collect();
+        let bisect = Bisect {
+            root_dir: grunt.root_dir().to_path_buf(),
+            suspects: addons.keys().cloned().collect(),
+            addons,
+            names,
+        };
+        bisect.apply()?;
+        Ok(bisect)
+    }
+
+    /// Loads the bisect in progress, if there is one
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Option<Self>> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        Ok(Some(serde_json::from_str(&text)?))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let text = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, text)
+    }
+
+    /// Display name of an addon, bisects started by older versions only have names
+    pub fn name<'a>(&'a self, key: &'a str) -> &'a str {
+        self.names.get(key).map_or(key, |name| name.as_str())
+    }
+
+    /// Addons that are enabled and disabled in the current step
+    pub fn halves(&self) -> (&[String], &[String]) {
+        self.suspects.split_at(self.suspects.len().div_ceil(2))
+    }
+
+    /// Narrows the suspects after testing the current step in game
+    /// Returns the key of the culprit once there's only one suspect left, with every addon
+    /// enabled again
+    pub fn mark(&mut self, broken: bool) -> std::io::Result<Option<String>> {
+        let (enabled, disabled) = self.halves();
+        self.suspects = if broken { enabled } else { disabled }.to_vec();
+        if self.suspects.len() <= 1 {
+            self.reset()?;
+            return Ok(self.suspects.first().cloned());
+        }
+        self.apply()?;
+        Ok(None)
+    }
+
+    /// Enables every addon again
+    pub fn reset(&self) -> std::io::Result<()> {
+        for dir in self.addons.values().flatten() {
+            self.enable(dir)?;
+        }
+        let disabled_dir = self.disabled_dir();
+        if disabled_dir.exists() && std::fs::read_dir(&disabled_dir)?.next().is_none() {
+            std::fs::remove_dir(disabled_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Where disabled dirs are moved to
+    pub fn disabled_dir(&self) -> PathBuf {
+        self.root_dir.with_file_name("AddOns.grunt-bisect")
+    }
+
+    /// Moves dirs so only the first half of the suspects and addons that were
+    /// cleared are enabled
+    fn apply(&self) -> std::io::Result<()> {
+        let (_, disabled) = self.halves();
+        // Dirs shared with an enabled addon stay enabled
+        let enabled_dirs: Vec<&String> = self
+            .addons
+            .iter()
+            .filter(|(name, _)| !disabled.contains(name))
+            .flat_map(|(_, dirs)| dirs)
+            .collect();
+        std::fs::create_dir_all(self.disabled_dir())?;
+        for (name, dirs) in self.addons.iter() {
+            for dir in dirs {
+                if disabled.contains(name) && !enabled_dirs.contains(&dir) {
+                    self.disable(dir)?;
+                } else {
+                    self.enable(dir)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn disable(&self, dir: &str) -> std::io::Result<()> {
+        let path = self.root_dir.join(dir);
+        if path.exists() {
+            std::fs::rename(path, self.disabled_dir().join(dir))?;
+        }
+        Ok(())
+    }
+
+    fn enable(&self, dir: &str) -> std::io::Result<()> {
+        let path = self.disabled_dir().join(dir);
+        if path.exists() {
+            std::fs::rename(path, self.root_dir.join(dir))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bisect() {
+        let root = tempfile::tempdir().unwrap();
+        let root_dir = root.path().join("AddOns");
+        let mut addons = BTreeMap::new();
+        for name in ["A", "B", "C", "D", "E"].iter() {
+            std::fs::create_dir_all(root_dir.join(name)).unwrap();
+            addons.insert(name.to_string(), vec![name.to_string()]);
+        }
+        let mut bisect = Bisect {
+            root_dir: root_dir.clone(),
+            suspects: addons.keys().cloned().collect(),
+            addons,
+            names: BTreeMap::new(),
+        };
+        bisect.apply().unwrap();
+        assert!(root_dir.join("C").exists() && !root_dir.join("D").exists());
+
+        // D is broken
+        assert_eq!(bisect.mark(false).unwrap(), None);
+        assert_eq!(bisect.suspects, vec!["D", "E"]);
+        assert!(root_dir.join("D").exists() && !root_dir.join("E").exists());
+        assert_eq!(bisect.mark(true).unwrap(), Some("D".to_string()));
+        assert!(root_dir.join("E").exists());
+        assert!(!bisect.disabled_dir().exists());
+    }
+}
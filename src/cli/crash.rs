@@ -0,0 +1,122 @@
+//! Opt-in crash reports, redacted so they can be pasted into GitHub issues
+
+use clap::crate_version;
+use grunt::UpdateProgress;
+use regex::Regex;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Mutex, TryLockError};
+
+/// Entries of the journal kept for crash reports
+const JOURNAL_SIZE: usize = 20;
+
+/// What grunt did last, oldest first
+static JOURNAL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Notes a step grunt took, so a crash report shows what led up to the crash
+pub fn journal(entry: String) {
+    // A panic while the journal was locked leaves it usable
+    let mut journal = JOURNAL.lock().unwrap_or_else(|err| err.into_inner());
+    if journal.len() == JOURNAL_SIZE {
+        journal.pop_front();
+    }
+    journal.push_back(format!(
+        "{} {}",
+        chrono::Utc::now().format("%H:%M:%S"),
+        entry
+    ));
+}
+
+/// Notes the steps of an update. Download progress is left out as it's too frequent
+pub fn journal_update(event: &UpdateProgress) {
+    match event {
+        UpdateProgress::DownloadStarted { name } => journal(format!("Downloading {}", name)),
+        UpdateProgress::Extracting { name } => journal(format!("Extracting {}", name)),
+        UpdateProgress::Installed { name } => journal(format!("Installed {}", name)),
+        UpdateProgress::PriorityInstalled { .. } | UpdateProgress::DownloadProgress { .. } => (),
+    }
+}
+
+/// Installs a panic hook that writes a report to `path`
+/// Every string in `redactions` is removed from the report, then anything else that
+/// looks like a path, so no paths or credentials leak
+pub fn install(path: PathBuf, command: String, mut redactions: Vec<String>) {
+    // Replace longest first so paths inside other paths are fully removed
+    redactions.retain(|s| !s.is_empty());
+    redactions.sort_by_key(|s| std::cmp::Reverse(s.len()));
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = match payload.downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(s) => s.clone(),
+                None => "Unknown error".to_string(),
+            },
+        };
+        let location = match info.location() {
+            Some(location) => format!("{}:{}", location.file(), location.line()),
+            None => "Unknown".to_string(),
+        };
+        // The journal can't be read if the panic happened while writing to it
+        let journal: Vec<String> = match JOURNAL.try_lock() {
+            Ok(journal) => journal.iter().cloned().collect(),
+            Err(TryLockError::Poisoned(err)) => err.into_inner().iter().cloned().collect(),
+            Err(TryLockError::WouldBlock) => Vec::new(),
+        };
+        let mut report = format!(
+            "grunt {}\nOS: {} {}\nCommand: {}\nTime: {}\nError: {}\nLocation: {}\nJournal:\n",
+            crate_version!(),
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            command,
+            chrono::Utc::now().to_rfc3339(),
+            message,
+            location
+        );
+        for entry in journal.iter() {
+            report.push_str(&format!("  {}\n", entry));
+        }
+        for redaction in redactions.iter() {
+            report = report.replace(redaction, "<redacted>");
+        }
+        if std::fs::write(&path, redact_paths(&report)).is_ok() {
+            eprintln!("Crash report saved. View it using `grunt report`");
+        }
+        default_hook(info);
+    }));
+}
+
+/// Replaces absolute, home and relative paths starting with `.`, and anything with a
+/// backslash, with `<path>`
+/// Paths inside grunt like `src/main.rs` are kept, they're the same for everyone
+fn redact_paths(text: &str) -> String {
+    let path = Regex::new(
+        r#"(^|[\s"'(\[=,])(?:[A-Za-z]:[\\/]|\\\\|~[\\/]|\.{1,2}[\\/]|/|[^\s"'(\[=,]*\\)[^\s"',)\]]*"#,
+    )
+    .expect("Invalid path regex");
+    path.replace_all(text, "${1}<path>").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_paths() {
+        let redacted = redact_paths(
+            "Error reading /home/me/wow/AddOns: denied\n\
+             Location: src/main.rs:10\n\
+             \"C:\\Users\\me\\WoW\", ~/games, ./AddOns/Foo and Interface\\AddOns\n\
+             https://example.com/a.zip 1/2",
+        );
+        assert_eq!(
+            redacted,
+            "Error reading <path> denied\n\
+             Location: src/main.rs:10\n\
+             \"<path>\", <path>, <path> and <path>\n\
+             https://example.com/a.zip 1/2"
+        );
+    }
+}
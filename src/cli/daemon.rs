@@ -0,0 +1,474 @@
+use chrono::{DateTime, Local};
+use grunt::settings::Settings;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Incidents kept in the status file
+const MAX_INCIDENTS: usize = 20;
+
+/// What the daemon has been doing, saved so `grunt daemon status` can show it
+#[derive(Serialize, Deserialize, Default)]
+pub struct Status {
+    pub pid: Option<u32>,
+    /// Set while an update is running
+    pub running_since: Option<DateTime<Local>>,
+    pub last_run: Option<RunResult>,
+    pub next_run: Option<DateTime<Local>>,
+    /// Stuck or failed runs, newest last
+    pub incidents: Vec<Incident>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RunResult {
+    pub started: DateTime<Local>,
+    pub finished: DateTime<Local>,
+    pub outcome: Outcome,
+    pub updated: usize,
+    pub attempts: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind", content = "message")]
+pub enum Outcome {
+    Succeeded,
+    Failed(String),
+    /// Every attempt was stuck and got cancelled
+    TimedOut,
+    /// Outside of the update windows
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Incident {
+    pub time: DateTime<Local>,
+    pub message: String,
+}
+
+impl Status {
+    /// Reads the status file, using an empty status if it's missing or unreadable
+    pub fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) {
+        let text = serde_json::to_string_pretty(self).expect("Error serializing daemon status");
+        std::fs::write(path, text).expect("Error saving daemon status");
+    }
+
+    /// Logs an incident and keeps it in the status
+    fn record_incident(&mut self, message: String) {
+        println!("\x1B[1mIncident:\x1B[0m {}", message);
+        self.incidents.push(Incident {
+            time: Local::now(),
+            message,
+        });
+        let extra = self.incidents.len().saturating_sub(MAX_INCIDENTS);
+        self.incidents.drain(..extra);
+    }
+}
+
+/// How a single attempt at updating ended
+enum Attempt {
+    Finished {
+        updated: usize,
+        error: Option<String>,
+    },
+    TimedOut,
+}
+
+/// Installs updates every `interval` minutes during the update windows
+pub fn run(interval: u64, settings: &Settings, status_path: &Path, verbose: bool) {
+    println!("Checking for updates every {} minutes", interval);
+    loop {
+        let next_run = Local::now() + chrono::Duration::minutes(interval as i64);
+        run_once(settings, status_path, verbose, Some(next_run));
+        std::thread::sleep(Duration::from_secs(interval * 60));
+    }
+}
+
+/// Installs updates if inside an update window and saves the result
+/// Updating is a separate `grunt update` process so a stuck run can be killed and retried
+pub fn run_once(
+    settings: &Settings,
+    status_path: &Path,
+    verbose: bool,
+    next_run: Option<DateTime<Local>>,
+) {
+    let mut status = Status::load(status_path);
+    status.pid = Some(std::process::id());
+    let started = Local::now();
+    let (outcome, updated, attempts) = if settings.update_allowed_at(started.naive_local()) {
+        status.running_since = Some(started);
+        status.save(status_path);
+        supervise(settings, verbose, &mut status)
+    } else {
+        println!("Outside of update windows. Skipping");
+        (Outcome::Skipped, 0, 0)
+    };
+    status.running_since = None;
+    status.last_run = Some(RunResult {
+        started,
+        finished: Local::now(),
+        outcome,
+        updated,
+        attempts,
+    });
+    status.next_run = next_run;
+    status.save(status_path);
+}
+
+/// Runs updates, cancelling attempts that take longer than the run timeout
+fn supervise(settings: &Settings, verbose: bool, status: &mut Status) -> (Outcome, usize, u32) {
+    let timeout = Duration::from_secs(settings.daemon_run_timeout() * 60);
+    let max_attempts = settings.daemon_retries() + 1;
+    let mut attempts = 0;
+    while attempts < max_attempts {
+        attempts += 1;
+        match run_update(settings, verbose, timeout) {
+            Attempt::Finished {
+                updated,
+                error: None,
+            } => return (Outcome::Succeeded, updated, attempts),
+            Attempt::Finished {
+                updated,
+                error: Some(err),
+            } => {
+                status.record_incident(format!("Update failed: {}", err));
+                return (Outcome::Failed(err), updated, attempts);
+            }
+            Attempt::TimedOut => status.record_incident(format!(
+                "Update was stuck for {} minutes and was cancelled (attempt {} of {})",
+                timeout.as_secs() / 60,
+                attempts,
+                max_attempts
+            )),
+        }
+    }
+    (Outcome::TimedOut, 0, attempts)
+}
+
+/// Runs `grunt update` and waits for it, killing it after `timeout`
+fn run_update(settings: &Settings, verbose: bool, timeout: Duration) -> Attempt {
+    let exe = std::env::current_exe().expect("Couldn't find the grunt executable");
+    let mut command = Command::new(exe);
+    command.arg("--yes");
+    if verbose {
+        command.arg("--verbose");
+    }
+    command.arg("update");
+    if let Some(kib) = settings.daemon_max_bandwidth() {
+        command.args(["--max-bandwidth", &kib.to_string()]);
+    }
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Error starting update");
+
+    // Pass output through, counting updates and keeping the first error
+    let updated = Arc::new(Mutex::new(0));
+    let error = Arc::new(Mutex::new(None));
+    let stdout = forward(child.stdout.take().unwrap(), false, {
+        let updated = updated.clone();
+        move |line| {
+            if line.starts_with("Updating ") {
+                *updated.lock().unwrap() += 1;
+            }
+        }
+    });
+    let stderr = forward(child.stderr.take().unwrap(), true, {
+        let error = error.clone();
+        move |line| {
+            let mut error = error.lock().unwrap();
+            if error.is_none() {
+                *error = line
+                    .strip_prefix("\x1B[1mError:\x1B[0m ")
+                    .map(str::to_string);
+            }
+        }
+    });
+
+    let exit_status = wait_timeout(&mut child, timeout);
+    if exit_status.is_none() {
+        // Ignore errors since the process could have just exited
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    let _ = stdout.join();
+    let _ = stderr.join();
+    let exit_status = match exit_status {
+        Some(exit_status) => exit_status,
+        None => return Attempt::TimedOut,
+    };
+    let updated = *updated.lock().unwrap();
+    let mut error = error.lock().unwrap().take();
+    if !exit_status.success() && error.is_none() {
+        error = Some(format!("Update exited with {}", exit_status));
+    }
+    Attempt::Finished { updated, error }
+}
+
+/// Echoes each line of a child's output, calling `inspect` with it
+fn forward<R, F>(reader: R, is_stderr: bool, mut inspect: F) -> std::thread::JoinHandle<()>
+where
+    R: Read + Send + 'static,
+    F: FnMut(&str) + Send + 'static,
+{
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            if is_stderr {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+            inspect(&line);
+        }
+    })
+}
+
+/// Waits for a child to exit. Returns `None` if it's still running after `timeout`
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Option<ExitStatus> {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        match child.try_wait() {
+            Ok(Some(exit_status)) => return Some(exit_status),
+            Ok(None) => std::thread::sleep(Duration::from_secs(1)),
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// Prints the status saved by the daemon
+pub fn print_status(status_path: &Path) {
+    let status = Status::load(status_path);
+    let format_time = |time: &DateTime<Local>| time.format("%Y-%m-%d %H:%M").to_string();
+    match status.pid {
+        Some(pid) => println!("Last started with pid {}", pid),
+        None => {
+            println!("The daemon hasn't run yet. Start it with `grunt daemon`");
+            return;
+        }
+    }
+    if let Some(since) = &status.running_since {
+        println!("Updating since {}", format_time(since));
+    }
+    if let Some(run) = &status.last_run {
+        let outcome = match &run.outcome {
+            Outcome::Succeeded => format!("{} updated", run.updated),
+            Outcome::Failed(err) => format!("failed: {}", err),
+            Outcome::TimedOut => "stuck and cancelled".to_string(),
+            Outcome::Skipped => "skipped, outside of update windows".to_string(),
+        };
+        let attempts = if run.attempts > 1 {
+            format!(" after {} attempts", run.attempts)
+        } else {
+            String::new()
+        };
+        println!(
+            "Last run: {}, {}{}",
+            format_time(&run.started),
+            outcome,
+            attempts
+        );
+    }
+    if let Some(next) = &status.next_run {
+        println!("Next run: {}", format_time(next));
+    }
+    if !status.incidents.is_empty() {
+        println!("\x1B[1m{} recent incidents:\x1B[0m", status.incidents.len());
+        for incident in status.incidents.iter() {
+            println!("{}  {}", format_time(&incident.time), incident.message);
+        }
+    }
+}
+
+/// Name used for the scheduled job on every platform
+const JOB_NAME: &str = "grunt-update";
+
+/// Schedules `grunt daemon --once` every `interval` minutes using the OS scheduler
+/// Returns where the job was installed
+#[cfg(target_os = "linux")]
+pub fn install(interval: u64) -> std::io::Result<String> {
+    let dir = systemd_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let service = format!(
+        "[Unit]\n\
+         Description=Install WoW addon updates with grunt\n\
+         \n\
+         [Service]\n\
+         Type=oneshot\n\
+         ExecStart=\"{}\" daemon --once\n",
+        exe_path()?
+    );
+    let timer = format!(
+        "[Unit]\n\
+         Description=Run grunt every {} minutes\n\
+         \n\
+         [Timer]\n\
+         OnBootSec=5min\n\
+         OnUnitActiveSec={}min\n\
+         Persistent=true\n\
+         \n\
+         [Install]\n\
+         WantedBy=timers.target\n",
+        interval, interval
+    );
+    std::fs::write(dir.join(format!("{}.service", JOB_NAME)), service)?;
+    std::fs::write(dir.join(format!("{}.timer", JOB_NAME)), timer)?;
+    run_command("systemctl", &["--user", "daemon-reload"])?;
+    run_command(
+        "systemctl",
+        &["--user", "enable", "--now", &format!("{}.timer", JOB_NAME)],
+    )?;
+    Ok(format!("systemd user timer {}.timer", JOB_NAME))
+}
+
+/// Removes the job added by `install`
+#[cfg(target_os = "linux")]
+pub fn uninstall() -> std::io::Result<()> {
+    let dir = systemd_dir()?;
+    let timer = format!("{}.timer", JOB_NAME);
+    // Fails if it was never enabled
+    let _ = run_command("systemctl", &["--user", "disable", "--now", &timer]);
+    for file in [timer, format!("{}.service", JOB_NAME)].iter() {
+        let path = dir.join(file);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+    run_command("systemctl", &["--user", "daemon-reload"])
+}
+
+#[cfg(target_os = "linux")]
+fn systemd_dir() -> std::io::Result<std::path::PathBuf> {
+    let dirs = directories::BaseDirs::new().ok_or_else(|| not_found("config dir"))?;
+    Ok(dirs.config_dir().join("systemd").join("user"))
+}
+
+#[cfg(target_os = "macos")]
+pub fn install(interval: u64) -> std::io::Result<String> {
+    let path = launchd_plist()?;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    let plist = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>daemon</string>
+        <string>--once</string>
+    </array>
+    <key>StartInterval</key>
+    <integer>{}</integer>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+        JOB_NAME,
+        exe_path()?,
+        interval * 60
+    );
+    std::fs::write(&path, plist)?;
+    run_command("launchctl", &["load", "-w", &path.to_string_lossy()])?;
+    Ok(format!("launchd agent {}", path.display()))
+}
+
+#[cfg(target_os = "macos")]
+pub fn uninstall() -> std::io::Result<()> {
+    let path = launchd_plist()?;
+    if path.exists() {
+        // Fails if it isn't loaded
+        let _ = run_command("launchctl", &["unload", "-w", &path.to_string_lossy()]);
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn launchd_plist() -> std::io::Result<std::path::PathBuf> {
+    let dirs = directories::BaseDirs::new().ok_or_else(|| not_found("home dir"))?;
+    Ok(dirs
+        .home_dir()
+        .join("Library/LaunchAgents")
+        .join(format!("{}.plist", JOB_NAME)))
+}
+
+#[cfg(windows)]
+pub fn install(interval: u64) -> std::io::Result<String> {
+    let command = format!("\"{}\" daemon --once", exe_path()?);
+    run_command(
+        "schtasks",
+        &[
+            "/Create",
+            "/F",
+            "/SC",
+            "MINUTE",
+            "/MO",
+            &interval.to_string(),
+            "/TN",
+            JOB_NAME,
+            "/TR",
+            &command,
+        ],
+    )?;
+    Ok(format!("scheduled task {}", JOB_NAME))
+}
+
+#[cfg(windows)]
+pub fn uninstall() -> std::io::Result<()> {
+    run_command("schtasks", &["/Delete", "/F", "/TN", JOB_NAME])
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn install(_interval: u64) -> std::io::Result<String> {
+    Err(std::io::Error::other(
+        "No supported scheduler on this platform. Run `grunt daemon` instead",
+    ))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn uninstall() -> std::io::Result<()> {
+    install(0).map(|_| ())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn exe_path() -> std::io::Result<String> {
+    Ok(std::env::current_exe()?.to_string_lossy().to_string())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn not_found(what: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("Couldn't find {}", what),
+    )
+}
+
+/// Runs a scheduler command, failing if it exits unsuccessfully
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn run_command(program: &str, args: &[&str]) -> std::io::Result<()> {
+    let status = Command::new(program).args(args).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "`{} {}` exited with {}",
+            program,
+            args.join(" "),
+            status
+        )))
+    }
+}
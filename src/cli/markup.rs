@@ -0,0 +1,501 @@
+//! Renders addon descriptions, which are HTML or Markdown, as terminal text
+
+const BOLD: &str = "\x1B[1m";
+const ITALIC: &str = "\x1B[3m";
+const RESET: &str = "\x1B[0m";
+
+/// Renders HTML, or Markdown if there are no tags, as text wrapped to `width`
+/// Styles are ANSI escapes, which are left out if `styled` is false
+pub fn render(source: &str, width: usize, styled: bool) -> String {
+    let mut writer = Writer::new(styled);
+    if looks_like_html(source) {
+        writer.html(source);
+    } else {
+        writer.markdown(source);
+    }
+    wrap(writer.out.trim(), width)
+}
+
+/// Width to wrap text to, from `$COLUMNS` if set
+pub fn width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(80)
+}
+
+/// Whether there's a `<` starting a tag, which `a < b` in Markdown doesn't
+fn looks_like_html(source: &str) -> bool {
+    source.match_indices('<').any(|(i, _)| {
+        source[i + 1..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '/' || c == '!')
+    })
+}
+
+/// Builds up text, collapsing whitespace and tracking styles
+struct Writer {
+    out: String,
+    styled: bool,
+    bold: usize,
+    italic: usize,
+    /// Depth of tags whose content is hidden, like `<script>`
+    hidden: usize,
+    pre: bool,
+    /// Whether whitespace was skipped since the last text
+    space: bool,
+    /// Item counts of open lists, `None` for unordered ones
+    lists: Vec<Option<usize>>,
+    /// Link targets and where their text starts in `out`
+    links: Vec<(Option<String>, usize)>,
+}
+
+impl Writer {
+    fn new(styled: bool) -> Self {
+        Writer {
+            out: String::new(),
+            styled,
+            bold: 0,
+            italic: 0,
+            hidden: 0,
+            pre: false,
+            space: false,
+            lists: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.out.is_empty() || self.out.ends_with('\n') || self.out.ends_with(' ')
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.hidden > 0 {
+            return;
+        }
+        if self.pre {
+            self.out.push_str(text);
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() {
+                self.space = true;
+                continue;
+            }
+            if self.space && !self.at_line_start() {
+                self.out.push(' ');
+            }
+            self.space = false;
+            self.out.push(c);
+        }
+    }
+
+    /// Ends the line, leaving `count` line breaks in a row
+    fn newlines(&mut self, count: usize) {
+        if self.hidden > 0 {
+            return;
+        }
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.out.truncate(trimmed);
+        self.space = false;
+        if self.out.is_empty() {
+            return;
+        }
+        let existing = self.out.len() - self.out.trim_end_matches('\n').len();
+        for _ in existing..count {
+            self.out.push('\n');
+        }
+    }
+
+    /// Writes the escapes for the current style
+    fn style(&mut self) {
+        if !self.styled || self.hidden > 0 {
+            return;
+        }
+        self.out.push_str(RESET);
+        if self.bold > 0 {
+            self.out.push_str(BOLD);
+        }
+        if self.italic > 0 {
+            self.out.push_str(ITALIC);
+        }
+    }
+
+    fn set_bold(&mut self, on: bool) {
+        if on {
+            self.bold += 1;
+        } else {
+            self.bold = self.bold.saturating_sub(1);
+        }
+        self.style();
+    }
+
+    fn set_italic(&mut self, on: bool) {
+        if on {
+            self.italic += 1;
+        } else {
+            self.italic = self.italic.saturating_sub(1);
+        }
+        self.style();
+    }
+
+    fn list_item(&mut self) {
+        self.newlines(1);
+        let depth = self.lists.len().saturating_sub(1);
+        let marker = match self.lists.last_mut() {
+            Some(Some(count)) => {
+                *count += 1;
+                format!("{}. ", count)
+            }
+            _ => "\u{2022} ".to_string(),
+        };
+        self.out.push_str(&"  ".repeat(depth));
+        self.out.push_str(&marker);
+    }
+
+    /// Ends a link, showing where it goes if the text doesn't
+    fn end_link(&mut self, href: Option<String>, start: usize) {
+        let href = match href {
+            Some(href) if href.starts_with("http") => href,
+            _ => return,
+        };
+        if self.out[start..].trim() != href {
+            self.text(&format!(" ({})", href));
+        }
+    }
+
+    fn html(&mut self, html: &str) {
+        let mut rest = html;
+        while let Some(start) = rest.find('<') {
+            self.text(&decode_entities(&rest[..start]));
+            rest = &rest[start..];
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+                continue;
+            }
+            let end = match rest.find('>') {
+                Some(end) => end,
+                None => break,
+            };
+            self.tag(&rest[1..end]);
+            rest = &rest[end + 1..];
+        }
+        self.text(&decode_entities(rest));
+    }
+
+    fn tag(&mut self, tag: &str) {
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match (name.as_str(), closing) {
+            ("script", _) | ("style", _) => {
+                if closing {
+                    self.hidden = self.hidden.saturating_sub(1);
+                } else {
+                    self.hidden += 1;
+                }
+            }
+            ("h1", _) | ("h2", _) | ("h3", _) | ("h4", _) | ("h5", _) | ("h6", _) => {
+                if !closing {
+                    self.newlines(2);
+                }
+                self.set_bold(!closing);
+                if closing {
+                    self.newlines(2);
+                }
+            }
+            ("p", _) | ("div", _) | ("blockquote", _) | ("table", _) => self.newlines(2),
+            ("pre", _) => {
+                self.newlines(2);
+                self.pre = !closing;
+            }
+            ("br", _) => {
+                let trimmed = self.out.trim_end_matches(' ').len();
+                self.out.truncate(trimmed);
+                self.out.push('\n');
+                self.space = false;
+            }
+            ("hr", _) => {
+                self.newlines(2);
+                self.text("\u{2500}\u{2500}\u{2500}");
+                self.newlines(2);
+            }
+            ("ul", false) | ("ol", false) => {
+                self.newlines(if self.lists.is_empty() { 2 } else { 1 });
+                self.lists.push(if name == "ol" { Some(0) } else { None });
+            }
+            ("ul", true) | ("ol", true) => {
+                self.lists.pop();
+                self.newlines(if self.lists.is_empty() { 2 } else { 1 });
+            }
+            ("li", false) => self.list_item(),
+            ("tr", _) => self.newlines(1),
+            ("td", false) | ("th", false) if !self.at_line_start() => self.out.push_str("  "),
+            ("b", _) | ("strong", _) => self.set_bold(!closing),
+            ("i", _) | ("em", _) => self.set_italic(!closing),
+            ("a", false) => self.links.push((attribute(tag, "href"), self.out.len())),
+            ("a", true) => {
+                if let Some((href, start)) = self.links.pop() {
+                    self.end_link(href, start);
+                }
+            }
+            ("img", false) => {
+                if let Some(alt) = attribute(tag, "alt").filter(|alt| !alt.is_empty()) {
+                    self.text(&format!("[{}]", alt));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn markdown(&mut self, markdown: &str) {
+        let mut in_code = false;
+        for line in markdown.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with("```") {
+                self.newlines(2);
+                in_code = !in_code;
+                self.pre = in_code;
+                continue;
+            }
+            if in_code {
+                self.text(line);
+                self.text("\n");
+                continue;
+            }
+            let heading = trimmed.trim_start_matches('#');
+            if trimmed.is_empty() {
+                // Lines after a list item continue it until a blank line
+                self.lists.clear();
+                self.newlines(2);
+            } else if trimmed.starts_with('#') && heading.starts_with(' ') {
+                self.newlines(2);
+                self.set_bold(true);
+                self.inline(heading);
+                self.set_bold(false);
+                self.newlines(2);
+            } else if let Some(item) = ["- ", "* ", "+ "]
+                .iter()
+                .find_map(|bullet| trimmed.strip_prefix(bullet))
+            {
+                if self.lists.is_empty() {
+                    self.lists.push(None);
+                }
+                self.list_item();
+                self.inline(item);
+            } else {
+                self.inline(trimmed);
+                self.text("\n");
+            }
+        }
+    }
+
+    /// Markdown inside a line: bold, links, images and code
+    fn inline(&mut self, line: &str) {
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            if let Some(after) = rest.strip_prefix("**").or_else(|| rest.strip_prefix("__")) {
+                self.set_bold(self.bold == 0);
+                rest = after;
+                continue;
+            }
+            if let Some(after) = rest.strip_prefix('!') {
+                if let Some((alt, _, after)) = markdown_link(after) {
+                    if !alt.is_empty() {
+                        self.text(&format!("[{}]", alt));
+                    }
+                    rest = after;
+                    continue;
+                }
+            }
+            if let Some((text, url, after)) = markdown_link(rest) {
+                let start = self.out.len();
+                self.inline(text);
+                self.end_link(Some(url.to_string()), start);
+                rest = after;
+                continue;
+            }
+            if let Some(after) = rest.strip_prefix('`') {
+                rest = after;
+                continue;
+            }
+            let next = rest[c.len_utf8()..]
+                .find(['*', '_', '!', '[', '`'])
+                .map_or(rest.len(), |i| i + c.len_utf8());
+            self.text(&rest[..next]);
+            rest = &rest[next..];
+        }
+    }
+}
+
+/// Splits `[text](url)rest`
+fn markdown_link(text: &str) -> Option<(&str, &str, &str)> {
+    let inner = text.strip_prefix('[')?;
+    let (label, after) = inner.split_once("](")?;
+    let (url, rest) = after.split_once(')')?;
+    Some((label, url, rest))
+}
+
+/// Gets an attribute's value from the inside of a tag like `a href="..."`
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let pattern = format!("{}=", name);
+    let start = lower
+        .match_indices(&pattern)
+        .find(|(i, _)| lower[..*i].ends_with(char::is_whitespace))?
+        .0
+        + pattern.len();
+    let value = &tag[start..];
+    let value = match value.chars().next()? {
+        quote @ ('"' | '\'') => value[1..].split(quote).next()?,
+        _ => value.split(char::is_whitespace).next()?,
+    };
+    Some(decode_entities(value))
+}
+
+/// Replaces entities like `&amp;` and `&#39;`
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((entity(&rest[1..end + 1])?, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn entity(name: &str) -> Option<char> {
+    if let Some(code) = name.strip_prefix('#') {
+        let code = match code.strip_prefix('x').or_else(|| code.strip_prefix('X')) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => code.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "hellip" => '\u{2026}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201C}',
+        "rdquo" => '\u{201D}',
+        "copy" => '\u{A9}',
+        _ => return None,
+    })
+}
+
+/// Wraps long lines at spaces, indenting the rest of list items
+fn wrap(text: &str, width: usize) -> String {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let body = line.trim_start_matches(' ');
+        let body = ["\u{2022} "]
+            .iter()
+            .find_map(|bullet| body.strip_prefix(bullet))
+            .or_else(|| {
+                let digits =
+                    body.len() - body.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                body[digits..].strip_prefix(". ").filter(|_| digits > 0)
+            })
+            .unwrap_or(body);
+        let prefix = &line[..line.len() - body.len()];
+        let indent = " ".repeat(prefix.chars().count());
+
+        let mut current = prefix.to_string();
+        let mut current_width = indent.len();
+        let mut empty = true;
+        for word in body.split(' ') {
+            let word_width = visible_width(word);
+            if !empty && current_width + 1 + word_width > width {
+                lines.push(current);
+                current = indent.clone();
+                current_width = indent.len();
+                empty = true;
+            }
+            if !empty {
+                current.push(' ');
+                current_width += 1;
+            }
+            current.push_str(word);
+            current_width += word_width;
+            empty = false;
+        }
+        lines.push(current);
+    }
+    lines.join("\n")
+}
+
+/// Chars shown in a terminal, skipping escape sequences
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut in_escape = false;
+    for c in text.chars() {
+        match c {
+            '\x1B' => in_escape = true,
+            'm' if in_escape => in_escape = false,
+            _ if in_escape => (),
+            _ => width += 1,
+        }
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html() {
+        let html = "<h2>Setup</h2><p>Type <b>/dbm</b> &amp; see the\n<a href=\"https://example.com/wiki\">wiki</a>.</p>\
+            <ul><li>One</li><li>Two<br>lines</li></ul><script>x()</script>";
+        assert_eq!(
+            render(html, 80, false),
+            "Setup\n\nType /dbm & see the wiki (https://example.com/wiki).\n\n\u{2022} One\n\u{2022} Two\nlines"
+        );
+        assert_eq!(render("<b>Bold</b>", 80, true), "\x1B[0m\x1B[1mBold\x1B[0m");
+    }
+
+    #[test]
+    fn test_markdown() {
+        let markdown = "# Title\nSome **bold** text\nover lines, see [docs](https://example.com) & a < b\n\n- One\n- Two";
+        assert_eq!(
+            render(markdown, 80, false),
+            "Title\n\nSome bold text over lines, see docs (https://example.com) & a < b\n\n\u{2022} One\n\u{2022} Two"
+        );
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(
+            wrap("\u{2022} one two three four", 12),
+            "\u{2022} one two\n  three four"
+        );
+    }
+}
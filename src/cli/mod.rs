@@ -0,0 +1,9 @@
+pub mod crash;
+pub mod daemon;
+pub mod markup;
+pub mod output;
+pub mod pager;
+pub mod progress;
+pub mod render;
+pub mod rpc;
+pub mod serve;
@@ -0,0 +1,407 @@
+use chrono::{DateTime, Local};
+use grunt::addon::{Addon, AddonType, Deprecation};
+use grunt::diff::AddonDiff;
+use grunt::error::GruntError;
+use grunt::flavor::Flavor;
+use grunt::plan::Updateable;
+use grunt::settings::UntrackedAction;
+use grunt::table::{AddonRow, Column};
+use grunt::{FlavorSupport, Grunt, Redistribution, SearchResult};
+use serde::Serialize;
+
+/// Version of the JSON output. Bumped when a field is removed or changes meaning,
+/// adding fields doesn't change it
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Wraps the output of a command with the schema version
+#[derive(Serialize)]
+pub struct Output<T> {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub data: T,
+}
+
+impl<T: Serialize> Output<T> {
+    pub fn new(data: T) -> Self {
+        Output {
+            schema_version: SCHEMA_VERSION,
+            data,
+        }
+    }
+
+    pub fn value(&self) -> serde_json::Value {
+        serde_json::to_value(self).expect("Error serializing output")
+    }
+}
+
+/// Output of `grunt list`
+#[derive(Serialize)]
+pub struct ListOutput {
+    pub root_dir: String,
+    pub flavor: Flavor,
+    pub addons: Vec<AddonOutput>,
+    pub untracked: Vec<String>,
+    /// When and why untracked dirs last failed to resolve
+    pub unresolved: Vec<UnresolvedOutput>,
+    pub local: Vec<String>,
+    pub removal_candidates: Vec<String>,
+    #[serde(skip)]
+    pub columns: Vec<Column>,
+    #[serde(skip)]
+    pub sort: Column,
+}
+
+impl ListOutput {
+    /// Collects the addons and untracked dirs, to be shown with `columns` sorted by `sort`
+    pub fn new(grunt: &Grunt, columns: Vec<Column>, sort: Column) -> Result<Self, GruntError> {
+        let rows = grunt.addon_rows();
+        let addons = grunt
+            .addons()
+            .iter()
+            .zip(rows.iter())
+            .map(|(addon, row)| AddonOutput::new(addon, row))
+            .collect();
+        let untracked = grunt.find_untracked()?;
+        let with_action = |action| -> Vec<String> {
+            untracked
+                .iter()
+                .filter(|dir| grunt.untracked_action(dir) == action)
+                .cloned()
+                .collect()
+        };
+        let unresolved = untracked
+            .iter()
+            .filter_map(|dir| {
+                let unresolved = grunt.unresolved_dir(dir)?;
+                Some(UnresolvedOutput {
+                    dir: dir.clone(),
+                    attempted: unresolved.attempted.map(DateTime::from),
+                    reason: unresolved.reason.clone(),
+                })
+            })
+            .collect();
+        Ok(ListOutput {
+            root_dir: grunt.root_dir().to_string_lossy().to_string(),
+            flavor: *grunt.flavor(),
+            addons,
+            untracked: with_action(None),
+            unresolved,
+            local: with_action(Some(UntrackedAction::TreatAsLocal)),
+            removal_candidates: with_action(Some(UntrackedAction::AutoRemoveCandidates)),
+            columns,
+            sort,
+        })
+    }
+}
+
+/// An untracked dir that `resolve` couldn't find an addon for
+#[derive(Serialize)]
+pub struct UnresolvedOutput {
+    pub dir: String,
+    /// Unknown if it was tried by an older version
+    pub attempted: Option<DateTime<Local>>,
+    pub reason: Option<String>,
+}
+
+/// An addon in `list` and the output of `grunt info`
+#[derive(Serialize)]
+pub struct AddonOutput {
+    pub name: String,
+    /// `source:id` identifier
+    pub identifier: String,
+    pub source: AddonType,
+    pub id: String,
+    pub slug: Option<String>,
+    pub version: String,
+    /// Newest version available, if it has been checked
+    pub latest: Option<String>,
+    pub dirs: Vec<String>,
+    /// Size on disk in bytes
+    pub size: u64,
+    pub updated: Option<DateTime<Local>>,
+    pub notes: Option<String>,
+    /// Set if the addon won't get updates anymore
+    pub deprecation: Option<Deprecation>,
+    /// Kept at its installed version by `grunt pin`
+    pub pinned: bool,
+    /// Downloaded and installed before other addons when updating
+    pub priority: bool,
+    /// HTML description, only fetched by `grunt info --full`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl AddonOutput {
+    pub fn new(addon: &Addon, row: &AddonRow) -> Self {
+        AddonOutput {
+            name: addon.name().clone(),
+            identifier: addon.identifier(),
+            source: addon.addon_type().clone(),
+            id: addon.addon_id().clone(),
+            slug: addon.slug().clone(),
+            version: addon.version().clone(),
+            latest: row.latest.clone(),
+            dirs: addon.dirs().clone(),
+            size: row.size,
+            updated: row.updated,
+            notes: addon.notes().clone(),
+            deprecation: row.deprecation,
+            pinned: row.pinned,
+            priority: *addon.priority(),
+            description: None,
+        }
+    }
+
+    /// The fields shown in tables
+    pub fn to_row(&self) -> AddonRow {
+        AddonRow {
+            name: self.name.clone(),
+            source: self.identifier.clone(),
+            version: self.version.clone(),
+            latest: self.latest.clone(),
+            size: self.size,
+            updated: self.updated,
+            notes: self.notes.clone(),
+            deprecation: self.deprecation,
+            pinned: self.pinned,
+        }
+    }
+}
+
+/// Output of `grunt license`
+#[derive(Serialize)]
+pub struct LicensesOutput {
+    pub addons: Vec<LicenseOutput>,
+}
+
+#[derive(Serialize)]
+pub struct LicenseOutput {
+    pub name: String,
+    pub identifier: String,
+    pub license: Option<String>,
+    pub redistribution: Redistribution,
+}
+
+/// Output of `grunt search`
+#[derive(Serialize)]
+pub struct SearchOutput {
+    pub query: String,
+    pub results: Vec<SearchResult>,
+}
+
+/// Output of `grunt outdated`
+#[derive(Serialize)]
+pub struct OutdatedOutput {
+    pub addons: Vec<OutdatedAddonOutput>,
+}
+
+#[derive(Serialize)]
+pub struct OutdatedAddonOutput {
+    pub name: String,
+    pub identifier: String,
+    pub current_version: String,
+    pub new_version: String,
+    /// Download size in bytes, only known for Curse addons
+    pub size: Option<u64>,
+}
+
+impl OutdatedOutput {
+    pub fn new(grunt: &Grunt, outdated: &[Updateable]) -> Self {
+        let addons = outdated
+            .iter()
+            .map(|upd| {
+                let addon = &grunt.addons()[upd.index];
+                OutdatedAddonOutput {
+                    name: upd.name.clone(),
+                    identifier: addon.identifier(),
+                    current_version: addon.version().clone(),
+                    new_version: upd.new_version.clone(),
+                    size: upd.size,
+                }
+            })
+            .collect();
+        OutdatedOutput { addons }
+    }
+}
+
+/// Output of `grunt diff`
+#[derive(Serialize)]
+pub struct DiffOutput {
+    pub name: String,
+    pub identifier: String,
+    pub version: String,
+    #[serde(flatten)]
+    pub diff: AddonDiff,
+}
+
+impl DiffOutput {
+    pub fn new(grunt: &Grunt, index: usize, diff: AddonDiff) -> Self {
+        let addon = &grunt.addons()[index];
+        DiffOutput {
+            name: addon.name().clone(),
+            identifier: addon.identifier(),
+            version: addon.version().clone(),
+            diff,
+        }
+    }
+}
+
+/// Output of `grunt compat`
+#[derive(Serialize)]
+pub struct CompatOutput {
+    pub name: String,
+    pub identifier: String,
+    /// Flavor of the AddOns dir
+    pub current_flavor: Flavor,
+    pub flavors: Vec<FlavorOutput>,
+}
+
+#[derive(Serialize)]
+pub struct FlavorOutput {
+    pub flavor: String,
+    /// Readable name of flavors grunt knows about
+    pub name: Option<String>,
+    pub supported: bool,
+    pub game_versions: Vec<String>,
+    pub latest_file: Option<String>,
+}
+
+impl CompatOutput {
+    pub fn new(grunt: &Grunt, index: usize, support: Vec<FlavorSupport>) -> Self {
+        let addon = &grunt.addons()[index];
+        CompatOutput {
+            name: addon.name().clone(),
+            identifier: addon.identifier(),
+            current_flavor: *grunt.flavor(),
+            flavors: support
+                .into_iter()
+                .map(|support| FlavorOutput {
+                    name: support.known.map(|flavor| flavor.name().to_string()),
+                    supported: !support.game_versions.is_empty(),
+                    flavor: support.flavor,
+                    game_versions: support.game_versions,
+                    latest_file: support.latest_file,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Output of `grunt conflicts`
+#[derive(Serialize)]
+pub struct ConflictsOutput {
+    pub conflicts: Vec<ConflictOutput>,
+}
+
+impl ConflictsOutput {
+    pub fn new(grunt: &Grunt) -> Self {
+        let conflicts = grunt
+            .check_conflicts()
+            .into_iter()
+            .map(|conflict| {
+                let addons = [
+                    &grunt.addons()[conflict.addon_a_index],
+                    &grunt.addons()[conflict.addon_b_index],
+                ];
+                ConflictOutput {
+                    addons: addons.iter().map(|addon| addon.identifier()).collect(),
+                    names: addons.iter().map(|addon| addon.name().clone()).collect(),
+                    dir: conflict.dir,
+                    files: conflict.files,
+                }
+            })
+            .collect();
+        ConflictsOutput { conflicts }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ConflictOutput {
+    pub dir: String,
+    /// Identifiers of the addons claiming the dir
+    pub addons: Vec<String>,
+    pub names: Vec<String>,
+    /// Files in the dir that both addons installed
+    pub files: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addon_output() -> AddonOutput {
+        AddonOutput {
+            name: "Details".to_string(),
+            identifier: "curse:61284".to_string(),
+            source: AddonType::Curse,
+            id: "61284".to_string(),
+            slug: Some("details".to_string()),
+            version: "3106483".to_string(),
+            latest: Some("3120000".to_string()),
+            dirs: vec!["Details".to_string(), "Details_DataStorage".to_string()],
+            size: 4096,
+            updated: None,
+            notes: None,
+            deprecation: Some(Deprecation::Abandoned),
+            pinned: true,
+            priority: false,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_list_schema() {
+        let output = ListOutput {
+            root_dir: "/wow/_retail_/Interface/AddOns".to_string(),
+            flavor: Flavor::Retail,
+            addons: vec![addon_output()],
+            untracked: vec!["MyScripts".to_string()],
+            unresolved: vec![UnresolvedOutput {
+                dir: "MyScripts".to_string(),
+                attempted: None,
+                reason: Some("No Curse fingerprint match".to_string()),
+            }],
+            local: Vec::new(),
+            removal_candidates: Vec::new(),
+            columns: Column::DEFAULT.to_vec(),
+            sort: Column::Name,
+        };
+        insta::assert_json_snapshot!(Output::new(output).value());
+    }
+
+    #[test]
+    fn test_info_schema() {
+        let mut output = addon_output();
+        output.description = Some("<p>Damage meter</p>".to_string());
+        insta::assert_json_snapshot!(Output::new(output).value());
+    }
+
+    #[test]
+    fn test_license_schema() {
+        let output = LicensesOutput {
+            addons: vec![LicenseOutput {
+                name: "Details".to_string(),
+                identifier: "curse:61284".to_string(),
+                license: Some("All Rights Reserved".to_string()),
+                redistribution: Redistribution::Forbidden,
+            }],
+        };
+        insta::assert_json_snapshot!(Output::new(output).value());
+    }
+
+    #[test]
+    fn test_search_schema() {
+        let output = SearchOutput {
+            query: "details".to_string(),
+            results: vec![SearchResult {
+                identifier: "curse:details".to_string(),
+                id: 61284,
+                name: "Details! Damage Meter".to_string(),
+                summary: "Computes damage and healing".to_string(),
+                downloads: 250000000,
+                installed: true,
+            }],
+        };
+        insta::assert_json_snapshot!(Output::new(output).value());
+    }
+}
@@ -0,0 +1,49 @@
+//! Shows long output a screen at a time instead of scrolling it all past
+
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Pagers tried in order when `$PAGER` isn't set
+/// `less` quits straight away if the text fits on one screen and keeps colors
+const PAGERS: [&str; 2] = ["less -RFX", "more"];
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Prints text directly from now on, for `--no-pager`
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Shows text through `$PAGER`, `less` or `more` when stdout is a terminal, where `/`
+/// searches it. Prints it directly otherwise or if no pager can be started
+pub fn page(text: &str) {
+    if std::io::stdout().is_terminal() && !DISABLED.load(Ordering::Relaxed) {
+        let pagers = match std::env::var("PAGER") {
+            // An empty `$PAGER` turns paging off, like git
+            Ok(pager) if pager.trim().is_empty() => Vec::new(),
+            Ok(pager) => vec![pager],
+            Err(_) => PAGERS.iter().map(|pager| pager.to_string()).collect(),
+        };
+        for pager in pagers {
+            let mut parts = pager.split_whitespace();
+            let program = match parts.next() {
+                Some(program) => program,
+                None => continue,
+            };
+            let child = Command::new(program)
+                .args(parts)
+                .stdin(Stdio::piped())
+                .spawn();
+            if let Ok(mut child) = child {
+                if let Some(mut stdin) = child.stdin.take() {
+                    // The pager closing early isn't an error
+                    let _ = writeln!(stdin, "{}", text);
+                }
+                let _ = child.wait();
+                return;
+            }
+        }
+    }
+    println!("{}", text);
+}
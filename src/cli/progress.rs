@@ -0,0 +1,80 @@
+//! Progress bars of the addons being downloaded and installed by `grunt update`
+
+use grunt::UpdateProgress;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+
+/// Used until the server says how big the download is
+const SPINNER_TEMPLATE: &str = "{spinner} {prefix:30!} {bytes:>10} {msg}";
+const BAR_TEMPLATE: &str = "{spinner} {prefix:30!} {bar:30} {bytes:>10}/{total_bytes} {msg}";
+
+/// A bar for each addon being updated, drawn to stderr when it's a terminal
+pub struct UpdateBars {
+    multi: MultiProgress,
+    bars: HashMap<String, ProgressBar>,
+}
+
+impl UpdateBars {
+    pub fn new() -> Self {
+        UpdateBars {
+            multi: MultiProgress::new(),
+            bars: HashMap::new(),
+        }
+    }
+
+    pub fn handle(&mut self, event: UpdateProgress) {
+        match event {
+            UpdateProgress::PriorityInstalled { names, remaining } => {
+                if !names.is_empty() && remaining > 0 {
+                    self.println(format!(
+                        "Installed {}. Safe to launch the game while {} more update",
+                        names.join(", "),
+                        remaining
+                    ));
+                }
+            }
+            UpdateProgress::DownloadStarted { name } => {
+                let bar = self.multi.add(ProgressBar::new_spinner());
+                bar.set_style(ProgressStyle::with_template(SPINNER_TEMPLATE).unwrap());
+                bar.set_prefix(name.clone());
+                bar.set_message("downloading");
+                self.bars.insert(name, bar);
+            }
+            UpdateProgress::DownloadProgress { name, bytes, total } => {
+                if let Some(bar) = self.bars.get(&name) {
+                    if let Some(total) = total.filter(|total| bar.length() != Some(*total)) {
+                        bar.set_style(ProgressStyle::with_template(BAR_TEMPLATE).unwrap());
+                        bar.set_length(total);
+                    }
+                    bar.set_position(bytes);
+                }
+            }
+            UpdateProgress::Extracting { name } => {
+                if let Some(bar) = self.bars.get(&name) {
+                    bar.set_message("extracting");
+                }
+            }
+            UpdateProgress::Installed { name } => {
+                if let Some(bar) = self.bars.get(&name) {
+                    bar.finish_with_message("installed");
+                }
+            }
+        }
+    }
+
+    /// Stops the bars of updates that failed, which never finished
+    pub fn finish(self) {
+        for bar in self.bars.values().filter(|bar| !bar.is_finished()) {
+            bar.abandon_with_message("failed");
+        }
+    }
+
+    /// Prints above the bars, or normally if they're hidden
+    fn println(&self, line: String) {
+        if self.multi.is_hidden() {
+            println!("{}", line);
+        } else {
+            let _ = self.multi.println(line);
+        }
+    }
+}
@@ -0,0 +1,533 @@
+use crate::cli::markup;
+use crate::cli::output::{
+    AddonOutput, CompatOutput, ConflictsOutput, DiffOutput, LicensesOutput, ListOutput,
+    OutdatedOutput, Output, SearchOutput, UnresolvedOutput,
+};
+use chrono::Local;
+use comfy_table::{Attribute, Cell};
+use grunt::table;
+use grunt::Redistribution;
+use serde_json::Value;
+
+/// Output that can be shown to people as well as serialized
+pub trait Renderable {
+    /// The output with its schema version for json and yaml
+    fn value(&self) -> Value;
+    /// Human readable output with tables
+    fn table(&self) -> String;
+    /// Tab separated lines for shell scripts
+    fn plain(&self) -> String;
+}
+
+/// Turns command output into text in a specific format
+pub trait Renderer {
+    fn render(&self, output: &dyn Renderable) -> String;
+    /// Whether the grunt header and progress messages should be printed
+    fn is_human(&self) -> bool {
+        false
+    }
+}
+
+pub struct TableRenderer;
+pub struct PlainRenderer;
+pub struct JsonRenderer;
+pub struct YamlRenderer;
+
+impl Renderer for TableRenderer {
+    fn render(&self, output: &dyn Renderable) -> String {
+        output.table()
+    }
+
+    fn is_human(&self) -> bool {
+        true
+    }
+}
+
+impl Renderer for PlainRenderer {
+    fn render(&self, output: &dyn Renderable) -> String {
+        output.plain()
+    }
+}
+
+impl Renderer for JsonRenderer {
+    fn render(&self, output: &dyn Renderable) -> String {
+        serde_json::to_string_pretty(&output.value()).expect("Error serializing output")
+    }
+}
+
+impl Renderer for YamlRenderer {
+    fn render(&self, output: &dyn Renderable) -> String {
+        let yaml = serde_yaml::to_string(&output.value()).expect("Error serializing output");
+        yaml.trim_end().to_string()
+    }
+}
+
+/// Picks a renderer from the value of `--output`
+pub fn renderer(format: &str) -> Box<dyn Renderer> {
+    match format {
+        "table" => Box::new(TableRenderer),
+        "plain" => Box::new(PlainRenderer),
+        "json" => Box::new(JsonRenderer),
+        "yaml" => Box::new(YamlRenderer),
+        _ => unreachable!(),
+    }
+}
+
+/// A line warning that an addon won't be updated
+pub fn deprecation_warning(addon: &AddonOutput) -> String {
+    match addon.deprecation {
+        Some(deprecation) => format!(
+            "{} ({}) {} and won't be updated",
+            addon.name,
+            addon.identifier,
+            deprecation.describe()
+        ),
+        None => String::new(),
+    }
+}
+
+/// Like `tried 3 days ago, no Curse fingerprint match`
+fn unresolved_context(unresolved: &UnresolvedOutput) -> String {
+    let tried = match unresolved.attempted {
+        Some(attempted) => format!("tried {}", table::format_age(Local::now() - attempted)),
+        None => "tried before".to_string(),
+    };
+    match &unresolved.reason {
+        Some(reason) => {
+            let mut chars = reason.chars();
+            let first = chars.next().map(|c| c.to_lowercase().to_string());
+            format!("{}, {}{}", tried, first.unwrap_or_default(), chars.as_str())
+        }
+        None => tried,
+    }
+}
+
+impl Renderable for ListOutput {
+    fn value(&self) -> Value {
+        Output::new(self).value()
+    }
+
+    fn table(&self) -> String {
+        let mut out = format!("\x1B[1m{} Addons:\x1B[0m\n", self.addons.len());
+        if !self.addons.is_empty() {
+            let mut rows: Vec<_> = self.addons.iter().map(|addon| addon.to_row()).collect();
+            out += &table::render_addons(&mut rows, &self.columns, self.sort);
+            out += "\n";
+        }
+        let deprecated: Vec<&AddonOutput> = self
+            .addons
+            .iter()
+            .filter(|addon| addon.deprecation.is_some())
+            .collect();
+        if !deprecated.is_empty() {
+            out += &format!("\x1B[1m{} Deprecated:\x1B[0m\n", deprecated.len());
+            for addon in deprecated {
+                out += &format!("{}\n", deprecation_warning(addon));
+            }
+        }
+        out += &format!("\x1B[1m{} Untracked:\x1B[0m\n", self.untracked.len());
+        for dir in self.untracked.iter() {
+            match self
+                .unresolved
+                .iter()
+                .find(|unresolved| &unresolved.dir == dir)
+            {
+                Some(unresolved) => {
+                    out += &format!("{} ({})\n", dir, unresolved_context(unresolved))
+                }
+                None => out += &format!("{}\n", dir),
+            }
+        }
+        if !self.local.is_empty() {
+            out += &format!("\x1B[1m{} Local:\x1B[0m\n", self.local.len());
+            self.local
+                .iter()
+                .for_each(|dir| out += &format!("{}\n", dir));
+        }
+        if !self.removal_candidates.is_empty() {
+            out += &format!(
+                "\x1B[1m{} Removal candidates:\x1B[0m (remove using `rmdir --candidates`)\n",
+                self.removal_candidates.len()
+            );
+            self.removal_candidates
+                .iter()
+                .for_each(|dir| out += &format!("{}\n", dir));
+        }
+        out.trim_end().to_string()
+    }
+
+    fn plain(&self) -> String {
+        let mut lines: Vec<String> = self
+            .addons
+            .iter()
+            .map(|addon| {
+                format!(
+                    "addon\t{}\t{}\t{}",
+                    addon.identifier, addon.name, addon.version
+                )
+            })
+            .collect();
+        lines.extend(self.addons.iter().filter_map(|addon| {
+            let deprecation = serde_json::to_value(addon.deprecation?).ok()?;
+            Some(format!(
+                "deprecated\t{}\t{}",
+                addon.identifier,
+                deprecation.as_str()?
+            ))
+        }));
+        lines.extend(
+            self.addons
+                .iter()
+                .filter(|addon| addon.pinned)
+                .map(|addon| format!("pinned\t{}", addon.identifier)),
+        );
+        let dirs = [
+            ("untracked", &self.untracked),
+            ("local", &self.local),
+            ("removal-candidate", &self.removal_candidates),
+        ];
+        for (kind, dirs) in dirs.iter() {
+            lines.extend(dirs.iter().map(|dir| format!("{}\t{}", kind, dir)));
+        }
+        lines.join("\n")
+    }
+}
+
+impl AddonOutput {
+    /// Label and value of each field shown by `info`
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("Name", self.name.clone()),
+            ("Source", self.identifier.clone()),
+            ("Version", self.version.clone()),
+            ("Dirs", self.dirs.join(", ")),
+            ("Size", table::format_size(self.size)),
+        ];
+        if let Some(updated) = self.updated {
+            fields.push(("Updated", updated.format("%Y-%m-%d %H:%M").to_string()));
+        }
+        if let Some(notes) = &self.notes {
+            fields.push(("Notes", notes.clone()));
+        }
+        if self.pinned {
+            fields.push(("Pinned", "Not updated until `grunt unpin`".to_string()));
+        }
+        if self.priority {
+            fields.push(("Priority", "Updated before other addons".to_string()));
+        }
+        if let Some(deprecation) = self.deprecation {
+            fields.push((
+                "Warning",
+                format!("{} {}", self.name, deprecation.describe()),
+            ));
+        }
+        fields
+    }
+}
+
+impl Renderable for AddonOutput {
+    fn value(&self) -> Value {
+        Output::new(self).value()
+    }
+
+    fn table(&self) -> String {
+        let mut info_table = table::new_table(&[]);
+        for (label, value) in self.fields() {
+            info_table.add_row(vec![
+                Cell::new(label).add_attribute(Attribute::Bold),
+                Cell::new(value),
+            ]);
+        }
+        match &self.description {
+            Some(description) => format!(
+                "{}\n\n{}",
+                info_table,
+                markup::render(description, markup::width(), true)
+            ),
+            None => info_table.to_string(),
+        }
+    }
+
+    fn plain(&self) -> String {
+        let mut lines: Vec<String> = self
+            .fields()
+            .iter()
+            .map(|(label, value)| format!("{}\t{}", label.to_lowercase(), value))
+            .collect();
+        if let Some(description) = &self.description {
+            lines.push(String::new());
+            lines.push(markup::render(description, markup::width(), false));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Renderable for SearchOutput {
+    fn value(&self) -> Value {
+        Output::new(self).value()
+    }
+
+    fn table(&self) -> String {
+        if self.results.is_empty() {
+            return format!("Nothing found for {}", self.query);
+        }
+        let mut search_table = table::new_table(&["Name", "ID", "Source", "Downloads", "Summary"]);
+        for result in self.results.iter() {
+            let mut name = Cell::new(&result.name);
+            if result.installed {
+                name = name.add_attribute(Attribute::Bold);
+            }
+            search_table.add_row(vec![
+                name,
+                Cell::new(result.id),
+                Cell::new(&result.identifier),
+                Cell::new(result.downloads),
+                Cell::new(&result.summary),
+            ]);
+        }
+        format!("{}\nInstalled addons are in bold", search_table)
+    }
+
+    fn plain(&self) -> String {
+        self.results
+            .iter()
+            .map(|result| {
+                // The summary is last and kept on one line as it's free text
+                let summary = result.summary.replace(&['\t', '\n', '\r'][..], " ");
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    result.identifier,
+                    result.id,
+                    result.name,
+                    result.downloads,
+                    result.installed,
+                    summary
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl Renderable for LicensesOutput {
+    fn value(&self) -> Value {
+        Output::new(self).value()
+    }
+
+    fn table(&self) -> String {
+        let mut license_table = table::new_table(&["Name", "Source", "License", "Redistribution"]);
+        for addon in self.addons.iter() {
+            let mut redistribution = Cell::new(addon.redistribution.name());
+            if addon.redistribution == Redistribution::Forbidden {
+                redistribution = redistribution.add_attribute(Attribute::Bold);
+            }
+            license_table.add_row(vec![
+                Cell::new(&addon.name),
+                Cell::new(&addon.identifier),
+                Cell::new(addon.license.as_deref().unwrap_or("-")),
+                redistribution,
+            ]);
+        }
+        let forbidden = self
+            .addons
+            .iter()
+            .filter(|addon| addon.redistribution == Redistribution::Forbidden)
+            .count();
+        if forbidden == 0 {
+            license_table.to_string()
+        } else {
+            format!(
+                "{}\n\x1B[1m{} addons forbid redistribution\x1B[0m",
+                license_table, forbidden
+            )
+        }
+    }
+
+    fn plain(&self) -> String {
+        self.addons
+            .iter()
+            .map(|addon| {
+                format!(
+                    "{}\t{}\t{}",
+                    addon.identifier,
+                    addon.license.as_deref().unwrap_or("-"),
+                    addon.redistribution.name()
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl Renderable for OutdatedOutput {
+    fn value(&self) -> Value {
+        Output::new(self).value()
+    }
+
+    fn table(&self) -> String {
+        if self.addons.is_empty() {
+            return "All addons are up to date".to_string();
+        }
+        let mut outdated_table = table::new_table(&["Name", "Current", "New", "Size"]);
+        for addon in self.addons.iter() {
+            outdated_table.add_row(vec![
+                addon.name.clone(),
+                addon.current_version.clone(),
+                addon.new_version.clone(),
+                addon.size.map_or("-".to_string(), table::format_size),
+            ]);
+        }
+        format!(
+            "\x1B[1m{} outdated:\x1B[0m\n{}",
+            self.addons.len(),
+            outdated_table
+        )
+    }
+
+    fn plain(&self) -> String {
+        self.addons
+            .iter()
+            .map(|addon| {
+                format!(
+                    "{}\t{}\t{}\t{}",
+                    addon.identifier,
+                    addon.current_version,
+                    addon.new_version,
+                    addon.size.map_or("-".to_string(), |size| size.to_string())
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl Renderable for DiffOutput {
+    fn value(&self) -> Value {
+        Output::new(self).value()
+    }
+
+    fn table(&self) -> String {
+        let mut out = format!(
+            "\x1B[1m{} ({}) against version {}\x1B[0m\n",
+            self.name, self.identifier, self.version
+        );
+        if self.diff.is_empty() {
+            out += "No changes";
+            return out;
+        }
+        let files = [
+            ("Modified", &self.diff.modified),
+            ("Added", &self.diff.added),
+            ("Deleted", &self.diff.deleted),
+        ];
+        for (label, files) in files.iter().filter(|(_, files)| !files.is_empty()) {
+            out += &format!("\x1B[1m{} {}:\x1B[0m\n", files.len(), label);
+            files.iter().for_each(|file| out += &format!("{}\n", file));
+        }
+        out.trim_end().to_string()
+    }
+
+    fn plain(&self) -> String {
+        let files = [
+            ("modified", &self.diff.modified),
+            ("added", &self.diff.added),
+            ("deleted", &self.diff.deleted),
+        ];
+        files
+            .iter()
+            .flat_map(|(kind, files)| files.iter().map(move |file| format!("{}\t{}", kind, file)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl Renderable for CompatOutput {
+    fn value(&self) -> Value {
+        Output::new(self).value()
+    }
+
+    fn table(&self) -> String {
+        let mut compat_table =
+            table::new_table(&["Flavor", "Supported", "Game versions", "Latest file"]);
+        for flavor in self.flavors.iter() {
+            let mut name = flavor.name.clone().unwrap_or_else(|| flavor.flavor.clone());
+            if flavor.flavor == self.current_flavor.curse_name() {
+                name += " (this install)";
+            }
+            compat_table.add_row(vec![
+                name,
+                if flavor.supported { "yes" } else { "no" }.to_string(),
+                flavor.game_versions.join(", "),
+                flavor
+                    .latest_file
+                    .clone()
+                    .unwrap_or_else(|| "-".to_string()),
+            ]);
+        }
+        format!(
+            "\x1B[1m{} ({})\x1B[0m\n{}",
+            self.name, self.identifier, compat_table
+        )
+    }
+
+    fn plain(&self) -> String {
+        self.flavors
+            .iter()
+            .map(|flavor| {
+                format!(
+                    "{}\t{}\t{}",
+                    flavor.flavor,
+                    flavor.supported,
+                    flavor.game_versions.join(",")
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// The first few files two conflicting addons both installed
+fn shared_files(files: &[String]) -> String {
+    const SHOWN: usize = 3;
+    let mut text = files
+        .iter()
+        .take(SHOWN)
+        .cloned()
+        .collect::<Vec<String>>()
+        .join("\n");
+    if files.len() > SHOWN {
+        text.push_str(&format!("\nand {} more", files.len() - SHOWN));
+    }
+    text
+}
+
+impl Renderable for ConflictsOutput {
+    fn value(&self) -> Value {
+        Output::new(self).value()
+    }
+
+    fn table(&self) -> String {
+        if self.conflicts.is_empty() {
+            return "No conflicts found".to_string();
+        }
+        let mut conflict_table = table::new_table(&["Directory", "Addon", "Addon", "Shared files"]);
+        for conflict in self.conflicts.iter() {
+            let mut row = vec![conflict.dir.clone()];
+            row.extend(conflict.names.iter().cloned());
+            row.push(shared_files(&conflict.files));
+            conflict_table.add_row(row);
+        }
+        format!(
+            "\x1B[1mError: Conflicting addons found!\x1B[0m\n{}",
+            conflict_table
+        )
+    }
+
+    fn plain(&self) -> String {
+        self.conflicts
+            .iter()
+            .map(|conflict| format!("{}\t{}", conflict.dir, conflict.addons.join("\t")))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
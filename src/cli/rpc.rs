@@ -0,0 +1,270 @@
+use crate::cli::output::{AddonOutput, ConflictsOutput, ListOutput, Output};
+use grunt::error::GruntError;
+use grunt::settings::Settings;
+use grunt::table::Column;
+use grunt::{Grunt, ResolveProgress};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Errors returned by a call, with JSON-RPC error codes
+pub enum RpcError {
+    UnknownMethod(String),
+    InvalidParams(String),
+    Grunt(GruntError),
+}
+
+impl From<GruntError> for RpcError {
+    fn from(err: GruntError) -> Self {
+        RpcError::Grunt(err)
+    }
+}
+
+impl RpcError {
+    pub fn code(&self) -> i64 {
+        match self {
+            RpcError::UnknownMethod(_) => -32601,
+            RpcError::InvalidParams(_) => -32602,
+            RpcError::Grunt(_) => 1,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            RpcError::UnknownMethod(method) => format!("Unknown method {}", method),
+            RpcError::InvalidParams(err) => format!("Invalid params: {}", err),
+            RpcError::Grunt(err) => err.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AddonParams {
+    addon: String,
+}
+
+#[derive(Deserialize)]
+struct NoteParams {
+    addon: String,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RemoveParams {
+    addons: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ResolveParams {
+    retry_unresolved: bool,
+    builtin_rules: bool,
+}
+
+/// Missing params are treated as an empty object
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|err| RpcError::InvalidParams(err.to_string()))
+}
+
+/// Runs an operation on `grunt`. Shared by the socket and HTTP interfaces
+/// Progress of long running operations is reported as events using `emit`, followed
+/// by a `lockfile_changed` event for each change to the tracked addons, even if the
+/// operation failed partway
+/// The lockfile is saved after anything that changes it
+pub fn call<F>(
+    grunt: &mut Grunt,
+    settings: &Settings,
+    method: &str,
+    params: Value,
+    mut emit: F,
+) -> Result<Value, RpcError>
+where
+    F: FnMut(&str, Value),
+{
+    let result = run_method(grunt, settings, method, params, &mut emit);
+    for change in grunt.take_changes() {
+        emit("lockfile_changed", json!(change));
+    }
+    result
+}
+
+fn run_method<F>(
+    grunt: &mut Grunt,
+    settings: &Settings,
+    method: &str,
+    params: Value,
+    mut emit: F,
+) -> Result<Value, RpcError>
+where
+    F: FnMut(&str, Value),
+{
+    let email = settings.tsm_email().as_ref();
+    let pass = settings.tsm_pass().as_ref();
+    let result = match method {
+        "list" => Output::new(ListOutput::new(
+            grunt,
+            Column::DEFAULT.to_vec(),
+            Column::Name,
+        )?)
+        .value(),
+        "info" => {
+            let params: AddonParams = parse_params(params)?;
+            let index = grunt.find_addon(&params.addon)?;
+            let row = grunt.addon_rows().remove(index);
+            Output::new(AddonOutput::new(&grunt.addons()[index], &row)).value()
+        }
+        "conflicts" => Output::new(ConflictsOutput::new(grunt)).value(),
+        "check" => Output::new(grunt.plan_updates(email, pass)).value(),
+        "update" => {
+            let result = grunt.update_addons(
+                |updateable| {
+                    for upd in updateable.iter() {
+                        emit(
+                            "update",
+                            json!({ "name": upd.name, "new_version": upd.new_version }),
+                        );
+                    }
+                    updateable
+                },
+                email,
+                pass,
+            );
+            if let Err(GruntError::Partial(_)) = result {
+                grunt.save_lockfile()?;
+            }
+            let metrics = result?;
+            grunt.save_lockfile()?;
+            json!({ "updated": metrics.downloads().len() })
+        }
+        "resolve" => {
+            let params: ResolveParams = parse_params(params)?;
+            grunt.set_builtin_rules(params.builtin_rules);
+            let mut done = Value::Null;
+            grunt.resolve(params.retry_unresolved, |prog| match prog {
+                ResolveProgress::NewAddon { name, desc } => {
+                    emit("found", json!({ "name": name, "identifier": desc }))
+                }
+                ResolveProgress::Finished { not_found, skipped } => {
+                    done = json!({ "not_found": not_found, "skipped": skipped })
+                }
+            })?;
+            grunt.save_lockfile()?;
+            done
+        }
+        "remove" => {
+            let params: RemoveParams = parse_params(params)?;
+            grunt.remove_addons(&params.addons)?;
+            grunt.save_lockfile()?;
+            Value::Null
+        }
+        "note" => {
+            let params: NoteParams = parse_params(params)?;
+            let index = grunt.find_addon(&params.addon)?;
+            grunt.set_notes(index, params.text);
+            grunt.save_lockfile()?;
+            Value::Null
+        }
+        _ => return Err(RpcError::UnknownMethod(method.to_string())),
+    };
+    Ok(result)
+}
+
+#[derive(Deserialize)]
+struct Request {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Serves JSON-RPC 2.0 over a unix socket, one request per line
+/// Progress events are sent as `event` notifications before the response
+/// `init` is called for every request so changes made by other commands are seen
+#[cfg(unix)]
+pub fn run<F>(path: &Path, settings: &Settings, init: F)
+where
+    F: Fn() -> Result<Grunt, GruntError>,
+{
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixListener;
+
+    // A socket left behind by a previous run stops bind from working
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path).expect("Error creating socket");
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .expect("Error setting socket permissions");
+    println!("Listening on {}", path.display());
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let mut writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(_) => continue,
+        };
+        // Write errors mean the client went away so are ignored
+        let mut send = |message: Value| {
+            let _ = writeln!(writer, "{}", message);
+            let _ = writer.flush();
+        };
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let request: Request = match serde_json::from_str(&line) {
+                Ok(request) => request,
+                Err(err) => {
+                    send(json!({
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": { "code": -32700, "message": err.to_string() },
+                    }));
+                    continue;
+                }
+            };
+            println!("{}", request.method);
+            let result = match init() {
+                Ok(mut grunt) => call(
+                    &mut grunt,
+                    settings,
+                    &request.method,
+                    request.params,
+                    |event, data| {
+                        send(json!({
+                            "jsonrpc": "2.0",
+                            "method": "event",
+                            "params": { "event": event, "data": data },
+                        }))
+                    },
+                ),
+                Err(err) => Err(err.into()),
+            };
+            // Requests without an id are notifications and don't get a response
+            let id = match request.id {
+                Some(id) => id,
+                None => continue,
+            };
+            send(match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err(err) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": err.code(), "message": err.message() },
+                }),
+            });
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn run<F>(_path: &Path, _settings: &Settings, _init: F)
+where
+    F: Fn() -> Result<Grunt, GruntError>,
+{
+    println!("The RPC interface needs unix sockets. Use `grunt serve` instead");
+    std::process::exit(1);
+}
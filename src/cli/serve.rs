@@ -0,0 +1,303 @@
+use crate::cli::{crash, rpc};
+use data_encoding::HEXLOWER;
+use grunt::error::GruntError;
+use grunt::settings::Settings;
+use grunt::Grunt;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How often the listener is polled for requests while idle
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often `/events` subscribers are sent a comment, so closed ones are noticed
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a client can stall a read or write before it's given up on, so one that
+/// stops reading can't block the server
+const STREAM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A parsed HTTP request. Bodies aren't used by any endpoint so they're ignored
+struct Request {
+    method: String,
+    /// The path without its query
+    path: String,
+    token: Option<String>,
+}
+
+/// Reads the API token, creating a random one the first time
+/// Only the owner can read the file on unix
+pub fn load_token(path: &Path) -> String {
+    if let Ok(token) = std::fs::read_to_string(path) {
+        return token.trim().to_string();
+    }
+    let mut bytes = [0; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("Error generating API token");
+    let token = HEXLOWER.encode(&bytes);
+    std::fs::write(path, &token).expect("Error saving API token");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .expect("Error setting API token permissions");
+    }
+    token
+}
+
+/// Serves the JSON API on localhost, handling one request at a time
+/// `init` is called for every request so changes made by other commands are seen
+/// With `check_interval` updates are checked for while `/events` has subscribers
+pub fn run<F>(
+    port: u16,
+    token: &str,
+    settings: &Settings,
+    check_interval: Option<Duration>,
+    init: F,
+) where
+    F: Fn() -> Result<Grunt, GruntError>,
+{
+    let listener = TcpListener::bind(("127.0.0.1", port)).expect("Error binding API port");
+    // Polled so checks and keepalives can be sent between requests
+    listener
+        .set_nonblocking(true)
+        .expect("Error setting up API port");
+    println!("Listening on http://127.0.0.1:{}", port);
+    let mut subscribers = Subscribers::default();
+    let mut last_check = Instant::now();
+    let mut last_keepalive = Instant::now();
+    loop {
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                let check_due =
+                    check_interval.is_some_and(|interval| last_check.elapsed() >= interval);
+                if check_due && !subscribers.is_empty() {
+                    last_check = Instant::now();
+                    if let Ok(mut grunt) = init() {
+                        let result =
+                            rpc::call(&mut grunt, settings, "check", Value::Null, |_, _| ());
+                        if let Ok(plan) = result {
+                            subscribers.send_updates_available(&plan);
+                        }
+                    }
+                }
+                if last_keepalive.elapsed() >= KEEPALIVE_INTERVAL {
+                    last_keepalive = Instant::now();
+                    subscribers.keepalive();
+                }
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            Err(_) => continue,
+        };
+        let blocking = stream
+            .set_nonblocking(false)
+            .and_then(|_| stream.set_read_timeout(Some(STREAM_TIMEOUT)))
+            .and_then(|_| stream.set_write_timeout(Some(STREAM_TIMEOUT)));
+        if blocking.is_err() {
+            continue;
+        }
+        let request = match read_request(&stream) {
+            Some(request) => request,
+            None => {
+                respond(&mut stream, "400 Bad Request", &error_body("Bad request"));
+                continue;
+            }
+        };
+        let authorized = request.token.as_ref().is_some_and(|given| {
+            ring::constant_time::verify_slices_are_equal(given.as_bytes(), token.as_bytes()).is_ok()
+        });
+        if !authorized {
+            respond(&mut stream, "401 Unauthorized", &error_body("Bad token"));
+            continue;
+        }
+        println!("{} {}", request.method, request.path);
+        crash::journal(format!("{} {}", request.method, request.path));
+        if (request.method.as_str(), request.path.as_str()) == ("GET", "/events") {
+            subscribers.add(stream);
+            continue;
+        }
+        match init() {
+            Ok(grunt) => handle(&mut stream, &request, settings, grunt, &mut subscribers),
+            Err(err) => respond(&mut stream, "500 Internal Server Error", &error_body(err)),
+        }
+    }
+}
+
+fn handle(
+    stream: &mut TcpStream,
+    request: &Request,
+    settings: &Settings,
+    mut grunt: Grunt,
+    subscribers: &mut Subscribers,
+) {
+    let method = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/list") => "list",
+        ("GET", "/conflicts") => "conflicts",
+        ("GET", "/check") => "check",
+        ("POST", "/update") => "update",
+        ("POST", "/resolve") => "resolve",
+        _ => {
+            respond(stream, "404 Not Found", &error_body("Unknown endpoint"));
+            return;
+        }
+    };
+    if request.method == "GET" {
+        match rpc::call(&mut grunt, settings, method, Value::Null, |_, _| ()) {
+            Ok(result) => {
+                if method == "check" {
+                    subscribers.send_updates_available(&result);
+                }
+                respond(stream, "200 OK", &result)
+            }
+            Err(err) => respond(
+                stream,
+                "500 Internal Server Error",
+                &error_body(err.message()),
+            ),
+        }
+    } else {
+        // Long running operations stream their progress, to `/events` subscribers too
+        let mut events = Events::start(stream);
+        subscribers.send("started", &json!({ "method": method }));
+        let result = rpc::call(&mut grunt, settings, method, Value::Null, |event, data| {
+            events.send(event, &data);
+            subscribers.send(event, &data);
+        });
+        let (event, data) = match result {
+            Ok(result) => ("done", result),
+            Err(err) => ("error", error_body(err.message())),
+        };
+        events.send(event, &data);
+        subscribers.send(event, &data);
+    }
+}
+
+/// Reads the request line and headers
+fn read_request(stream: &TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = path.to_string();
+    // Browsers' EventSource and stream overlays can't set headers, so `/events` takes
+    // the token in the query too
+    let mut token = query
+        .split('&')
+        .filter(|_| path == "/events")
+        .find_map(|pair| pair.strip_prefix("token="))
+        .map(str::to_string);
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("authorization") {
+                token = value.trim().strip_prefix("Bearer ").map(str::to_string);
+            }
+        }
+    }
+    Some(Request {
+        method,
+        path,
+        token,
+    })
+}
+
+fn error_body<E: ToString>(err: E) -> serde_json::Value {
+    json!({ "error": err.to_string() })
+}
+
+/// Writes a complete JSON response. Write errors mean the client went away so are ignored
+fn respond<T: Serialize>(stream: &mut TcpStream, status: &str, body: &T) {
+    let body = serde_json::to_string(body).expect("Error serializing response");
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+}
+
+/// A server-sent event stream reporting progress of a long running operation
+struct Events<'a> {
+    stream: &'a mut TcpStream,
+}
+
+impl<'a> Events<'a> {
+    fn start(stream: &'a mut TcpStream) -> Self {
+        let _ = start_events(stream);
+        Events { stream }
+    }
+
+    fn send<T: Serialize>(&mut self, event: &str, data: &T) {
+        let _ = write_event(self.stream, event, data);
+    }
+}
+
+/// Clients of `/events`, like dashboards or stream overlays. They get the events of
+/// every operation and `updates_available` whenever a check finds updates
+#[derive(Default)]
+struct Subscribers {
+    streams: Vec<TcpStream>,
+}
+
+impl Subscribers {
+    fn add(&mut self, mut stream: TcpStream) {
+        if start_events(&mut stream).is_ok() {
+            self.streams.push(stream);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Sends an event to every subscriber, dropping ones that disconnected or timed out
+    fn send<T: Serialize>(&mut self, event: &str, data: &T) {
+        self.streams
+            .retain_mut(|stream| write_event(stream, event, data).is_ok());
+    }
+
+    /// Sends the result of a check if it found updates
+    fn send_updates_available(&mut self, plan: &Value) {
+        if plan["updates"]
+            .as_array()
+            .is_some_and(|updates| !updates.is_empty())
+        {
+            self.send("updates_available", plan);
+        }
+    }
+
+    /// Keeps idle subscribers open, dropping ones that disconnected or timed out
+    fn keepalive(&mut self) {
+        self.streams.retain_mut(|stream| {
+            write!(stream, ": keepalive\n\n")
+                .and_then(|_| stream.flush())
+                .is_ok()
+        });
+    }
+}
+
+fn start_events(stream: &mut TcpStream) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+    stream.flush()
+}
+
+fn write_event<T: Serialize>(stream: &mut TcpStream, event: &str, data: &T) -> std::io::Result<()> {
+    let data = serde_json::to_string(data).expect("Error serializing event");
+    write!(stream, "event: {}\ndata: {}\n\n", event, data)?;
+    stream.flush()
+}
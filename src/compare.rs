@@ -0,0 +1,112 @@
+use crate::addon::Addon;
+use crate::backup;
+use crate::error::GruntError;
+use crate::lockfile::Lockfile;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// First bytes of a zstd frame, which backups start with
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// An addon that differs between two snapshots
+#[derive(Debug, PartialEq)]
+pub struct AddonChange {
+    pub name: String,
+    pub identifier: String,
+    /// Version in the older snapshot, `None` if it was added since
+    pub before: Option<String>,
+    /// Version in the newer snapshot, `None` if it was removed since
+    pub after: Option<String>,
+}
+
+/// Reads the addons of a snapshot, either a backup made by `grunt backup` or a lockfile
+pub fn load(path: &Path) -> Result<Vec<Addon>, GruntError> {
+    let mut magic = [0; 4];
+    let is_backup = File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && magic == ZSTD_MAGIC;
+    let lockfile = if is_backup {
+        backup::read_lockfile(BufReader::new(File::open(path)?))?
+    } else {
+        Lockfile::from_file(path)?
+    };
+    Ok(lockfile.addons.into_iter().map(Addon::from_info).collect())
+}
+
+/// Lists the addons added, removed or with a different version in `after`, by name
+/// Addons are matched by their `source:id` key so renamed ones aren't listed
+pub fn diff(before: &[Addon], after: &[Addon]) -> Vec<AddonChange> {
+    let find = |addons: &[Addon], key: &str| {
+        addons
+            .iter()
+            .find(|addon| addon.key() == key)
+            .map(|addon| addon.version().clone())
+    };
+    let mut changes: Vec<AddonChange> = before
+        .iter()
+        .chain(
+            after
+                .iter()
+                .filter(|addon| find(before, &addon.key()).is_none()),
+        )
+        .map(|addon| AddonChange {
+            name: addon.name().clone(),
+            identifier: addon.identifier(),
+            before: find(before, &addon.key()),
+            after: find(after, &addon.key()),
+        })
+        .filter(|change| change.before != change.after)
+        .collect();
+    changes.sort_by_key(|change| change.name.to_lowercase());
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let addon = |name: &str, version: &str| {
+            Addon::from_custom(
+                name.to_string(),
+                vec![name.to_string()],
+                version.to_string(),
+            )
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grunt.lockfile");
+        let before = [addon("A", "1"), addon("B", "1"), addon("C", "1")];
+        Lockfile {
+            addons: before.iter().map(Addon::to_info).collect(),
+            curse_cache: Default::default(),
+            unresolved: Default::default(),
+            stats: Default::default(),
+            latest: Default::default(),
+            history: Default::default(),
+            game_version: None,
+            flavor: None,
+        }
+        .save(&path)
+        .unwrap();
+        let before = load(&path).unwrap();
+
+        let after = vec![addon("B", "2"), addon("C", "1"), addon("D", "1")];
+        let change = |name: &str, before: Option<&str>, after: Option<&str>| AddonChange {
+            name: name.to_string(),
+            identifier: format!("custom:{}", name),
+            before: before.map(str::to_string),
+            after: after.map(str::to_string),
+        };
+        assert_eq!(
+            diff(&before, &after),
+            vec![
+                change("A", Some("1"), None),
+                change("B", Some("1"), Some("2")),
+                change("D", None, Some("1")),
+            ]
+        );
+    }
+}
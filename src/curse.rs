@@ -1,67 +1,337 @@
+use crate::addon::Deprecation;
+#[cfg(feature = "io")]
+use crate::error::{check_status, GruntError};
+#[cfg(feature = "io")]
+use crate::net::{self, NetOptions};
+#[cfg(feature = "io")]
 use reqwest::blocking::Client;
+#[cfg(feature = "io")]
 use reqwest::header::{HeaderMap, HeaderValue};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(feature = "io")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 pub const WOW_GAME_ID: i32 = 1;
+#[cfg(feature = "io")]
+const API_URL: &str = "https://addons-ecs.forgesvc.net/api/v2";
+/// Projects on each page of recently changed ones
+#[cfg(feature = "io")]
+const CHANGED_PAGE_SIZE: usize = 50;
+/// Pages of recently changed projects read before it's quicker to refetch everything
+#[cfg(feature = "io")]
+const MAX_CHANGED_PAGES: usize = 10;
 
+#[cfg(feature = "io")]
 pub struct CurseAPI {
     client: Client,
 }
 
+#[cfg(feature = "io")]
 impl CurseAPI {
     /// Initializes the API
-    pub fn init() -> Self {
+    pub fn init(options: &NetOptions) -> Self {
         let mut headers = HeaderMap::new();
         headers.insert("Accept", HeaderValue::from_static("application/json"));
-        headers.insert("Accept-Encoding", HeaderValue::from_static("gzip"));
-        let client = Client::builder()
+        let client = options
+            .client_builder()
             .default_headers(headers)
             .build()
             .expect("Error creating HTTP client");
         CurseAPI { client }
     }
 
-    pub fn get_game_info(&self, game_id: i32) -> GameInfo {
+    pub fn get_game_info(&self, game_id: i32) -> Result<GameInfo, GruntError> {
         self.make_request::<(), GameInfo>(&format!("game/{}", game_id), None)
     }
 
-    pub fn fingerprint_search(&self, fingerprints: &[u32]) -> FingerprintInfo {
-        let info = self.make_request::<_, FingerprintInfo>("fingerprint", Some(fingerprints));
+    pub fn fingerprint_search(&self, fingerprints: &[u32]) -> Result<FingerprintInfo, GruntError> {
+        let info = self.make_request::<_, FingerprintInfo>("fingerprint", Some(fingerprints))?;
         assert!(info
             .partial_match_fingerprints
             .as_object()
             .unwrap()
             .is_empty()); // Never seen and assumed later to be empty. Check to make sure
-        info
+        Ok(info)
     }
 
     /// Request the information for multiple addons by id
-    pub fn get_addons_info(&self, addon_ids: &[&String]) -> Vec<AddonInfo> {
+    pub fn get_addons_info(&self, addon_ids: &[&String]) -> Result<Vec<AddonInfo>, GruntError> {
         self.make_request("addon", Some(addon_ids))
     }
 
-    fn make_request<P, Q>(&self, endpoint: &str, data: Option<P>) -> Q
+    /// Request the time any addon was last changed
+    pub fn get_timestamp(&self) -> Result<String, GruntError> {
+        self.make_request::<(), String>("addon/timestamp", None)
+    }
+
+    /// Request the information for a single addon by id
+    pub fn get_addon_info(&self, addon_id: i64) -> Result<AddonInfo, GruntError> {
+        self.make_request::<(), AddonInfo>(&format!("addon/{}", addon_id), None)
+    }
+
+    /// Request the HTML description of an addon
+    pub fn get_description(&self, addon_id: i64) -> Result<String, GruntError> {
+        let url = format!("{}/addon/{}/description", API_URL, addon_id);
+        let resp = net::send(&self.client, self.client.get(&url)).map_err(|source| {
+            GruntError::Request {
+                url: url.clone(),
+                source,
+            }
+        })?;
+        check_status(resp)?
+            .text()
+            .map_err(|source| GruntError::Decode {
+                url,
+                source: source.into(),
+            })
+    }
+
+    /// Request the download URL of a file of an addon
+    pub fn get_download_url(&self, addon_id: i64, file_id: i64) -> Result<String, GruntError> {
+        let url = format!(
+            "{}/addon/{}/file/{}/download-url",
+            API_URL, addon_id, file_id
+        );
+        let resp = net::send(&self.client, self.client.get(&url)).map_err(|source| {
+            GruntError::Request {
+                url: url.clone(),
+                source,
+            }
+        })?;
+        let text = check_status(resp)?
+            .text()
+            .map_err(|source| GruntError::Decode {
+                url,
+                source: source.into(),
+            })?;
+        Ok(text.trim().to_string())
+    }
+
+    /// Search WoW addons by name
+    pub fn search(&self, filter: &str) -> Result<Vec<AddonInfo>, GruntError> {
+        let endpoint = format!(
+            "addon/search?gameId={}&searchFilter={}",
+            WOW_GAME_ID,
+            filter.replace('&', "%26").replace(' ', "%20")
+        );
+        self.make_request::<(), Vec<AddonInfo>>(&endpoint, None)
+    }
+
+    /// `date_modified` of WoW projects changed after `since`, by id, read from the search
+    /// sorted by last update. `None` if more changed than are worth paging through
+    pub fn changed_since(
+        &self,
+        since: &str,
+    ) -> Result<Option<HashMap<String, String>>, GruntError> {
+        let since = match parse_date(since) {
+            Some(since) => since,
+            None => return Ok(None),
+        };
+        let mut changed = HashMap::new();
+        for page in 0..MAX_CHANGED_PAGES {
+            let endpoint = format!(
+                "addon/search?gameId={}&sort=2&sortDescending=true&pageSize={}&index={}",
+                WOW_GAME_ID,
+                CHANGED_PAGE_SIZE,
+                page * CHANGED_PAGE_SIZE
+            );
+            let infos = self.make_request::<(), Vec<AddonInfo>>(&endpoint, None)?;
+            let last_page = infos.len() < CHANGED_PAGE_SIZE;
+            for info in infos {
+                if parse_date(&info.date_modified).is_some_and(|modified| modified <= since) {
+                    return Ok(Some(changed));
+                }
+                changed.insert(info.id.to_string(), info.date_modified);
+            }
+            if last_page {
+                return Ok(Some(changed));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find an addon using the slug from its CurseForge URL
+    pub fn find_addon_by_slug(&self, slug: &str) -> Result<AddonInfo, GruntError> {
+        self.search(&slug.replace('-', " "))?
+            .into_iter()
+            .find(|info| info.slug == slug)
+            .ok_or_else(|| GruntError::NotFound(slug.to_string()))
+    }
+
+    fn make_request<P, Q>(&self, endpoint: &str, data: Option<P>) -> Result<Q, GruntError>
     where
         P: Serialize,
         Q: DeserializeOwned,
     {
-        let url = format!("https://addons-ecs.forgesvc.net/api/v2/{}", endpoint);
+        let url = format!("{}/{}", API_URL, endpoint);
 
         let resp = match data {
-            Some(data) => self.client.post(&url).json(&data).send(),
-            None => self.client.get(&url).send(),
+            Some(data) => net::send(&self.client, self.client.post(&url).json(&data)),
+            None => net::send(&self.client, self.client.get(&url)),
         }
-        .expect("Error making curse api request");
-        let resp = resp
-            .error_for_status()
-            .expect("Error sending curse api request");
+        .map_err(|source| GruntError::Request {
+            url: url.clone(),
+            source,
+        })?;
+        let resp = check_status(resp)?;
 
         // Debug: Write response to temp file before deserializing
         // let body = resp.text().unwrap();
         // std::fs::write("/tmp/grunt.json", &body).unwrap();
         // return serde_json::from_str(&body).unwrap();
 
-        resp.json().expect("Error decoding curse api response")
+        resp.json().map_err(|source| GruntError::Decode {
+            url,
+            source: source.into(),
+        })
+    }
+}
+
+/// Gets the project slug from a CurseForge URL like
+/// `https://www.curseforge.com/wow/addons/deadly-boss-mods`
+pub fn parse_project_url(url: &str) -> Option<String> {
+    let path = url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.")
+        .strip_prefix("curseforge.com/wow/addons/")?;
+    let slug = path.split(['/', '?', '#']).next()?;
+    if slug.is_empty() {
+        None
+    } else {
+        Some(slug.to_string())
+    }
+}
+
+/// Hosts of the Curse CDN. Files reach one a while before the other
+const CDN_HOSTS: [&str; 2] = ["https://edge.forgecdn.net", "https://media.forgecdn.net"];
+
+/// URLs to try in order when downloading a file. The mirror comes first if set,
+/// followed by `url` and the same path on the other CDN host
+pub fn download_urls(url: &str, mirror: Option<&str>) -> Vec<String> {
+    let path = match CDN_HOSTS.iter().find_map(|host| url.strip_prefix(host)) {
+        Some(path) => path,
+        None => return vec![url.to_string()],
+    };
+    let mut urls = Vec::new();
+    if let Some(mirror) = mirror {
+        urls.push(format!("{}{}", mirror.trim_end_matches('/'), path));
+    }
+    urls.push(url.to_string());
+    for host in CDN_HOSTS.iter() {
+        let alternate = format!("{}{}", host, path);
+        if alternate != url {
+            urls.push(alternate);
+        }
+    }
+    urls
+}
+
+#[cfg(feature = "io")]
+fn parse_date(date: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    chrono::DateTime::parse_from_rfc3339(date).ok()
+}
+
+impl CurseCache {
+    /// Projects of `ids` whose info has to be fetched, because they aren't cached or
+    /// their cached `date_modified` is older than the one in `changed`
+    /// Everything is stale when it isn't known what changed
+    pub fn stale<'a, I>(&self, ids: I, changed: Option<&HashMap<String, String>>) -> Vec<&'a String>
+    where
+        I: IntoIterator<Item = &'a String>,
+    {
+        ids.into_iter()
+            .filter(|id| match (self.projects.get(*id), changed) {
+                (Some(project), Some(changed)) => changed
+                    .get(*id)
+                    .is_some_and(|modified| modified != &project.date_modified),
+                _ => true,
+            })
+            .collect()
+    }
+}
+
+/// Latest file info saved between runs so unchanged projects aren't refetched
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct CurseCache {
+    /// Value of the `addon/timestamp` endpoint when the cache was last refreshed
+    pub timestamp: Option<String>,
+    /// Cached info by project id
+    pub projects: HashMap<String, CachedProject>,
+    /// Fingerprinting rules from the last time `game/1` was fetched
+    #[serde(default)]
+    pub rules: Option<GameRules>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedProject {
+    pub date_modified: String,
+    pub slug: String,
+    pub latest_file_id: i64,
+    pub download_url: String,
+    /// Size of the latest file in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_length: Option<u64>,
+    /// Set if the project won't get updates anymore
+    #[serde(default)]
+    pub deprecation: Option<Deprecation>,
+}
+
+/// Patterns deciding which files of an addon dir Curse fingerprints
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameRules {
+    /// Files parsed for more files to include
+    pub initial_inclusion_pattern: String,
+    /// Files included without being parsed
+    pub extra_include_pattern: String,
+    pub file_parsing_rules: Vec<FileParsingRule>,
+}
+
+impl GameRules {
+    /// Snapshot of the WoW rules, used when `game/1` can't be
+    pub fn builtin() -> Self {
+        let rule = |file_extension: &str, comment_strip_pattern: &str, inclusion_pattern: &str| {
+            FileParsingRule {
+                comment_strip_pattern: comment_strip_pattern.to_string(),
+                file_extension: file_extension.to_string(),
+                inclusion_pattern: inclusion_pattern.to_string(),
+                game_id: WOW_GAME_ID as i64,
+                id: 0,
+            }
+        };
+        GameRules {
+            initial_inclusion_pattern: r"(?i)^([^/]+)[\\/]\1\.toc$".to_string(),
+            extra_include_pattern: r"(?i)^[^/\\]+[/\\]Bindings\.xml$".to_string(),
+            file_parsing_rules: vec![
+                rule(
+                    ".xml",
+                    r"(?s)<!--.*?-->",
+                    r#"(?i)<(?:Include|Script)\s+file=["']((?:(?<!\.\.).)+)["']\s*/>"#,
+                ),
+                rule(
+                    ".toc",
+                    r"(?m)\s*#.*$",
+                    r"(?mi)^\s*((?:(?<!\.\.).)+\.(?:xml|lua))\s*$",
+                ),
+            ],
+        }
+    }
+
+    /// Picks the rules out of the game info. Returns `None` if it isn't shaped as expected
+    pub fn from_game_info(info: &GameInfo) -> Option<Self> {
+        let addon_cat = info
+            .category_sections
+            .iter()
+            .find(|cat| cat.name == "Addons" && cat.package_type == 1)?;
+        if info.file_parsing_rules.is_empty() {
+            return None;
+        }
+        Some(GameRules {
+            initial_inclusion_pattern: addon_cat.initial_inclusion_pattern.clone(),
+            extra_include_pattern: addon_cat.extra_include_pattern.clone(),
+            file_parsing_rules: info.file_parsing_rules.clone(),
+        })
     }
 }
 
@@ -253,6 +523,22 @@ pub struct AddonInfo {
     pub date_released: String,
     pub is_available: bool,
     pub is_experiemental: bool,
+    /// Whether the author allows the files to be shared outside of Curse
+    #[serde(default)]
+    pub allow_mod_distribution: Option<bool>,
+}
+
+impl AddonInfo {
+    /// Checks the project status for ones that won't be updated again
+    pub fn deprecation(&self) -> Option<Deprecation> {
+        match self.status {
+            7 => Some(Deprecation::Inactive),
+            8 => Some(Deprecation::Abandoned),
+            9 => Some(Deprecation::Deleted),
+            _ if !self.is_available => Some(Deprecation::Unavailable),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -356,3 +642,97 @@ pub struct GameVersionLatestFile {
     pub file_type: i64,
     pub game_version_flavor: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale() {
+        let project = |date_modified: &str| CachedProject {
+            date_modified: date_modified.to_string(),
+            slug: String::new(),
+            latest_file_id: 1,
+            download_url: String::new(),
+            file_length: None,
+            deprecation: None,
+        };
+        let mut cache = CurseCache::default();
+        cache
+            .projects
+            .insert("1".to_string(), project("2026-01-01T00:00:00Z"));
+        cache
+            .projects
+            .insert("2".to_string(), project("2026-01-01T00:00:00Z"));
+        let ids: Vec<String> = ["1", "2", "3"].iter().map(|id| id.to_string()).collect();
+        let mut changed = HashMap::new();
+        changed.insert("2".to_string(), "2026-01-02T00:00:00Z".to_string());
+        assert_eq!(cache.stale(&ids, Some(&changed)), vec!["2", "3"]);
+        assert_eq!(cache.stale(&ids, Some(&HashMap::new())), vec!["3"]);
+        assert_eq!(cache.stale(&ids, None), vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_parse_project_url() {
+        let slug = Some("deadly-boss-mods".to_string());
+        assert_eq!(
+            parse_project_url("https://www.curseforge.com/wow/addons/deadly-boss-mods"),
+            slug
+        );
+        assert_eq!(
+            parse_project_url("curseforge.com/wow/addons/deadly-boss-mods/files?sort=date"),
+            slug
+        );
+        assert_eq!(
+            parse_project_url("https://www.curseforge.com/wow/addons/"),
+            None
+        );
+        assert_eq!(
+            parse_project_url("https://www.tukui.org/download.php"),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn test_builtin_rules() {
+        let rules = GameRules::builtin();
+        let initial = fancy_regex::Regex::new(&rules.initial_inclusion_pattern).unwrap();
+        assert!(initial.is_match(r"addon\addon.toc").unwrap());
+        assert!(!initial.is_match(r"addon\other.toc").unwrap());
+        let extra = fancy_regex::Regex::new(&rules.extra_include_pattern).unwrap();
+        assert!(extra.is_match(r"addon\bindings.xml").unwrap());
+        let toc = rules
+            .file_parsing_rules
+            .iter()
+            .find(|rule| rule.file_extension == ".toc")
+            .unwrap();
+        let strip = regex::Regex::new(&toc.comment_strip_pattern).unwrap();
+        let inclusion = fancy_regex::Regex::new(&toc.inclusion_pattern).unwrap();
+        let text = strip.replace_all("## Title: Addon\nCore.lua\n..\\Other\\Lib.lua\n", "");
+        let files: Vec<&str> = text
+            .lines()
+            .filter_map(|line| Some(inclusion.captures(line).unwrap()?.get(1)?.as_str()))
+            .collect();
+        assert_eq!(files, vec!["Core.lua"]);
+    }
+
+    #[test]
+    fn test_download_urls() {
+        let url = "https://edge.forgecdn.net/files/2900/123/addon.zip";
+        assert_eq!(
+            download_urls(url, None),
+            vec![url, "https://media.forgecdn.net/files/2900/123/addon.zip"]
+        );
+        assert_eq!(
+            download_urls(url, Some("http://localhost:8080/")),
+            vec![
+                "http://localhost:8080/files/2900/123/addon.zip",
+                url,
+                "https://media.forgecdn.net/files/2900/123/addon.zip"
+            ]
+        );
+        let tukui = "https://www.tukui.org/downloads/elvui-12.00.zip";
+        assert_eq!(download_urls(tukui, Some("http://localhost")), vec![tukui]);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::error::{check_status, GruntError};
+use crate::net::{self, NetOptions};
+use serde::{Deserialize, Serialize};
+
+/// An addon source set up in the settings, for addons that aren't on Curse or Tukui
+/// Tracked as `custom:<name>` where the name is its key in `custom_sources`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CustomSource {
+    /// Page or API that contains the latest version
+    pub version_url: String,
+    /// JSON pointer to the version in the response, like `/tag_name`
+    #[serde(default)]
+    pub version_pointer: Option<String>,
+    /// Regex matching the version, applied after the pointer
+    /// The first capture group is used if there is one, otherwise the whole match
+    #[serde(default)]
+    pub version_regex: Option<String>,
+    /// Archive to download, with `{version}` replaced by the latest version
+    pub download_url: String,
+}
+
+impl CustomSource {
+    /// Fetches the latest version
+    pub fn latest_version(&self, name: &str, options: &NetOptions) -> Result<String, GruntError> {
+        let url = &self.version_url;
+        let client = options.client();
+        let resp = net::send(&client, client.get(url)).map_err(|source| GruntError::Request {
+            url: url.clone(),
+            source,
+        })?;
+        let text = check_status(resp)?
+            .text()
+            .map_err(|source| GruntError::Decode {
+                url: url.clone(),
+                source: source.into(),
+            })?;
+        self.find_version(name, &text).map_err(|err| match err {
+            FindError::Json(source) => GruntError::Decode {
+                url: url.clone(),
+                source: source.into(),
+            },
+            FindError::Invalid(reason) => GruntError::InvalidCustomSource {
+                name: name.to_string(),
+                reason,
+            },
+            FindError::Missing => GruntError::NotFound(format!("Version of custom:{}", name)),
+        })
+    }
+
+    /// The archive URL of `version`
+    pub fn download_url(&self, version: &str) -> String {
+        self.download_url.replace("{version}", version)
+    }
+
+    /// Picks the version out of the response body
+    fn find_version(&self, name: &str, text: &str) -> Result<String, FindError> {
+        let mut version = text.trim().to_string();
+        if let Some(pointer) = &self.version_pointer {
+            let json: serde_json::Value = serde_json::from_str(text).map_err(FindError::Json)?;
+            version = match json.pointer(pointer).ok_or(FindError::Missing)? {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+        }
+        if let Some(pattern) = &self.version_regex {
+            let regex = regex::Regex::new(pattern).map_err(|err| {
+                FindError::Invalid(format!(
+                    "version_regex of {} doesn't compile: {}",
+                    name, err
+                ))
+            })?;
+            let captures = regex.captures(&version).ok_or(FindError::Missing)?;
+            version = captures
+                .get(1)
+                .or_else(|| captures.get(0))
+                .unwrap()
+                .as_str()
+                .to_string();
+        }
+        if version.is_empty() {
+            return Err(FindError::Missing);
+        }
+        Ok(version)
+    }
+}
+
+#[derive(Debug)]
+enum FindError {
+    Json(serde_json::Error),
+    Invalid(String),
+    Missing,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(version_pointer: Option<&str>, version_regex: Option<&str>) -> CustomSource {
+        CustomSource {
+            version_url: "https://example.com/latest".to_string(),
+            version_pointer: version_pointer.map(str::to_string),
+            version_regex: version_regex.map(str::to_string),
+            download_url: "https://example.com/Addon-{version}.zip".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_find_version() {
+        let json = r#"{"tag_name": "v1.2.3", "build": 45}"#;
+        let find = |source: CustomSource, text| source.find_version("addon", text).ok();
+        assert_eq!(
+            find(source(Some("/tag_name"), None), json),
+            Some("v1.2.3".to_string())
+        );
+        assert_eq!(
+            find(source(Some("/build"), None), json),
+            Some("45".to_string())
+        );
+        assert_eq!(
+            find(source(Some("/tag_name"), Some(r"v(\d+\.\d+\.\d+)")), json),
+            Some("1.2.3".to_string())
+        );
+        assert_eq!(
+            find(
+                source(None, Some(r"Version ([\d.]+)")),
+                "<p>Version 2.0</p>"
+            ),
+            Some("2.0".to_string())
+        );
+        assert_eq!(find(source(Some("/missing"), None), json), None);
+        assert_eq!(
+            source(None, None).download_url("1.2.3"),
+            "https://example.com/Addon-1.2.3.zip"
+        );
+    }
+}
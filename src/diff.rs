@@ -0,0 +1,84 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Files of an installed addon that differ from the archive of its version
+/// Paths are relative to the dir the addon is installed in, separated by `/`
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct AddonDiff {
+    /// Files whose contents changed
+    pub modified: Vec<String>,
+    /// Files that aren't in the archive
+    pub added: Vec<String>,
+    /// Files in the archive that are missing
+    pub deleted: Vec<String>,
+}
+
+impl AddonDiff {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.added.is_empty() && self.deleted.is_empty()
+    }
+}
+
+/// Compares the files in `dirs` of `installed_dir` with the same dirs in `unpacked_dir`
+pub(crate) fn diff_dirs(
+    installed_dir: &Path,
+    unpacked_dir: &Path,
+    dirs: &[String],
+) -> std::io::Result<AddonDiff> {
+    let installed = list_files(installed_dir, dirs);
+    let unpacked = list_files(unpacked_dir, dirs);
+    let mut diff = AddonDiff::default();
+    for file in installed.union(&unpacked) {
+        match (installed.contains(file), unpacked.contains(file)) {
+            (true, false) => diff.added.push(file.clone()),
+            (false, true) => diff.deleted.push(file.clone()),
+            _ => {
+                if std::fs::read(installed_dir.join(file))?
+                    != std::fs::read(unpacked_dir.join(file))?
+                {
+                    diff.modified.push(file.clone());
+                }
+            }
+        }
+    }
+    Ok(diff)
+}
+
+/// Paths of the files in `dirs`, relative to `root`
+pub(crate) fn list_files(root: &Path, dirs: &[String]) -> BTreeSet<String> {
+    dirs.iter()
+        .flat_map(|dir| walkdir::WalkDir::new(root.join(dir)).into_iter().flatten())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| Some(crate::path_to_slash(entry.path().strip_prefix(root).ok()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_dirs() {
+        let installed = tempfile::tempdir().unwrap();
+        let unpacked = tempfile::tempdir().unwrap();
+        for root in [installed.path(), unpacked.path()].iter() {
+            std::fs::create_dir_all(root.join("Addon/Libs")).unwrap();
+            std::fs::write(root.join("Addon/Addon.toc"), "## Title: Addon").unwrap();
+            std::fs::write(root.join("Addon/Core.lua"), "print('hi')").unwrap();
+        }
+        std::fs::write(installed.path().join("Addon/Core.lua"), "print('edited')").unwrap();
+        std::fs::write(installed.path().join("Addon/Libs/Extra.lua"), "").unwrap();
+        std::fs::write(unpacked.path().join("Addon/Options.lua"), "").unwrap();
+
+        let diff = diff_dirs(installed.path(), unpacked.path(), &["Addon".to_string()]).unwrap();
+        assert_eq!(
+            diff,
+            AddonDiff {
+                modified: vec!["Addon/Core.lua".to_string()],
+                added: vec!["Addon/Libs/Extra.lua".to_string()],
+                deleted: vec!["Addon/Options.lua".to_string()],
+            }
+        );
+    }
+}
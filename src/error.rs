@@ -0,0 +1,355 @@
+use crate::addon::AddonType;
+use http::StatusCode;
+#[cfg(feature = "io")]
+use reqwest::blocking::Response;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum GruntError {
+    /// A request couldn't be sent or its response couldn't be read
+    #[cfg(feature = "io")]
+    #[error("Request to {} failed", redact_url(.url))]
+    Request {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    /// The server responded with an error status code
+    #[error("{} responded with {status}", redact_url(.url))]
+    Status { url: String, status: StatusCode },
+    /// The response wasn't in the expected format
+    #[error("Error decoding response from {}", redact_url(.url))]
+    Decode {
+        url: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// An addon is missing from the data returned by its source
+    #[error("{0} not found")]
+    NotFound(String),
+    /// A name matches more than one addon
+    #[error("{query} could refer to any of {}", .matches.join(", "))]
+    Ambiguous { query: String, matches: Vec<String> },
+    /// A dir doesn't exist in the AddOns folder
+    #[error("{0} doesn't exist in the AddOns folder")]
+    MissingDir(String),
+    /// Addons in an update plan changed locally or upstream since it was made
+    #[error("Update plan is out of date for {}", .0.join(", "))]
+    PlanChanged(Vec<String>),
+    /// The addon being installed is already tracked
+    #[error("{0} is already installed")]
+    AlreadyInstalled(String),
+    /// A new addon contains a dir owned by another addon
+    #[error("{dir} is already owned by {owner}")]
+    DirConflict { dir: String, owner: String },
+    /// Dirs that are tracked can only be deleted by removing their addon
+    #[error("{0} is tracked by an addon")]
+    TrackedDir(String),
+    /// A dir can't be moved into the AddOns folder because one with its name is there
+    #[error("{0} already exists in the AddOns folder")]
+    DirExists(String),
+    /// A dir given as the AddOns dir can't be used
+    #[error("{path} can't be used as the AddOns dir, {reason}")]
+    InvalidAddonDir { path: String, reason: String },
+    /// An install root isn't a dir inside the WoW dir
+    #[error("{0} isn't a dir inside the WoW dir")]
+    InvalidInstallRoot(String),
+    /// An identifier's `source:` prefix isn't a source grunt knows
+    #[error("{0} isn't a source grunt can install from")]
+    UnknownSource(String),
+    /// The lockfile has an id that can't be the addon's source's
+    #[error("{0} has an invalid id in the lockfile")]
+    InvalidAddonId(String),
+    /// The source is turned off in the settings
+    #[error("The {} source is disabled", .0.prefix())]
+    SourceDisabled(AddonType),
+    /// The source's cargo feature was left out when building grunt
+    #[error("The {} source isn't built into this version of grunt", .0.prefix())]
+    SourceNotBuilt(AddonType),
+    /// A custom source in the settings can't be used
+    #[error("Custom source {name} is invalid: {reason}")]
+    InvalidCustomSource { name: String, reason: String },
+    /// A file from another addon manager couldn't be read
+    #[error("Error importing {path}")]
+    Import {
+        path: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// A downloaded archive couldn't be read
+    #[error("Error extracting archive")]
+    #[cfg(feature = "io")]
+    Zip(#[from] zip::result::ZipError),
+    /// A glob from the settings can't be parsed
+    #[error("{pattern} isn't a valid glob")]
+    #[cfg(feature = "io")]
+    InvalidPattern {
+        pattern: String,
+        #[source]
+        source: glob::PatternError,
+    },
+    /// An archive broke the extract limits and could be a zip bomb
+    #[error("Refusing to extract archive: {0}")]
+    UnsafeArchive(String),
+    /// A file given to restore isn't a backup grunt can read
+    #[error("Invalid backup: {0}")]
+    InvalidBackup(String),
+    /// An addon's archive can't be installed, like one without any addon dirs
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(String),
+    /// An addon's archive has files next to its dirs, where the game wouldn't load them
+    #[error("Archive has {0} outside of an addon dir")]
+    LooseFile(String),
+    /// The source needs a login that isn't in the settings
+    #[error("No login set for the {} source", .0.prefix())]
+    MissingLogin(AddonType),
+    /// Reading or writing files failed
+    #[error("Filesystem error")]
+    Io(#[from] std::io::Error),
+    /// The lockfile couldn't be read or saved
+    #[error("Error accessing lockfile {path}")]
+    Storage {
+        path: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// An update plan file couldn't be read or saved
+    #[error("Error accessing plan file {path}")]
+    Plan {
+        path: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// The lockfile was changed outside of grunt and the changes haven't been accepted
+    #[error("{0} was changed outside of grunt, refusing to save it")]
+    UntrustedLockfile(String),
+    /// The settings file couldn't be read or saved
+    #[error("Error accessing settings file {path}")]
+    Settings {
+        path: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// A value in the settings file can't be used
+    #[error("Setting {name} is invalid: {reason}")]
+    InvalidSetting { name: String, reason: String },
+    /// The settings file isn't valid and was moved to `backup`
+    #[error("Settings file {path} is corrupt, it was moved to {backup}")]
+    CorruptSettings {
+        path: String,
+        backup: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A source couldn't be reached, so its addons were skipped
+    #[error("Couldn't check {name} addons for updates")]
+    SourceUnavailable {
+        name: String,
+        #[source]
+        source: Box<GruntError>,
+    },
+    /// Some addons or sources failed while the rest were installed
+    #[error("{} errors while updating", .0.len())]
+    Partial(Vec<GruntError>),
+    /// Wraps an error that happened while working on a specific addon
+    #[error("Error updating {name} ({addon_type:?})")]
+    Addon {
+        name: String,
+        addon_type: AddonType,
+        #[source]
+        source: Box<GruntError>,
+    },
+}
+
+impl GruntError {
+    /// Wraps the error with the addon it happened to
+    pub fn for_addon(self, name: &str, addon_type: &AddonType) -> Self {
+        GruntError::Addon {
+            name: name.to_string(),
+            addon_type: addon_type.clone(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Returns the innermost grunt error
+    pub fn root(&self) -> &GruntError {
+        match self {
+            GruntError::Addon { source, .. } | GruntError::SourceUnavailable { source, .. } => {
+                source.root()
+            }
+            _ => self,
+        }
+    }
+
+    /// The name and type of the addon the error happened to
+    pub fn addon(&self) -> Option<(&String, &AddonType)> {
+        match self {
+            GruntError::Addon {
+                name, addon_type, ..
+            } => Some((name, addon_type)),
+            _ => None,
+        }
+    }
+
+    /// The URL of the failing request with query values redacted
+    pub fn url(&self) -> Option<String> {
+        match self.root() {
+            #[cfg(feature = "io")]
+            GruntError::Request { url, .. } => Some(redact_url(url)),
+            GruntError::Status { url, .. } | GruntError::Decode { url, .. } => {
+                Some(redact_url(url))
+            }
+            _ => None,
+        }
+    }
+
+    /// The HTTP status of the failing request
+    pub fn status(&self) -> Option<StatusCode> {
+        match self.root() {
+            GruntError::Status { status, .. } => Some(*status),
+            #[cfg(feature = "io")]
+            GruntError::Request { source, .. } => source.status(),
+            _ => None,
+        }
+    }
+
+    /// A suggestion for how the user can fix the error
+    pub fn remediation(&self) -> &'static str {
+        match self.root() {
+            GruntError::Status { status, .. }
+                if *status == StatusCode::NOT_FOUND || *status == StatusCode::GONE =>
+            {
+                "The addon or file no longer exists upstream. Remove it and run `grunt resolve` to track it again"
+            }
+            GruntError::Status { status, .. }
+                if status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS =>
+            {
+                "The server is having problems. Retry later"
+            }
+            GruntError::Status { .. } => {
+                "Retry later. If it keeps failing the API may have changed"
+            }
+            #[cfg(feature = "io")]
+            GruntError::Request { .. } => "Check your internet connection and retry",
+            GruntError::Decode { .. } => {
+                "The API returned something unexpected. Check for a newer version of grunt"
+            }
+            GruntError::NotFound(_) => {
+                "The addon may have been deleted or moved. Remove it and run `grunt resolve`"
+            }
+            GruntError::Ambiguous { .. } => "Use a `source:id` identifier from `grunt list` instead",
+            GruntError::MissingDir(_) => "Check the spelling of the dir name",
+            GruntError::PlanChanged(_) => {
+                "Make and review a new plan with `grunt update --dry-run --plan-out`"
+            }
+            GruntError::AlreadyInstalled(_) => "Run `grunt update` to get the latest version",
+            GruntError::DirConflict { .. } => {
+                "Remove the other addon first with `grunt remove`"
+            }
+            GruntError::TrackedDir(_) => "Remove the addon with `grunt remove` instead",
+            GruntError::DirExists(_) => "Move or delete the existing dir first, then try again",
+            GruntError::InvalidAddonDir { .. } => {
+                "Pass the Interface/AddOns dir of the game, like `World of Warcraft/_retail_/Interface/AddOns`"
+            }
+            GruntError::InvalidInstallRoot(_) => {
+                "Use a path relative to the WoW dir, like Interface/Sounds"
+            }
+            GruntError::UnknownSource(_) => {
+                "Use curse:, tukui:, github:<owner>/<repo>, custom:<name> with a source from the settings, or the URL of a zip archive"
+            }
+            GruntError::InvalidAddonId(_) => {
+                "Remove the addon and run `grunt resolve` to track it again"
+            }
+            GruntError::SourceDisabled(_) => {
+                "Remove it from `disabled_sources` in the settings file to use it"
+            }
+            GruntError::SourceNotBuilt(_) => {
+                "Build grunt with the source's cargo feature, like `--features tsm`"
+            }
+            GruntError::InvalidCustomSource { .. } => {
+                "Fix the source in `custom_sources` in the settings file"
+            }
+            GruntError::Import { .. } => {
+                "Check the path points at the other manager's data and that it's a supported version"
+            }
+            #[cfg(feature = "io")]
+            GruntError::Zip(_) => "The download may be corrupt. Retry the update",
+            #[cfg(feature = "io")]
+            GruntError::InvalidPattern { .. } => {
+                "Fix the pattern in the settings file. `[` and `]` need to be escaped as `[[]` and `[]]`"
+            }
+            GruntError::UnsafeArchive(_) => {
+                "The archive may be malicious. Report it to the addon's author, or raise `extract_limits` in the settings if you trust it"
+            }
+            GruntError::LooseFile(_) | GruntError::InvalidArchive(_) => {
+                "The archive is broken. Report it to the addon's author"
+            }
+            GruntError::InvalidBackup(_) => {
+                "Pass an archive made by `grunt backup` with the same or a newer version of grunt. Saved variables are only included with `--saved-variables`"
+            }
+            GruntError::MissingLogin(_) => {
+                "Set `tsm_email` and `tsm_pass`, or `tsm_app_config` to use the TSM app's login, in the settings file"
+            }
+            #[cfg(feature = "io")]
+            GruntError::Io(err) if crate::net::is_network_io(err) => {
+                "The download stalled. Check your connection or raise `stall_timeout` in the settings"
+            }
+            GruntError::Io(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+                "Files are owned by another user. Ask them or an admin to make the AddOns directory group writable"
+            }
+            GruntError::Io(_) => "Check the AddOns directory is writable and has free space",
+            GruntError::Storage { .. } => {
+                "Check the lockfile is writable and isn't corrupt. Deleting it and running `grunt resolve` rebuilds it"
+            }
+            GruntError::Plan { .. } => {
+                "Check the path is writable, or that it's a plan saved by `grunt update --plan-out`"
+            }
+            GruntError::UntrustedLockfile(_) => {
+                "Check the lockfile's addons, then run `grunt lockfile accept` to trust the changes"
+            }
+            GruntError::Settings { .. } => {
+                "Check the settings file is readable and writable. Settings saved by a newer version of grunt need that version"
+            }
+            GruntError::InvalidSetting { .. } => "Fix the setting in the settings file",
+            GruntError::CorruptSettings { .. } => {
+                "The default settings are used until it's fixed. Correct the backup and move it back, or set the settings again"
+            }
+            GruntError::Partial(_) => "See the errors above. Addons that succeeded were saved",
+            GruntError::Addon { .. } | GruntError::SourceUnavailable { .. } => unreachable!(),
+        }
+    }
+}
+
+/// Converts error status codes into a `GruntError::Status`
+#[cfg(feature = "io")]
+pub(crate) fn check_status(resp: Response) -> Result<Response, GruntError> {
+    let status = resp.status();
+    if status.is_client_error() || status.is_server_error() {
+        Err(GruntError::Status {
+            url: resp.url().to_string(),
+            status,
+        })
+    } else {
+        Ok(resp)
+    }
+}
+
+/// Replaces query parameter values so sessions and tokens aren't printed
+pub fn redact_url(url: &str) -> String {
+    let mut split = url.splitn(2, '?');
+    let base = split.next().unwrap();
+    match split.next() {
+        Some(query) => {
+            let query = query
+                .split('&')
+                .map(|pair| match pair.find('=') {
+                    Some(index) => format!("{}=<redacted>", &pair[..index]),
+                    None => pair.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join("&");
+            format!("{}?{}", base, query)
+        }
+        None => base.to_string(),
+    }
+}
@@ -0,0 +1,219 @@
+use crate::addon::{Addon, AddonType};
+use crate::flavor::Flavor;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// A format `grunt export` writes the addon list in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    /// WowUp's `addon-store.json`, which `grunt migrate wowup` reads too
+    WowUp,
+    /// Ajour's addon export
+    Ajour,
+    Csv,
+    /// A table to post for others to install the same addons
+    Markdown,
+}
+
+impl ExportFormat {
+    pub const NAMES: [&'static str; 4] = ["wowup", "ajour", "csv", "markdown"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "wowup" => Some(ExportFormat::WowUp),
+            "ajour" => Some(ExportFormat::Ajour),
+            "csv" => Some(ExportFormat::Csv),
+            "markdown" => Some(ExportFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// The exported addon list
+pub struct Export {
+    pub text: String,
+    /// Addons the format has no way to refer to, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Converts addons to another format
+pub fn export(addons: &[Addon], flavor: Flavor, format: ExportFormat) -> Export {
+    let mut skipped = Vec::new();
+    let text = match format {
+        ExportFormat::WowUp => {
+            let mut store = BTreeMap::new();
+            for addon in addons {
+                match WowUpAddon::new(addon, flavor) {
+                    Ok(entry) => {
+                        store.insert(entry.id.clone(), entry);
+                    }
+                    Err(reason) => skipped.push((addon.name().clone(), reason)),
+                }
+            }
+            serde_json::to_string_pretty(&store).expect("Error serializing export")
+        }
+        ExportFormat::Ajour => {
+            let mut entries = Vec::new();
+            for addon in addons {
+                match repository(addon) {
+                    Ok(repository) => entries.push(json!({
+                        "name": addon.name(),
+                        // Ajour doesn't capitalize the I
+                        "repository": repository.replace("TukUI", "Tukui"),
+                        "repository_id": addon.addon_id(),
+                    })),
+                    Err(reason) => skipped.push((addon.name().clone(), reason)),
+                }
+            }
+            // Ajour exports every flavor at once, keyed by its flavor ids
+            let export = json!({ flavor.id(): entries });
+            serde_json::to_string_pretty(&export).expect("Error serializing export")
+        }
+        ExportFormat::Csv => {
+            let mut lines = vec!["name,source,id,version,dirs,url".to_string()];
+            for addon in addons {
+                let fields = [
+                    addon.name().clone(),
+                    addon.addon_type().prefix().to_string(),
+                    addon.addon_id().clone(),
+                    addon.version().clone(),
+                    addon.dirs().join(";"),
+                    project_url(addon).unwrap_or_default(),
+                ];
+                let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+                lines.push(fields.join(","));
+            }
+            lines.join("\n")
+        }
+        ExportFormat::Markdown => {
+            let mut lines = vec![
+                "| Addon | Source |".to_string(),
+                "| --- | --- |".to_string(),
+            ];
+            for addon in addons {
+                let name = addon.name().replace('|', "\\|");
+                let name = match project_url(addon) {
+                    Some(url) => format!("[{}]({})", name, url),
+                    None => name,
+                };
+                lines.push(format!("| {} | `{}` |", name, addon.identifier()));
+            }
+            lines.join("\n")
+        }
+    };
+    Export { text, skipped }
+}
+
+/// The project page of an addon, if it's known
+fn project_url(addon: &Addon) -> Option<String> {
+    match addon.addon_type() {
+        AddonType::Curse => Some(format!(
+            "https://www.curseforge.com/wow/addons/{}",
+            addon.slug().as_ref()?
+        )),
+        AddonType::Url => Some(addon.addon_id().clone()),
+        AddonType::GitHub => Some(format!("https://github.com/{}", addon.addon_id())),
+        AddonType::Tukui | AddonType::TSM | AddonType::Custom => None,
+    }
+}
+
+/// The name WowUp and Ajour use for an addon's source
+fn repository(addon: &Addon) -> Result<&'static str, String> {
+    match addon.addon_type() {
+        AddonType::Curse => Ok("Curse"),
+        AddonType::Tukui => Ok("TukUI"),
+        source => Err(format!("{} addons can't be exported", source.prefix())),
+    }
+}
+
+/// Quotes a field if it has characters that would split it
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// An entry of WowUp's addon store, with the fields `grunt migrate wowup` reads
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WowUpAddon {
+    id: String,
+    name: String,
+    provider_name: &'static str,
+    external_id: String,
+    installed_version: String,
+    /// Curse file id of the installed version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    installed_external_release_id: Option<String>,
+    installed_folders: String,
+    client_type: &'static str,
+}
+
+impl WowUpAddon {
+    fn new(addon: &Addon, flavor: Flavor) -> Result<Self, String> {
+        let provider_name = repository(addon)?;
+        let release_id = match addon.addon_type() {
+            AddonType::Curse => Some(addon.version().clone()),
+            _ => None,
+        };
+        Ok(WowUpAddon {
+            id: format!("{}-{}", provider_name.to_lowercase(), addon.addon_id()),
+            name: addon.name().clone(),
+            provider_name,
+            external_id: addon.addon_id().clone(),
+            installed_version: addon.version().clone(),
+            installed_external_release_id: release_id,
+            installed_folders: addon.dirs().join(","),
+            client_type: match flavor {
+                Flavor::Retail => "Retail",
+                Flavor::ClassicEra => "ClassicEra",
+                Flavor::ClassicTbc => "ClassicTbc",
+                Flavor::ClassicWrath => "ClassicWrath",
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export() {
+        let addons = vec![
+            Addon::from_curse_file(
+                "Details".to_string(),
+                61284,
+                Some("details".to_string()),
+                3120000,
+                vec!["Details".to_string(), "Details_DataStorage".to_string()],
+            ),
+            Addon::from_tukui_info(
+                "ElvUI".to_string(),
+                -2,
+                vec!["ElvUI".to_string()],
+                "12.00".to_string(),
+            ),
+            Addon::from_custom(
+                "My, Addon".to_string(),
+                vec!["MyAddon".to_string()],
+                "1".to_string(),
+            ),
+        ];
+        let export_as = |format| export(&addons, Flavor::Retail, format);
+        for format in ExportFormat::NAMES.iter() {
+            let export = export_as(ExportFormat::from_name(format).unwrap());
+            insta::assert_snapshot!(format!("export_{}", format), export.text);
+        }
+        assert_eq!(
+            export_as(ExportFormat::WowUp).skipped,
+            vec![(
+                "My, Addon".to_string(),
+                "custom addons can't be exported".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Hidden hooks that make downloads and installs fail on purpose, for testing error handling
+//! Set `GRUNT_FAULT_INJECT` to a comma separated list of:
+//! - `fail-after-bytes=N`: downloads fail once N bytes have been read
+//! - `fail-on-addon=NAME`: installing the addon with that name fails
+//! - `crash-after-delete`: the process aborts right after old addon dirs are deleted
+use std::io::Read;
+use std::sync::OnceLock;
+
+#[derive(Debug, Default, PartialEq)]
+struct Faults {
+    fail_after_bytes: Option<u64>,
+    fail_on_addon: Option<String>,
+    crash_after_delete: bool,
+}
+
+impl Faults {
+    fn parse(spec: &str) -> Self {
+        let mut faults = Faults::default();
+        for fault in spec.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            match fault.split_once('=') {
+                Some(("fail-after-bytes", bytes)) => {
+                    faults.fail_after_bytes = Some(bytes.parse().expect("Invalid fault byte count"))
+                }
+                Some(("fail-on-addon", name)) => faults.fail_on_addon = Some(name.to_string()),
+                None if fault == "crash-after-delete" => faults.crash_after_delete = true,
+                _ => panic!("Unknown fault {}", fault),
+            }
+        }
+        faults
+    }
+}
+
+fn faults() -> &'static Faults {
+    static FAULTS: OnceLock<Faults> = OnceLock::new();
+    FAULTS.get_or_init(|| match std::env::var("GRUNT_FAULT_INJECT") {
+        Ok(spec) => Faults::parse(&spec),
+        Err(_) => Faults::default(),
+    })
+}
+
+fn injected(what: &str) -> std::io::Error {
+    std::io::Error::other(format!("Injected fault: {}", what))
+}
+
+/// Wraps a download so it fails after `fail-after-bytes`
+pub fn reader<'a>(inner: Box<dyn Read + 'a>) -> Box<dyn Read + 'a> {
+    match faults().fail_after_bytes {
+        Some(limit) => Box::new(FailingReader {
+            inner,
+            remaining: limit,
+        }),
+        None => inner,
+    }
+}
+
+/// Fails if the addon is the `fail-on-addon` one
+pub fn check_addon(name: &str) -> std::io::Result<()> {
+    match &faults().fail_on_addon {
+        Some(fail_on) if fail_on.eq_ignore_ascii_case(name) => {
+            Err(injected(&format!("failed on {}", name)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Aborts if `crash-after-delete` is set. Called once old addon files are gone
+pub fn after_delete() {
+    if faults().crash_after_delete {
+        eprintln!("Injected fault: crashing after delete");
+        std::process::abort();
+    }
+}
+
+struct FailingReader<'a> {
+    inner: Box<dyn Read + 'a>,
+    remaining: u64,
+}
+
+impl Read for FailingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            return Err(injected("download failed"));
+        }
+        let len = buf.len().min(self.remaining as usize);
+        let read = self.inner.read(&mut buf[..len])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let faults =
+            Faults::parse("fail-after-bytes=100, fail-on-addon=Details,crash-after-delete");
+        assert_eq!(
+            faults,
+            Faults {
+                fail_after_bytes: Some(100),
+                fail_on_addon: Some("Details".to_string()),
+                crash_after_delete: true,
+            }
+        );
+        assert_eq!(Faults::parse(""), Faults::default());
+    }
+}
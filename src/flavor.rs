@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// The version of the game an AddOns dir belongs to
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Flavor {
+    Retail,
+    ClassicEra,
+    ClassicTbc,
+    ClassicWrath,
+}
+
+impl Flavor {
+    /// Guesses the flavor from the game's install layout, e.g. `World of Warcraft/_classic_/Interface/AddOns`
+    /// Wrath shares `_classic_` with TBC so it has to be set with `grunt flavor`
+    pub fn from_path(path: &Path) -> Self {
+        let is_dir = |name: &str| path.iter().any(|part| part == name);
+        if is_dir("_classic_era_") {
+            Flavor::ClassicEra
+        } else if is_dir("_classic_") {
+            Flavor::ClassicTbc
+        } else {
+            Flavor::Retail
+        }
+    }
+
+    pub const ALL: [Flavor; 4] = [
+        Flavor::Retail,
+        Flavor::ClassicEra,
+        Flavor::ClassicTbc,
+        Flavor::ClassicWrath,
+    ];
+
+    pub fn is_classic(&self) -> bool {
+        *self != Flavor::Retail
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Flavor::Retail => "Retail",
+            Flavor::ClassicEra => "Classic Era",
+            Flavor::ClassicTbc => "Classic TBC",
+            Flavor::ClassicWrath => "Classic Wrath",
+        }
+    }
+
+    /// The name used in settings and on the command line, e.g. `classic_era`
+    pub fn id(&self) -> &'static str {
+        match self {
+            Flavor::Retail => "retail",
+            Flavor::ClassicEra => "classic_era",
+            Flavor::ClassicTbc => "classic_tbc",
+            Flavor::ClassicWrath => "classic_wrath",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Flavor::ALL.iter().copied().find(|flavor| flavor.id() == id)
+    }
+
+    /// The `gameVersionFlavor` Curse uses for files of this flavor
+    pub fn curse_name(&self) -> &'static str {
+        match self {
+            Flavor::Retail => "wow_retail",
+            Flavor::ClassicEra => "wow_classic",
+            Flavor::ClassicTbc => "wow_burning_crusade",
+            Flavor::ClassicWrath => "wow_wrath",
+        }
+    }
+
+    pub fn from_curse_name(name: &str) -> Option<Self> {
+        Flavor::ALL
+            .iter()
+            .copied()
+            .find(|flavor| flavor.curse_name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        let flavor = |path: &str| Flavor::from_path(Path::new(path));
+        assert_eq!(
+            flavor("/games/World of Warcraft/_retail_/Interface/AddOns"),
+            Flavor::Retail
+        );
+        assert_eq!(
+            flavor("/games/World of Warcraft/_classic_/Interface/AddOns"),
+            Flavor::ClassicTbc
+        );
+        assert_eq!(
+            flavor("/games/World of Warcraft/_classic_era_/Interface/AddOns"),
+            Flavor::ClassicEra
+        );
+        assert_eq!(flavor("/tmp/AddOns"), Flavor::Retail);
+    }
+
+    #[test]
+    fn test_curse_name() {
+        for flavor in Flavor::ALL.iter() {
+            assert_eq!(Flavor::from_curse_name(flavor.curse_name()), Some(*flavor));
+            assert_eq!(Flavor::from_id(flavor.id()), Some(*flavor));
+        }
+        assert_eq!(Flavor::from_curse_name("wow_cata"), None);
+    }
+}
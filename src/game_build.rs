@@ -0,0 +1,86 @@
+use crate::flavor::Flavor;
+use std::path::Path;
+
+/// A game patch seen for the first time, with the addons that say they support it
+#[derive(Debug, PartialEq)]
+pub struct GamePatch {
+    /// Version of the game before, like `9.0.2.36949`
+    pub old_version: String,
+    pub new_version: String,
+    /// `## Interface` number of the new version, like `90005`
+    pub interface: Option<u32>,
+    /// Names of addons whose `.toc` lists the new interface or a later one
+    pub supported: Vec<String>,
+    /// Names of addons whose `.toc` lists an older interface
+    pub outdated: Vec<String>,
+}
+
+/// Reads the installed version of a flavor from `.build.info` in the game's install dir
+/// The file is a table with `Name!TYPE:size` headers and a row per installed product
+pub(crate) fn read_version(install_dir: &Path, flavor: Flavor) -> Option<String> {
+    let text = std::fs::read_to_string(install_dir.join(".build.info")).ok()?;
+    parse_version(&text, flavor)
+}
+
+fn parse_version(text: &str, flavor: Flavor) -> Option<String> {
+    let mut lines = text.lines();
+    let headers: Vec<&str> = lines
+        .next()?
+        .split('|')
+        .map(|header| header.split('!').next().unwrap_or(header))
+        .collect();
+    let column = |name| headers.iter().position(|header| *header == name);
+    let (product, version) = (column("Product")?, column("Version")?);
+    let active = column("Active");
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split('|').collect()).collect();
+    let matches = |row: &&Vec<&str>| row.get(product) == Some(&product_name(flavor));
+    // Prefer the active region if there are several
+    rows.iter()
+        .filter(matches)
+        .find(|row| active.is_none_or(|active| row.get(active) == Some(&"1")))
+        .or_else(|| rows.iter().find(matches))
+        .and_then(|row| row.get(version))
+        .map(|version| version.to_string())
+}
+
+/// Name of the flavor in `.build.info`
+fn product_name(flavor: Flavor) -> &'static str {
+    match flavor {
+        Flavor::Retail => "wow",
+        Flavor::ClassicEra => "wow_classic_era",
+        Flavor::ClassicTbc | Flavor::ClassicWrath => "wow_classic",
+    }
+}
+
+/// The `## Interface` number addons use for a game version, `9.0.5.37988` is `90005`
+pub fn interface_version(version: &str) -> Option<u32> {
+    let mut parts = version.split('.').map(|part| part.parse::<u32>().ok());
+    let (major, minor, patch) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(major * 10000 + minor * 100 + patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        let text =
+            "Branch!STRING:0|Active!DEC:1|Build Key!HEX:16|Version!STRING:0|Product!STRING:0\n\
+                    us|1|abc|9.0.5.37988|wow\n\
+                    eu|0|def|9.0.2.36949|wow\n\
+                    us|1|ghi|1.13.6.37497|wow_classic_era\n";
+        assert_eq!(
+            parse_version(text, Flavor::Retail),
+            Some("9.0.5.37988".to_string())
+        );
+        assert_eq!(
+            parse_version(text, Flavor::ClassicEra),
+            Some("1.13.6.37497".to_string())
+        );
+        assert_eq!(parse_version(text, Flavor::ClassicTbc), None);
+        assert_eq!(interface_version("9.0.5.37988"), Some(90005));
+        assert_eq!(interface_version("1.13.6.37497"), Some(11306));
+        assert_eq!(interface_version("9.0"), None);
+    }
+}
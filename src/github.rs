@@ -0,0 +1,102 @@
+use crate::error::{check_status, GruntError};
+use crate::net::{self, NetOptions};
+use serde::Deserialize;
+
+const API_URL: &str = "https://api.github.com/repos";
+
+/// A release of a GitHub repository, tracked as `github:<owner>/<repo>` by its tag
+#[derive(Deserialize, Clone, Debug)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+/// A file attached to a release
+#[derive(Deserialize, Clone, Debug)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// The first zip archive attached to the release
+    fn zip_url(&self) -> Option<&str> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name.to_lowercase().ends_with(".zip"))
+            .map(|asset| asset.browser_download_url.as_str())
+    }
+}
+
+/// Fetches the tag and zip archive of the latest release of `repo`, like
+/// `WeakAuras/WeakAuras2`. Drafts and prereleases aren't included
+pub fn latest_release(repo: &str, options: &NetOptions) -> Result<(String, String), GruntError> {
+    let release = get_release(repo, "latest", options)?;
+    let url = zip_url(repo, &release)?;
+    Ok((release.tag_name, url))
+}
+
+/// Fetches the zip archive of the release of `repo` tagged `tag`
+pub fn release_url(repo: &str, tag: &str, options: &NetOptions) -> Result<String, GruntError> {
+    let release = get_release(repo, &format!("tags/{}", tag), options)?;
+    zip_url(repo, &release)
+}
+
+fn get_release(repo: &str, release: &str, options: &NetOptions) -> Result<Release, GruntError> {
+    match repo.split_once('/') {
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {}
+        _ => return Err(GruntError::NotFound(format!("github:{}", repo))),
+    }
+    let url = format!("{}/{}/releases/{}", API_URL, repo, release);
+    let client = options.client();
+    // The API rejects requests without a user agent
+    let request = client
+        .get(&url)
+        .header(reqwest::header::USER_AGENT, "grunt")
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json");
+    let resp = net::send(&client, request).map_err(|source| GruntError::Request {
+        url: url.clone(),
+        source,
+    })?;
+    check_status(resp)?
+        .json()
+        .map_err(|source| GruntError::Decode {
+            url,
+            source: source.into(),
+        })
+}
+
+fn zip_url(repo: &str, release: &Release) -> Result<String, GruntError> {
+    release.zip_url().map(str::to_string).ok_or_else(|| {
+        GruntError::NotFound(format!(
+            "Zip archive in release {} of github:{}",
+            release.tag_name, repo
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zip_url() {
+        let json = r#"{
+            "tag_name": "5.8.1",
+            "assets": [
+                {"name": "WeakAuras-5.8.1.tar.gz", "browser_download_url": "https://example.com/a.tar.gz"},
+                {"name": "WeakAuras-5.8.1.ZIP", "browser_download_url": "https://example.com/a.zip"},
+                {"name": "WeakAuras-5.8.1-nolib.zip", "browser_download_url": "https://example.com/b.zip"}
+            ]
+        }"#;
+        let release: Release = serde_json::from_str(json).unwrap();
+        assert_eq!(release.zip_url(), Some("https://example.com/a.zip"));
+        let release: Release = serde_json::from_str(r#"{"tag_name": "v1"}"#).unwrap();
+        assert_eq!(release.zip_url(), None);
+        assert!(matches!(
+            latest_release("WeakAuras", &NetOptions::default()),
+            Err(GruntError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::{Path, PathBuf};
+
+/// Makes the changes to `AddOns` dirs, or only lists them when dry running
+/// Everything that deletes or writes addon dirs goes through here so a dry run can't
+/// touch them by accident
+#[derive(Debug, Default)]
+pub struct WriteGuard {
+    dry_run: bool,
+    /// Dirs deleted, or that would be
+    pub deleted: Vec<PathBuf>,
+    /// Dirs written, or that would be
+    pub written: Vec<PathBuf>,
+}
+
+impl WriteGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dry_run() -> Self {
+        WriteGuard {
+            dry_run: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Deletes a dir and everything inside it
+    pub fn remove_dir_all(&mut self, path: &Path) -> std::io::Result<()> {
+        if !self.deleted.iter().any(|deleted| deleted == path) {
+            self.deleted.push(path.to_path_buf());
+        }
+        if self.dry_run {
+            return Ok(());
+        }
+        std::fs::remove_dir_all(path)
+    }
+
+    /// Runs `write`, which writes the dirs `paths`
+    pub fn write<E, F>(&mut self, paths: Vec<PathBuf>, write: F) -> Result<(), E>
+    where
+        F: FnOnce() -> Result<(), E>,
+    {
+        for path in paths {
+            if !self.written.contains(&path) {
+                self.written.push(path);
+            }
+        }
+        if self.dry_run {
+            return Ok(());
+        }
+        write()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let addon_dir = dir.path().join("A");
+        std::fs::create_dir(&addon_dir).unwrap();
+
+        let mut guard = WriteGuard::dry_run();
+        guard.remove_dir_all(&addon_dir).unwrap();
+        guard
+            .write(vec![dir.path().join("B")], || {
+                std::fs::create_dir(dir.path().join("B"))
+            })
+            .unwrap();
+        assert!(addon_dir.is_dir());
+        assert!(!dir.path().join("B").exists());
+        assert_eq!(guard.deleted, vec![addon_dir.clone()]);
+        assert_eq!(guard.written, vec![dir.path().join("B")]);
+
+        WriteGuard::new().remove_dir_all(&addon_dir).unwrap();
+        assert!(!addon_dir.exists());
+    }
+}
@@ -0,0 +1,108 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Days of history kept
+const KEEP_DAYS: i64 = 90;
+
+/// Updates applied to an `AddOns` dir and its size over time, for `grunt report`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    /// Oldest first
+    #[serde(default)]
+    pub updates: Vec<AppliedUpdate>,
+    /// Total size of the addons, at most one a day, oldest first
+    #[serde(default)]
+    pub disk_usage: Vec<DiskUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AppliedUpdate {
+    pub time: DateTime<Utc>,
+    pub name: String,
+    /// `source:id` identifier of the addon
+    pub identifier: String,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub time: DateTime<Utc>,
+    pub bytes: u64,
+}
+
+impl History {
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty() && self.disk_usage.is_empty()
+    }
+
+    pub fn record_update(&mut self, update: AppliedUpdate) {
+        let time = update.time;
+        self.updates.push(update);
+        self.prune(time);
+    }
+
+    /// Saves the size of the addons, replacing the one saved earlier the same day
+    pub fn record_disk_usage(&mut self, time: DateTime<Utc>, bytes: u64) {
+        if let Some(last) = self.disk_usage.last() {
+            if last.time.date_naive() == time.date_naive() {
+                self.disk_usage.pop();
+            }
+        }
+        self.disk_usage.push(DiskUsage { time, bytes });
+        self.prune(time);
+    }
+
+    /// Updates applied since `start`
+    pub fn updates_since(&self, start: DateTime<Utc>) -> Vec<&AppliedUpdate> {
+        self.updates
+            .iter()
+            .filter(|update| update.time >= start)
+            .collect()
+    }
+
+    /// Sizes saved since `start`, starting with the last one before it if there is one
+    pub fn disk_usage_since(&self, start: DateTime<Utc>) -> &[DiskUsage] {
+        let first = self
+            .disk_usage
+            .iter()
+            .rposition(|usage| usage.time < start)
+            .unwrap_or(0);
+        &self.disk_usage[first..]
+    }
+
+    fn prune(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - Duration::days(KEEP_DAYS);
+        self.updates.retain(|update| update.time >= cutoff);
+        self.disk_usage.retain(|usage| usage.time >= cutoff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_disk_usage() {
+        let day = |day, hour| Utc.with_ymd_and_hms(2026, 1, day, hour, 0, 0).unwrap();
+        let mut history = History::default();
+        history.record_disk_usage(day(1, 12), 100);
+        history.record_disk_usage(day(3, 12), 200);
+        history.record_disk_usage(day(3, 18), 300);
+        history.record_disk_usage(day(5, 12), 400);
+        let bytes =
+            |usages: &[DiskUsage]| -> Vec<u64> { usages.iter().map(|usage| usage.bytes).collect() };
+        assert_eq!(bytes(&history.disk_usage), vec![100, 300, 400]);
+        assert_eq!(bytes(history.disk_usage_since(day(4, 0))), vec![300, 400]);
+        assert_eq!(
+            bytes(history.disk_usage_since(day(1, 0))),
+            vec![100, 300, 400]
+        );
+
+        // Old sizes are dropped
+        let later = day(5, 12) + Duration::days(KEEP_DAYS);
+        history.record_disk_usage(later, 500);
+        assert_eq!(bytes(&history.disk_usage), vec![400, 500]);
+    }
+}
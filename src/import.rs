@@ -0,0 +1,173 @@
+use crate::error::GruntError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Addons listed in another addon manager's export, to be installed fresh
+pub struct Import {
+    /// Identifiers `Grunt::install_many` takes
+    pub identifiers: Vec<String>,
+    /// Entries that can't be installed, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Reads an export file of CurseBreaker or WowUp, telling them apart by their contents
+/// WowUp exports and addon stores are json, CurseBreaker's is an `install` command
+pub fn from_file(path: &Path) -> Result<Import, GruntError> {
+    let import_err = |source: Box<dyn std::error::Error + Send + Sync>| GruntError::Import {
+        path: path.to_string_lossy().to_string(),
+        source,
+    };
+    let text = std::fs::read_to_string(path).map_err(|err| import_err(err.into()))?;
+    let text = text.trim();
+    if text.starts_with('{') {
+        let export = serde_json::from_str(text).map_err(|err| import_err(err.into()))?;
+        Ok(from_wowup(export))
+    } else {
+        Ok(from_cursebreaker(text))
+    }
+}
+
+/// Parses the output of CurseBreaker's `export` command, like
+/// `install cf:details,tu:12,wowi:1234`
+fn from_cursebreaker(text: &str) -> Import {
+    let mut import = Import {
+        identifiers: Vec::new(),
+        skipped: Vec::new(),
+    };
+    let list = text.strip_prefix("install").unwrap_or(text);
+    for entry in list
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        match cursebreaker_identifier(entry) {
+            Ok(identifier) => import.identifiers.push(identifier),
+            Err(reason) => import.skipped.push((entry.to_string(), reason)),
+        }
+    }
+    import
+}
+
+fn cursebreaker_identifier(entry: &str) -> Result<String, String> {
+    if entry.starts_with("https://www.curseforge.com/") {
+        return Ok(entry.to_string());
+    }
+    if let Some(url) = entry.strip_prefix("https://www.tukui.org/") {
+        return match url.split_once('=') {
+            Some((_, "elvui")) => Ok("tukui:-2".to_string()),
+            Some(("addons.php?id", id)) | Some(("classic-addons.php?id", id)) => {
+                Ok(format!("tukui:{}", id))
+            }
+            _ => Err("Only ElvUI and addons of Tukui can be installed".to_string()),
+        };
+    }
+    // ElvUI is listed by name, with the branch after a colon
+    if entry
+        .split(':')
+        .next()
+        .unwrap()
+        .eq_ignore_ascii_case("elvui")
+    {
+        return Ok("tukui:-2".to_string());
+    }
+    match entry.split_once(':') {
+        Some(("cf", slug)) => Ok(format!("curse:{}", slug)),
+        Some(("tu", id)) | Some(("tuc", id)) => Ok(format!("tukui:{}", id)),
+        Some((prefix, _)) if !entry.contains("://") => {
+            Err(format!("{} addons aren't supported", prefix))
+        }
+        _ => Err("Unknown source".to_string()),
+    }
+}
+
+fn from_wowup(export: WowUpExport) -> Import {
+    let mut entries = match export {
+        WowUpExport::Export { addons } => addons,
+        WowUpExport::Store(store) => store.into_values().collect(),
+    };
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut import = Import {
+        identifiers: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for entry in entries {
+        // Store entries keep the source's id separately
+        let id = entry.external_id.as_ref().unwrap_or(&entry.id);
+        match entry.provider_name.as_str() {
+            "Curse" => import.identifiers.push(format!("curse:{}", id)),
+            "TukUI" => import.identifiers.push(format!("tukui:{}", id)),
+            "GitHub" => {
+                let repo = id.strip_prefix("https://github.com/").unwrap_or(id);
+                import.identifiers.push(format!("github:{}", repo));
+            }
+            provider => import
+                .skipped
+                .push((entry.name, format!("{} addons aren't supported", provider))),
+        }
+    }
+    import
+}
+
+/// WowUp's export, or its `addon-store.json`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WowUpExport {
+    Export { addons: Vec<WowUpEntry> },
+    Store(BTreeMap<String, WowUpEntry>),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WowUpEntry {
+    id: String,
+    name: String,
+    provider_name: String,
+    external_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export");
+        let import = |text: &str| {
+            std::fs::write(&path, text).unwrap();
+            from_file(&path).unwrap()
+        };
+
+        let cursebreaker = import("install cf:details,tu:12,ElvUI:Dev,wowi:1234\n");
+        assert_eq!(
+            cursebreaker.identifiers,
+            vec!["curse:details", "tukui:12", "tukui:-2"]
+        );
+        assert_eq!(
+            cursebreaker.skipped,
+            vec![(
+                "wowi:1234".to_string(),
+                "wowi addons aren't supported".to_string()
+            )]
+        );
+
+        let wowup = import(
+            r#"{"addons": [
+                {"id": "61284", "name": "Details", "version": "1", "providerName": "Curse"},
+                {"id": "WeakAuras/WeakAuras2", "name": "WeakAuras", "providerName": "GitHub"}
+            ]}"#,
+        );
+        assert_eq!(
+            wowup.identifiers,
+            vec!["curse:61284", "github:WeakAuras/WeakAuras2"]
+        );
+        assert!(wowup.skipped.is_empty());
+
+        let store = import(
+            r#"{"tukui--2": {"id": "tukui--2", "name": "ElvUI", "providerName": "TukUI",
+                "externalId": "-2"}}"#,
+        );
+        assert_eq!(store.identifiers, vec!["tukui:-2"]);
+    }
+}
@@ -1,32 +1,114 @@
-use crate::addon::AddonType;
-use crate::Grunt;
+use crate::addon::{AddonType, UpdateFailures, UpdateFrequency};
+use crate::curse::CurseCache;
+use crate::error::GruntError;
+use crate::flavor::Flavor;
+use crate::history::History;
+use crate::stats::Stats;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::collections::BTreeMap;
 use std::path::Path;
 
 #[derive(Serialize, Deserialize)]
 pub struct Lockfile {
     pub addons: Vec<AddonInfo>,
+    #[serde(default)]
+    pub curse_cache: CurseCache,
+    /// Dirs that couldn't be resolved
+    #[serde(default)]
+    pub unresolved: BTreeMap<String, UnresolvedDir>,
+    #[serde(default)]
+    pub stats: Stats,
+    /// Newest versions of addons not from Curse seen by the last check, by key
+    /// Curse ones are in its cache
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub latest: BTreeMap<String, String>,
+    /// Updates applied and disk usage, for reports
+    #[serde(default, skip_serializing_if = "History::is_empty")]
+    pub history: History,
+    /// Game version seen on the last run, to notice patches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_version: Option<String>,
+    /// Flavor of the AddOns dir, guessed from its path by older versions of grunt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flavor: Option<Flavor>,
 }
 
 impl Lockfile {
     /// Initialize using data from the specified file
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Self {
-        let file = File::open(path).expect("Error opening lockfile");
-        let reader = BufReader::new(file);
-        serde_json::from_reader(reader).expect("Error reading lockfile")
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, GruntError> {
+        let path = path.as_ref();
+        let error = |source: Box<dyn std::error::Error + Send + Sync>| GruntError::Storage {
+            path: path.display().to_string(),
+            source,
+        };
+        let text = std::fs::read_to_string(path).map_err(|err| error(err.into()))?;
+        Lockfile::from_json(&text).map_err(|err| error(err.into()))
     }
 
-    pub fn from_grunt(grunt: &Grunt) -> Self {
-        let addons = grunt.addons.iter().map(|addon| addon.to_info()).collect();
-        Lockfile { addons }
+    /// Parses a lockfile, including ones saved by older versions of grunt
+    pub fn from_json(text: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(text)
     }
 
-    pub fn save<P: AsRef<Path>>(&self, path: P) {
-        let file = File::create(path).expect("Error opening lockfile for write");
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, self).expect("Error writing to lockfile");
+    /// The lockfile as written to disk
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Error serializing lockfile")
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GruntError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json()).map_err(|source| GruntError::Storage {
+            path: path.display().to_string(),
+            source: source.into(),
+        })
+    }
+}
+
+/// A dir that couldn't be resolved the last time it was tried
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(from = "UnresolvedEntry")]
+pub struct UnresolvedDir {
+    /// Fingerprint of the dir when it was tried, it's only tried again once this changes
+    pub fingerprint: u32,
+    /// Unknown for dirs tried by older versions of grunt
+    pub attempted: Option<DateTime<Utc>>,
+    /// Why no addon was found, like the sources that were checked
+    pub reason: Option<String>,
+}
+
+/// Older lockfiles only saved the fingerprint
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UnresolvedEntry {
+    Fingerprint(u32),
+    Dir {
+        fingerprint: u32,
+        #[serde(default)]
+        attempted: Option<DateTime<Utc>>,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+}
+
+impl From<UnresolvedEntry> for UnresolvedDir {
+    fn from(entry: UnresolvedEntry) -> Self {
+        match entry {
+            UnresolvedEntry::Fingerprint(fingerprint) => UnresolvedDir {
+                fingerprint,
+                attempted: None,
+                reason: None,
+            },
+            UnresolvedEntry::Dir {
+                fingerprint,
+                attempted,
+                reason,
+            } => UnresolvedDir {
+                fingerprint,
+                attempted,
+                reason,
+            },
+        }
     }
 }
 
@@ -35,6 +117,49 @@ pub struct AddonInfo {
     pub name: String,
     pub addon_type: AddonType,
     pub addon_id: String,
+    /// Curse project slug
+    #[serde(default)]
+    pub slug: Option<String>,
     pub version: String,
     pub dirs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "UpdateFrequency::is_fast")]
+    pub frequency: UpdateFrequency,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_checked: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failures: Option<UpdateFailures>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install_root: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fingerprints: BTreeMap<String, u32>,
+    /// Paths of the installed files, empty for addons tracked before they were kept
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub priority: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_lockfile() {
+        // Saved before slugs, the Curse cache and unresolved fingerprints were added
+        let lockfile = Lockfile::from_json(include_str!("fixtures/lockfile_legacy.json")).unwrap();
+        insta::assert_snapshot!(lockfile.to_json());
+    }
+
+    #[test]
+    fn test_lockfile_roundtrip() {
+        let text = include_str!("fixtures/lockfile.json");
+        let lockfile = Lockfile::from_json(text).unwrap();
+        insta::assert_snapshot!(lockfile.to_json());
+        let reparsed = Lockfile::from_json(&lockfile.to_json()).unwrap();
+        assert_eq!(reparsed.to_json(), lockfile.to_json());
+    }
 }
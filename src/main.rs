@@ -1,183 +1,906 @@
-use clap::{clap_app, crate_description, crate_version, AppSettings};
-use dialoguer;
-use directories::ProjectDirs;
-use grunt::settings::Settings;
-use grunt::Grunt;
+use clap::{clap_app, crate_description, crate_version, AppSettings, Arg, SubCommand};
+use cli::output::{
+    AddonOutput, CompatOutput, ConflictsOutput, DiffOutput, LicenseOutput, LicensesOutput,
+    ListOutput, OutdatedOutput, SearchOutput,
+};
+use cli::progress::UpdateBars;
+use cli::render::{self, Renderer, TableRenderer};
+use cli::{crash, pager};
+use directories::{BaseDirs, ProjectDirs};
+use grunt::addon::{AddonType, UpdateFrequency};
+use grunt::bisect::Bisect;
+use grunt::error::GruntError;
+use grunt::export::ExportFormat;
+use grunt::game_build::GamePatch;
+use grunt::metrics::Metrics;
+use grunt::plan::UpdatePlan;
+use grunt::report::ReportFormat;
+use grunt::settings::{Settings, StorageBackend, UntrackedAction};
+use grunt::shared_cache::SharedCache;
+use grunt::signature::Signature;
+use grunt::table::{self, Column};
+use grunt::{backup, compare};
+use grunt::{Adoption, DryRun, Grunt};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+mod cli;
 
 /// Parses inputs and initializes grunt
 fn main() {
+    // Init project dirs
+    let project_dirs = ProjectDirs::from("", "", "grunt").expect("Couldn't find project dirs");
+    std::fs::create_dir_all(project_dirs.data_dir()).expect("Couldn't create data directory");
+
+    // Create directories if they don't exist
+    let config_dir = project_dirs.config_dir();
+    if !config_dir.exists() {
+        std::fs::create_dir(config_dir).expect("Error creating config dir");
+    }
+
+    // Init settings
+    let settings_path = config_dir.join("config.json");
+    let (mut settings, settings_error) = match Settings::from_file_or_new(&settings_path) {
+        Ok(settings) => (settings, None),
+        Err(err) => (Settings::new(), Some(err)),
+    };
+
     let app = clap_app!(("grunt") =>
         (version: crate_version!())
         (about: crate_description!())
         (setting: AppSettings::ArgRequiredElseHelp)
+        (@arg explain: --explain +global "Print details and suggestions when something fails")
+        (@arg verbose: -v --verbose +global "Print timings after resolving or updating")
+        (@arg profile_perf: --("profile-perf") +global
+            "Print how long each phase of resolving or updating took, to include when reporting slowness")
+        (@arg yes: -y --yes visible_alias("non-interactive") +global
+            "Answer prompts with their defaults. Fails instead of asking when a choice is needed")
+        (@arg trace_http: --("trace-http") +global
+            "Log every HTTP request to http-trace.log in the data dir, with tokens redacted")
+        (@arg record: --record +global +takes_value value_name("snapshot-dir")
+            "Save every source response to a dir, to attach to bug reports")
+        (@arg replay: --replay +global +takes_value value_name("snapshot-dir") conflicts_with[record]
+            "Serve every source response from a dir saved with --record instead of the network")
+        (@arg no_pager: --("no-pager") +global
+            "Print long output directly instead of through $PAGER or less")
+        (@arg all_profiles: --("all-profiles") +global
+            "Run update in the AddOns dir of every profile instead of the default dir")
+        (@subcommand profile =>
+            (about: "Name AddOns dirs, e.g. retail and classic, to update together with --all-profiles")
+            (@subcommand add =>
+                (about: "Add or change a profile")
+                (@arg name: +required "Name of the profile")
+                (@arg dir: +required "The AddOns dir")
+            )
+            (@subcommand remove =>
+                (about: "Remove a profile")
+                (@arg name: +required "Name of the profile")
+            )
+        )
+        (@subcommand flavor =>
+            (about: "Show or set the game flavor of the AddOns dir, used to pick Curse and Tukui files")
+            (@arg flavor: possible_value[retail classic_era classic_tbc classic_wrath]
+                "The flavor to use")
+        )
         (@subcommand setdir =>
             (about: "Change default directory")
-            (@arg dir: +required "The directory to use")
+            (@arg dir: "The directory to use. Windows paths are looked up in Wine prefixes. Looks for WoW in Wine, Lutris and Proton prefixes if left out")
+        )
+        (@subcommand stats =>
+            (about: "Show usage statistics. They're kept locally and never sent anywhere")
+        )
+        (@subcommand starter =>
+            (about: "Install a starter pack of addons for new players")
+            (@arg pack: "Pack to install, e.g. raid. Lists the packs if not given")
         )
         (@subcommand resolve =>
             (about: "Resolve untracked addons")
+            (@arg retry_unresolved: --("retry-unresolved")
+                "Check dirs that failed to resolve before even if they haven't changed")
+            (@arg builtin_rules: --("builtin-rules")
+                "Use the compiled-in Curse fingerprinting rules instead of fetching them")
         )
         (@subcommand update =>
             (about: "Update addons")
+            (@arg dry_run: --("dry-run")
+                "Download and check the updates, listing the dirs they'd delete and write without changing anything")
+            (@arg plan_out: --("plan-out") +takes_value requires[dry_run]
+                "Save the dry run as a plan file")
+            (@arg plan: --plan +takes_value conflicts_with[dry_run]
+                "Apply exactly the updates in a plan file")
+            (@arg max_bandwidth: --("max-bandwidth") +takes_value {validate_number}
+                "Download speed limit in KiB/s. 0 doesn't limit it")
+            (@arg keep_extra: --("keep-extra")
+                "Keep files in updated addon dirs that the new version doesn't have")
+            (@arg force: --force "Download addons installed with `add --url` again even if unchanged")
+        )
+        (@subcommand outdated =>
+            (about: "List addons with updates without installing them. Exits with 1 if any are outdated")
+            (@arg output: -o --output +takes_value possible_value[table plain json yaml]
+                "Output format. Defaults to table")
         )
         (@subcommand add =>
             (about: "Add addon(s)")
+            (@arg addons: required_unless[url] +multiple
+                "Curse project IDs, slugs like deadly-boss-mods or curse:<slug>, CurseForge URLs, tukui:<id>, github:<owner>/<repo> for a repository's latest release, custom:<name> for a source in the settings or zip URLs")
+            (@arg url: --url +takes_value +multiple number_of_values(1)
+                "Install a zip archive from a URL. It's updated when the server reports a newer Last-Modified date")
+            (@arg install_root: --("install-root") +takes_value
+                "Install into this dir instead of AddOns, relative to the WoW dir, e.g. Interface/Sounds")
+        )
+        (@subcommand search =>
+            (about: "Search Curse for addons")
+            (@arg query: +required +multiple "Words in the addon's name")
+            (@arg install: --install conflicts_with[json output]
+                "Pick results to install from a list")
+            (@arg output: -o --output +takes_value possible_value[table plain json yaml]
+                "Output format. Defaults to table")
+            (@arg json: --json conflicts_with[output] "Print as json. Same as `--output json`")
         )
         (@subcommand remove =>
             (about: "Remove addon(s)")
-            (@arg addons: +multiple "The addons to remove")
+            (@arg addons: +multiple "Names or source:id identifiers of the addons to remove")
+            (@arg dry_run: --("dry-run") requires[addons] "List the dirs that would be deleted")
+            (@arg unused_libs: --("unused-libs") conflicts_with[addons]
+                "Remove library addons that no other addon depends on")
         )
         (@subcommand rmdir =>
             (about: "Remove untracked directories")
             (@arg addons: +multiple "The directories to remove")
+            (@arg candidates: --candidates "Remove all dirs matching an auto-remove-candidates rule")
+        )
+        (@subcommand conflicts =>
+            (about: "List dirs claimed by more than one addon")
+            (@arg fix: --fix conflicts_with[json output] "Interactively fix each conflict")
+            (@arg output: -o --output +takes_value possible_value[table plain json yaml]
+                "Output format. Defaults to table")
+            (@arg json: --json conflicts_with[output] "Print as json. Same as `--output json`")
+        )
+        (@subcommand dedupe =>
+            (about: "Find addons tracked more than once, e.g. from both Curse and Tukui, and merge them")
+            (@arg prefer: --prefer +takes_value possible_value[curse tukui tsm custom]
+                "Source to keep unless another is picked. Defaults to curse")
+        )
+        (@subcommand bisect =>
+            (about: "Find the addon breaking the UI by disabling half of the suspects at a time")
+            (@setting SubcommandRequiredElseHelp)
+            (@subcommand start =>
+                (about: "Start with every addon as a suspect")
+            )
+            (@subcommand good =>
+                (about: "The UI works with the addons currently enabled")
+            )
+            (@subcommand bad =>
+                (about: "The UI is still broken with the addons currently enabled")
+            )
+            (@subcommand reset =>
+                (about: "Stop bisecting and enable every addon again")
+            )
         )
-        (@subcommand tsm =>
-            (about: "Update TSM auction data")
+        (@subcommand own =>
+            (about: "Make an addon the only owner of a directory")
+            (@arg dir: +required "The directory")
+            (@arg addon: +required "Name or source:id identifier of the new owner")
         )
         (@subcommand list =>
             (about: "List addons and untracked dirs")
+            (@arg columns: -c --columns +takes_value {validate_columns}
+                "Comma separated columns to show. Any of name,source,version,latest,size,updated,notes")
+            (@arg sort: -s --sort +takes_value
+                possible_value[name source version latest size updated notes] "Column to sort by")
+            (@arg long: -l --long conflicts_with[columns] "Show every column including notes")
+            (@arg output: -o --output +takes_value possible_value[table plain json yaml]
+                "Output format. Defaults to table")
+            (@arg json: --json conflicts_with[output] "Print as json. Same as `--output json`")
+        )
+        (@subcommand info =>
+            (about: "Show details about an addon")
+            (@arg addon: +required "Name or source:id identifier of the addon")
+            (@arg full: --full "Also show the addon's description")
+            (@arg output: -o --output +takes_value possible_value[table plain json yaml]
+                "Output format. Defaults to table")
+        )
+        (@subcommand compat =>
+            (about: "Show which game flavors and versions an addon supports")
+            (@arg addon: +required "Name or source:id identifier of the addon")
+            (@arg output: -o --output +takes_value possible_value[table plain json yaml]
+                "Output format. Defaults to table")
+        )
+        (@subcommand diff =>
+            (about: "List files of an addon that differ from the archive of its installed version")
+            (@arg addon: +required "Name or source:id identifier of the addon")
+            (@arg output: -o --output +takes_value possible_value[table plain json yaml]
+                "Output format. Defaults to table")
+        )
+        (@subcommand license =>
+            (about: "List addon licenses and flag addons that can't be redistributed")
+            (@arg output: -o --output +takes_value possible_value[table plain json yaml]
+                "Output format. Defaults to table")
+        )
+        (@subcommand note =>
+            (about: "Set the notes of an addon")
+            (@arg addon: +required "Name or source:id identifier of the addon")
+            (@arg text: conflicts_with[clear] "The notes. Prints the current notes if left out")
+            (@arg clear: --clear "Remove the notes")
+        )
+        (@subcommand frequency =>
+            (about: "Set how often an addon is checked for updates")
+            (@arg addon: +required "Name or source:id identifier of the addon")
+            (@arg frequency: possible_value[fast weekly frozen]
+                "Every run, once a week or never. Prints the current one if left out")
+        )
+        (@subcommand priority =>
+            (about: "Get or set whether an addon is updated before others, like ones needed to raid")
+            (@arg addon: +required "Name or source:id identifier of the addon")
+            (@arg priority: possible_value[high normal]
+                "high addons are installed first so the game can be started before the rest finish")
+        )
+        (@subcommand pin =>
+            (about: "Keep an addon at its installed version, update skips it")
+            (@arg addon: +required "Name or source:id identifier of the addon")
+        )
+        (@subcommand unpin =>
+            (about: "Let update install new versions of a pinned addon again")
+            (@arg addon: +required "Name or source:id identifier of the addon")
+        )
+        (@subcommand verify =>
+            (about: "List addons whose files were edited since grunt installed them")
+            (@arg addons: +multiple "Names or source:id identifiers of addons to check. Checks all if left out")
+            (@arg accept: --accept "Keep the edits, so they're no longer reported or skipped by update")
+        )
+        (@subcommand adopt =>
+            (about: "Check tracked addons against their dirs after moving or copying the AddOns dir")
+            (@arg resolve: --resolve "Stop tracking addons whose dirs changed or are missing and resolve the dirs again")
+        )
+        (@subcommand retry =>
+            (about: "Clear the failures of an addon that stopped being updated after failing too often")
+            (@arg addon: +required "Name or source:id identifier of the addon")
+        )
+        (@subcommand daemon =>
+            (about: "Periodically install updates during the configured update windows")
+            (@arg interval: -i --interval +takes_value {validate_number} "Minutes between update checks. Defaults to 60")
+            (@arg once: --once "Check for updates once then exit, for use by a scheduler")
+            (@subcommand status =>
+                (about: "Show the last run's results and when the next run is")
+            )
+            (@subcommand install =>
+                (about: "Run the daemon using the system scheduler (systemd, launchd or Task Scheduler)")
+                (@arg interval: -i --interval +takes_value {validate_number} "Minutes between update checks. Defaults to 60")
+            )
+            (@subcommand uninstall =>
+                (about: "Remove the scheduled job added by `daemon install`")
+            )
+        )
+        (@subcommand serve =>
+            (about: "Serve a JSON API on localhost for other tools")
+            (@arg port: -p --port +takes_value {validate_port} "Port to listen on. Defaults to 7531")
+            (@arg interval: -i --interval +takes_value {validate_number}
+                "Minutes between update checks while something listens to /events. Doesn't check if left out")
+        )
+        (@subcommand migrate =>
+            (about: "Import addons tracked by another addon manager")
+            (setting: AppSettings::SubcommandRequiredElseHelp)
+            (@subcommand curse =>
+                (about: "Import from the CurseForge client")
+                (@arg path: +required "An instance json file or the client's data dir to search")
+            )
+            (@subcommand wowup =>
+                (about: "Import from WowUp")
+                (@arg path: "WowUp's addon-store.json or data dir. Defaults to the usual location")
+            )
+        )
+        (@subcommand import =>
+            (about: "Install the addons in a list exported by another addon manager")
+            (@arg file: +required "A CurseBreaker export or a WowUp export or addon-store.json")
+        )
+        (@subcommand export =>
+            (about: "Print tracked addons in a format other addon managers or spreadsheets read")
+            (@arg format: -f --format +required +takes_value possible_values(&ExportFormat::NAMES)
+                "wowup and ajour write their import files, csv a spreadsheet, markdown a table to share")
+        )
+        (@subcommand rpc =>
+            (about: "Serve JSON-RPC on a unix socket for other tools")
+            (@arg socket: -s --socket +takes_value "Socket path. Defaults to grunt.sock in the data dir")
+        )
+        (@subcommand gc =>
+            (about: "Clean up stale cached data and leftover files")
+        )
+        (@subcommand backup =>
+            (about: "Save tracked addons and the lockfile to a compressed archive")
+            (@arg archive: -o --output +takes_value
+                "Archive to write. Defaults to grunt-backup-<date>.tar.zst")
+            (@arg saved_variables: --("saved-variables")
+                "Include the account wide saved variables in WTF, which hold addon settings")
+        )
+        (@subcommand restore =>
+            (about: "Replace tracked addons and the lockfile with a backup")
+            (@arg archive: +required "Archive made by `grunt backup`")
+            (@arg saved_variables: --("saved-variables")
+                "Only restore saved variables, leaving addons and the lockfile alone")
+            (@arg addons: +multiple requires[saved_variables]
+                "Names or source:id identifiers of addons to restore the saved variables of. Restores all if left out")
+            (@arg accept: --accept conflicts_with[saved_variables]
+                "Trust the restored lockfile and sign it with the current lockfile_secret")
+        )
+        (@subcommand snapshot =>
+            (about: "Compare backups and lockfiles, which record the addons at a point in time")
+            (setting: AppSettings::SubcommandRequiredElseHelp)
+            (@subcommand diff =>
+                (about: "List addons added, removed or updated between two snapshots")
+                (@arg before: +required "Backup made by `grunt backup` or a copy of the lockfile")
+                (@arg after: "Snapshot to compare with. Defaults to the addons now")
+            )
+        )
+        (@subcommand lockfile =>
+            (about: "Manage the signature of the lockfile, when `lockfile_secret` is set")
+            (@subcommand accept =>
+                (about: "Trust changes made to the lockfile outside of grunt and sign it again")
+            )
+        )
+        (@subcommand storage =>
+            (about: "Move saved addon data to another storage backend")
+            (@arg backend: +required possible_values(&["json", "sqlite"])
+                "json saves to grunt.lockfile, sqlite to grunt.db")
+        )
+        (@subcommand report =>
+            (about: "Print the last crash report")
+            (@arg enable: --enable conflicts_with[disable] "Opt in to saving crash reports")
+            (@arg disable: --disable "Stop saving crash reports")
+            (@subcommand weekly =>
+                (about: "Summarize updates applied, pending releases, abandoned addons and disk usage")
+                (@arg format: -f --format +takes_value possible_value[markdown html]
+                    "Format of the report. Defaults to markdown")
+                (@arg days: --days +takes_value {validate_number} "Days the report covers. Defaults to 7")
+                (@arg file: --file +takes_value "Write the report to a file instead of printing it")
+                (@arg post: --post "Post the report to the report_webhook in the settings")
+            )
         )
     );
+    // Commands of disabled sources are hidden
+    let mut tsm = SubCommand::with_name("tsm")
+        .about("Update TSM auction data")
+        .subcommand(
+            SubCommand::with_name("addons")
+                .about("List the addons TSM distributes and pick which to install or update")
+                .arg(
+                    Arg::with_name("addons")
+                        .multiple(true)
+                        .help("Names of the addons to install or update instead of picking"),
+                ),
+        );
+    if !settings.source_enabled(&AddonType::TSM) {
+        tsm = tsm.setting(AppSettings::Hidden);
+    }
+    let app = app.subcommand(tsm);
 
     // Parse args
     let matches = app.get_matches();
+    let explain = matches.is_present("explain");
+    let verbose = matches.is_present("verbose");
+    let profile = matches.is_present("profile_perf");
+    let yes = matches.is_present("yes");
+    if matches.is_present("no_pager") {
+        pager::disable();
+    }
 
-    // Init project dirs
-    let project_dirs = ProjectDirs::from("", "", "grunt").expect("Couldn't find project dirs");
-    std::fs::create_dir_all(project_dirs.data_dir()).expect("Couldn't create data directory");
+    if let Some(err) = settings_error {
+        print_error(&err, explain);
+        // Corrupt settings were moved aside, so the defaults can be used
+        if !matches!(err, GruntError::CorruptSettings { .. }) {
+            std::process::exit(1);
+        }
+    }
 
-    // Create directories if they don't exist
-    let config_dir = project_dirs.config_dir();
-    if !config_dir.exists() {
-        std::fs::create_dir(config_dir).expect("Error creating config dir");
+    // Log requests when debugging failed ones
+    if matches.is_present("trace_http") {
+        let trace_path = project_dirs.data_dir().join("http-trace.log");
+        grunt::net::trace_to(&trace_path).expect("Error opening HTTP trace log");
+        eprintln!("Tracing HTTP requests to {}", trace_path.display());
     }
 
-    // Init settings
-    let settings_path = config_dir.join("config.json");
-    let mut settings = Settings::from_file_or_new(&settings_path);
+    // Snapshots of source responses, to reproduce a run somewhere else
+    if let Some(dir) = matches.value_of("record") {
+        grunt::net::record_to(dir).expect("Error creating snapshot dir");
+        eprintln!("Recording responses to {}", dir);
+    } else if let Some(dir) = matches.value_of("replay") {
+        grunt::net::replay_from(dir);
+        eprintln!("Replaying responses from {}", dir);
+    }
+
+    // Shared installs can need files to be group writable
+    if let Some(umask) = or_exit(settings.umask_bits(), explain) {
+        grunt::perms::set_umask(umask);
+    }
+
+    // Save a report on crash if opted in
+    let crash_report_path = project_dirs.data_dir().join("crash-report.txt");
+    if *settings.crash_reports() {
+        let mut redactions = vec![
+            settings.addon_dir(),
+            settings.tsm_email().clone(),
+            settings.tsm_pass().clone(),
+            BaseDirs::new().map(|dirs| dirs.home_dir().to_string_lossy().to_string()),
+        ];
+        if let Some(Ok(dir)) = settings.addon_dir().map(std::fs::canonicalize) {
+            redactions.push(Some(dir.to_string_lossy().to_string()));
+        }
+        crash::install(
+            crash_report_path.clone(),
+            matches.subcommand_name().unwrap_or("").to_string(),
+            redactions.into_iter().flatten().collect(),
+        );
+    }
+
+    // Crash reports don't need an addon dir, unlike `report weekly`
+    let crash_report_args = match matches.subcommand() {
+        ("report", Some(args)) if args.subcommand_name().is_none() => Some(args),
+        _ => None,
+    };
+    if let Some(args) = crash_report_args {
+        if args.is_present("enable") || args.is_present("disable") {
+            settings.set_crash_reports(args.is_present("enable"));
+            or_exit(settings.save(&settings_path), explain);
+            println!(
+                "Crash reports {}",
+                if *settings.crash_reports() {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            );
+        } else if crash_report_path.exists() {
+            let report =
+                std::fs::read_to_string(&crash_report_path).expect("Error reading crash report");
+            print!("{}", report);
+        } else if *settings.crash_reports() {
+            println!("No crash report found");
+        } else {
+            println!("No crash report found. Enable them using `grunt report --enable`");
+        }
+        return;
+    }
+
+    // Profiles don't need an addon dir either
+    if let ("profile", Some(args)) = matches.subcommand() {
+        let mut profiles = settings.profiles().clone();
+        match args.subcommand() {
+            ("add", Some(args)) => {
+                let name = args.value_of("name").unwrap();
+                let dir = args.value_of("dir").unwrap();
+                if !Path::new(&grunt::settings::expand_path(dir)).is_dir() {
+                    println!("{} isn't a directory", dir);
+                    std::process::exit(1);
+                }
+                profiles.insert(name.to_string(), dir.to_string());
+                println!("Profile {} set to '{}'", name, dir);
+            }
+            ("remove", Some(args)) => {
+                let name = args.value_of("name").unwrap();
+                if profiles.remove(name).is_none() {
+                    println!("No profile named {}", name);
+                    std::process::exit(1);
+                }
+                println!("Removed profile {}", name);
+            }
+            _ => {
+                if profiles.is_empty() {
+                    println!("No profiles. Add one using `grunt profile add`");
+                }
+                for (name, dir) in settings.profile_dirs().iter() {
+                    println!("{}\t{}", name, dir);
+                }
+                return;
+            }
+        }
+        settings.set_profiles(profiles);
+        or_exit(settings.save(&settings_path), explain);
+        return;
+    }
+
+    // Update every profile, fetching and downloading each addon once
+    if matches.is_present("all_profiles") {
+        let args = match matches.subcommand() {
+            ("update", Some(args)) if !args.is_present("plan") => args,
+            _ => {
+                println!("--all-profiles can only be used with update and update --dry-run");
+                std::process::exit(1);
+            }
+        };
+        let profiles = settings.profile_dirs();
+        if profiles.is_empty() {
+            println!("No profiles. Add one using `grunt profile add`");
+            std::process::exit(1);
+        }
+        let shared_dir = project_dirs.data_dir().join("shared-cache");
+        // Profiles still update without the cache, they just fetch everything themselves
+        let shared = match SharedCache::create(&shared_dir) {
+            Ok(shared) => Some(shared),
+            Err(err) => {
+                eprintln!(
+                    "\x1B[1mWarning:\x1B[0m Couldn't create the shared cache: {}",
+                    err
+                );
+                None
+            }
+        };
+        let mut failed = false;
+        for (name, dir) in profiles.iter() {
+            println!("\x1B[1m== {} ==\x1B[0m", name);
+            println!("{}", dir);
+            if !Path::new(dir).is_dir() {
+                println!("{} doesn't exist\n", dir);
+                failed = true;
+                continue;
+            }
+            let mut grunt = match init_grunt(dir, &settings) {
+                Ok(grunt) => grunt,
+                Err(err) => {
+                    print_error(&err, explain);
+                    println!();
+                    failed = true;
+                    continue;
+                }
+            };
+            grunt.record_command("update");
+            grunt.set_shared_cache(shared.clone());
+            if !run_update(&mut grunt, args, &settings, yes, verbose, profile, explain) {
+                failed = true;
+            }
+            println!();
+        }
+        if let Some(Err(err)) = shared.map(|shared| shared.clear()) {
+            eprintln!(
+                "\x1B[1mWarning:\x1B[0m Couldn't delete the shared cache: {}",
+                err
+            );
+        }
+        if failed {
+            std::process::exit(1);
+        }
+        return;
+    }
 
     // Set addon dir first
     let subcommand = matches.subcommand();
     if subcommand.0 == "setdir" {
         let args = subcommand.1.unwrap();
-        let dir = args.value_of("dir").unwrap().to_string();
-        settings.set_default_dir(Some(dir.clone()));
-        settings.save(&settings_path);
-        println!("Addon directory set to '{}'", dir);
+        let prefixes = grunt::wine::find_prefixes();
+        let given = match args.value_of("dir") {
+            // Paths copied from the game's Windows settings, like `C:\Program Files (x86)\...`
+            Some(dir) if !cfg!(windows) => {
+                grunt::wine::translate_path(dir, &prefixes).unwrap_or_else(|| PathBuf::from(dir))
+            }
+            Some(dir) => PathBuf::from(dir),
+            None => match grunt::wine::find_addons_dirs(&prefixes).as_slice() {
+                [dir] => dir.clone(),
+                [] => {
+                    println!("Couldn't find WoW in a Wine prefix. Pass its AddOns directory");
+                    std::process::exit(1);
+                }
+                dirs => {
+                    println!("Found several AddOns directories, pass the one to use:");
+                    for dir in dirs {
+                        println!("{}", dir.display());
+                    }
+                    std::process::exit(1);
+                }
+            },
+        };
+        let dir = or_exit(grunt::find_addons_dir(&given), explain);
+        if !grunt::looks_like_wow(&dir) {
+            eprintln!(
+                "\x1B[1mWarning:\x1B[0m {} doesn't look like it's in a WoW install",
+                dir.display()
+            );
+        }
+        settings.set_addon_dir(grunt::settings::portable_path(&dir));
+        or_exit(settings.save(&settings_path), explain);
+        println!("Addon directory set to '{}'", dir.display());
     }
 
     // Init grunt
-    let addon_dir = match settings.default_dir() {
+    let addon_dir = match settings.addon_dir() {
         Some(dir) => dir,
         None => {
             println!("No Addon directory setup. Change it using the `setdir` command");
             return;
         }
     };
-    let mut grunt = Grunt::new(addon_dir);
+    let addon_dir = addon_dir.as_str();
+    let mut grunt = or_exit(init_grunt(addon_dir, &settings), explain);
+    // Only saved by commands that change the lockfile anyway, so read-only commands and
+    // dry runs don't write anything
+    grunt.record_command(subcommand.0);
 
-    // Print header
+    // Print header unless the output is meant for other programs
+    let renderer = match matches.subcommand() {
+        (_, Some(args)) if args.is_present("json") => render::renderer("json"),
+        (_, Some(args)) => render::renderer(args.value_of("output").unwrap_or("table")),
+        _ => render::renderer("table"),
+    };
+    if !renderer.is_human() {
+        match matches.subcommand() {
+            ("list", Some(args)) => println!("{}", renderer.render(&list_output(&grunt, args))),
+            ("conflicts", _) => println!("{}", renderer.render(&ConflictsOutput::new(&grunt))),
+            ("info", Some(args)) => println!("{}", renderer.render(&info_output(&grunt, args))),
+            ("license", _) => println!("{}", renderer.render(&licenses_output(&grunt, explain))),
+            ("search", Some(args)) => {
+                println!("{}", renderer.render(&search_output(&grunt, args, explain)))
+            }
+            ("compat", Some(args)) => {
+                println!("{}", renderer.render(&compat_output(&grunt, args, explain)))
+            }
+            ("diff", Some(args)) => {
+                println!("{}", renderer.render(&diff_output(&grunt, args, explain)))
+            }
+            ("outdated", _) => print_outdated(&mut grunt, &settings, renderer.as_ref(), explain),
+            _ => unreachable!(),
+        }
+        return;
+    }
+    // Exports are printed alone so they can be redirected to a file
+    if let ("export", Some(args)) = matches.subcommand() {
+        let format = ExportFormat::from_name(args.value_of("format").unwrap()).unwrap();
+        let export = grunt::export::export(grunt.addons(), *grunt.flavor(), format);
+        println!("{}", export.text);
+        for (name, reason) in export.skipped {
+            eprintln!("Skipped {}: {}", name, reason);
+        }
+        return;
+    }
+    // Reports too, unless they're written to a file or posted
+    if let ("report", Some(args)) = matches.subcommand() {
+        let args = args.subcommand_matches("weekly").unwrap();
+        weekly_report(&mut grunt, args, &settings, explain);
+        return;
+    }
     println!("\x1B[1mGrunt - WoW Addon Manager+\x1B[0m");
     println!("{}", grunt.root_dir().to_str().unwrap());
+    if grunt.flavor().is_classic() {
+        println!("Flavor: {:?}", grunt.flavor());
+    }
     println!("{} addons", grunt.addons().len());
-    let untracked = grunt.find_untracked();
+    let bisect_path = project_dirs.data_dir().join("bisect.json");
+    if subcommand.0 != "bisect" && bisect_path.exists() {
+        println!("Bisecting with some addons disabled. Use `grunt bisect reset` to enable them");
+    }
+    let untracked = or_exit(grunt.find_untracked(), explain);
     if !untracked.is_empty() {
         println!("{} untracked addon dirs", untracked.len());
     }
+    // The new game version is saved with the next change, so the patch is shown until then
+    if let Some(patch) = grunt.check_game_patch() {
+        print_game_patch(&patch);
+    }
     println!();
 
     // Run command
     // Always save lockfile after every command that makes changes to addons
     match matches.subcommand() {
         ("setdir", _) => (), // Implemented further up
-        ("update", _) => {
-            let check_fn = |mut updateable: Vec<grunt::Updateable>| -> Vec<grunt::Updateable> {
-                // Return early if no updateable addons
-                if updateable.is_empty() {
-                    return updateable;
-                }
-                println!("{} addons to update", updateable.len());
-                updateable.sort_by(|a, b| a.name.cmp(&b.name));
-                let names: Vec<(&String, bool)> =
-                    updateable.iter().map(|upd| (&upd.name, true)).collect();
-                let picked_indexes = dialoguer::MultiSelect::new()
-                    .with_prompt("Addons to update")
-                    .items_checked(&names)
-                    .paged(true)
-                    .interact()
-                    .unwrap();
-
-                // Return early if user picks no addons to update
-                if picked_indexes.is_empty() {
-                    return Vec::new();
+        ("flavor", Some(args)) => match args.value_of("flavor") {
+            Some(id) => {
+                grunt.change_flavor(grunt::flavor::Flavor::from_id(id).unwrap());
+                or_exit(grunt.save_lockfile(), explain);
+                println!("Flavor set to {}", grunt.flavor().name());
+                if settings.flavor_of(addon_dir).is_some() {
+                    println!("The flavor in the settings file is used instead. Remove it to use this one");
                 }
-
-                // Confirm selection
-                let is_sure = dialoguer::Confirm::new()
-                    .with_prompt("Are you sure?")
-                    .interact()
-                    .unwrap();
-                if !is_sure {
-                    return Vec::new();
-                }
-
-                // Filter updateable by indexes picked and return
-                updateable
+            }
+            None => println!("{} ({})", grunt.flavor().name(), grunt.flavor().id()),
+        },
+        ("update", Some(args)) => {
+            if !run_update(&mut grunt, args, &settings, yes, verbose, profile, explain) {
+                std::process::exit(1);
+            }
+        }
+        ("add", matches) => {
+            let matches = matches.unwrap();
+            let to_add = matches.values_of("addons").into_iter().flatten();
+            // `--url` installs the URL as an archive even if it's a CurseForge page
+            let urls = matches.values_of("url").into_iter().flatten();
+            let urls: Vec<String> = urls.map(|url| format!("url:{}", url)).collect();
+            let queries: Vec<&str> = to_add.chain(urls.iter().map(String::as_str)).collect();
+            if !install_all(
+                &mut grunt,
+                &queries,
+                matches.value_of("install_root"),
+                explain,
+            ) {
+                std::process::exit(1);
+            }
+        }
+        ("stats", _) => {
+            let stats = grunt.stats();
+            let mut rows = vec![
+                ("Addons managed".to_string(), grunt.addons().len()),
+                ("Installed".to_string(), stats.installed as usize),
+                ("Updates applied".to_string(), stats.updated as usize),
+                ("Removed".to_string(), stats.removed as usize),
+                (
+                    "Commands run".to_string(),
+                    stats.commands.values().sum::<u64>() as usize,
+                ),
+            ];
+            // Most used commands
+            let mut commands: Vec<(&String, &u64)> = stats.commands.iter().collect();
+            commands.sort_by(|a, b| b.1.cmp(a.1));
+            rows.extend(
+                commands
                     .into_iter()
-                    .enumerate()
-                    .filter(|(index, _)| picked_indexes.contains(index))
-                    .map(|(_, upd)| upd)
-                    .collect()
-            };
-            println!("Checking for addons to update");
-            grunt.update_addons(
-                check_fn,
-                settings.tsm_email().as_ref(),
-                settings.tsm_pass().as_ref(),
+                    .take(5)
+                    .map(|(command, count)| (format!("  {}", command), *count as usize)),
             );
-            grunt.save_lockfile();
-            println!("Done");
+            let mut stats_table = table::new_table(&[]);
+            for (label, value) in rows {
+                stats_table.add_row(vec![label, value.to_string()]);
+            }
+            if let Some(since) = stats.since {
+                println!(
+                    "\x1B[1mSince {}\x1B[0m",
+                    since.with_timezone(&chrono::Local).format("%Y-%m-%d")
+                );
+            }
+            println!("{}", stats_table);
+            println!("Stats are only kept on this computer and never sent anywhere");
+        }
+        ("starter", Some(args)) => {
+            let packs = grunt::starter::packs(settings.starter_packs());
+            let name = match args.value_of("pack") {
+                Some(name) => name,
+                None => {
+                    let mut pack_table = table::new_table(&["Pack", "Addons", "Description"]);
+                    for (name, pack) in packs.iter() {
+                        pack_table.add_row(vec![
+                            name.clone(),
+                            pack.addons_for(*grunt.flavor()).len().to_string(),
+                            pack.description.clone(),
+                        ]);
+                    }
+                    println!("{}", pack_table);
+                    return;
+                }
+            };
+            let pack = match packs.get(name) {
+                Some(pack) => pack,
+                None => {
+                    print_error(&GruntError::NotFound(format!("Pack {}", name)), explain);
+                    std::process::exit(1);
+                }
+            };
+            let flavor = *grunt.flavor();
+            if !install_all(&mut grunt, pack.addons_for(flavor), None, explain) {
+                std::process::exit(1);
+            }
+        }
+        ("search", Some(args)) => {
+            let output = search_output(&grunt, args, explain);
+            if !args.is_present("install") {
+                pager::page(&renderer.render(&output));
+                return;
+            }
+            if output.results.is_empty() {
+                println!("Nothing found for {}", output.query);
+                return;
+            }
+            if yes {
+                println!("Installing from search needs a choice. Use `grunt add` instead");
+                std::process::exit(1);
+            }
+            let items: Vec<(String, bool)> = output
+                .results
+                .iter()
+                .map(|result| {
+                    let item = format!(
+                        "{} ({} downloads{}) - {}",
+                        result.name,
+                        result.downloads,
+                        if result.installed { ", installed" } else { "" },
+                        result.summary
+                    );
+                    (item, false)
+                })
+                .collect();
+            let picked_indexes = dialoguer::MultiSelect::new()
+                .with_prompt("Addons to install")
+                .items_checked(&items)
+                .paged(true)
+                .interact()
+                .unwrap();
+            let identifiers: Vec<String> = picked_indexes
+                .into_iter()
+                .map(|index| output.results[index].identifier.clone())
+                .collect();
+            if !install_all(&mut grunt, &identifiers, None, explain) {
+                std::process::exit(1);
+            }
         }
-        ("resolve", _) => {
+        ("resolve", Some(args)) => {
             // Resolve
+            grunt.set_builtin_rules(args.is_present("builtin_rules"));
+            flatten_nested(&mut grunt, yes, explain);
             println!("Resolving untracked addons...");
             println!();
-            let mut first = true;
+            let mut found = table::new_table(&["Name", "Source"]);
             let prog_func = move |prog| match prog {
                 grunt::ResolveProgress::NewAddon { name, desc } => {
-                    if first {
+                    found.add_row(vec![name, desc]);
+                }
+                grunt::ResolveProgress::Finished { not_found, skipped } => {
+                    if found.row_count() > 0 {
                         println!("\x1B[1mFound:\x1B[0m");
-                        first = false;
+                        println!("{}", found);
                     }
-                    println!("{:32} {}", name, desc)
-                }
-                grunt::ResolveProgress::Finished { not_found } => {
                     println!("\x1B[1m{} unresolved:\x1B[0m", not_found.len());
                     not_found.iter().for_each(|x| println!("{}", x));
+                    if skipped > 0 {
+                        println!(
+                            "{} unchanged dirs weren't checked again. Use --retry-unresolved to check them",
+                            skipped
+                        );
+                    }
+                }
+            };
+            let metrics = match grunt.resolve(args.is_present("retry_unresolved"), prog_func) {
+                Ok(metrics) => metrics,
+                Err(err) => {
+                    print_error(&err, explain);
+                    std::process::exit(1);
                 }
             };
-            grunt.resolve(prog_func);
+            print_moved_projects(&metrics);
+            if verbose {
+                print_metrics(&metrics);
+            }
 
             // Check conflicts
-            let conflicts = grunt.check_conflicts();
-            if !conflicts.is_empty() {
-                println!("\x1B[1mError: Conflicting addons found!\x1B[0m");
-                println!("{:16} {:16} {:16}", "Directory", "Addon", "Addon");
-                for conflict in conflicts {
-                    let addon_a = &grunt.addons()[conflict.addon_a_index];
-                    let addon_b = &grunt.addons()[conflict.addon_b_index];
-                    println!(
-                        "{:16} {:16} {:16}",
-                        conflict.dir,
-                        addon_a.name(),
-                        addon_b.name()
-                    );
-                }
+            if print_conflicts(&grunt) {
+                println!("Run `grunt conflicts --fix` to fix them");
                 println!();
             }
 
             // Save
-            grunt.save_lockfile();
+            or_exit(grunt.save_lockfile(), explain);
+            if profile {
+                print_profile(&metrics, grunt.lockfile_io());
+            }
+        }
+        ("remove", Some(args)) if args.is_present("unused_libs") => {
+            println!("Checking for unused libraries");
+            let unused = match grunt.unused_libs() {
+                Ok(unused) => unused,
+                Err(err) => {
+                    print_error(&err, explain);
+                    std::process::exit(1);
+                }
+            };
+            if unused.is_empty() {
+                println!("No unused libraries");
+                return;
+            }
+            println!("\x1B[1m{} unused libraries\x1B[0m", unused.len());
+            for &index in unused.iter() {
+                println!("  {}", grunt.addons()[index].name());
+            }
+            if !yes {
+                let is_sure = dialoguer::Confirm::new()
+                    .with_prompt("Remove them?")
+                    .interact()
+                    .unwrap();
+                if !is_sure {
+                    return;
+                }
+            }
+            let to_remove: Vec<String> = unused
+                .iter()
+                .map(|&index| grunt.addons()[index].key())
+                .collect();
+            if let Err(err) = grunt.remove_addons(&to_remove) {
+                print_error(&err, explain);
+                std::process::exit(1);
+            }
+            or_exit(grunt.save_lockfile(), explain);
+            println!("Removed {} libraries", to_remove.len());
         }
         ("remove", matches) => {
             // Remove
@@ -185,11 +908,14 @@ fn main() {
                 if let Some(addon_names) = matches.unwrap().values_of("addons") {
                     // Get addon names from cli arguments
                     addon_names.map(|s| s.to_string()).collect()
+                } else if yes {
+                    println!("No addons given. Pass the addons to remove when using --yes");
+                    std::process::exit(1);
                 } else {
                     // Get addon names via a multiselect dialogue
-                    let mut options: Vec<&String> =
-                        grunt.addons().iter().map(|addon| addon.name()).collect();
-                    options.sort();
+                    let mut addons: Vec<&grunt::addon::Addon> = grunt.addons().iter().collect();
+                    addons.sort_by(|a, b| a.name().cmp(b.name()));
+                    let options: Vec<&String> = addons.iter().map(|addon| addon.name()).collect();
                     let result = dialoguer::MultiSelect::new()
                         .with_prompt("Addons to remove")
                         .items(&options)
@@ -206,46 +932,1595 @@ fn main() {
                     if !is_sure {
                         return;
                     }
-                    result.iter().map(|&i| options[i].to_string()).collect()
+                    result.iter().map(|&i| addons[i].key()).collect()
                 };
+            if matches.unwrap().is_present("dry_run") {
+                let dirs = or_exit(grunt.dry_run_remove(&to_remove), explain);
+                let dirs: Vec<String> = dirs
+                    .iter()
+                    .map(|dir| dir.to_string_lossy().to_string())
+                    .collect();
+                print_dir_changes("Would delete", &dirs);
+                if dirs.is_empty() {
+                    println!("No dirs would be deleted");
+                }
+                return;
+            }
             // Remove addons
-            grunt.remove_addons(&to_remove);
+            if let Err(err) = grunt.remove_addons(&to_remove) {
+                print_error(&err, explain);
+                std::process::exit(1);
+            }
 
             // Save
-            grunt.save_lockfile();
+            or_exit(grunt.save_lockfile(), explain);
         }
         ("rmdir", matches) => {
-            if let Some(dir_names) = matches.unwrap().values_of("addons") {
+            let matches = matches.unwrap();
+            if matches.is_present("candidates") {
+                let dirs: Vec<String> = or_exit(grunt.find_untracked(), explain)
+                    .into_iter()
+                    .filter(|dir| {
+                        grunt.untracked_action(dir) == Some(UntrackedAction::AutoRemoveCandidates)
+                    })
+                    .collect();
+                let len = dirs.len();
+                or_exit(grunt.remove_dirs(dirs), explain);
+                println!("Deleted {} directories", len);
+            } else if let Some(dir_names) = matches.values_of("addons") {
                 // Get addon names from cli arguments
                 let dirs: Vec<String> = dir_names.map(|s| s.to_string()).collect();
                 let len = dirs.len();
-                grunt.remove_dirs(dirs);
+                or_exit(grunt.remove_dirs(dirs), explain);
                 println!("Deleted {} directories", len);
             } else {
                 println!("No directories specified");
             }
         }
-        ("list", _) => {
-            let addons = grunt.addons();
-            let mut addon_strings: Vec<String> = addons
+        ("outdated", _) => print_outdated(&mut grunt, &settings, renderer.as_ref(), explain),
+        ("list", matches) => {
+            pager::page(&renderer.render(&list_output(&grunt, matches.unwrap())));
+        }
+        ("info", Some(args)) if args.is_present("full") => {
+            pager::page(&renderer.render(&info_output(&grunt, args)));
+        }
+        ("info", Some(args)) => {
+            println!("{}", renderer.render(&info_output(&grunt, args)));
+        }
+        ("license", _) => {
+            pager::page(&renderer.render(&licenses_output(&grunt, explain)));
+        }
+        ("compat", Some(args)) => {
+            println!("{}", renderer.render(&compat_output(&grunt, args, explain)));
+        }
+        ("diff", Some(args)) => {
+            println!("Comparing with the archive of the installed version...");
+            pager::page(&renderer.render(&diff_output(&grunt, args, explain)));
+        }
+        ("note", Some(args)) => {
+            let index = match grunt.find_addon(args.value_of("addon").unwrap()) {
+                Ok(index) => index,
+                Err(err) => {
+                    print_error(&err, explain);
+                    std::process::exit(1);
+                }
+            };
+            let name = grunt.addons()[index].name().clone();
+            if args.is_present("clear") {
+                grunt.set_notes(index, None);
+                or_exit(grunt.save_lockfile(), explain);
+                println!("Removed notes from {}", name);
+            } else if let Some(text) = args.value_of("text") {
+                grunt.set_notes(index, Some(text.to_string()));
+                or_exit(grunt.save_lockfile(), explain);
+                println!("Notes saved for {}", name);
+            } else {
+                match grunt.addons()[index].notes() {
+                    Some(notes) => println!("{}", notes),
+                    None => println!("{} has no notes", name),
+                }
+            }
+        }
+        ("frequency", Some(args)) => {
+            let index = match grunt.find_addon(args.value_of("addon").unwrap()) {
+                Ok(index) => index,
+                Err(err) => {
+                    print_error(&err, explain);
+                    std::process::exit(1);
+                }
+            };
+            let name = grunt.addons()[index].name().clone();
+            match args.value_of("frequency") {
+                Some(frequency) => {
+                    grunt.set_frequency(index, UpdateFrequency::from_name(frequency).unwrap());
+                    or_exit(grunt.save_lockfile(), explain);
+                    println!(
+                        "{} will be checked for updates {}",
+                        name,
+                        frequency_text(frequency)
+                    );
+                }
+                None => {
+                    let frequency = grunt.addons()[index].frequency().name();
+                    println!(
+                        "{} is checked for updates {}",
+                        name,
+                        frequency_text(frequency)
+                    );
+                }
+            }
+        }
+        ("priority", Some(args)) => {
+            let index = or_exit(grunt.find_addon(args.value_of("addon").unwrap()), explain);
+            let name = grunt.addons()[index].name().clone();
+            match args.value_of("priority") {
+                Some(priority) => {
+                    grunt.set_priority(index, priority == "high");
+                    or_exit(grunt.save_lockfile(), explain);
+                    println!("{} has {} priority", name, priority);
+                }
+                None if *grunt.addons()[index].priority() => {
+                    println!("{} has high priority", name)
+                }
+                None => println!("{} has normal priority", name),
+            }
+        }
+        (command @ "pin", Some(args)) | (command @ "unpin", Some(args)) => {
+            let index = or_exit(grunt.find_addon(args.value_of("addon").unwrap()), explain);
+            let addon = &grunt.addons()[index];
+            let name = addon.name().clone();
+            let version = addon.version().clone();
+            // The frequency is left alone, so unpinning goes back to it
+            let pin = command == "pin";
+            match (pin, *addon.pinned()) {
+                (true, true) => println!("{} is already pinned at {}", name, version),
+                (false, false) => println!("{} isn't pinned", name),
+                _ => {
+                    grunt.set_pinned(index, pin);
+                    or_exit(grunt.save_lockfile(), explain);
+                    if pin {
+                        println!("Pinned {} at {}", name, version);
+                    } else {
+                        println!("Unpinned {}", name);
+                    }
+                }
+            }
+        }
+        ("verify", Some(args)) => {
+            let indexes: Vec<usize> = match args.values_of("addons") {
+                Some(queries) => queries
+                    .map(|query| match grunt.find_addon(query) {
+                        Ok(index) => index,
+                        Err(err) => {
+                            print_error(&err, explain);
+                            std::process::exit(1);
+                        }
+                    })
+                    .collect(),
+                None => (0..grunt.addons().len()).collect(),
+            };
+            let modified: Vec<(usize, Vec<String>)> = indexes
+                .into_iter()
+                .map(|index| (index, grunt.modified_dirs(&grunt.addons()[index])))
+                .filter(|(_, dirs)| !dirs.is_empty())
+                .collect();
+            for (index, dirs) in modified.iter() {
+                println!(
+                    "{} was edited in {}",
+                    grunt.addons()[*index].name(),
+                    dirs.join(", ")
+                );
+            }
+            if modified.is_empty() {
+                println!("No addons were edited");
+            } else if args.is_present("accept") {
+                for (index, _) in modified.iter() {
+                    grunt.accept_modified(*index);
+                }
+                or_exit(grunt.save_lockfile(), explain);
+                println!("Kept the edits to {} addons", modified.len());
+            } else if *settings.skip_modified_addons() {
+                println!("Edited addons aren't updated. Run with --accept to keep the edits and update them again");
+            } else {
+                println!("Updating these addons will overwrite the edits. Set `skip_modified_addons` in the settings to skip them");
+            }
+        }
+        ("adopt", Some(args)) => {
+            let checks: Vec<Adoption> = grunt
+                .addons()
                 .iter()
-                .map(|addon| format!("{:32} {}", addon.name(), addon.desc_string()))
+                .map(|addon| grunt.check_adoption(addon))
                 .collect();
-            addon_strings.sort();
-            println!("\x1B[1m{} Addons:\x1B[0m", addon_strings.len());
-            addon_strings.iter().for_each(|s| println!("{}", s));
-
-            let untracked = grunt.find_untracked();
-            println!("\x1B[1m{} Untracked:\x1B[0m", untracked.len());
-            untracked.iter().for_each(|s| println!("{}", s));
-        }
-        ("tsm", _) => {
-            grunt.update_tsm_data(
-                settings.tsm_email().as_ref().unwrap(),
-                settings.tsm_pass().as_ref().unwrap(),
-            );
-            println!("TSM data updated");
+            let mut moved = 0;
+            let mut mismatched = Vec::new();
+            for (index, check) in checks.into_iter().enumerate() {
+                let name = grunt.addons()[index].name().clone();
+                match check {
+                    Adoption::Unchanged => {}
+                    Adoption::Moved => {
+                        grunt.accept_modified(index);
+                        moved += 1;
+                    }
+                    Adoption::Changed(dirs) => {
+                        println!("{} has different files in {}", name, dirs.join(", "));
+                        mismatched.push(index);
+                    }
+                    Adoption::Missing(dirs) => {
+                        println!("{} is missing {}", name, dirs.join(", "));
+                        mismatched.push(index);
+                    }
+                }
+            }
+            if moved > 0 {
+                println!("Took the files of {} moved addons as installed", moved);
+            }
+            if mismatched.is_empty() {
+                println!("Every addon matches its dirs");
+            } else if args.is_present("resolve") {
+                // Resolving only finds addons in AddOns
+                let (in_root, elsewhere): (Vec<usize>, Vec<usize>) = mismatched
+                    .into_iter()
+                    .partition(|index| grunt.addons()[*index].install_root().is_none());
+                for index in elsewhere {
+                    println!(
+                        "{} isn't in AddOns. Remove it and add it again",
+                        grunt.addons()[index].name()
+                    );
+                }
+                for index in in_root.into_iter().rev() {
+                    grunt.untrack_addon(index);
+                }
+                println!("Resolving untracked dirs...");
+                let resolved = grunt.resolve(false, |prog| match prog {
+                    grunt::ResolveProgress::NewAddon { name, desc } => {
+                        println!("Found {} ({})", name, desc)
+                    }
+                    grunt::ResolveProgress::Finished { not_found, .. } => {
+                        if !not_found.is_empty() {
+                            println!("Unresolved: {}", not_found.join(", "));
+                        }
+                    }
+                });
+                if let Err(err) = resolved {
+                    or_exit(grunt.save_lockfile(), explain);
+                    print_error(&err, explain);
+                    std::process::exit(1);
+                }
+            } else {
+                println!("Run with --resolve to stop tracking them and resolve their dirs again");
+            }
+            or_exit(grunt.save_lockfile(), explain);
         }
-        _ => println!("No matched command"),
+        ("retry", Some(args)) => {
+            let index = match grunt.find_addon(args.value_of("addon").unwrap()) {
+                Ok(index) => index,
+                Err(err) => {
+                    print_error(&err, explain);
+                    std::process::exit(1);
+                }
+            };
+            let name = grunt.addons()[index].name().clone();
+            if grunt.addons()[index].failures().is_none() {
+                println!("{} hasn't failed to update", name);
+            } else {
+                grunt.clear_failures(index);
+                or_exit(grunt.save_lockfile(), explain);
+                println!("{} will be updated again on the next run", name);
+            }
+        }
+        ("daemon", Some(args)) => {
+            let status_path = project_dirs.data_dir().join("daemon-status.json");
+            let interval = |args: &clap::ArgMatches| -> u64 {
+                args.value_of("interval")
+                    .map(|s| s.parse().expect("Error parsing interval"))
+                    .unwrap_or(60)
+            };
+            match args.subcommand() {
+                ("status", _) => cli::daemon::print_status(&status_path),
+                ("install", Some(args)) => match cli::daemon::install(interval(args)) {
+                    Ok(job) => println!("Installed {}", job),
+                    Err(err) => {
+                        println!("Error installing scheduled job: {}", err);
+                        std::process::exit(1);
+                    }
+                },
+                ("uninstall", _) => match cli::daemon::uninstall() {
+                    Ok(()) => println!("Removed scheduled job"),
+                    Err(err) => {
+                        println!("Error removing scheduled job: {}", err);
+                        std::process::exit(1);
+                    }
+                },
+                _ if args.is_present("once") => {
+                    cli::daemon::run_once(&settings, &status_path, verbose, None)
+                }
+                _ => cli::daemon::run(interval(args), &settings, &status_path, verbose),
+            }
+        }
+        ("conflicts", matches) => {
+            if !print_conflicts(&grunt) {
+                println!("No conflicts found");
+                return;
+            }
+            if !matches.unwrap().is_present("fix") {
+                return;
+            }
+            if yes {
+                println!("Fixing conflicts needs a choice for each one. Use `grunt own` instead");
+                std::process::exit(1);
+            }
+            println!();
+            let mut skipped = Vec::new();
+            // Conflicts change after every fix so recheck each time
+            while let Some(conflict) = grunt
+                .check_conflicts()
+                .into_iter()
+                .find(|conflict| !skipped.contains(&conflict_key(&grunt, conflict)))
+            {
+                let indexes = [conflict.addon_a_index, conflict.addon_b_index];
+                let writer = grunt.last_writer(&conflict);
+                println!("\x1B[1m{}\x1B[0m is claimed by:", conflict.dir);
+                let mut addon_table =
+                    table::new_table(&["Name", "Source", "Version", "Dirs", "Last wrote"]);
+                for &index in indexes.iter() {
+                    let addon = &grunt.addons()[index];
+                    addon_table.add_row(vec![
+                        addon.name().clone(),
+                        addon.identifier(),
+                        addon.version().clone(),
+                        addon.dirs().join(", "),
+                        if writer == Some(index) {
+                            "probably"
+                        } else {
+                            ""
+                        }
+                        .to_string(),
+                    ]);
+                }
+                println!("{}", addon_table);
+                if !conflict.files.is_empty() {
+                    println!("Both installed {}", conflict.files.join(", "));
+                }
+
+                // Three actions per addon followed by skip
+                let mut actions = Vec::new();
+                for &index in indexes.iter() {
+                    let name = grunt.addons()[index].name();
+                    actions.push(format!("Give {} to {}", conflict.dir, name));
+                    actions.push(format!("Reinstall {}", name));
+                    actions.push(format!("Remove {}", name));
+                }
+                actions.push("Skip".to_string());
+                let picked = dialoguer::Select::new()
+                    .with_prompt("Fix")
+                    .items(&actions)
+                    .default(0)
+                    .interact()
+                    .unwrap();
+                if picked == actions.len() - 1 {
+                    skipped.push(conflict_key(&grunt, &conflict));
+                    continue;
+                }
+                let index = indexes[picked / 3];
+                match picked % 3 {
+                    0 => grunt.set_dir_owner(&conflict.dir, index),
+                    1 => {
+                        // The reinstalled addon takes over the dir
+                        grunt.set_dir_owner(&conflict.dir, index);
+                        if let Err(err) = grunt.reinstall_addon(
+                            index,
+                            settings.tsm_email().as_ref(),
+                            settings.tsm_pass().as_ref(),
+                        ) {
+                            print_error(&err, explain);
+                            std::process::exit(1);
+                        }
+                    }
+                    _ => or_exit(grunt.remove_addon(index), explain),
+                }
+                or_exit(grunt.save_lockfile(), explain);
+                println!();
+            }
+        }
+        ("dedupe", Some(args)) => {
+            let prefer = args.value_of("prefer").unwrap_or("curse");
+            let mut skipped = Vec::new();
+            let mut found = false;
+            // Indexes change after every merge so recheck each time
+            while let Some(group) = grunt
+                .find_duplicates()
+                .into_iter()
+                .find(|group| !skipped.contains(&duplicates_key(&grunt, group)))
+            {
+                found = true;
+                let mut addon_table = table::new_table(&["Name", "Source", "Version", "Dirs"]);
+                for &index in group.iter() {
+                    let addon = &grunt.addons()[index];
+                    addon_table.add_row(vec![
+                        addon.name().clone(),
+                        addon.identifier(),
+                        addon.version().clone(),
+                        addon.dirs().join(", "),
+                    ]);
+                }
+                println!("\x1B[1mProbably the same addon:\x1B[0m");
+                println!("{}", addon_table);
+
+                let preferred = group
+                    .iter()
+                    .position(|&index| grunt.addons()[index].addon_type().prefix() == prefer)
+                    .unwrap_or(0);
+                let picked = if yes {
+                    preferred
+                } else {
+                    let mut actions: Vec<String> = group
+                        .iter()
+                        .map(|&index| format!("Keep {}", grunt.addons()[index].identifier()))
+                        .collect();
+                    actions.push("Skip".to_string());
+                    dialoguer::Select::new()
+                        .with_prompt("Merge")
+                        .items(&actions)
+                        .default(preferred)
+                        .interact()
+                        .unwrap()
+                };
+                if picked == group.len() {
+                    skipped.push(duplicates_key(&grunt, &group));
+                    continue;
+                }
+                let kept = grunt.addons()[group[picked]].identifier();
+                grunt.merge_duplicates(group[picked], &group);
+                or_exit(grunt.save_lockfile(), explain);
+                println!("Kept {}", kept);
+                println!();
+            }
+            if !found {
+                println!("No duplicates found");
+            }
+        }
+        ("bisect", Some(args)) => {
+            let bisect = or_exit(Bisect::load(&bisect_path).map_err(Into::into), explain);
+            let result = match (args.subcommand_name(), bisect) {
+                (Some("start"), Some(_)) => {
+                    println!("Already bisecting. Use `grunt bisect reset` to start over");
+                    std::process::exit(1);
+                }
+                (Some("start"), None) if grunt.addons().len() < 2 => {
+                    println!("Bisecting needs at least two addons");
+                    std::process::exit(1);
+                }
+                (Some("start"), None) => Bisect::start(&grunt).and_then(|bisect| {
+                    bisect.save(&bisect_path)?;
+                    print_bisect_step(&bisect);
+                    Ok(())
+                }),
+                (_, None) => {
+                    println!("Not bisecting. Use `grunt bisect start` first");
+                    std::process::exit(1);
+                }
+                (Some("reset"), Some(bisect)) => bisect.reset().and_then(|_| {
+                    std::fs::remove_file(&bisect_path)?;
+                    println!("Every addon is enabled again");
+                    Ok(())
+                }),
+                (broken, Some(mut bisect)) => {
+                    bisect
+                        .mark(broken == Some("bad"))
+                        .and_then(|culprit| match culprit {
+                            Some(culprit) => {
+                                std::fs::remove_file(&bisect_path)?;
+                                println!(
+                                    "\x1B[1m{}\x1B[0m is breaking the UI",
+                                    bisect.name(&culprit)
+                                );
+                                println!("Every addon is enabled again");
+                                Ok(())
+                            }
+                            None => {
+                                bisect.save(&bisect_path)?;
+                                print_bisect_step(&bisect);
+                                Ok(())
+                            }
+                        })
+                }
+            };
+            if let Err(err) = result {
+                print_error(&err.into(), explain);
+                std::process::exit(1);
+            }
+        }
+        ("own", matches) => {
+            let matches = matches.unwrap();
+            let dir = matches.value_of("dir").unwrap();
+            let addon = matches.value_of("addon").unwrap();
+            if let Err(err) = grunt.reassign_dir(dir, addon) {
+                print_error(&err, explain);
+                std::process::exit(1);
+            }
+            or_exit(grunt.save_lockfile(), explain);
+            let index = grunt.find_addon(addon).unwrap();
+            println!("{} now owns {}", grunt.addons()[index].name(), dir);
+            print_conflicts(&grunt);
+        }
+        ("serve", Some(args)) => {
+            let port: u16 = args
+                .value_of("port")
+                .map(|s| s.parse().expect("Error parsing port"))
+                .unwrap_or(7531);
+            let token_path = project_dirs.data_dir().join("api-token");
+            let token = cli::serve::load_token(&token_path);
+            println!("Requests need the header `Authorization: Bearer <token>`");
+            println!("The token is in {}", token_path.display());
+            println!("GET /events streams progress and found updates, with the token as `?token=`");
+            let check_interval = args.value_of("interval").map(|s| {
+                Duration::from_secs(s.parse::<u64>().expect("Error parsing interval") * 60)
+            });
+            cli::serve::run(port, &token, &settings, check_interval, || {
+                init_grunt(addon_dir, &settings)
+            });
+        }
+        ("migrate", Some(args)) => {
+            let result = match args.subcommand() {
+                ("curse", Some(args)) => {
+                    grunt::migrate::from_curse_client(Path::new(args.value_of("path").unwrap()))
+                }
+                ("wowup", Some(args)) => {
+                    let path = match args.value_of("path") {
+                        Some(path) => PathBuf::from(path),
+                        None => BaseDirs::new()
+                            .expect("Couldn't find config dir")
+                            .config_dir()
+                            .join("WowUp"),
+                    };
+                    grunt::migrate::from_wowup(&path)
+                }
+                _ => unreachable!(),
+            };
+            let migration = match result {
+                Ok(migration) => migration,
+                Err(err) => {
+                    print_error(&err, explain);
+                    std::process::exit(1);
+                }
+            };
+            let before = grunt.addons().len();
+            let mut skipped = migration.skipped;
+            skipped.extend(grunt.import_addons(migration.addons));
+            or_exit(grunt.save_lockfile(), explain);
+            println!("Imported {} addons", grunt.addons().len() - before);
+            if !skipped.is_empty() {
+                println!("\x1B[1m{} skipped:\x1B[0m", skipped.len());
+                let mut skipped_table = table::new_table(&["Name", "Reason"]);
+                for (name, reason) in skipped {
+                    skipped_table.add_row(vec![name, reason]);
+                }
+                println!("{}", skipped_table);
+            }
+        }
+        ("import", Some(args)) => {
+            let import = or_exit(
+                grunt::import::from_file(Path::new(args.value_of("file").unwrap())),
+                explain,
+            );
+            for (entry, reason) in import.skipped {
+                println!("Skipped {}: {}", entry, reason);
+            }
+            println!("Installing {} addons", import.identifiers.len());
+            if !install_all(&mut grunt, &import.identifiers, None, explain) {
+                std::process::exit(1);
+            }
+        }
+        ("snapshot", Some(args)) => {
+            let args = args.subcommand_matches("diff").unwrap();
+            let load = |path| or_exit(compare::load(Path::new(path)), explain);
+            let before = load(args.value_of("before").unwrap());
+            let changes = match args.value_of("after") {
+                Some(after) => compare::diff(&before, &load(after)),
+                None => compare::diff(&before, grunt.addons()),
+            };
+            if changes.is_empty() {
+                println!("No changes");
+                return;
+            }
+            let mut changes_table = table::new_table(&["Addon", "Source", "Before", "After"]);
+            for change in changes {
+                let version = |version: Option<String>| version.unwrap_or_else(|| "-".to_string());
+                changes_table.add_row(vec![
+                    change.name,
+                    change.identifier,
+                    version(change.before),
+                    version(change.after),
+                ]);
+            }
+            pager::page(&changes_table.to_string());
+        }
+        ("gc", _) => {
+            let report = match grunt.gc() {
+                Ok(report) => report,
+                Err(err) => {
+                    print_error(&err, explain);
+                    std::process::exit(1);
+                }
+            };
+            println!("Removed {} stale Curse cache entries", report.cache_entries);
+            println!(
+                "Removed {} fingerprints of deleted dirs",
+                report.fingerprints
+            );
+            println!(
+                "Removed {} leftover temp dirs ({})",
+                report.temp_dirs,
+                table::format_size(report.temp_bytes)
+            );
+            if let Some(path) = report.old_lockfile {
+                println!("Removed unused lockfile {}", path.display());
+            }
+        }
+        ("backup", Some(args)) => {
+            let path = match args.value_of("archive") {
+                Some(path) => PathBuf::from(path),
+                None => PathBuf::from(format!(
+                    "grunt-backup-{}.tar.zst",
+                    chrono::Local::now().format("%Y%m%d-%H%M%S")
+                )),
+            };
+            let backed_up = std::fs::File::create(&path)
+                .map_err(GruntError::from)
+                .and_then(|file| {
+                    let writer = std::io::BufWriter::new(file);
+                    backup::create(&grunt, writer, args.is_present("saved_variables"))
+                });
+            let manifest = match backed_up {
+                Ok(manifest) => manifest,
+                Err(err) => {
+                    std::fs::remove_file(&path).ok();
+                    print_error(&err, explain);
+                    std::process::exit(1);
+                }
+            };
+            let size = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+            println!(
+                "Backed up {} addons to {} ({})",
+                manifest.addons.len(),
+                path.display(),
+                table::format_size(size)
+            );
+        }
+        ("restore", Some(args)) => {
+            let path = args.value_of("archive").unwrap();
+            let open = || {
+                let file = std::fs::File::open(path).map_err(GruntError::from);
+                std::io::BufReader::new(or_exit(file, explain))
+            };
+            let manifest = or_exit(backup::read_manifest(open()), explain);
+            println!(
+                "Backup of {} addons in {}, made {}",
+                manifest.addons.len(),
+                manifest.root_dir.display(),
+                manifest
+                    .created
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+            );
+            if manifest.flavor != *grunt.flavor() {
+                println!(
+                    "The backup is of {}, not {}",
+                    manifest.flavor.name(),
+                    grunt.flavor().name()
+                );
+            }
+            if args.is_present("saved_variables") {
+                // Addons are looked up in the backup first, since they may have been removed
+                let queries = args.values_of("addons").into_iter().flatten();
+                let dirs: Vec<String> = queries
+                    .flat_map(|query| match manifest.find_addon(query) {
+                        Some(addon) => addon.dirs.clone(),
+                        None => {
+                            let index = or_exit(grunt.find_addon(query), explain);
+                            grunt.addons()[index].dirs().clone()
+                        }
+                    })
+                    .collect();
+                println!(
+                    "Saved variables are overwritten by the game when it exits, so close it first"
+                );
+                if !yes {
+                    let is_sure = dialoguer::Confirm::new()
+                        .with_prompt("Replace the saved variables?")
+                        .interact()
+                        .unwrap();
+                    if !is_sure {
+                        return;
+                    }
+                }
+                or_exit(
+                    backup::restore_saved_variables(&grunt, open(), &dirs),
+                    explain,
+                );
+                println!("Restored saved variables");
+                return;
+            }
+            if !yes {
+                let is_sure = dialoguer::Confirm::new()
+                    .with_prompt("Replace their dirs and the lockfile?")
+                    .interact()
+                    .unwrap();
+                if !is_sure {
+                    return;
+                }
+            }
+            or_exit(backup::restore(&grunt, open()), explain);
+            grunt = or_exit(init_grunt(addon_dir, &settings), explain);
+            println!("Restored {} addons", grunt.addons().len());
+            // The backup's lockfile is only signed when asked to, like any other change
+            if args.is_present("accept") {
+                grunt.accept_lockfile();
+                or_exit(grunt.save_lockfile(), explain);
+                println!("Signed the restored lockfile");
+            }
+        }
+        ("lockfile", Some(args)) => match args.subcommand_name() {
+            Some("accept") => {
+                if settings.lockfile_secret().is_none() {
+                    println!("No lockfile_secret is set, so the lockfile isn't signed");
+                    return;
+                }
+                grunt.accept_lockfile();
+                or_exit(grunt.save_lockfile(), explain);
+                println!("Signed {}", grunt.lockfile_path().display());
+            }
+            _ => println!("No matched command"),
+        },
+        ("storage", Some(args)) => {
+            let backend = match args.value_of("backend").unwrap() {
+                "sqlite" => StorageBackend::Sqlite,
+                _ => StorageBackend::Json,
+            };
+            let old_path = grunt.lockfile_path();
+            grunt.set_storage_backend(backend);
+            or_exit(grunt.save_lockfile(), explain);
+            settings.set_storage(backend);
+            or_exit(settings.save(&settings_path), explain);
+            if old_path != grunt.lockfile_path() {
+                println!(
+                    "Moved addon data to {}. Run `grunt gc` to delete {}",
+                    grunt.lockfile_path().display(),
+                    old_path.display()
+                );
+            }
+        }
+        ("rpc", Some(args)) => {
+            let socket = match args.value_of("socket") {
+                Some(path) => PathBuf::from(path),
+                None => project_dirs.data_dir().join("grunt.sock"),
+            };
+            cli::rpc::run(&socket, &settings, || init_grunt(addon_dir, &settings));
+        }
+        ("tsm", args) => {
+            if !settings.source_enabled(&AddonType::TSM) {
+                print_error(&GruntError::SourceDisabled(AddonType::TSM), explain);
+                std::process::exit(1);
+            }
+            if let Some(args) = args.and_then(|args| args.subcommand_matches("addons")) {
+                manage_tsm_addons(&mut grunt, args, &settings, yes, explain);
+                return;
+            }
+            if let Err(err) =
+                grunt.update_tsm_data(settings.tsm_email().as_ref(), settings.tsm_pass().as_ref())
+            {
+                print_error(&err, explain);
+                std::process::exit(1);
+            }
+            println!("TSM data updated");
+        }
+        _ => println!("No matched command"),
+    }
+}
+
+/// Builds the output of `grunt list`, exiting if the AddOns dir can't be read
+fn list_output(grunt: &Grunt, args: &clap::ArgMatches) -> ListOutput {
+    let columns = match args.value_of("columns") {
+        Some(columns) => Column::parse_list(columns).unwrap(),
+        None if args.is_present("long") => Column::LONG.to_vec(),
+        None => Column::DEFAULT.to_vec(),
+    };
+    let sort = match args.value_of("sort") {
+        Some(sort) => sort.parse().unwrap(),
+        None => Column::Name,
+    };
+    or_exit(
+        ListOutput::new(grunt, columns, sort),
+        args.is_present("explain"),
+    )
+}
+
+/// Builds the output of `grunt info`, exiting if the addon isn't found
+fn info_output(grunt: &Grunt, args: &clap::ArgMatches) -> AddonOutput {
+    let index = match grunt.find_addon(args.value_of("addon").unwrap()) {
+        Ok(index) => index,
+        Err(err) => {
+            print_error(&err, args.is_present("explain"));
+            std::process::exit(1);
+        }
+    };
+    let row = grunt.addon_rows().remove(index);
+    let mut output = AddonOutput::new(&grunt.addons()[index], &row);
+    if args.is_present("full") {
+        match grunt.description(index) {
+            Ok(description) => output.description = Some(description),
+            Err(err) => {
+                print_error(&err, args.is_present("explain"));
+                std::process::exit(1);
+            }
+        }
+    }
+    output
+}
+
+/// Builds the output of `grunt license`, exiting if licenses can't be checked
+fn licenses_output(grunt: &Grunt, explain: bool) -> LicensesOutput {
+    let licenses = match grunt.licenses() {
+        Ok(licenses) => licenses,
+        Err(err) => {
+            print_error(&err, explain);
+            std::process::exit(1);
+        }
+    };
+    let addons = licenses
+        .into_iter()
+        .map(|license| {
+            let addon = &grunt.addons()[license.index];
+            LicenseOutput {
+                name: addon.name().clone(),
+                identifier: addon.identifier(),
+                license: license.license,
+                redistribution: license.redistribution,
+            }
+        })
+        .collect();
+    LicensesOutput { addons }
+}
+
+/// Searches Curse, exiting if the search fails
+fn search_output(grunt: &Grunt, args: &clap::ArgMatches, explain: bool) -> SearchOutput {
+    let query = args
+        .values_of("query")
+        .unwrap()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    match grunt.search(&query) {
+        Ok(results) => SearchOutput { query, results },
+        Err(err) => {
+            print_error(&err, explain);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Installs addons by any identifiers `add` takes, skipping ones that are already installed
+/// Returns false if any failed
+fn install_all<S: AsRef<str>>(
+    grunt: &mut Grunt,
+    identifiers: &[S],
+    install_root: Option<&str>,
+    explain: bool,
+) -> bool {
+    let identifiers: Vec<&str> = identifiers.iter().map(AsRef::as_ref).collect();
+    let results = or_exit(grunt.install_many(&identifiers, install_root), explain);
+    let mut failed = false;
+    for (_, result) in results {
+        match result {
+            Ok(name) => println!("Installed {}", name),
+            Err(GruntError::AlreadyInstalled(name)) => println!("{} is already installed", name),
+            Err(err) => {
+                print_error(&err, explain);
+                failed = true;
+            }
+        }
+    }
+    or_exit(grunt.save_lockfile(), explain);
+    !failed
+}
+
+/// Writes, prints or posts a report of the last week, or `--days`
+fn weekly_report(grunt: &mut Grunt, args: &clap::ArgMatches, settings: &Settings, explain: bool) {
+    let format = ReportFormat::from_name(args.value_of("format").unwrap_or("markdown")).unwrap();
+    let days: i64 = args
+        .value_of("days")
+        .map_or(7, |days| days.parse().expect("Error parsing days"));
+    let webhook = settings.report_webhook().as_ref();
+    if args.is_present("post") && webhook.is_none() {
+        println!("No report_webhook in the settings to post the report to");
+        std::process::exit(1);
+    }
+    let (report, errors) = grunt.report(
+        days,
+        settings.tsm_email().as_ref(),
+        settings.tsm_pass().as_ref(),
+    );
+    // The check refreshed cached project info and the disk usage was saved
+    or_exit(grunt.save_lockfile(), explain);
+    // The report can be incomplete, but it's still useful
+    for err in errors.iter() {
+        eprintln!("\x1B[1mWarning:\x1B[0m {}", err);
+    }
+    let text = report.render(format);
+    if let Some(path) = args.value_of("file") {
+        or_exit(
+            std::fs::write(path, &text).map_err(GruntError::from),
+            explain,
+        );
+        eprintln!("Report saved to {}", path);
+    }
+    if args.is_present("post") {
+        let posted = grunt::net::post(
+            webhook.unwrap(),
+            text.clone(),
+            format.content_type(),
+            &settings.net_options(),
+        );
+        or_exit(posted, explain);
+        eprintln!("Report posted");
+    }
+    if !args.is_present("file") && !args.is_present("post") {
+        println!("{}", text);
+    }
+}
+
+/// Lists the addons TSM distributes, then installs or updates the ones named or picked
+/// With `--yes` and no names it only lists them
+fn manage_tsm_addons(
+    grunt: &mut Grunt,
+    args: &clap::ArgMatches,
+    settings: &Settings,
+    yes: bool,
+    explain: bool,
+) {
+    let (email, pass) = (settings.tsm_email().as_ref(), settings.tsm_pass().as_ref());
+    let available = or_exit(grunt.tsm_addons(email, pass), explain);
+    let mut table = table::new_table(&["Name", "Latest", "Installed"]);
+    for addon in available.iter() {
+        table.add_row(vec![
+            addon.name.clone(),
+            addon.version.clone(),
+            addon.installed.clone().unwrap_or_default(),
+        ]);
+    }
+    println!("{}", table);
+    let picked: Vec<String> = match args.values_of("addons") {
+        Some(names) => names.map(str::to_string).collect(),
+        None if yes => return,
+        None => {
+            // Installed addons with updates start picked
+            let items: Vec<(&String, bool)> = available
+                .iter()
+                .map(|addon| {
+                    let outdated = addon
+                        .installed
+                        .as_ref()
+                        .is_some_and(|installed| installed != &addon.version);
+                    (&addon.name, outdated)
+                })
+                .collect();
+            let picked_indexes = dialoguer::MultiSelect::new()
+                .with_prompt("TSM addons to install or update")
+                .items_checked(&items)
+                .interact()
+                .unwrap();
+            picked_indexes
+                .into_iter()
+                .map(|index| available[index].name.clone())
+                .collect()
+        }
+    };
+    let (installed, new): (Vec<String>, Vec<String>) = picked.into_iter().partition(|name| {
+        available
+            .iter()
+            .any(|addon| &addon.name == name && addon.installed.is_some())
+    });
+    let mut failed = false;
+    if !new.is_empty() {
+        for (_, result) in or_exit(grunt.install_tsm(&new, email, pass), explain) {
+            match result {
+                Ok(name) => println!("Installed {}", name),
+                Err(err) => {
+                    print_error(&err, explain);
+                    failed = true;
+                }
+            }
+        }
+    }
+    if !installed.is_empty() {
+        let tsm_indexes: Vec<usize> = grunt
+            .addons()
+            .iter()
+            .enumerate()
+            .filter(|(_, addon)| addon.addon_type() == &AddonType::TSM)
+            .map(|(index, _)| index)
+            .collect();
+        let check_fn = |upds: Vec<grunt::Updateable>| {
+            let upds: Vec<_> = upds
+                .into_iter()
+                .filter(|upd| tsm_indexes.contains(&upd.index) && installed.contains(&upd.name))
+                .collect();
+            for upd in upds.iter() {
+                println!("Updating {} to {}", upd.name, upd.new_version);
+            }
+            upds
+        };
+        if let Err(err) = grunt.update_addons(check_fn, email, pass) {
+            print_error(&err, explain);
+            failed = true;
+        }
+    }
+    or_exit(grunt.save_lockfile(), explain);
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Prints addons with updates, then exits with 1 if there are any
+/// Addons that couldn't be checked are printed as errors and exit with 2 if nothing's outdated
+fn print_outdated(grunt: &mut Grunt, settings: &Settings, renderer: &dyn Renderer, explain: bool) {
+    let check = grunt.check_updates(settings.tsm_email().as_ref(), settings.tsm_pass().as_ref());
+    // The check refreshes cached project info
+    or_exit(grunt.save_lockfile(), explain);
+    println!(
+        "{}",
+        renderer.render(&OutdatedOutput::new(grunt, &check.outdated))
+    );
+    let mut failed = false;
+    for err in check.errors() {
+        print_error(err, explain);
+        failed = true;
+    }
+    if !check.outdated.is_empty() {
+        std::process::exit(1);
+    }
+    if failed {
+        std::process::exit(2);
+    }
+}
+
+/// Builds the output of `grunt compat`, exiting if the addon can't be checked
+fn compat_output(grunt: &Grunt, args: &clap::ArgMatches, explain: bool) -> CompatOutput {
+    let result = grunt
+        .find_addon(args.value_of("addon").unwrap())
+        .and_then(|index| Ok((index, grunt.compat(index)?)));
+    match result {
+        Ok((index, support)) => CompatOutput::new(grunt, index, support),
+        Err(err) => {
+            print_error(&err, explain);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Offers to move addons out of dirs they were extracted into by mistake, so they
+/// can be resolved
+fn flatten_nested(grunt: &mut Grunt, yes: bool, explain: bool) {
+    let nested = or_exit(grunt.find_nested(), explain);
+    if nested.is_empty() {
+        return;
+    }
+    println!(
+        "\x1B[1m{} dirs have addons nested inside them:\x1B[0m",
+        nested.len()
+    );
+    for nested in nested.iter() {
+        println!("{} contains {}", nested.dir, nested.inner.join(", "));
+    }
+    if !yes {
+        let flatten = dialoguer::Confirm::new()
+            .with_prompt("Move the addons up into AddOns? Other files in those dirs are deleted")
+            .default(true)
+            .interact()
+            .unwrap();
+        if !flatten {
+            println!();
+            return;
+        }
+    }
+    for nested in nested.iter() {
+        match grunt.flatten_nested(nested) {
+            Ok(dirs) => println!("Moved {} out of {}", dirs.join(", "), nested.dir),
+            Err(err) => print_error(&err, explain),
+        }
+    }
+    println!();
+}
+
+/// Builds the output of `grunt diff`, exiting if the archive can't be fetched
+fn diff_output(grunt: &Grunt, args: &clap::ArgMatches, explain: bool) -> DiffOutput {
+    let result = grunt
+        .find_addon(args.value_of("addon").unwrap())
+        .and_then(|index| Ok((index, grunt.diff_addon(index)?)));
+    match result {
+        Ok((index, diff)) => DiffOutput::new(grunt, index, diff),
+        Err(err) => {
+            print_error(&err, explain);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Runs `grunt update` in the AddOns dir of `grunt`. Returns false if it failed
+fn run_update(
+    grunt: &mut Grunt,
+    args: &clap::ArgMatches,
+    settings: &Settings,
+    yes: bool,
+    verbose: bool,
+    profile: bool,
+    explain: bool,
+) -> bool {
+    if let Some(kib) = args.value_of("max_bandwidth") {
+        let kib: u64 = kib.parse().expect("Error parsing max bandwidth");
+        grunt.set_max_bandwidth(Some(kib * 1024));
+    }
+    grunt.set_keep_extra(args.is_present("keep_extra"));
+    grunt.set_force_urls(args.is_present("force"));
+    print_paused(grunt);
+    if args.is_present("dry_run") {
+        println!("Checking for addons to update");
+        let DryRun { plan, errors } = match grunt
+            .dry_run_updates(settings.tsm_email().as_ref(), settings.tsm_pass().as_ref())
+        {
+            Ok(dry_run) => dry_run,
+            Err(err) => {
+                print_error(&err, explain);
+                return false;
+            }
+        };
+        println!("\x1B[1m{} addons to update\x1B[0m", plan.updates.len());
+        if !plan.updates.is_empty() {
+            let mut plan_table = table::new_table(&["Name", "Source", "Current", "New"]);
+            for upd in plan.updates.iter() {
+                plan_table.add_row(vec![
+                    &upd.name,
+                    &upd.identifier,
+                    &upd.current_version,
+                    &upd.new_version,
+                ]);
+            }
+            println!("{}", plan_table);
+        }
+        print_dir_changes("Would delete", &plan.deleted_dirs);
+        print_dir_changes("Would write", &plan.written_dirs);
+        for err in errors.iter() {
+            print_error(err, explain);
+        }
+        if let Some(path) = args.value_of("plan_out") {
+            or_exit(plan.save(path), explain);
+            println!("Plan saved to {}", path);
+        }
+        print_deprecations(grunt);
+        if !errors.is_empty() {
+            return false;
+        }
+    } else if args.is_present("plan") {
+        let plan = or_exit(
+            UpdatePlan::from_file(args.value_of("plan").unwrap()),
+            explain,
+        );
+        if Path::new(&plan.root_dir) != grunt.root_dir() {
+            println!("Plan was made for {}", plan.root_dir);
+            return false;
+        }
+        println!("Applying {} planned updates", plan.updates.len());
+        let metrics = match grunt.apply_plan(
+            &plan,
+            settings.tsm_email().as_ref(),
+            settings.tsm_pass().as_ref(),
+        ) {
+            Ok(metrics) => metrics,
+            Err(err) => {
+                if let GruntError::Partial(_) = err {
+                    or_exit(grunt.save_lockfile(), explain);
+                }
+                print_error(&err, explain);
+                return false;
+            }
+        };
+        or_exit(grunt.save_lockfile(), explain);
+        println!("Done");
+        print_renames(&metrics);
+        print_moved_projects(&metrics);
+        print_skipped_modified(&metrics);
+        if verbose {
+            print_metrics(&metrics);
+        }
+        if profile {
+            print_profile(&metrics, grunt.lockfile_io());
+        }
+    } else {
+        let check_fn = |mut updateable: Vec<grunt::Updateable>| -> Vec<grunt::Updateable> {
+            // Return early if no updateable addons
+            if updateable.is_empty() {
+                return updateable;
+            }
+            println!("{} addons to update", updateable.len());
+            updateable.sort_by(|a, b| a.name.cmp(&b.name));
+            if yes {
+                updateable
+                    .iter()
+                    .for_each(|upd| println!("Updating {} to {}", upd.name, upd.new_version));
+                return updateable;
+            }
+            let names: Vec<(&String, bool)> =
+                updateable.iter().map(|upd| (&upd.name, true)).collect();
+            let picked_indexes = dialoguer::MultiSelect::new()
+                .with_prompt("Addons to update")
+                .items_checked(&names)
+                .paged(true)
+                .interact()
+                .unwrap();
+
+            // Return early if user picks no addons to update
+            if picked_indexes.is_empty() {
+                return Vec::new();
+            }
+
+            // Confirm selection
+            let is_sure = dialoguer::Confirm::new()
+                .with_prompt("Are you sure?")
+                .interact()
+                .unwrap();
+            if !is_sure {
+                return Vec::new();
+            }
+
+            // Filter updateable by indexes picked and return
+            updateable
+                .into_iter()
+                .enumerate()
+                .filter(|(index, _)| picked_indexes.contains(index))
+                .map(|(_, upd)| upd)
+                .collect()
+        };
+        println!("Checking for addons to update");
+        let mut bars = UpdateBars::new();
+        let result = grunt.update_addons_with_progress(
+            check_fn,
+            |event| {
+                crash::journal_update(&event);
+                bars.handle(event)
+            },
+            settings.tsm_email().as_ref(),
+            settings.tsm_pass().as_ref(),
+        );
+        bars.finish();
+        let metrics = match result {
+            Ok(metrics) => metrics,
+            Err(err) => {
+                if let GruntError::Partial(_) = err {
+                    or_exit(grunt.save_lockfile(), explain);
+                }
+                print_error(&err, explain);
+                return false;
+            }
+        };
+        or_exit(grunt.save_lockfile(), explain);
+        println!("Done");
+        print_renames(&metrics);
+        print_moved_projects(&metrics);
+        print_skipped_modified(&metrics);
+        print_deprecations(grunt);
+        if verbose {
+            print_metrics(&metrics);
+        }
+        if profile {
+            print_profile(&metrics, grunt.lockfile_io());
+        }
+    }
+    true
+}
+
+/// Creates a grunt instance using the options in `settings`
+fn init_grunt(addon_dir: &str, settings: &Settings) -> Result<Grunt, GruntError> {
+    let mut grunt = Grunt::with_storage(addon_dir, *settings.storage())?;
+    grunt.set_untracked_rules(settings.untracked_rules().clone());
+    grunt.set_net_options(settings.net_options());
+    grunt.set_extract_excludes(settings.extract_excludes().clone());
+    grunt.set_extract_limits(settings.extract_limits().clone());
+    grunt.set_custom_sources(settings.custom_sources().clone());
+    grunt.set_disabled_sources(settings.disabled_sources().clone());
+    grunt.set_lockfile_secret(settings.lockfile_secret().clone());
+    grunt.set_max_update_failures(*settings.max_update_failures());
+    grunt.set_skip_modified(*settings.skip_modified_addons());
+    grunt.set_tsm_app_config(
+        settings
+            .tsm_app_config()
+            .as_ref()
+            .map(|path| PathBuf::from(grunt::settings::expand_path(path))),
+    );
+    if let Some(flavor) = settings.flavor_of(addon_dir) {
+        grunt.change_flavor(flavor);
+    }
+    match grunt.verify_lockfile()? {
+        Some(Signature::Invalid) => eprintln!(
+            "\x1B[1mWarning:\x1B[0m {} was changed outside of grunt. It won't be saved until the changes are accepted with `grunt lockfile accept`",
+            grunt.lockfile_path().display()
+        ),
+        Some(Signature::Missing) => eprintln!(
+            "\x1B[1mWarning:\x1B[0m {} isn't signed. It will be once grunt saves it",
+            grunt.lockfile_path().display()
+        ),
+        _ => (),
+    }
+    if let Some(group) = settings.file_group() {
+        let gid = grunt::perms::group_id(group).ok_or_else(|| GruntError::InvalidSetting {
+            name: "file_group".to_string(),
+            reason: format!("group {} doesn't exist", group),
+        })?;
+        grunt.set_file_group(Some(gid));
+    }
+    Ok(grunt)
+}
+
+/// Prints a table of conflicting dirs. Returns false if there are none
+fn print_conflicts(grunt: &Grunt) -> bool {
+    let output = ConflictsOutput::new(grunt);
+    if output.conflicts.is_empty() {
+        return false;
+    }
+    println!("{}", TableRenderer.render(&output));
+    true
+}
+
+/// Prints the dirs a dry run would change under a heading
+fn print_dir_changes(heading: &str, dirs: &[String]) {
+    if dirs.is_empty() {
+        return;
+    }
+    println!("\x1B[1m{}:\x1B[0m", heading);
+    for dir in dirs {
+        println!("  {}", dir);
+    }
+}
+
+/// Warns about addons that won't get updates anymore
+fn print_deprecations(grunt: &Grunt) {
+    let rows = grunt.addon_rows();
+    for (addon, row) in grunt.addons().iter().zip(rows.iter()) {
+        if row.deprecation.is_some() {
+            let output = AddonOutput::new(addon, row);
+            println!(
+                "\x1B[1mWarning:\x1B[0m {}",
+                render::deprecation_warning(&output)
+            );
+        }
+    }
+}
+
+/// Warns about addons that aren't updated anymore because they kept failing
+fn print_paused(grunt: &Grunt) {
+    for addon in grunt.addons().iter().filter(|addon| grunt.is_paused(addon)) {
+        let failures = addon.failures().as_ref().unwrap();
+        println!(
+            "\x1B[1mWarning:\x1B[0m {} was skipped after failing {} times in a row: {}",
+            addon.name(),
+            failures.count,
+            failures.last_error
+        );
+        println!("  Run `grunt retry {}` to try it again", addon.name());
+    }
+}
+
+/// Suggests checking addons after the game was patched, with the ones that say they
+/// support the new version
+fn print_game_patch(patch: &GamePatch) {
+    println!();
+    println!(
+        "\x1B[1mThe game was patched from {} to {}.\x1B[0m Check for addon updates with `grunt update --dry-run`",
+        patch.old_version, patch.new_version
+    );
+    let interface = match patch.interface {
+        Some(interface) => interface,
+        None => return,
+    };
+    if !patch.supported.is_empty() {
+        println!(
+            "Support interface {}: {}",
+            interface,
+            patch.supported.join(", ")
+        );
+    }
+    if !patch.outdated.is_empty() {
+        println!("Only list older interfaces: {}", patch.outdated.join(", "));
+    }
+}
+
+/// Describes an update frequency for `grunt frequency`
+fn frequency_text(frequency: &str) -> &'static str {
+    match frequency {
+        "weekly" => "once a week",
+        "frozen" => "never",
+        _ => "every run",
+    }
+}
+
+/// Shows what to test next while bisecting
+fn print_bisect_step(bisect: &Bisect) {
+    let (enabled, disabled) = bisect.halves();
+    let names = |keys: &[String]| {
+        let names: Vec<&str> = keys.iter().map(|key| bisect.name(key)).collect();
+        names.join(", ")
+    };
+    println!(
+        "{} suspects left, {} of them are disabled: {}",
+        bisect.suspects.len(),
+        disabled.len(),
+        names(disabled)
+    );
+    println!("Still enabled: {}", names(enabled));
+    println!(
+        "Reload the UI, then run `grunt bisect good` if it works or `grunt bisect bad` if not"
+    );
+}
+
+/// Identifies a group of duplicates in a way that survives addons being merged
+fn duplicates_key(grunt: &Grunt, group: &[usize]) -> Vec<String> {
+    let mut keys: Vec<String> = group
+        .iter()
+        .map(|&index| grunt.addons()[index].key())
+        .collect();
+    keys.sort();
+    keys
+}
+
+/// Identifies a conflict in a way that survives addons being removed
+fn conflict_key(grunt: &Grunt, conflict: &grunt::Conflict) -> (String, String, String) {
+    (
+        conflict.dir.clone(),
+        grunt.addons()[conflict.addon_a_index].key(),
+        grunt.addons()[conflict.addon_b_index].key(),
+    )
+}
+
+/// Checks the value of `--columns` can be parsed
+fn validate_columns(columns: String) -> Result<(), String> {
+    Column::parse_list(&columns).map(|_| ())
+}
+
+/// Checks a count like `--days` is a whole number
+fn validate_number(value: String) -> Result<(), String> {
+    value
+        .parse::<u32>()
+        .map(|_| ())
+        .map_err(|_| format!("{} isn't a whole number", value))
+}
+
+fn validate_port(value: String) -> Result<(), String> {
+    value
+        .parse::<u16>()
+        .map(|_| ())
+        .map_err(|_| format!("{} isn't a port number", value))
+}
+
+/// Lists dirs that updates renamed, since scripts and macros may refer to them
+fn print_renames(metrics: &Metrics) {
+    for rename in metrics.renames() {
+        println!(
+            "{} renamed {} to {}",
+            rename.addon(),
+            rename.from(),
+            rename.to()
+        );
+    }
+}
+
+fn print_moved_projects(metrics: &Metrics) {
+    for moved in metrics.moved_projects() {
+        println!(
+            "{} moved to Curse project {}, it's tracked there now",
+            moved.addon(),
+            moved.to()
+        );
+    }
+}
+
+fn print_skipped_modified(metrics: &Metrics) {
+    if !metrics.skipped_modified().is_empty() {
+        println!(
+            "Skipped addons edited locally: {}. See `grunt verify`",
+            metrics.skipped_modified().join(", ")
+        );
+    }
+}
+
+/// Prints the timings of an operation
+fn print_metrics(metrics: &Metrics) {
+    println!();
+    println!(
+        "\x1B[1mTook {:.2}s\x1B[0m",
+        metrics.duration().as_secs_f64()
+    );
+    if !metrics.hash_times().is_empty() {
+        println!(
+            "Fingerprinted {} dirs in {:.2}s (summed across threads)",
+            metrics.hash_times().len(),
+            metrics.total_hash_time().as_secs_f64()
+        );
+        let mut hash_times = metrics.hash_times().clone();
+        hash_times.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+        for (dir, time) in hash_times.iter().take(5) {
+            println!("  {:32} {:.3}s", dir, time.as_secs_f64());
+        }
+    }
+    if !metrics.downloads().is_empty() {
+        println!("Downloads:");
+        for download in metrics.downloads() {
+            println!(
+                "  {:32} {:8.1} KiB in {:.2}s ({:.1} KiB/s)",
+                download.name(),
+                *download.bytes() as f64 / 1024.0,
+                download.duration().as_secs_f64(),
+                download.throughput() / 1024.0
+            );
+        }
+    }
+}
+
+/// Prints the time spent in each phase, for `--profile-perf`
+fn print_profile(metrics: &Metrics, lockfile_io: Duration) {
+    let phases = metrics.phases();
+    println!();
+    println!(
+        "\x1B[1mPhases of {:.2}s total:\x1B[0m",
+        metrics.duration().as_secs_f64()
+    );
+    let rows = [
+        ("Metadata fetch", *phases.metadata(), ""),
+        ("Hashing", *phases.hashing(), ""),
+        ("Download", *phases.download(), " (summed across threads)"),
+        ("Extract", *phases.extract(), " (summed across threads)"),
+        ("Install", *phases.install(), ""),
+        ("Lockfile IO", lockfile_io, ""),
+    ];
+    for (name, time, note) in rows.iter() {
+        println!("  {:16} {:7.3}s{}", name, time.as_secs_f64(), note);
+    }
+}
+
+/// Returns the value, or prints the error and exits if the command can't go on without it
+fn or_exit<T>(result: Result<T, GruntError>, explain: bool) -> T {
+    result.unwrap_or_else(|err| {
+        print_error(&err, explain);
+        std::process::exit(1)
+    })
+}
+
+/// Prints an error and what caused it
+/// `explain` adds the failing addon, request and a suggested fix
+fn print_error(err: &GruntError, explain: bool) {
+    crash::journal(format!("Error: {}", err));
+    eprintln!("\x1B[1mError:\x1B[0m {}", err);
+    if let GruntError::Partial(errors) = err {
+        for err in errors {
+            print_error(err, explain);
+        }
+        return;
+    }
+    if !explain {
+        eprintln!("Run with --explain for more details");
+        return;
+    }
+    if let Some((name, addon_type)) = err.addon() {
+        eprintln!("{:12} {}", "Addon:", name);
+        eprintln!("{:12} {:?}", "Source:", addon_type);
+    }
+    if let Some(url) = err.url() {
+        eprintln!("{:12} {}", "URL:", url);
+    }
+    if let Some(status) = err.status() {
+        eprintln!("{:12} {}", "HTTP status:", status);
+    }
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        eprintln!("{:12} {}", "Caused by:", cause);
+        source = cause.source();
     }
+    eprintln!("{:12} {}", "Suggestion:", err.remediation());
 }
@@ -0,0 +1,4959 @@
+use crate::addon::{Addon, AddonType, Deprecation, UpdateFailures, UpdateFrequency};
+use crate::curse::{CachedProject, CurseAPI, CurseCache, GameRules, WOW_GAME_ID};
+use crate::custom::CustomSource;
+use crate::diff::AddonDiff;
+use crate::error::GruntError;
+use crate::flavor::Flavor;
+use crate::game_build::GamePatch;
+use crate::guard::WriteGuard;
+use crate::history::{AppliedUpdate, History};
+use crate::lockfile::{Lockfile, UnresolvedDir};
+use crate::metrics::{DownloadMetrics, Metrics, PhaseTimes, ProjectMove};
+use crate::net::NetOptions;
+use crate::par::*;
+use crate::perms::Ownership;
+use crate::plan::{PausedAddon, PlannedUpdate, UpdatePlan, Updateable};
+use crate::rename::DirRename;
+use crate::report::{PendingRelease, Report};
+use crate::settings::{
+    ExtractExcludes, ExtractLimits, StorageBackend, UntrackedAction, UntrackedRule,
+};
+use crate::shared_cache::SharedCache;
+#[cfg(feature = "crypto")]
+use crate::signature::{self, Signature};
+use crate::stats::Stats;
+use crate::table::AddonRow;
+use crate::throttle::{Throttle, ThrottledReader};
+use crate::toc::Toc;
+use crate::{
+    curse, diff, error, fault, game_build, github, murmur2, net, perms, rename, settings, storage,
+    toc, tsm, tukui, wine,
+};
+use fancy_regex::Regex;
+use getset::{Getters, Setters};
+use reqwest::blocking::Client;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Getters, Setters)]
+#[getset(get = "pub", set = "pub")]
+pub struct Grunt {
+    is_new: bool,
+    root_dir: PathBuf,
+    /// Format the lockfile is saved in
+    storage_backend: StorageBackend,
+    addons: Vec<Addon>,
+    curse_api: CurseAPI,
+    /// Download speed limit in bytes per second
+    max_bandwidth: Option<u64>,
+    /// Rules deciding how untracked dirs are treated
+    untracked_rules: Vec<UntrackedRule>,
+    /// Group given to installed files on shared installs
+    file_group: Option<u32>,
+    /// Game flavor of the AddOns dir
+    flavor: Flavor,
+    /// Files left out when installing addons
+    extract_excludes: ExtractExcludes,
+    /// Limits that reject suspicious archives
+    extract_limits: ExtractLimits,
+    /// Resolve with the compiled-in Curse rules instead of fetching them
+    builtin_rules: bool,
+    /// Timeouts and retries for every request
+    #[getset(skip)]
+    net_options: NetOptions,
+    /// Curse metadata from the last update check
+    #[getset(skip)]
+    curse_cache: CurseCache,
+    /// Fingerprints of dirs that couldn't be resolved last time
+    #[getset(skip)]
+    unresolved: BTreeMap<String, UnresolvedDir>,
+    /// Local usage counters
+    stats: Stats,
+    /// Newest versions of addons not from Curse seen by the last check, by key
+    #[getset(skip)]
+    latest: BTreeMap<String, String>,
+    /// Updates applied and disk usage over time
+    history: History,
+    /// Game version seen on the last run
+    #[getset(skip)]
+    game_version: Option<String>,
+    /// Cache shared with other `AddOns` dirs updated in the same run
+    shared_cache: Option<SharedCache>,
+    /// Sources of `custom:<name>` addons by name
+    custom_sources: HashMap<String, CustomSource>,
+    /// Sources that aren't queried or installed from
+    disabled_sources: Vec<AddonType>,
+    /// Key the lockfile is signed with, to detect changes made outside of grunt
+    #[getset(skip)]
+    #[cfg(feature = "crypto")]
+    lockfile_secret: Option<String>,
+    /// Result of the last `verify_lockfile`. Nothing is saved while it's invalid, so
+    /// changes made outside of grunt aren't signed without being accepted
+    #[getset(skip)]
+    #[cfg(feature = "crypto")]
+    signature: Option<Signature>,
+    /// Updates that can fail in a row before an addon is paused, 0 to never pause
+    max_update_failures: u32,
+    /// Leave addons with local edits out of updates
+    skip_modified: bool,
+    /// Settings file of the TSM desktop app, whose session is used when there's no password
+    tsm_app_config: Option<PathBuf>,
+    /// Keep files in updated dirs that the new version doesn't have
+    keep_extra: bool,
+    /// Download addons installed from a URL again even if it doesn't look newer
+    force_urls: bool,
+    /// Addons as of the last `LockfileChanged` event
+    #[getset(skip)]
+    reported: Vec<Addon>,
+    /// Changes not yet taken by `take_changes`
+    #[getset(skip)]
+    changes: Vec<LockfileChanged>,
+    /// Time spent loading and saving the lockfile
+    #[getset(skip)]
+    lockfile_io: Mutex<Duration>,
+}
+
+impl Grunt {
+    /// Create a new grunt instance from a given `AddOns` dir
+    /// Reads data from `grunt.lockfile` if one exists
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, GruntError> {
+        Self::with_storage(path, StorageBackend::Json)
+    }
+
+    /// Create a new grunt instance, reading and saving data with the given backend
+    pub fn with_storage<P: AsRef<Path>>(
+        path: P,
+        storage_backend: StorageBackend,
+    ) -> Result<Self, GruntError> {
+        let path = path.as_ref();
+
+        // Setup struct data
+        let root_dir: PathBuf = std::fs::canonicalize(path)?; // Get absolute path
+        let addons;
+        let is_new;
+        let curse_cache;
+        let unresolved;
+        let stats;
+        let latest;
+        let history;
+        let game_version;
+        let flavor;
+
+        // Read lockfile if it exists
+        // Newer data saved with the other backend is moved over on the next save
+        let load_start = Instant::now();
+        let lockfile =
+            storage::open(newest_backend(storage_backend, &root_dir), &root_dir)?.load()?;
+        if let Some(lockfile) = lockfile {
+            is_new = true;
+            addons = lockfile.addons.into_iter().map(Addon::from_info).collect();
+            curse_cache = lockfile.curse_cache;
+            unresolved = lockfile.unresolved;
+            stats = lockfile.stats;
+            latest = lockfile.latest;
+            history = lockfile.history;
+            game_version = lockfile.game_version;
+            flavor = lockfile.flavor;
+        } else {
+            is_new = false;
+            addons = Vec::new();
+            curse_cache = CurseCache::default();
+            unresolved = BTreeMap::new();
+            stats = Stats::default();
+            latest = BTreeMap::new();
+            history = History::default();
+            game_version = None;
+            flavor = None;
+        }
+        let flavor = flavor.unwrap_or_else(|| Flavor::from_path(&root_dir));
+
+        // Return instance
+        let reported = addons.clone();
+        Ok(Grunt {
+            root_dir,
+            storage_backend,
+            is_new,
+            addons,
+            curse_api: CurseAPI::init(&NetOptions::default()),
+            max_bandwidth: None,
+            untracked_rules: Vec::new(),
+            file_group: None,
+            flavor,
+            extract_excludes: ExtractExcludes::default(),
+            extract_limits: ExtractLimits::default(),
+            builtin_rules: false,
+            net_options: NetOptions::default(),
+            curse_cache,
+            unresolved,
+            stats,
+            latest,
+            history,
+            game_version,
+            shared_cache: None,
+            custom_sources: HashMap::new(),
+            disabled_sources: Vec::new(),
+            #[cfg(feature = "crypto")]
+            lockfile_secret: None,
+            #[cfg(feature = "crypto")]
+            signature: None,
+            max_update_failures: 5,
+            skip_modified: false,
+            tsm_app_config: None,
+            keep_extra: false,
+            force_urls: false,
+            reported,
+            changes: Vec::new(),
+            lockfile_io: Mutex::new(load_start.elapsed()),
+        })
+    }
+
+    pub fn net_options(&self) -> &NetOptions {
+        &self.net_options
+    }
+
+    /// Changes the timeouts and retries used for requests
+    pub fn set_net_options(&mut self, options: NetOptions) {
+        self.curse_api = CurseAPI::init(&options);
+        self.net_options = options;
+    }
+
+    /// Returns directories that aren't owned by any tracked addons
+    /// Directories matching an `ignore` rule are left out
+    pub fn find_untracked(&self) -> Result<Vec<String>, GruntError> {
+        // Get all directories in the root folder
+        // Names that aren't UTF-8 can't belong to an addon so they're skipped
+        let mut all_dirs: Vec<String> = Vec::new();
+        for entry in self.root_dir.read_dir()? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                if let Ok(name) = entry.file_name().into_string() {
+                    all_dirs.push(name);
+                }
+            }
+        }
+        // Get all directories owned by addons
+        let all_tracked: Vec<&String> = self.addons.iter().flat_map(|addon| addon.dirs()).collect();
+        // Return directories not owned by addons
+        Ok(all_dirs
+            .into_iter()
+            // Dirs on case sensitive filesystems can differ in case from the tracked ones
+            .filter(|dir| {
+                !all_tracked
+                    .iter()
+                    .any(|tracked| tracked.eq_ignore_ascii_case(dir))
+            })
+            .filter(|dir| self.untracked_action(dir) != Some(UntrackedAction::Ignore))
+            .collect())
+    }
+
+    /// Returns untracked directories that no rule applies to
+    pub fn find_unresolved(&self) -> Result<Vec<String>, GruntError> {
+        Ok(self
+            .find_untracked()?
+            .into_iter()
+            .filter(|dir| self.untracked_action(dir).is_none())
+            .collect())
+    }
+
+    /// Returns the action of the first untracked rule matching `dir`
+    pub fn untracked_action(&self, dir: &str) -> Option<UntrackedAction> {
+        self.untracked_rules
+            .iter()
+            .find(|rule| rule.matches(dir))
+            .map(|rule| *rule.action())
+    }
+
+    /// When and why `dir` last failed to resolve, if it did
+    pub fn unresolved_dir(&self, dir: &str) -> Option<&UnresolvedDir> {
+        self.unresolved.get(dir)
+    }
+
+    /// Returns unresolved dirs that have changed since they last failed to resolve
+    fn find_changed_unresolved(&self) -> Result<Vec<String>, GruntError> {
+        Ok(self
+            .find_unresolved()?
+            .into_iter()
+            .filter(|dir| {
+                self.unresolved
+                    .get(dir)
+                    .map(|unresolved| unresolved.fingerprint)
+                    != Some(dir_fingerprint(&self.root_dir.join(dir)))
+            })
+            .collect())
+    }
+
+    /// Attempts to resolve untracked addons
+    /// Adds any found to the lockfile
+    /// Dirs that failed to resolve before are skipped unless they changed or `retry` is set
+    /// Progress is reported using `prog`
+    pub fn resolve<F>(&mut self, retry: bool, mut prog: F) -> Result<Metrics, GruntError>
+    where
+        F: FnMut(ResolveProgress),
+    {
+        let start = Instant::now();
+        let mut metrics = Metrics::default();
+        if retry {
+            self.unresolved.clear();
+        }
+        let untracked = self.find_changed_unresolved()?;
+        let skipped = self.find_unresolved()?.len() - untracked.len();
+        let mut new_addons = Vec::new();
+
+        // Check for TSM addons
+        let tsm_enabled = self.check_source(AddonType::TSM).is_ok();
+        let tsm_string = "TradeSkillMaster";
+        let tsm_dir = self.root_dir.join(tsm_string);
+        if tsm_enabled && untracked.contains(&tsm_string.to_string()) && tsm_dir.exists() {
+            let version = get_toc_version(tsm_dir.join("TradeSkillMaster.toc"))?;
+            let tsm_addon = Addon::init_tsm(version);
+            prog(ResolveProgress::NewAddon {
+                name: tsm_string.to_string(),
+                desc: tsm_addon.identifier(),
+            });
+            self.addons.push(tsm_addon);
+        }
+        let tsm_helper_string = "TradeSkillMaster_AppHelper";
+        let tsm_helper_dir = self.root_dir.join(tsm_helper_string);
+        if tsm_enabled
+            && untracked.contains(&tsm_helper_string.to_string())
+            && tsm_helper_dir.exists()
+        {
+            let version = get_toc_version(tsm_helper_dir.join("TradeSkillMaster_AppHelper.toc"))?;
+            let tsm_helper_addon = Addon::init_tsm_helper(version);
+            prog(ResolveProgress::NewAddon {
+                name: tsm_helper_string.to_string(),
+                desc: tsm_helper_addon.identifier(),
+            });
+            self.addons.push(tsm_helper_addon);
+        }
+        let untracked = self.find_changed_unresolved()?;
+
+        // Get addon information from `{Addon}.toc` if it is there
+        let tukui_enabled = self.check_source(AddonType::Tukui).is_ok();
+        for dir in untracked.iter().filter(|_| tukui_enabled) {
+            // Get the path to the .toc for each addon
+            // Dirs without one, like ones holding nested addons, can't be Tukui addons
+            let toc = main_toc(&self.root_dir, dir);
+            if !toc.exists() {
+                continue;
+            }
+
+            let toc = Toc::from_file(toc)?;
+            let tukui_id = toc
+                .field("X-Tukui-ProjectID")
+                .and_then(|value| value.parse::<i64>().ok());
+            let tukui_dirs = toc.field("X-Tukui-ProjectFolders").map(|value| {
+                value
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .collect::<Vec<String>>()
+            });
+            let version = toc.field("Version").map(str::to_string);
+
+            // Dirs missing any of the fields are left for the other sources
+            if let (Some(tukui_id), Some(tukui_dirs), Some(version)) =
+                (tukui_id, tukui_dirs, version)
+            {
+                let addon = Addon::from_tukui_info(dir.clone(), tukui_id, tukui_dirs, version);
+                prog(ResolveProgress::NewAddon {
+                    name: dir.clone(),
+                    desc: addon.identifier(),
+                });
+                new_addons.push(addon);
+            }
+        }
+        self.addons.extend(new_addons);
+        let untracked = self.find_changed_unresolved()?;
+
+        // Curse
+        let curse_enabled = self.check_source(AddonType::Curse).is_ok();
+        if curse_enabled {
+            metrics.moved_projects = self.find_moved_projects(&mut metrics)?;
+        }
+        let curse_addons = if untracked.is_empty() || !curse_enabled {
+            Vec::new()
+        } else {
+            self.resolve_curse(untracked, &mut metrics)?
+        };
+        for addon in curse_addons.iter() {
+            prog(ResolveProgress::NewAddon {
+                name: addon.name().clone(),
+                desc: addon.identifier(),
+            })
+        }
+        self.addons.extend(curse_addons);
+
+        // Remember what's left so unchanged dirs aren't checked again
+        // Dirs that were skipped keep when and why they failed before
+        // Dirs no source checked aren't recorded so they're checked once one is enabled
+        let not_found = self.find_unresolved()?;
+        let now = chrono::Utc::now();
+        let mut unresolved = BTreeMap::new();
+        for dir in not_found.iter() {
+            let fingerprint = dir_fingerprint(&self.root_dir.join(dir));
+            let entry = match self.unresolved.remove(dir) {
+                Some(entry) if entry.fingerprint == fingerprint => entry,
+                _ if !curse_enabled => continue,
+                _ => UnresolvedDir {
+                    fingerprint,
+                    attempted: Some(now),
+                    reason: Some("No Curse fingerprint match".to_string()),
+                },
+            };
+            unresolved.insert(dir.clone(), entry);
+        }
+        self.unresolved = unresolved;
+
+        // Finish
+        let hash_start = Instant::now();
+        self.fill_fingerprints();
+        metrics.phases.hashing += hash_start.elapsed();
+        self.note_changes();
+        prog(ResolveProgress::Finished { not_found, skipped });
+        metrics.duration = start.elapsed();
+        Ok(metrics)
+    }
+
+    /// Finds unresolved dirs that hold addons instead of being one, like
+    /// `MyAddon/MyAddon/MyAddon.toc` from extracting a zip into a dir of its own
+    pub fn find_nested(&self) -> Result<Vec<NestedDir>, GruntError> {
+        Ok(self
+            .find_unresolved()?
+            .into_iter()
+            .filter_map(|dir| {
+                let path = self.root_dir.join(&dir);
+                if has_toc(&path) {
+                    return None;
+                }
+                let inner: Vec<String> = find_toc_dirs(&path, NESTED_DEPTH)
+                    .iter()
+                    .filter_map(|inner| Some(path_to_slash(inner.strip_prefix(&path).ok()?)))
+                    .collect();
+                if inner.is_empty() {
+                    return None;
+                }
+                Some(NestedDir { dir, inner })
+            })
+            .collect())
+    }
+
+    /// Moves the addons inside a nested dir up into `AddOns`, then deletes the rest of it
+    /// Returns the names of the moved dirs so they can be resolved
+    pub fn flatten_nested(&mut self, nested: &NestedDir) -> Result<Vec<String>, GruntError> {
+        let mut names: Vec<String> = Vec::new();
+        for inner in nested.inner.iter() {
+            let name = inner.rsplit('/').next().unwrap_or(inner).to_string();
+            let taken = name != nested.dir && self.root_dir.join(&name).exists();
+            if taken || names.contains(&name) {
+                return Err(GruntError::DirExists(name));
+            }
+            names.push(name);
+        }
+        // Moved aside first since an inner dir usually has the same name
+        let staging = self.root_dir.join(format!("{}.grunt-flatten", nested.dir));
+        std::fs::rename(self.root_dir.join(&nested.dir), &staging)?;
+        for (inner, name) in nested.inner.iter().zip(names.iter()) {
+            std::fs::rename(staging.join(inner), self.root_dir.join(name))?;
+        }
+        std::fs::remove_dir_all(&staging)?;
+        self.unresolved.remove(&nested.dir);
+        Ok(names)
+    }
+
+    /// Save the lockfile, signing it if a secret is set
+    pub fn save_lockfile(&self) -> Result<(), GruntError> {
+        #[cfg(feature = "crypto")]
+        if self.signature == Some(Signature::Invalid) {
+            return Err(GruntError::UntrustedLockfile(
+                self.lockfile_path().display().to_string(),
+            ));
+        }
+        let start = Instant::now();
+        let storage = storage::open(self.storage_backend, &self.root_dir)?;
+        let lockfile = Lockfile::from_grunt(self);
+        storage.save(&lockfile)?;
+        #[cfg(feature = "crypto")]
+        if let Some(secret) = &self.lockfile_secret {
+            std::fs::write(
+                signature_path(&self.lockfile_path()),
+                signature::sign(secret, &lockfile),
+            )?;
+        }
+        *self.lockfile_io.lock().unwrap() += start.elapsed();
+        Ok(())
+    }
+
+    /// Total time spent loading and saving the lockfile so far
+    pub fn lockfile_io(&self) -> Duration {
+        *self.lockfile_io.lock().unwrap()
+    }
+
+    /// Fails if `source` is in the disabled sources or its feature wasn't built
+    fn check_source(&self, source: AddonType) -> Result<(), GruntError> {
+        if !source.is_built() {
+            Err(GruntError::SourceNotBuilt(source))
+        } else if self.disabled_sources.contains(&source) {
+            Err(GruntError::SourceDisabled(source))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sets the key used to sign the lockfile
+    #[cfg(feature = "crypto")]
+    pub fn set_lockfile_secret(&mut self, secret: Option<String>) {
+        self.lockfile_secret = secret;
+    }
+
+    /// Checks the saved lockfile against its signature
+    /// Returns `None` if no secret is set or nothing has been saved yet
+    /// Saving is refused afterwards if it's invalid, until `accept_lockfile` is called
+    #[cfg(feature = "crypto")]
+    pub fn verify_lockfile(&mut self) -> Result<Option<Signature>, GruntError> {
+        let secret = match &self.lockfile_secret {
+            Some(secret) => secret,
+            None => return Ok(None),
+        };
+        // The data that was loaded, which may be the other backend's
+        let backend = newest_backend(self.storage_backend, &self.root_dir);
+        let storage = storage::open(backend, &self.root_dir)?;
+        let lockfile = match storage.load()? {
+            Some(lockfile) => lockfile,
+            None => return Ok(None),
+        };
+        let lockfile_path = storage::path(backend, &self.root_dir);
+        let result = match std::fs::read_to_string(signature_path(&lockfile_path)) {
+            Ok(saved) => signature::verify(secret, &lockfile, &saved),
+            Err(_) => Signature::Missing,
+        };
+        self.signature = Some(result);
+        Ok(Some(result))
+    }
+
+    /// Trusts a lockfile changed outside of grunt, so it's signed again when saved
+    #[cfg(feature = "crypto")]
+    pub fn accept_lockfile(&mut self) {
+        self.signature = Some(Signature::Valid);
+    }
+
+    /// Counts a run of `command` in the usage stats
+    pub fn record_command(&mut self, command: &str) {
+        self.stats.record_command(command);
+    }
+
+    /// The file the lockfile is saved to
+    pub fn lockfile_path(&self) -> PathBuf {
+        storage::path(self.storage_backend, &self.root_dir)
+    }
+
+    /// Updates addons
+    pub fn update_addons<F>(
+        &mut self,
+        check_update: F,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> Result<Metrics, GruntError>
+    where
+        F: FnMut(Vec<Updateable>) -> Vec<Updateable>,
+    {
+        self.update_addons_with_progress(check_update, |_| (), tsm_email, tsm_pass)
+    }
+
+    /// Checks which addons have a newer version without installing anything
+    /// Addons of a source that's down are skipped, with the error in `errors`
+    pub fn check_updates(
+        &mut self,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> UpdateCheck {
+        let hash_start = Instant::now();
+        let mut phases = PhaseTimes::default();
+        self.fill_fingerprints();
+        phases.hashing = hash_start.elapsed();
+        // Skip addons of disabled or unbuilt sources, paused ones and ones whose
+        // frequency says they aren't due a check
+        let now = chrono::Utc::now();
+        let due: Vec<bool> = self
+            .addons
+            .iter()
+            .map(|addon| {
+                self.check_source(addon.addon_type().clone()).is_ok()
+                    && !self.is_paused(addon)
+                    && !addon.pinned()
+                    && addon.frequency().is_due(*addon.last_checked(), now)
+            })
+            .collect();
+        // Local edits are kept if asked to
+        let modified_start = Instant::now();
+        let mut skipped_modified = Vec::new();
+        let due: Vec<bool> = due
+            .into_iter()
+            .zip(self.addons.iter())
+            .map(|(due, addon)| {
+                if due && self.skip_modified && !self.modified_dirs(addon).is_empty() {
+                    skipped_modified.push(addon.name().clone());
+                    return false;
+                }
+                due
+            })
+            .collect();
+        phases.hashing += modified_start.elapsed();
+        let metadata_start = Instant::now();
+        let due_addons = || {
+            self.addons
+                .iter()
+                .zip(due.iter())
+                .filter(|(_, due)| **due)
+                .map(|(addon, _)| addon)
+        };
+        // Get information from addon list needed to download update information
+        // Curse IDs
+        // Versions are file ids, but one edited by hand might not parse
+        let curse_ids: Vec<(String, Option<i64>)> = due_addons()
+            .filter(|addon| addon.addon_type() == &AddonType::Curse)
+            .map(|addon| (addon.addon_id().clone(), addon.version().parse().ok()))
+            .collect();
+        // Cached projects of addons not due are kept
+        let all_curse_ids: HashSet<String> = self
+            .addons
+            .iter()
+            .filter(|addon| addon.addon_type() == &AddonType::Curse)
+            .map(|addon| addon.addon_id().clone())
+            .collect();
+        // Tukui IDs
+        let tukui_ids: Vec<String> = due_addons()
+            .filter(|addon| addon.addon_type() == &AddonType::Tukui && addon.addon_id() != "-2")
+            .map(|addon| addon.addon_id().clone())
+            .collect();
+        // Get ElvUI addon if it exists. (Tukui special case)
+        let has_elvui_addon = due_addons()
+            .any(|addon| addon.addon_type() == &AddonType::Tukui && addon.addon_id() == "-2");
+        // Custom sources
+        let custom_sources: Vec<(String, CustomSource)> = due_addons()
+            .filter(|addon| addon.addon_type() == &AddonType::Custom)
+            .filter_map(|addon| {
+                let source = self.custom_sources.get(addon.addon_id())?;
+                Some((addon.addon_id().clone(), source.clone()))
+            })
+            .collect();
+        // URLs
+        let urls: Vec<String> = due_addons()
+            .filter(|addon| addon.addon_type() == &AddonType::Url)
+            .map(|addon| addon.addon_id().clone())
+            .collect();
+        // GitHub repos
+        let repos: Vec<String> = due_addons()
+            .filter(|addon| addon.addon_type() == &AddonType::GitHub)
+            .map(|addon| addon.addon_id().clone())
+            .collect();
+        // TSM
+        let has_tsm_addon = due_addons().any(|addon| addon.addon_type() == &AddonType::TSM);
+
+        // Create threads to download info for each set of IDs
+        // Curse
+        let net_options = self.net_options.clone();
+        let mut curse_cache = self.curse_cache.clone();
+        let shared = self.shared_cache.clone();
+        let flavor = self.flavor;
+        let curse_thread = thread::spawn(move || -> Result<_, GruntError> {
+            // Return early if no curse addons
+            if curse_ids.is_empty() {
+                return Ok((HashMap::new(), curse_cache, HashMap::new()));
+            }
+            let api = CurseAPI::init(&net_options); // Bit of a hack
+
+            // Only fetch full info for projects that changed since the last check
+            // Not knowing which changed only means fetching all of them
+            let timestamp = api.get_timestamp()?;
+            let changed = match &curse_cache.timestamp {
+                Some(cached) if cached == &timestamp => Some(HashMap::new()),
+                Some(cached) => api.changed_since(cached).unwrap_or(None),
+                None => None,
+            };
+            let stale: HashSet<&String> = curse_cache
+                .stale(curse_ids.iter().map(|(id, _)| id), changed.as_ref())
+                .into_iter()
+                .collect();
+            // Projects another AddOns dir fetched at the same timestamp
+            let shared_key = |id: &str| format!("curse-{}-{}", flavor.curse_name(), id);
+            let mut shared_ids = HashSet::new();
+            if let Some(shared) = &shared {
+                for (id, _) in curse_ids.iter() {
+                    match shared.load::<(String, CachedProject)>(&shared_key(id)) {
+                        Some((shared_timestamp, project)) if shared_timestamp == timestamp => {
+                            curse_cache.projects.insert(id.clone(), project);
+                            shared_ids.insert(id.clone());
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            let ids: Vec<&String> = curse_ids
+                .iter()
+                .map(|(id, _)| id)
+                .filter(|id| stale.contains(*id))
+                .filter(|id| !shared_ids.contains(*id))
+                .collect();
+            let mut addon_infos = if ids.is_empty() {
+                Vec::new()
+            } else {
+                api.get_addons_info(&ids)?
+            };
+            // Curse sometimes moves a project's files to a new project id. Track the new
+            // project instead of the old one, which stays unavailable
+            let moved: HashMap<String, String> = addon_infos
+                .iter()
+                .filter_map(|info| {
+                    let new_id = info
+                        .latest_files
+                        .iter()
+                        .map(|file| file.project_id)
+                        .find(|id| *id != 0 && *id != info.id)?;
+                    Some((info.id.to_string(), new_id.to_string()))
+                })
+                .collect();
+            if !moved.is_empty() {
+                let new_ids: Vec<&String> = moved.values().collect();
+                addon_infos.retain(|info| !moved.contains_key(&info.id.to_string()));
+                addon_infos.extend(api.get_addons_info(&new_ids)?);
+            }
+            let current_id = |id: &String| moved.get(id).unwrap_or(id).clone();
+            for info in addon_infos {
+                // Get the latest version by selecting the file with the highest id (newest)
+                let latest = info
+                    .latest_files
+                    .iter()
+                    // Only look at files for the flavor of the AddOns dir
+                    .filter(|file| file.game_version_flavor == flavor.curse_name())
+                    .max_by(|file_a, &file_b| file_a.id.cmp(&file_b.id));
+                // Dead projects can have no files left. Keep the installed one
+                let (latest_file_id, download_url) = match latest {
+                    Some(latest) => (latest.id, latest.download_url.clone()),
+                    None => {
+                        let current = curse_ids
+                            .iter()
+                            .find(|(id, _)| current_id(id) == info.id.to_string())
+                            .and_then(|(_, version)| *version);
+                        match current {
+                            Some(current) => (current, String::new()),
+                            None => continue,
+                        }
+                    }
+                };
+                let project = CachedProject {
+                    date_modified: info.date_modified.clone(),
+                    slug: info.slug.clone(),
+                    latest_file_id,
+                    download_url,
+                    file_length: latest.map(|latest| latest.file_length as u64),
+                    deprecation: info.deprecation(),
+                };
+                if let Some(shared) = &shared {
+                    shared.store(&shared_key(&info.id.to_string()), &(&timestamp, &project));
+                }
+                curse_cache.projects.insert(info.id.to_string(), project);
+            }
+            curse_cache.timestamp = Some(timestamp);
+            curse_cache.projects.retain(|id, _| {
+                all_curse_ids.contains(id) || moved.values().any(|new_id| new_id == id)
+            });
+
+            let to_update = curse_ids
+                .iter()
+                .filter_map(|(id, _)| {
+                    let id = current_id(id);
+                    let project = curse_cache.projects.get(&id)?;
+                    let latest = (
+                        project.latest_file_id,
+                        project.download_url.clone(),
+                        project.slug.clone(),
+                    );
+                    Some((id, latest))
+                })
+                .collect::<HashMap<_, _>>();
+            Ok((to_update, curse_cache, moved))
+        });
+        // Tukui
+        let net_options = self.net_options.clone();
+        let shared = self.shared_cache.clone();
+        let tukui_thread = thread::spawn(move || -> Result<_, GruntError> {
+            if tukui_ids.is_empty() {
+                return Ok(HashMap::new());
+            }
+            let key = format!("tukui-{}", flavor.curse_name());
+            let tukui_infos: Vec<tukui::AddonInfo> =
+                match shared.as_ref().and_then(|shared| shared.load(&key)) {
+                    Some(infos) => infos,
+                    None => {
+                        let infos = tukui::get_addon_infos(flavor, &net_options)?;
+                        if let Some(shared) = &shared {
+                            shared.store(&key, &infos);
+                        }
+                        infos
+                    }
+                };
+            let mut map = HashMap::new();
+            for id in tukui_ids {
+                if let Some(info) = tukui_infos.iter().find(|info| info.id == id) {
+                    map.insert(id, (info.version.clone(), info.url.clone()));
+                }
+            }
+            Ok(map)
+        });
+        // ElvUI special case
+        let net_options = self.net_options.clone();
+        let shared = self.shared_cache.clone();
+        let elvui_thread = thread::spawn(move || -> Result<_, GruntError> {
+            if !has_elvui_addon {
+                return Ok(("".to_string(), "".to_string()));
+            }
+            let elvui_info = match shared.as_ref().and_then(|shared| shared.load("elvui")) {
+                Some(info) => info,
+                None => {
+                    let info = tukui::get_elvui_info(&net_options)?;
+                    if let Some(shared) = &shared {
+                        shared.store("elvui", &info);
+                    }
+                    info
+                }
+            };
+            Ok((elvui_info.version, elvui_info.url))
+        });
+        // Custom, URL and GitHub addons each have their own server or repo, so one failing
+        // only fails its addon
+        let net_options = self.net_options.clone();
+        let custom_thread = thread::spawn(move || {
+            let mut map = HashMap::new();
+            for (name, source) in custom_sources {
+                let latest = source.latest_version(&name, &net_options).map(|version| {
+                    let url = source.download_url(&version);
+                    (version, url)
+                });
+                map.insert(name, latest);
+            }
+            map
+        });
+        // URL
+        let net_options = self.net_options.clone();
+        let url_thread = thread::spawn(move || {
+            let mut map = HashMap::new();
+            for url in urls {
+                let latest = net::last_modified(&url, &net_options)
+                    .map(|version| (version.unwrap_or_default(), url.clone()));
+                map.insert(url, latest);
+            }
+            map
+        });
+        // GitHub
+        let net_options = self.net_options.clone();
+        let github_thread = thread::spawn(move || {
+            let mut map = HashMap::new();
+            for repo in repos {
+                let latest = github::latest_release(&repo, &net_options);
+                map.insert(repo, latest);
+            }
+            map
+        });
+        // TSM
+        let tsm_login = self.tsm_credentials(tsm_email, tsm_pass);
+        let net_options = self.net_options.clone();
+        let tsm_thread = thread::spawn(move || -> Result<_, GruntError> {
+            let mut tsm_api = tsm::TSMApi::new(&net_options);
+            if !has_tsm_addon {
+                return Ok((tsm_api, tsm::StatusRespData::default()));
+            }
+            tsm_api.sign_in(&tsm_login?)?;
+            let status = tsm_api.get_status()?;
+            Ok((tsm_api, status))
+        });
+
+        // Wait for threads to finish
+        // A source that's down skips its addons so the others still update
+        let mut errors = Vec::new();
+        let mut unavailable = |name: &str, err: GruntError| {
+            errors.push(GruntError::SourceUnavailable {
+                name: name.to_string(),
+                source: Box::new(err),
+            });
+        };
+        let (curse_down, mut latest_curse, moved) = match curse_thread.join().unwrap() {
+            Ok((latest, curse_cache, moved)) => {
+                self.curse_cache = curse_cache;
+                (false, latest, moved)
+            }
+            Err(err) => {
+                unavailable("curse", err);
+                (true, HashMap::new(), HashMap::new())
+            }
+        };
+        let (tukui_down, mut latest_tukui) = match tukui_thread.join().unwrap() {
+            Ok(latest) => (false, latest),
+            Err(err) => {
+                unavailable("tukui", err);
+                (true, HashMap::new())
+            }
+        };
+        let (elvui_down, elvui_info) = match elvui_thread.join().unwrap() {
+            Ok(info) => (false, info),
+            Err(err) => {
+                unavailable("elvui", err);
+                (true, Default::default())
+            }
+        };
+        let mut latest_custom = custom_thread.join().unwrap();
+        let mut latest_url = url_thread.join().unwrap();
+        let mut latest_github = github_thread.join().unwrap();
+        let (tsm_down, tsm_api, tsm_status) = match tsm_thread.join().unwrap() {
+            Ok((tsm_api, status)) => (false, tsm_api, status),
+            Err(err) => {
+                unavailable("tsm", err);
+                (
+                    true,
+                    tsm::TSMApi::new(&self.net_options),
+                    Default::default(),
+                )
+            }
+        };
+        phases.metadata = metadata_start.elapsed();
+        // Addons of a source that's down aren't checked, so they aren't failures either
+        let due: Vec<bool> = due
+            .into_iter()
+            .zip(self.addons.iter())
+            .map(|(due, addon)| {
+                let down = match addon.addon_type() {
+                    AddonType::Curse => curse_down,
+                    AddonType::Tukui if addon.addon_id() == "-2" => elvui_down,
+                    AddonType::Tukui => tukui_down,
+                    AddonType::TSM => tsm_down,
+                    AddonType::Custom | AddonType::Url | AddonType::GitHub => false,
+                };
+                due && !down
+            })
+            .collect();
+
+        let mut moved_projects = Vec::new();
+        for (from, to) in moved {
+            let slug = latest_curse.get(&to).map(|(_, _, slug)| slug.clone());
+            moved_projects.extend(self.move_curse_project(&from, to, slug));
+        }
+
+        // Fill in slugs missing from older lockfiles
+        for addon in self.addons.iter_mut() {
+            if addon.addon_type() == &AddonType::Curse && addon.slug().is_none() {
+                if let Some((_, _, slug)) = latest_curse.get(addon.addon_id()) {
+                    addon.set_slug(Some(slug.clone()));
+                }
+            }
+        }
+
+        // Find out which addons need updating
+        let checked: Vec<Result<Updateable, (usize, GruntError)>> = self
+            .addons
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| due[*index])
+            .filter_map(|(index, addon)| {
+                let not_found = || {
+                    let err = GruntError::NotFound(addon.name().clone())
+                        .for_addon(addon.name(), addon.addon_type());
+                    Some(Err((index, err)))
+                };
+                let (latest, url) = match addon.addon_type() {
+                    AddonType::Curse => match latest_curse.remove(addon.addon_id()) {
+                        Some((latest, url, _)) => (latest.to_string(), url),
+                        None => return not_found(),
+                    },
+                    AddonType::Tukui if addon.addon_id() == "-2" => elvui_info.clone(),
+                    AddonType::Tukui => match latest_tukui.remove(addon.addon_id()) {
+                        Some(latest) => latest,
+                        None => return not_found(),
+                    },
+                    AddonType::Custom | AddonType::Url | AddonType::GitHub => {
+                        let latest = match addon.addon_type() {
+                            AddonType::Custom => latest_custom.remove(addon.addon_id()),
+                            AddonType::GitHub => latest_github.remove(addon.addon_id()),
+                            _ => latest_url.remove(addon.addon_id()),
+                        };
+                        match latest {
+                            Some(Ok(latest)) => latest,
+                            Some(Err(err)) => {
+                                let err = err.for_addon(addon.name(), addon.addon_type());
+                                return Some(Err((index, err)));
+                            }
+                            None => return not_found(),
+                        }
+                    }
+                    AddonType::TSM => match tsm_status
+                        .addons_for(self.flavor)
+                        .iter()
+                        .find(|data| &data.name == addon.name())
+                    {
+                        Some(data) => (data.version_str.clone(), "tsm".to_string()),
+                        None => return not_found(),
+                    },
+                };
+                let forced = self.force_urls && addon.addon_type() == &AddonType::Url;
+                let data = if forced || addon.addon_type().is_newer(addon.version(), &latest) {
+                    Some((latest, url))
+                } else {
+                    None
+                };
+                // Only Curse says how big files are before downloading them
+                let size = match addon.addon_type() {
+                    AddonType::Curse => self
+                        .curse_cache
+                        .projects
+                        .get(addon.addon_id())
+                        .and_then(|project| project.file_length),
+                    _ => None,
+                };
+                if let Some((version, url)) = data {
+                    Some(Ok(Updateable {
+                        index,
+                        name: addon.name().clone(),
+                        new_version: version,
+                        url,
+                        size,
+                    }))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        // Addons that fail are left out so the rest still update
+        let mut failed = Vec::new();
+        let mut outdated = Vec::new();
+        for result in checked {
+            match result {
+                Ok(upd) => outdated.push(upd),
+                Err(failure) => failed.push(failure),
+            }
+        }
+        // Saved for `grunt list`, an addon that isn't outdated has the latest version
+        for (index, addon) in self.addons.iter().enumerate() {
+            if !due[index]
+                || addon.addon_type() == &AddonType::Curse
+                || failed.iter().any(|(failed, _)| *failed == index)
+            {
+                continue;
+            }
+            let latest = match outdated.iter().find(|upd| upd.index == index) {
+                Some(upd) => upd.new_version.clone(),
+                None => addon.version().clone(),
+            };
+            self.latest.insert(addon.key(), latest);
+        }
+        UpdateCheck {
+            outdated,
+            failed,
+            unavailable: errors,
+            due,
+            checked_at: now,
+            skipped_modified,
+            moved_projects,
+            phases,
+            tsm_api,
+        }
+    }
+
+    /// Updates addons, reporting progress using `prog`
+    pub fn update_addons_with_progress<F, P>(
+        &mut self,
+        check_update: F,
+        prog: P,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> Result<Metrics, GruntError>
+    where
+        F: FnMut(Vec<Updateable>) -> Vec<Updateable>,
+        P: FnMut(UpdateProgress) + Send,
+    {
+        let mut guard = WriteGuard::new();
+        self.run_updates(check_update, prog, tsm_email, tsm_pass, &mut guard)
+    }
+
+    /// Updates addons, changing the `AddOns` dir through `guard`
+    /// Addons and their state are left as they are when dry running
+    fn run_updates<F, P>(
+        &mut self,
+        mut check_update: F,
+        prog: P,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+        guard: &mut WriteGuard,
+    ) -> Result<Metrics, GruntError>
+    where
+        F: FnMut(Vec<Updateable>) -> Vec<Updateable>,
+        P: FnMut(UpdateProgress) + Send,
+    {
+        // Downloads report progress from their own threads
+        let prog = Mutex::new(prog);
+        let report = |event: UpdateProgress| (*prog.lock().unwrap())(event);
+        let start = Instant::now();
+        let UpdateCheck {
+            outdated,
+            mut failed,
+            unavailable: mut errors,
+            due,
+            checked_at: now,
+            skipped_modified,
+            moved_projects,
+            mut phases,
+            tsm_api,
+        } = self.check_updates(tsm_email, tsm_pass);
+        for (addon, due) in self.addons.iter_mut().zip(due.iter()) {
+            if *due && !addon.frequency().is_fast() && !guard.is_dry_run() {
+                addon.set_last_checked(Some(now));
+            }
+        }
+        // Addons that are up to date or get updated clear their failures
+        let mut succeeded: Vec<usize> = (0..self.addons.len())
+            .filter(|index| due[*index])
+            .filter(|index| !outdated.iter().any(|upd| upd.index == *index))
+            .filter(|index| !failed.iter().any(|(failed, _)| failed == index))
+            .collect();
+
+        // Ask user
+        let outdated = check_update(outdated);
+
+        // Download/unpack updates
+        // High priority addons are downloaded and installed in a wave before the rest
+        let tmp_dir = tempfile::Builder::new().prefix("grunt").tempdir()?;
+        let throttle = self.max_bandwidth.map(Throttle::new);
+        let client = self.net_options.client();
+        // Check the new dirs don't clash with each other or with dirs of addons that
+        // aren't being updated, skipping the addons that would
+        let outdated_indexes: Vec<usize> = outdated.iter().map(|upd| upd.index).collect();
+        let mut owners: HashMap<String, String> = self
+            .addons
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !outdated_indexes.contains(index))
+            .flat_map(|(_, addon)| {
+                addon
+                    .dirs()
+                    .iter()
+                    .map(move |dir| (dir.clone(), addon.name().clone()))
+            })
+            .collect();
+        let mut downloads = Vec::new();
+        let mut renames = Vec::new();
+        let mut any_installed = false;
+        let (priority, normal): (Vec<Updateable>, Vec<Updateable>) = outdated
+            .into_iter()
+            .partition(|upd| *self.addons[upd.index].priority());
+        let has_priority = !priority.is_empty();
+        let remaining = normal.len();
+        for (wave, outdated) in vec![priority, normal].into_iter().enumerate() {
+            let results: Vec<Result<DownloadMetrics, GruntError>> = outdated
+                .par_iter()
+                .map(|upd| {
+                    let addon = &self.addons[upd.index];
+                    let download = || -> Result<DownloadMetrics, GruntError> {
+                        report(UpdateProgress::DownloadStarted {
+                            name: upd.name.clone(),
+                        });
+                        let download_start = Instant::now();
+                        let download_loc =
+                            tmp_dir.path().join(format!("update{}.download", upd.index));
+                        self.fetch_archive(
+                            &addon.identifier(),
+                            &upd.new_version,
+                            &download_loc,
+                            || {
+                                if upd.url == "tsm" {
+                                    // Use api
+                                    tsm_api.addon(&upd.name, &download_loc)
+                                } else {
+                                    let progress = |bytes, total| {
+                                        report(UpdateProgress::DownloadProgress {
+                                            name: upd.name.clone(),
+                                            bytes,
+                                            total,
+                                        })
+                                    };
+                                    download_file(
+                                        &client,
+                                        &self.net_options,
+                                        &upd.url,
+                                        &download_loc,
+                                        throttle.as_ref(),
+                                        Some(&progress),
+                                    )
+                                }
+                            },
+                        )?;
+
+                        let duration = download_start.elapsed();
+
+                        // Unzip downloaded file to temp dir
+                        report(UpdateProgress::Extracting {
+                            name: upd.name.clone(),
+                        });
+                        let extract_start = Instant::now();
+                        let unzip_dir = tmp_dir.path().join(format!("unpacked{}", upd.index));
+                        let excludes = self.extract_excludes.patterns(&addon.identifier())?;
+                        extract_zip(&download_loc, &unzip_dir, &excludes, &self.extract_limits)?;
+                        Ok(DownloadMetrics {
+                            name: upd.name.clone(),
+                            bytes: std::fs::metadata(&download_loc)?.len(),
+                            duration,
+                            extract_duration: extract_start.elapsed(),
+                        })
+                    };
+                    download().map_err(|err| err.for_addon(addon.name(), addon.addon_type()))
+                })
+                .collect();
+            let mut downloaded = Vec::new();
+            for (upd, result) in outdated.into_iter().zip(results) {
+                match result {
+                    Ok(metrics) => {
+                        downloads.push(metrics);
+                        downloaded.push(upd);
+                    }
+                    Err(err) => failed.push((upd.index, err)),
+                }
+            }
+            let outdated = downloaded;
+            let mut checked = Vec::new();
+            for upd in outdated.into_iter() {
+                let addon = &self.addons[upd.index];
+                let unpacked_dir = tmp_dir.path().join(format!("unpacked{}", upd.index));
+                let claimed = top_level_dirs(&unpacked_dir).and_then(|dirs| {
+                    if let Some(dir) = dirs.iter().find(|dir| owners.contains_key(*dir)) {
+                        return Err(GruntError::DirConflict {
+                            dir: dir.clone(),
+                            owner: owners[dir].clone(),
+                        });
+                    }
+                    owners.extend(dirs.into_iter().map(|dir| (dir, addon.name().clone())));
+                    Ok(())
+                });
+                match claimed {
+                    Ok(()) => checked.push(upd),
+                    Err(err) => {
+                        failed.push((upd.index, err.for_addon(addon.name(), addon.addon_type())))
+                    }
+                }
+            }
+            let outdated = checked;
+
+            // Replace each addon's files separately so one failing doesn't stop the rest
+            let mut installed = Vec::new();
+            for upd in outdated.into_iter() {
+                let unpacked_dir = tmp_dir.path().join(format!("unpacked{}", upd.index));
+                let addon = &self.addons[upd.index];
+                let install_start = Instant::now();
+                let replaced = fault::check_addon(addon.name())
+                    .map_err(GruntError::from)
+                    .and_then(|_| {
+                        self.replace_dirs(
+                            &self.addon_dir(addon),
+                            addon.dirs(),
+                            &unpacked_dir,
+                            guard,
+                        )
+                    });
+                phases.install += install_start.elapsed();
+                match replaced {
+                    Ok(_) if guard.is_dry_run() => installed.push(upd.name),
+                    Ok(new_dirs) => {
+                        renames.extend(
+                            rename::find_renames(addon.dirs(), &new_dirs)
+                                .into_iter()
+                                .map(|(from, to)| DirRename {
+                                    addon: addon.name().clone(),
+                                    from,
+                                    to,
+                                }),
+                        );
+                        // Update addon data including updating the dirs
+                        let hash_start = Instant::now();
+                        let fingerprints = fingerprint_dirs(&self.addon_dir(addon), &new_dirs);
+                        phases.hashing += hash_start.elapsed();
+                        // Listed from the archive so files kept with `keep_extra` aren't included
+                        let files = diff::list_files(&unpacked_dir, &new_dirs);
+                        let addon = self.addons.get_mut(upd.index).unwrap();
+                        self.history.record_update(AppliedUpdate {
+                            time: chrono::Utc::now(),
+                            name: addon.name().clone(),
+                            identifier: addon.identifier(),
+                            from: addon.version().clone(),
+                            to: upd.new_version.clone(),
+                        });
+                        addon.set_fingerprints(fingerprints);
+                        addon.set_files(files.into_iter().collect());
+                        addon.set_dirs(new_dirs);
+                        addon.set_version(upd.new_version);
+                        self.stats.updated += 1;
+                        any_installed = true;
+                        report(UpdateProgress::Installed {
+                            name: upd.name.clone(),
+                        });
+                        installed.push(upd.name);
+                        succeeded.push(upd.index);
+                    }
+                    Err(err) => {
+                        failed.push((upd.index, err.for_addon(addon.name(), addon.addon_type())))
+                    }
+                }
+            }
+            if wave == 0 && has_priority {
+                report(UpdateProgress::PriorityInstalled {
+                    names: installed,
+                    remaining,
+                });
+            }
+        }
+        phases.download = downloads.iter().map(|download| download.duration).sum();
+        phases.extract = downloads
+            .iter()
+            .map(|download| download.extract_duration)
+            .sum();
+
+        if guard.is_dry_run() {
+            errors.extend(failed.into_iter().map(|(_, err)| err));
+            if !errors.is_empty() {
+                return Err(GruntError::Partial(errors));
+            }
+            return Ok(Metrics {
+                duration: start.elapsed(),
+                downloads,
+                phases,
+                ..Default::default()
+            });
+        }
+        for index in succeeded {
+            self.addons[index].set_failures(None);
+        }
+        for (index, err) in failed {
+            let addon = &mut self.addons[index];
+            let count = addon
+                .failures()
+                .as_ref()
+                .map_or(0, |failures| failures.count);
+            addon.set_failures(Some(UpdateFailures {
+                count: count + 1,
+                last_error: err.root().to_string(),
+            }));
+            errors.push(err);
+        }
+        // Only sized when something changed since it walks every addon dir
+        if any_installed {
+            self.history
+                .record_disk_usage(chrono::Utc::now(), self.disk_usage());
+        }
+        // Noted before returning partial failures too, since the rest are saved
+        self.note_changes();
+        // Dirs the game tracks by name keep their enabled state when renamed
+        // Failing here is partial since the new files are already in place
+        if let Err(err) = rename::migrate_addons_txt(&self.root_dir, &renames) {
+            errors.push(err.into());
+        }
+        if !errors.is_empty() {
+            return Err(GruntError::Partial(errors));
+        }
+        Ok(Metrics {
+            duration: start.elapsed(),
+            downloads,
+            renames,
+            moved_projects,
+            skipped_modified,
+            phases,
+            ..Default::default()
+        })
+    }
+
+    /// Downloads an addon archive to `path` with `download`, or copies it from the
+    /// shared cache if another `AddOns` dir already downloaded the same version
+    fn fetch_archive<F>(
+        &self,
+        identifier: &str,
+        version: &str,
+        path: &Path,
+        download: F,
+    ) -> Result<(), GruntError>
+    where
+        F: FnOnce() -> Result<(), GruntError>,
+    {
+        let cached = self
+            .shared_cache
+            .as_ref()
+            .map(|shared| shared.archive_path(identifier, version));
+        if let Some(cached) = cached.as_ref().filter(|cached| cached.exists()) {
+            std::fs::copy(cached, path)?;
+            return Ok(());
+        }
+        download()?;
+        if let Some(cached) = cached {
+            // Not saving it only means downloading it again
+            let _ = std::fs::copy(path, cached);
+        }
+        Ok(())
+    }
+
+    /// Deletes `old_dirs` from `addon_dir` and copies in the dirs inside `unpacked_dir`
+    /// Files the new version doesn't ship are removed with the old dirs, unless
+    /// `keep_extra` is set
+    /// New dirs keep the owner and permissions of the dirs they replace, including
+    /// ones that were renamed
+    fn replace_dirs(
+        &self,
+        addon_dir: &Path,
+        old_dirs: &[String],
+        unpacked_dir: &Path,
+        guard: &mut WriteGuard,
+    ) -> Result<Vec<String>, GruntError> {
+        let new_dirs = read_dir_names(unpacked_dir)?;
+        let renames: HashMap<String, String> = rename::find_renames(old_dirs, &new_dirs)
+            .into_iter()
+            .map(|(from, to)| (to, from))
+            .collect();
+        if self.keep_extra {
+            for dir in new_dirs.iter() {
+                let old_dir = renames.get(dir).unwrap_or(dir);
+                if old_dirs.contains(old_dir) {
+                    copy_missing_files(&addon_dir.join(old_dir), &unpacked_dir.join(dir))?;
+                }
+            }
+        }
+        let mut ownerships = HashMap::new();
+        for dir in old_dirs {
+            let path =
+                wine::find_ignore_case(addon_dir, dir).unwrap_or_else(|| addon_dir.join(dir));
+            if let Some(ownership) = Ownership::of(&path) {
+                ownerships.insert(dir.clone(), ownership);
+            }
+            if path.exists() {
+                guard.remove_dir_all(&path)?;
+            }
+        }
+        fault::after_delete();
+        let paths = new_dirs.iter().map(|dir| addon_dir.join(dir)).collect();
+        guard.write(paths, || -> Result<(), GruntError> {
+            std::fs::create_dir_all(addon_dir)?;
+            copy_dir_contents(unpacked_dir, addon_dir)?;
+            for dir in new_dirs.iter() {
+                let path = addon_dir.join(dir);
+                let old_dir = renames.get(dir).unwrap_or(dir);
+                if let Some(ownership) = ownerships.get(old_dir) {
+                    ownership.apply(&path);
+                }
+                if let Some(gid) = self.file_group {
+                    perms::apply_group(&path, gid);
+                }
+            }
+            Ok(())
+        })?;
+        Ok(new_dirs)
+    }
+
+    /// Checks for updates without installing anything
+    /// Addons and sources that couldn't be checked are listed in the plan's warnings,
+    /// without counting as failures
+    pub fn plan_updates(
+        &mut self,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> UpdatePlan {
+        let check = self.check_updates(tsm_email, tsm_pass);
+        let warnings = check
+            .errors()
+            .map(|err| match err.root() {
+                root if std::ptr::eq(root, err) => err.to_string(),
+                root => format!("{}: {}", err, root),
+            })
+            .collect();
+        let mut plan = self.make_plan(check.outdated);
+        plan.warnings = warnings;
+        plan
+    }
+
+    /// Goes through a whole update without changing the `AddOns` dir, downloading the
+    /// updates and checking their dirs don't clash with other addons
+    /// The plan lists the dirs that would be deleted and written
+    pub fn dry_run_updates(
+        &mut self,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> Result<DryRun, GruntError> {
+        let mut guard = WriteGuard::dry_run();
+        let mut outdated = Vec::new();
+        let result = self.run_updates(
+            |upds| {
+                outdated = upds.clone();
+                upds
+            },
+            |_| (),
+            tsm_email,
+            tsm_pass,
+            &mut guard,
+        );
+        let errors = match result {
+            Ok(_) => Vec::new(),
+            Err(GruntError::Partial(errors)) => errors,
+            Err(err) => return Err(err),
+        };
+        let mut plan = self.make_plan(outdated);
+        let to_strings = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths
+                .iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect()
+        };
+        plan.deleted_dirs = to_strings(guard.deleted);
+        plan.written_dirs = to_strings(guard.written);
+        Ok(DryRun { plan, errors })
+    }
+
+    fn make_plan(&self, outdated: Vec<Updateable>) -> UpdatePlan {
+        let updates = outdated
+            .into_iter()
+            .map(|upd| {
+                let addon = &self.addons[upd.index];
+                PlannedUpdate {
+                    name: upd.name,
+                    identifier: addon.identifier(),
+                    current_version: addon.version().clone(),
+                    new_version: upd.new_version,
+                    url: upd.url,
+                }
+            })
+            .collect();
+        let paused = self
+            .addons
+            .iter()
+            .filter(|addon| self.is_paused(addon))
+            .filter_map(|addon| {
+                let failures = addon.failures().as_ref()?;
+                Some(PausedAddon {
+                    name: addon.name().clone(),
+                    identifier: addon.identifier(),
+                    failures: failures.count,
+                    last_error: failures.last_error.clone(),
+                })
+            })
+            .collect();
+        UpdatePlan {
+            created: chrono::Local::now(),
+            root_dir: self.root_dir.to_string_lossy().to_string(),
+            updates,
+            paused,
+            warnings: Vec::new(),
+            deleted_dirs: Vec::new(),
+            written_dirs: Vec::new(),
+        }
+    }
+
+    /// Installs exactly the updates in a plan
+    /// Nothing is installed if any addon or upstream version changed since it was made
+    pub fn apply_plan(
+        &mut self,
+        plan: &UpdatePlan,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> Result<Metrics, GruntError> {
+        let current: Vec<(String, String)> = self
+            .addons
+            .iter()
+            .map(|addon| (addon.identifier(), addon.version().clone()))
+            .collect();
+        let mut changed = Vec::new();
+        let metrics = self.update_addons(
+            |outdated| match plan.pick(&current, &outdated) {
+                Ok(picked) => outdated
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| picked.contains(index))
+                    .map(|(_, upd)| upd)
+                    .collect(),
+                Err(names) => {
+                    changed = names;
+                    Vec::new()
+                }
+            },
+            tsm_email,
+            tsm_pass,
+        )?;
+        if !changed.is_empty() {
+            return Err(GruntError::PlanChanged(changed));
+        }
+        Ok(metrics)
+    }
+
+    /// Searches Curse for projects with files for the flavor of the `AddOns` dir
+    /// Most downloaded first
+    pub fn search(&self, query: &str) -> Result<Vec<SearchResult>, GruntError> {
+        self.check_source(AddonType::Curse)?;
+        let mut results: Vec<SearchResult> = self
+            .curse_api
+            .search(query)?
+            .into_iter()
+            .filter(|info| {
+                info.latest_files
+                    .iter()
+                    .any(|file| file.game_version_flavor == self.flavor.curse_name())
+            })
+            .map(|info| {
+                let id = info.id.to_string();
+                SearchResult {
+                    installed: self.addons.iter().any(|addon| {
+                        addon.addon_type() == &AddonType::Curse && addon.addon_id() == &id
+                    }),
+                    identifier: format!("curse:{}", info.slug),
+                    id: info.id,
+                    name: info.name,
+                    summary: info.summary,
+                    downloads: info.download_count as u64,
+                }
+            })
+            .collect();
+        results.sort_by_key(|result| std::cmp::Reverse(result.downloads));
+        Ok(results)
+    }
+
+    /// Installs addons given by any identifier `find_install` takes, downloading them
+    /// at the same time. Each result is paired with its identifier
+    /// Addons that are already installed, or given twice, fail with `AlreadyInstalled`
+    /// They're installed in `install_root` instead of `AddOns` if given
+    pub fn install_many(
+        &mut self,
+        queries: &[&str],
+        install_root: Option<&str>,
+    ) -> Result<Vec<InstallResult>, GruntError> {
+        let found = queries
+            .iter()
+            .map(|query| self.find_install(query))
+            .collect();
+        let results = self.install_found(found, install_root)?;
+        Ok(queries
+            .iter()
+            .map(|query| query.to_string())
+            .zip(results)
+            .collect())
+    }
+
+    /// Installs the latest file for the configured flavor of a Curse project given its ID,
+    /// slug with or without `curse:` or CurseForge URL
+    /// It's installed in `install_root` instead of `AddOns` if given
+    /// Returns the name of the new addon
+    pub fn install_curse(
+        &mut self,
+        id_or_url: &str,
+        install_root: Option<&str>,
+    ) -> Result<String, GruntError> {
+        let found = vec![self.find_curse(id_or_url)];
+        self.install_found(found, install_root)?.remove(0)
+    }
+
+    /// Installs the latest version of an addon from the custom source `name`
+    /// It's installed in `install_root` instead of `AddOns` if given
+    pub fn install_custom(
+        &mut self,
+        name: &str,
+        install_root: Option<&str>,
+    ) -> Result<(), GruntError> {
+        let found = vec![self.find_custom(name)];
+        self.install_found(found, install_root)?
+            .remove(0)
+            .map(|_| ())
+    }
+
+    /// Installs the zip archive at `url`, tracked by its `Last-Modified` date
+    /// It's installed in `install_root` instead of `AddOns` if given
+    /// Returns the name of the new addon
+    pub fn install_url(
+        &mut self,
+        url: &str,
+        install_root: Option<&str>,
+    ) -> Result<String, GruntError> {
+        let found = vec![self.find_url(url)];
+        self.install_found(found, install_root)?.remove(0)
+    }
+
+    /// Installs the zip archive of the latest release of the GitHub repository `repo`,
+    /// like `WeakAuras/WeakAuras2`, tracked by its tag
+    /// It's installed in `install_root` instead of `AddOns` if given
+    /// Returns the name of the new addon
+    pub fn install_github(
+        &mut self,
+        repo: &str,
+        install_root: Option<&str>,
+    ) -> Result<String, GruntError> {
+        let found = vec![self.find_github(repo)];
+        self.install_found(found, install_root)?.remove(0)
+    }
+
+    /// Finds what to install for a Curse project ID, slug with or without `curse:` or
+    /// CurseForge URL, `tukui:<id>`, `custom:<name>`, `github:<owner>/<repo>` or the URL of
+    /// a zip archive
+    fn find_install(&self, query: &str) -> Result<PendingInstall, GruntError> {
+        if let Some(name) = query.strip_prefix("custom:") {
+            return self.find_custom(name);
+        }
+        if let Some(id) = query.strip_prefix("tukui:") {
+            return self.find_tukui(id);
+        }
+        if let Some(url) = query.strip_prefix("url:") {
+            return self.find_url(url);
+        }
+        if let Some(repo) = query.strip_prefix("github:") {
+            return self.find_github(repo);
+        }
+        let is_url = query.starts_with("https://") || query.starts_with("http://");
+        match query.split_once(':') {
+            _ if is_url && curse::parse_project_url(query).is_none() => self.find_url(query),
+            Some((prefix, _)) if !is_url && prefix != "curse" => {
+                Err(GruntError::UnknownSource(prefix.to_string()))
+            }
+            _ => self.find_curse(query),
+        }
+    }
+
+    fn find_curse(&self, id_or_url: &str) -> Result<PendingInstall, GruntError> {
+        self.check_source(AddonType::Curse)?;
+        // Find the project
+        let query = id_or_url.strip_prefix("curse:");
+        let info = match query.unwrap_or(id_or_url).parse::<i64>() {
+            Ok(id) => self.curse_api.get_addon_info(id)?,
+            Err(_) => {
+                let slug = match query {
+                    Some(slug) => slug.to_string(),
+                    None if id_or_url.contains("://") => curse::parse_project_url(id_or_url)
+                        .ok_or_else(|| GruntError::NotFound(id_or_url.to_string()))?,
+                    // A bare slug, like `deadly-boss-mods`
+                    None => id_or_url.to_string(),
+                };
+                self.curse_api.find_addon_by_slug(&slug)?
+            }
+        };
+        let latest = info
+            .latest_files
+            .iter()
+            .filter(|file| file.game_version_flavor == self.flavor.curse_name())
+            .max_by_key(|file| file.id)
+            .ok_or_else(|| {
+                GruntError::NotFound(format!("{} file for {}", self.flavor.name(), info.name))
+            })?;
+        let (id, slug, file_id) = (info.id, info.slug.clone(), latest.id);
+        let main_dir = latest
+            .modules
+            .last()
+            .map(|module| module.foldername.clone());
+        Ok(PendingInstall {
+            name: info.name.clone(),
+            addon_type: AddonType::Curse,
+            addon_id: id.to_string(),
+            identifier: format!("curse:{}", slug),
+            version: file_id.to_string(),
+            url: latest.download_url.clone(),
+            into_addon: Box::new(move |dirs| {
+                // Name the addon after its main directory
+                let name = main_dir
+                    .filter(|name| dirs.contains(name))
+                    .unwrap_or_else(|| dirs[0].clone());
+                Addon::from_curse_file(name, id, Some(slug), file_id, dirs)
+            }),
+        })
+    }
+
+    /// Finds a Tukui addon by its id, or ElvUI by `elvui`
+    fn find_tukui(&self, id: &str) -> Result<PendingInstall, GruntError> {
+        self.check_source(AddonType::Tukui)?;
+        let (id, name, version, url) = if id == "-2" || id.eq_ignore_ascii_case("elvui") {
+            let info = tukui::get_elvui_info(&self.net_options)?;
+            (-2, info.name, info.version, info.url)
+        } else {
+            let info = tukui::get_addon_infos(self.flavor, &self.net_options)?
+                .into_iter()
+                .find(|info| info.id == id)
+                .ok_or_else(|| GruntError::NotFound(format!("tukui:{}", id)))?;
+            let id = info
+                .id
+                .parse::<i64>()
+                .map_err(|_| GruntError::NotFound(format!("tukui:{}", id)))?;
+            (id, info.name, info.version, info.url)
+        };
+        Ok(PendingInstall {
+            name: name.clone(),
+            addon_type: AddonType::Tukui,
+            addon_id: id.to_string(),
+            identifier: format!("tukui:{}", id),
+            version: version.clone(),
+            url,
+            into_addon: Box::new(move |dirs| Addon::from_tukui_info(name, id, dirs, version)),
+        })
+    }
+
+    fn find_custom(&self, name: &str) -> Result<PendingInstall, GruntError> {
+        self.check_source(AddonType::Custom)?;
+        let source = self
+            .custom_sources
+            .get(name)
+            .ok_or_else(|| GruntError::NotFound(format!("Custom source {}", name)))?;
+        self.check_not_installed(&format!("custom:{}", name))?;
+        let version = source
+            .latest_version(name, &self.net_options)
+            .map_err(|err| err.for_addon(name, &AddonType::Custom))?;
+        let addon_name = name.to_string();
+        Ok(PendingInstall {
+            name: name.to_string(),
+            addon_type: AddonType::Custom,
+            addon_id: name.to_string(),
+            identifier: format!("custom:{}", name),
+            url: source.download_url(&version),
+            version: version.clone(),
+            into_addon: Box::new(move |dirs| Addon::from_custom(addon_name, dirs, version)),
+        })
+    }
+
+    fn find_url(&self, url: &str) -> Result<PendingInstall, GruntError> {
+        self.check_source(AddonType::Url)?;
+        self.check_not_installed(&format!("url:{}", url))?;
+        let version = net::last_modified(url, &self.net_options)
+            .map_err(|err| err.for_addon(url, &AddonType::Url))?
+            .unwrap_or_default();
+        let addon_url = url.to_string();
+        Ok(PendingInstall {
+            name: url.to_string(),
+            addon_type: AddonType::Url,
+            addon_id: url.to_string(),
+            identifier: format!("url:{}", url),
+            version: version.clone(),
+            url: url.to_string(),
+            into_addon: Box::new(move |dirs| Addon::from_url(addon_url, dirs, version)),
+        })
+    }
+
+    fn find_github(&self, repo: &str) -> Result<PendingInstall, GruntError> {
+        self.check_source(AddonType::GitHub)?;
+        self.check_not_installed(&format!("github:{}", repo))?;
+        let (version, url) = github::latest_release(repo, &self.net_options)
+            .map_err(|err| err.for_addon(repo, &AddonType::GitHub))?;
+        let addon_repo = repo.to_string();
+        Ok(PendingInstall {
+            name: repo.to_string(),
+            addon_type: AddonType::GitHub,
+            addon_id: repo.to_string(),
+            identifier: format!("github:{}", repo),
+            version: version.clone(),
+            url,
+            into_addon: Box::new(move |dirs| Addon::from_github(addon_repo, dirs, version)),
+        })
+    }
+
+    /// Fails with `AlreadyInstalled` if an addon with the key is tracked, before anything
+    /// is looked up for sources whose key is known from the identifier
+    fn check_not_installed(&self, key: &str) -> Result<(), GruntError> {
+        match self.addons.iter().find(|addon| addon.key() == key) {
+            Some(addon) => Err(GruntError::AlreadyInstalled(addon.name().clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Downloads the addons that were found at the same time, then installs them in order
+    /// Addons already tracked or found twice aren't downloaded
+    fn install_found(
+        &mut self,
+        found: Vec<Result<PendingInstall, GruntError>>,
+        install_root: Option<&str>,
+    ) -> Result<Vec<Result<String, GruntError>>, GruntError> {
+        let install_dir = self.install_dir(install_root)?;
+        let mut results: Vec<Option<Result<String, GruntError>>> = Vec::new();
+        let mut pending: Vec<(usize, PendingInstall)> = Vec::new();
+        for (index, found) in found.into_iter().enumerate() {
+            let found = found.and_then(|found| {
+                let key = found.key();
+                let installed = self
+                    .addons
+                    .iter()
+                    .find(|addon| addon.key() == key)
+                    .map(|addon| addon.name().clone())
+                    .or_else(|| {
+                        let mut earlier = pending.iter().map(|(_, other)| other);
+                        earlier
+                            .find(|other| other.key() == key)
+                            .map(|other| other.name.clone())
+                    });
+                match installed {
+                    Some(name) => Err(GruntError::AlreadyInstalled(name)),
+                    None => Ok(found),
+                }
+            });
+            match found {
+                Ok(found) => {
+                    pending.push((index, found));
+                    results.push(None);
+                }
+                Err(err) => results.push(Some(Err(err))),
+            }
+        }
+        let downloads: Vec<Result<tempfile::TempDir, GruntError>> = pending
+            .par_iter()
+            .map(|(_, found)| self.download_install(found))
+            .collect();
+        for ((index, found), download) in pending.into_iter().zip(downloads) {
+            let (name, addon_type) = (found.name.clone(), found.addon_type.clone());
+            let result = download.and_then(|tmp_dir| {
+                let unzip_dir = tmp_dir.path().join("unpacked");
+                self.finish_install(found, &unzip_dir, &install_dir, install_root)
+            });
+            results[index] = Some(result.map_err(|err| err.for_addon(&name, &addon_type)));
+        }
+        Ok(results.into_iter().flatten().collect())
+    }
+
+    /// Logs in to TSM, returning the API and its status with the available addons
+    fn tsm_login(
+        &self,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> Result<(tsm::TSMApi, tsm::StatusRespData), GruntError> {
+        self.check_source(AddonType::TSM)?;
+        let login = self.tsm_credentials(tsm_email, tsm_pass)?;
+        let mut api = tsm::TSMApi::new(&self.net_options);
+        api.sign_in(&login)?;
+        let status = api.get_status()?;
+        Ok((api, status))
+    }
+
+    /// The password if it's set, otherwise the desktop app's session
+    fn tsm_credentials(
+        &self,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> Result<tsm::TsmLogin, GruntError> {
+        match (tsm_email, tsm_pass, &self.tsm_app_config) {
+            (Some(email), Some(pass), _) => Ok(tsm::TsmLogin::Password {
+                email: email.clone(),
+                pass: pass.clone(),
+            }),
+            (_, _, Some(path)) => Ok(tsm::TsmLogin::AppSession(path.clone())),
+            _ => Err(GruntError::MissingLogin(AddonType::TSM)),
+        }
+    }
+
+    /// Addons TSM distributes for the flavor of the `AddOns` dir, like the AppHelper
+    pub fn tsm_addons(
+        &self,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> Result<Vec<TsmAddon>, GruntError> {
+        let (_, status) = self.tsm_login(tsm_email, tsm_pass)?;
+        Ok(status
+            .addons_for(self.flavor)
+            .iter()
+            .map(|data| TsmAddon {
+                name: data.name.clone(),
+                version: data.version_str.clone(),
+                installed: self
+                    .addons
+                    .iter()
+                    .find(|addon| {
+                        addon.addon_type() == &AddonType::TSM && addon.name() == &data.name
+                    })
+                    .map(|addon| addon.version().clone()),
+            })
+            .collect())
+    }
+
+    /// Installs addons TSM distributes by name
+    pub fn install_tsm(
+        &mut self,
+        names: &[String],
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> Result<Vec<InstallResult>, GruntError> {
+        let (api, status) = self.tsm_login(tsm_email, tsm_pass)?;
+        let results = names
+            .iter()
+            .map(|name| {
+                let result = self
+                    .install_tsm_addon(&api, &status, name)
+                    .map_err(|err| err.for_addon(name, &AddonType::TSM));
+                (name.clone(), result)
+            })
+            .collect();
+        Ok(results)
+    }
+
+    fn install_tsm_addon(
+        &mut self,
+        api: &tsm::TSMApi,
+        status: &tsm::StatusRespData,
+        name: &str,
+    ) -> Result<String, GruntError> {
+        let data = status
+            .addons_for(self.flavor)
+            .iter()
+            .find(|data| data.name == name)
+            .ok_or_else(|| GruntError::NotFound(format!("TSM addon {}", name)))?;
+        let (name, version) = (data.name.clone(), data.version_str.clone());
+        let addon = Addon::from_tsm(name.clone(), version.clone(), Vec::new());
+        if let Some(installed) = self.get_addon(&addon.key()) {
+            return Err(GruntError::AlreadyInstalled(installed.name().clone()));
+        }
+        let found = PendingInstall {
+            name: name.clone(),
+            addon_type: AddonType::TSM,
+            addon_id: addon.addon_id().clone(),
+            identifier: addon.identifier(),
+            version: version.clone(),
+            url: "tsm".to_string(),
+            into_addon: Box::new(move |dirs| Addon::from_tsm(name, version, dirs)),
+        };
+        let tmp_dir = tempfile::Builder::new().prefix("grunt").tempdir()?;
+        let download_loc = tmp_dir.path().join("install.download");
+        let unzip_dir = tmp_dir.path().join("unpacked");
+        api.addon(&found.name, &download_loc)?;
+        let excludes = self.extract_excludes.patterns(&found.identifier)?;
+        extract_zip(&download_loc, &unzip_dir, &excludes, &self.extract_limits)?;
+        let root_dir = self.root_dir.clone();
+        self.finish_install(found, &unzip_dir, &root_dir, None)
+    }
+
+    /// Downloads and unpacks an addon into the `unpacked` dir of a new temporary dir
+    fn download_install(&self, found: &PendingInstall) -> Result<tempfile::TempDir, GruntError> {
+        let tmp_dir = tempfile::Builder::new().prefix("grunt").tempdir()?;
+        let download_loc = tmp_dir.path().join("install.download");
+        let unzip_dir = tmp_dir.path().join("unpacked");
+        let throttle = self.max_bandwidth.map(Throttle::new);
+        self.fetch_archive(&found.identifier, &found.version, &download_loc, || {
+            download_file(
+                &self.net_options.client(),
+                &self.net_options,
+                &found.url,
+                &download_loc,
+                throttle.as_ref(),
+                None,
+            )
+        })?;
+        let excludes = self.extract_excludes.patterns(&found.identifier)?;
+        extract_zip(&download_loc, &unzip_dir, &excludes, &self.extract_limits)?;
+        Ok(tmp_dir)
+    }
+
+    /// Moves the dirs of an unpacked addon into `install_dir` and tracks it
+    /// Fails if a dir is owned by another addon installed in the same root
+    /// Returns the name of the new addon
+    fn finish_install(
+        &mut self,
+        found: PendingInstall,
+        unzip_dir: &Path,
+        install_dir: &Path,
+        install_root: Option<&str>,
+    ) -> Result<String, GruntError> {
+        let dirs = read_dir_names(unzip_dir)?;
+        if dirs.is_empty() {
+            return Err(GruntError::InvalidArchive("no addon dirs".to_string()));
+        }
+        // Don't overwrite directories owned by other addons
+        for dir in dirs.iter() {
+            if let Some(owner) = self.addons.iter().find(|addon| {
+                addon.install_root().as_deref() == install_root && addon.dirs().contains(dir)
+            }) {
+                return Err(GruntError::DirConflict {
+                    dir: dir.clone(),
+                    owner: owner.name().clone(),
+                });
+            }
+        }
+        fault::check_addon(&found.name)?;
+        for dir in dirs.iter() {
+            if let Some(path) = wine::find_ignore_case(install_dir, dir) {
+                std::fs::remove_dir_all(path)?;
+            }
+        }
+        fault::after_delete();
+        std::fs::create_dir_all(install_dir)?;
+        copy_dir_contents(unzip_dir, install_dir)?;
+
+        let fingerprints = fingerprint_dirs(install_dir, &dirs);
+        let files = diff::list_files(install_dir, &dirs);
+        let mut addon = (found.into_addon)(dirs);
+        addon.set_install_root(install_root.map(str::to_string));
+        addon.set_fingerprints(fingerprints);
+        addon.set_files(files.into_iter().collect());
+        let name = addon.name().clone();
+        self.addons.push(addon);
+        self.stats.installed += 1;
+        self.note_changes();
+        Ok(name)
+    }
+
+    /// The dir an addon's dirs are in, `AddOns` unless it has an install root
+    pub fn addon_dir(&self, addon: &Addon) -> PathBuf {
+        match (addon.install_root(), self.wow_dir()) {
+            (Some(install_root), Some(wow_dir)) => wow_dir.join(install_root),
+            _ => self.root_dir.clone(),
+        }
+    }
+
+    /// Checks an install root stays inside the WoW dir and returns its path
+    pub(crate) fn install_dir(&self, install_root: Option<&str>) -> Result<PathBuf, GruntError> {
+        let install_root = match install_root {
+            Some(install_root) => install_root,
+            None => return Ok(self.root_dir.clone()),
+        };
+        let invalid = || GruntError::InvalidInstallRoot(install_root.to_string());
+        let inside = Path::new(install_root)
+            .components()
+            .all(|component| matches!(component, std::path::Component::Normal(_)));
+        if !inside || install_root.is_empty() {
+            return Err(invalid());
+        }
+        Ok(self.wow_dir().ok_or_else(invalid)?.join(install_root))
+    }
+
+    /// The WoW dir of the game flavor, two levels above `AddOns`
+    fn wow_dir(&self) -> Option<&Path> {
+        self.root_dir.parent().and_then(Path::parent)
+    }
+
+    /// Tracks addons read from another addon manager
+    /// Addons that are already tracked or whose dirs are missing or owned by another addon
+    /// are skipped and returned with the reason
+    pub fn import_addons(&mut self, addons: Vec<Addon>) -> Vec<(String, String)> {
+        let mut skipped = Vec::new();
+        for addon in addons {
+            if self.addons.iter().any(|other| other.key() == addon.key()) {
+                skipped.push((addon.name().clone(), "Already tracked".to_string()));
+                continue;
+            }
+            if let Some(dir) = addon
+                .dirs()
+                .iter()
+                .find(|dir| !self.root_dir.join(dir).is_dir())
+            {
+                skipped.push((addon.name().clone(), format!("{} isn't installed", dir)));
+                continue;
+            }
+            let owner = addon.dirs().iter().find_map(|dir| {
+                self.addons
+                    .iter()
+                    .find(|other| other.dirs().contains(dir))
+                    .map(|other| (dir, other))
+            });
+            if let Some((dir, other)) = owner {
+                skipped.push((
+                    addon.name().clone(),
+                    format!("{} is owned by {}", dir, other.name()),
+                ));
+                continue;
+            }
+            self.addons.push(addon);
+        }
+        self.fill_fingerprints();
+        self.note_changes();
+        skipped
+    }
+
+    /// Tracks the Curse addon with the id `from` as the project `to`, noting the move in
+    /// its notes
+    fn move_curse_project(
+        &mut self,
+        from: &str,
+        to: String,
+        slug: Option<String>,
+    ) -> Option<ProjectMove> {
+        let is_curse = |addon: &Addon, id: &str| {
+            addon.addon_type() == &AddonType::Curse && addon.addon_id() == id
+        };
+        // Leave it if the new project is tracked already, `dedupe` can merge them
+        if self.addons.iter().any(|addon| is_curse(addon, &to)) {
+            return None;
+        }
+        let addon = self.addons.iter_mut().find(|addon| is_curse(addon, from))?;
+        let note = format!(
+            "Curse project moved from {} to {} on {}",
+            from,
+            to,
+            chrono::Utc::now().format("%Y-%m-%d")
+        );
+        let notes = match addon.notes() {
+            Some(notes) => format!("{}\n{}", notes, note),
+            None => note,
+        };
+        addon.set_notes(Some(notes));
+        addon.set_addon_id(to.clone());
+        addon.set_slug(slug);
+        self.curse_cache.projects.remove(from);
+        Some(ProjectMove {
+            addon: addon.name().clone(),
+            from: from.to_string(),
+            to,
+        })
+    }
+
+    /// Returns why an addon won't get updates anymore, as of the last update check
+    pub fn deprecation(&self, addon: &Addon) -> Option<Deprecation> {
+        if addon.addon_type() != &AddonType::Curse {
+            return None;
+        }
+        self.curse_cache.projects.get(addon.addon_id())?.deprecation
+    }
+
+    /// Fetches the long description of the addon at `index` as HTML
+    pub fn description(&self, index: usize) -> Result<String, GruntError> {
+        let addon = &self.addons[index];
+        if addon.addon_type() != &AddonType::Curse {
+            return Err(GruntError::NotFound(format!(
+                "Description of {}",
+                addon.name()
+            )));
+        }
+        self.check_source(AddonType::Curse)?;
+        self.curse_api.get_description(curse_id(addon)?)
+    }
+
+    /// Records the installed game version, returning the patch if the game was patched
+    /// since the last run
+    /// Nothing is returned the first time or if `.build.info` can't be read
+    pub fn check_game_patch(&mut self) -> Option<GamePatch> {
+        // `.build.info` is shared by the flavors, above their dirs
+        let install_dir = self.wow_dir()?.parent()?;
+        let new_version = game_build::read_version(install_dir, self.flavor)?;
+        let old_version = self.game_version.replace(new_version.clone())?;
+        let interface = game_build::interface_version(&new_version);
+        // New builds of the same patch are hotfixes that don't affect addons
+        let patched = match (game_build::interface_version(&old_version), interface) {
+            (Some(old), Some(new)) => old != new,
+            _ => old_version != new_version,
+        };
+        if !patched {
+            return None;
+        }
+        let mut supported = Vec::new();
+        let mut outdated = Vec::new();
+        for addon in self.addons.iter() {
+            match (self.toc_interface(addon), interface) {
+                (Some(listed), Some(interface)) if listed >= interface => {
+                    supported.push(addon.name().clone())
+                }
+                (Some(_), Some(_)) => outdated.push(addon.name().clone()),
+                _ => {}
+            }
+        }
+        Some(GamePatch {
+            old_version,
+            new_version,
+            interface,
+            supported,
+            outdated,
+        })
+    }
+
+    /// Highest `## Interface` listed in the `.toc` files of an addon's dirs
+    /// Newer `.toc` files can list one per flavor, separated by commas
+    fn toc_interface(&self, addon: &Addon) -> Option<u32> {
+        addon
+            .dirs()
+            .iter()
+            .filter_map(|dir| {
+                let toc = main_toc(&self.addon_dir(addon), dir);
+                get_toc_field(toc, "Interface")
+            })
+            .flat_map(|value| {
+                value
+                    .split(',')
+                    .filter_map(|part| part.trim().parse::<u32>().ok())
+                    .collect::<Vec<u32>>()
+            })
+            .max()
+    }
+
+    /// Compares the files of the addon at `index` with the archive of its installed
+    /// version, taken from the shared cache or downloaded again
+    pub fn diff_addon(&self, index: usize) -> Result<AddonDiff, GruntError> {
+        let addon = &self.addons[index];
+        let diff = || -> Result<AddonDiff, GruntError> {
+            self.check_source(addon.addon_type().clone())?;
+            let tmp_dir = tempfile::Builder::new().prefix("grunt").tempdir()?;
+            let download_loc = tmp_dir.path().join("installed.download");
+            let unzip_dir = tmp_dir.path().join("unpacked");
+            let identifier = addon.identifier();
+            self.fetch_archive(&identifier, addon.version(), &download_loc, || {
+                let url = self.installed_url(addon)?;
+                download_file(
+                    &self.net_options.client(),
+                    &self.net_options,
+                    &url,
+                    &download_loc,
+                    None,
+                    None,
+                )
+            })?;
+            let excludes = self.extract_excludes.patterns(&identifier)?;
+            extract_zip(&download_loc, &unzip_dir, &excludes, &self.extract_limits)?;
+            // Dirs the archive has but the addon lost count as deleted
+            let mut dirs = addon.dirs().clone();
+            for dir in read_dir_names(&unzip_dir)? {
+                if !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+            Ok(diff::diff_dirs(&self.addon_dir(addon), &unzip_dir, &dirs)?)
+        };
+        diff().map_err(|err| err.for_addon(addon.name(), addon.addon_type()))
+    }
+
+    /// Where the archive of the installed version of an addon can be downloaded from
+    /// Tukui only has the latest version, TSM addons can't be downloaded again
+    fn installed_url(&self, addon: &Addon) -> Result<String, GruntError> {
+        let not_found = || {
+            GruntError::NotFound(format!(
+                "Archive of {} version {}",
+                addon.name(),
+                addon.version()
+            ))
+        };
+        match addon.addon_type() {
+            AddonType::Curse => {
+                let file_id = addon.version().parse().map_err(|_| not_found())?;
+                self.curse_api.get_download_url(curse_id(addon)?, file_id)
+            }
+            AddonType::Tukui if addon.addon_id() == "-2" => {
+                let info = tukui::get_elvui_info(&self.net_options)?;
+                if &info.version == addon.version() {
+                    Ok(info.url)
+                } else {
+                    Err(not_found())
+                }
+            }
+            AddonType::Tukui => tukui::get_addon_infos(self.flavor, &self.net_options)?
+                .into_iter()
+                .find(|info| &info.id == addon.addon_id() && &info.version == addon.version())
+                .map(|info| info.url)
+                .ok_or_else(not_found),
+            AddonType::Custom => self
+                .custom_sources
+                .get(addon.addon_id())
+                .map(|source| source.download_url(addon.version()))
+                .ok_or_else(not_found),
+            // Only the latest file is there
+            AddonType::Url => Ok(addon.addon_id().clone()),
+            AddonType::GitHub => {
+                github::release_url(addon.addon_id(), addon.version(), &self.net_options)
+            }
+            AddonType::TSM => Err(not_found()),
+        }
+    }
+
+    /// Lists the game versions the Curse project of the addon at `index` has files for
+    /// Every known flavor is included, with no versions if it isn't supported
+    pub fn compat(&self, index: usize) -> Result<Vec<FlavorSupport>, GruntError> {
+        let addon = &self.addons[index];
+        if addon.addon_type() != &AddonType::Curse {
+            return Err(GruntError::NotFound(format!(
+                "Flavor info for {}",
+                addon.name()
+            )));
+        }
+        self.check_source(AddonType::Curse)?;
+        let info = self.curse_api.get_addon_info(curse_id(addon)?)?;
+
+        let mut support: Vec<FlavorSupport> = Flavor::ALL
+            .iter()
+            .map(|flavor| FlavorSupport {
+                flavor: flavor.curse_name().to_string(),
+                known: Some(*flavor),
+                game_versions: Vec::new(),
+                latest_file: None,
+            })
+            .collect();
+        let mut latest_ids = HashMap::new();
+        for file in info.game_version_latest_files.iter() {
+            let index = match support
+                .iter()
+                .position(|flavor| flavor.flavor == file.game_version_flavor)
+            {
+                Some(index) => index,
+                None => {
+                    support.push(FlavorSupport {
+                        flavor: file.game_version_flavor.clone(),
+                        known: None,
+                        game_versions: Vec::new(),
+                        latest_file: None,
+                    });
+                    support.len() - 1
+                }
+            };
+            let flavor = &mut support[index];
+            if !flavor.game_versions.contains(&file.game_version) {
+                flavor.game_versions.push(file.game_version.clone());
+            }
+            let latest_id = latest_ids.entry(index).or_insert(file.project_file_id);
+            if file.project_file_id >= *latest_id {
+                *latest_id = file.project_file_id;
+                flavor.latest_file = Some(file.project_file_name.clone());
+            }
+        }
+        Ok(support)
+    }
+
+    /// Collects the license of every addon from its .toc and whether it can be redistributed
+    /// Curse projects are also checked for the author's distribution setting
+    pub fn licenses(&self) -> Result<Vec<License>, GruntError> {
+        let curse_ids: Vec<&String> = self
+            .addons
+            .iter()
+            .filter(|addon| addon.addon_type() == &AddonType::Curse)
+            .map(|addon| addon.addon_id())
+            .collect();
+        // Without Curse only the .toc licenses are used
+        let curse_infos = if curse_ids.is_empty() || self.check_source(AddonType::Curse).is_err() {
+            Vec::new()
+        } else {
+            self.curse_api.get_addons_info(&curse_ids)?
+        };
+
+        Ok(self
+            .addons
+            .iter()
+            .enumerate()
+            .map(|(index, addon)| {
+                let license = addon.dirs().iter().find_map(|dir| {
+                    let toc = main_toc(&self.addon_dir(addon), dir);
+                    get_toc_field(toc, "X-License")
+                });
+                let allowed = curse_infos
+                    .iter()
+                    .filter(|_| addon.addon_type() == &AddonType::Curse)
+                    .find(|info| info.id.to_string() == *addon.addon_id())
+                    .and_then(|info| info.allow_mod_distribution);
+                let redistribution = match allowed {
+                    Some(true) => Redistribution::Allowed,
+                    Some(false) => Redistribution::Forbidden,
+                    None => Redistribution::from_license(license.as_deref()),
+                };
+                License {
+                    index,
+                    license,
+                    redistribution,
+                }
+            })
+            .collect())
+    }
+
+    /// Finds Curse library addons that nothing else in the `AddOns` dir depends on
+    /// Libraries only needed by other unused libraries are included too
+    pub fn unused_libs(&self) -> Result<Vec<usize>, GruntError> {
+        let curse_ids: Vec<&String> = self
+            .addons
+            .iter()
+            .filter(|addon| addon.addon_type() == &AddonType::Curse)
+            .map(|addon| addon.addon_id())
+            .collect();
+        if curse_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.check_source(AddonType::Curse)?;
+        let library_ids: HashSet<String> = self
+            .curse_api
+            .get_addons_info(&curse_ids)?
+            .into_iter()
+            .filter(|info| {
+                info.categories
+                    .iter()
+                    .any(|category| category.name == "Libraries")
+            })
+            .map(|info| info.id.to_string())
+            .collect();
+        let mut libs: Vec<usize> = self
+            .addons
+            .iter()
+            .enumerate()
+            .filter(|(_, addon)| {
+                addon.addon_type() == &AddonType::Curse && library_ids.contains(addon.addon_id())
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        // Dependencies of every dir, including untracked ones
+        let deps: HashMap<String, Vec<String>> = self
+            .root_dir
+            .read_dir()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| {
+                let dir = entry.file_name().to_string_lossy().to_string();
+                (dir, dir_dependencies(&entry.path()))
+            })
+            .collect();
+
+        // Keep libraries that are depended on until nothing changes
+        let mut unused: Vec<usize> = Vec::new();
+        loop {
+            let needed: HashSet<&String> = deps
+                .iter()
+                .filter(|(dir, _)| {
+                    !unused
+                        .iter()
+                        .any(|&index| self.addons[index].dirs().contains(dir))
+                })
+                .flat_map(|(dir, dir_deps)| {
+                    dir_deps
+                        .iter()
+                        .filter(move |dep| !dep.eq_ignore_ascii_case(dir))
+                })
+                .collect();
+            let (newly_unused, used): (Vec<usize>, Vec<usize>) =
+                libs.into_iter().partition(|&index| {
+                    !self.addons[index]
+                        .dirs()
+                        .iter()
+                        .any(|dir| needed.contains(&dir.to_ascii_lowercase()))
+                });
+            libs = used;
+            if newly_unused.is_empty() {
+                break;
+            }
+            unused.extend(newly_unused);
+        }
+        unused.sort_unstable();
+        Ok(unused)
+    }
+
+    /// Check that two addons don't claim the same directory
+    pub fn check_conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+        for (i, addon) in self.addons.iter().enumerate() {
+            for (j, other) in self.addons.iter().enumerate().skip(i + 1) {
+                // Check no match between dirs
+                for dir in addon.dirs() {
+                    if other.dirs().contains(dir) {
+                        let files = addon
+                            .files()
+                            .iter()
+                            .filter(|file| in_dir(file, dir) && other.files().contains(file))
+                            .cloned()
+                            .collect();
+                        let conflict = Conflict {
+                            addon_a_index: i,
+                            addon_b_index: j,
+                            dir: dir.clone(),
+                            files,
+                        };
+                        conflicts.push(conflict);
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Finds groups of addons that are probably the same project tracked more than once,
+    /// e.g. from both Curse and Tukui after an import
+    /// Addons match if they have the same source and id, the same dirs ignoring case,
+    /// or if one's .toc names the Curse or Tukui project of the other
+    pub fn find_duplicates(&self) -> Vec<Vec<usize>> {
+        let mut group_of: Vec<usize> = (0..self.addons.len()).collect();
+        fn root(group_of: &mut [usize], mut index: usize) -> usize {
+            while group_of[index] != index {
+                group_of[index] = group_of[group_of[index]];
+                index = group_of[index];
+            }
+            index
+        }
+        let mut first_with_key: HashMap<String, usize> = HashMap::new();
+        for (index, addon) in self.addons.iter().enumerate() {
+            for key in self.identity_keys(addon) {
+                match first_with_key.get(&key) {
+                    Some(&other) => {
+                        let (a, b) = (root(&mut group_of, index), root(&mut group_of, other));
+                        group_of[a] = b;
+                    }
+                    None => {
+                        first_with_key.insert(key, index);
+                    }
+                }
+            }
+        }
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_index: HashMap<usize, usize> = HashMap::new();
+        for index in 0..self.addons.len() {
+            let group = root(&mut group_of, index);
+            match group_index.get(&group) {
+                Some(&existing) => groups[existing].push(index),
+                None => {
+                    group_index.insert(group, groups.len());
+                    groups.push(vec![index]);
+                }
+            }
+        }
+        groups.retain(|group| group.len() > 1);
+        groups
+    }
+
+    /// Keys that identify the project an addon comes from
+    fn identity_keys(&self, addon: &Addon) -> HashSet<String> {
+        let mut keys = HashSet::new();
+        keys.insert(format!(
+            "{}:{}",
+            addon.addon_type().prefix(),
+            addon.addon_id()
+        ));
+        let mut dirs: Vec<String> = addon.dirs().iter().map(|dir| dir.to_lowercase()).collect();
+        dirs.sort();
+        if !dirs.is_empty() {
+            keys.insert(format!("dirs:{}", dirs.join("/")));
+        }
+        for dir in addon.dirs() {
+            let toc_path = main_toc(&self.addon_dir(addon), dir);
+            let toc = match Toc::from_file(toc_path) {
+                Ok(toc) => toc,
+                Err(_) => continue,
+            };
+            if let Some(id) = toc.field("X-Curse-Project-ID") {
+                keys.insert(format!("curse:{}", id.trim()));
+            }
+            if let Some(id) = toc.field("X-Tukui-ProjectID") {
+                keys.insert(format!("tukui:{}", id.trim()));
+            }
+        }
+        keys
+    }
+
+    /// Merges duplicates of the addon at `keep` into it, without touching any files
+    /// Dirs only the duplicates had are given to the kept addon, as are their notes if
+    /// it has none
+    pub fn merge_duplicates(&mut self, keep: usize, duplicates: &[usize]) {
+        let mut duplicates = duplicates.to_vec();
+        duplicates.retain(|&index| index != keep);
+        duplicates.sort_unstable();
+        for &index in duplicates.iter() {
+            let duplicate = &self.addons[index];
+            let mut dirs = self.addons[keep].dirs().clone();
+            for dir in duplicate.dirs() {
+                if !dirs.iter().any(|kept| kept.eq_ignore_ascii_case(dir)) {
+                    dirs.push(dir.clone());
+                }
+            }
+            let notes = self.addons[keep]
+                .notes()
+                .clone()
+                .or_else(|| duplicate.notes().clone());
+            let kept = &mut self.addons[keep];
+            kept.set_dirs(dirs);
+            kept.set_notes(notes);
+        }
+        for &index in duplicates.iter().rev() {
+            self.addons.remove(index);
+        }
+        self.note_changes();
+    }
+
+    /// Sets the game flavor of the `AddOns` dir
+    /// Cached Curse files are for the old flavor, so they're fetched again
+    pub fn change_flavor(&mut self, flavor: Flavor) {
+        if flavor != self.flavor {
+            self.flavor = flavor;
+            self.curse_cache.projects.clear();
+        }
+    }
+
+    /// Gets the addon with a `source:id` key
+    pub fn get_addon(&self, key: &str) -> Option<&Addon> {
+        self.addons.iter().find(|addon| addon.key() == key)
+    }
+
+    /// Total size of the addons' dirs in bytes
+    pub fn disk_usage(&self) -> u64 {
+        self.addon_rows().iter().map(|row| row.size).sum()
+    }
+
+    /// Collects a report of the last `days` days, checking for releases that aren't
+    /// installed yet and saving the current disk usage
+    /// Errors are of addons and sources that couldn't be checked
+    pub fn report(
+        &mut self,
+        days: i64,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> (Report, Vec<GruntError>) {
+        let end = chrono::Utc::now();
+        let start = end - chrono::Duration::days(days);
+        let check = self.check_updates(tsm_email, tsm_pass);
+        let mut pending: Vec<PendingRelease> = check
+            .outdated
+            .iter()
+            .map(|upd| PendingRelease {
+                name: upd.name.clone(),
+                current_version: self.addons[upd.index].version().clone(),
+                new_version: upd.new_version.clone(),
+            })
+            .collect();
+        pending.sort_by_key(|release| release.name.to_lowercase());
+        let errors = check
+            .unavailable
+            .into_iter()
+            .chain(check.failed.into_iter().map(|(_, err)| err))
+            .collect();
+        self.history.record_disk_usage(end, self.disk_usage());
+        let abandoned = self
+            .addons
+            .iter()
+            .filter_map(|addon| {
+                let deprecation = self.deprecation(addon)?;
+                Some((addon.name().clone(), deprecation.describe().to_string()))
+            })
+            .collect();
+        let report = Report {
+            root_dir: self.root_dir.to_string_lossy().to_string(),
+            start,
+            end,
+            updates: self
+                .history
+                .updates_since(start)
+                .into_iter()
+                .cloned()
+                .collect(),
+            pending,
+            abandoned,
+            disk_usage: self.history.disk_usage_since(start).to_vec(),
+        };
+        (report, errors)
+    }
+
+    /// Collects the on-disk size and last change time of every addon for display
+    pub fn addon_rows(&self) -> Vec<AddonRow> {
+        self.addons
+            .iter()
+            .map(|addon| {
+                let mut size = 0;
+                let mut updated = None;
+                for dir in addon.dirs() {
+                    for entry in walkdir::WalkDir::new(self.addon_dir(addon).join(dir))
+                        .into_iter()
+                        .filter_map(|entry| entry.ok())
+                    {
+                        let metadata = match entry.metadata() {
+                            Ok(metadata) => metadata,
+                            Err(_) => continue,
+                        };
+                        if metadata.is_file() {
+                            size += metadata.len();
+                        }
+                        if let Ok(modified) = metadata.modified() {
+                            updated = updated.max(Some(modified));
+                        }
+                    }
+                }
+                AddonRow {
+                    name: addon.name().clone(),
+                    source: addon.identifier(),
+                    version: addon.version().clone(),
+                    latest: self.latest_version(addon),
+                    size,
+                    updated: updated.map(chrono::DateTime::from),
+                    notes: addon.notes().clone(),
+                    deprecation: self.deprecation(addon),
+                    pinned: *addon.pinned(),
+                }
+            })
+            .collect()
+    }
+
+    /// Newest version of an addon seen by the last update check
+    fn latest_version(&self, addon: &Addon) -> Option<String> {
+        match addon.addon_type() {
+            AddonType::Curse => self
+                .curse_cache
+                .projects
+                .get(addon.addon_id())
+                .map(|project| project.latest_file_id.to_string()),
+            _ => self.latest.get(&addon.key()).cloned(),
+        }
+    }
+
+    /// Finds the index of the addon with a display name or `source:id` identifier
+    /// A key always finds its addon, even if another addon is named the same
+    pub fn find_addon(&self, query: &str) -> Result<usize, GruntError> {
+        if let Some(index) = self.addons.iter().position(|addon| addon.key() == query) {
+            return Ok(index);
+        }
+        let matches: Vec<usize> = self
+            .addons
+            .iter()
+            .enumerate()
+            .filter(|(_, addon)| addon.matches(query))
+            .map(|(index, _)| index)
+            .collect();
+        match matches.len() {
+            0 => Err(GruntError::NotFound(query.to_string())),
+            1 => Ok(matches[0]),
+            _ => Err(GruntError::Ambiguous {
+                query: query.to_string(),
+                matches: matches
+                    .iter()
+                    .map(|&index| self.addons[index].identifier())
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Removes all the addons with the specified names or identifiers
+    /// Nothing is removed if any can't be found
+    pub fn remove_addons(&mut self, queries: &[String]) -> Result<(), GruntError> {
+        let mut indexes = queries
+            .iter()
+            .map(|query| self.find_addon(query))
+            .collect::<Result<Vec<usize>, GruntError>>()?;
+        indexes.sort_unstable();
+        indexes.dedup();
+        for index in indexes.into_iter().rev() {
+            self.remove_addon(index)?;
+        }
+        Ok(())
+    }
+
+    /// Removes an addon by index, deleting its dirs unless another addon also owns them
+    /// The addon is still tracked if deleting a dir fails
+    pub fn remove_addon(&mut self, index: usize) -> Result<(), GruntError> {
+        self.delete_dirs(index, &[index], &mut WriteGuard::new())?;
+        self.addons.remove(index);
+        self.stats.removed += 1;
+        self.note_changes();
+        Ok(())
+    }
+
+    /// Lists the dirs `remove_addons` would delete without removing anything
+    pub fn dry_run_remove(&self, queries: &[String]) -> Result<Vec<PathBuf>, GruntError> {
+        let indexes = queries
+            .iter()
+            .map(|query| self.find_addon(query))
+            .collect::<Result<Vec<usize>, GruntError>>()?;
+        let mut guard = WriteGuard::dry_run();
+        for index in indexes.iter() {
+            self.delete_dirs(*index, &indexes, &mut guard)?;
+        }
+        Ok(guard.deleted)
+    }
+
+    /// Deletes the dirs of an addon that no addon outside `removing` owns
+    fn delete_dirs(
+        &self,
+        index: usize,
+        removing: &[usize],
+        guard: &mut WriteGuard,
+    ) -> Result<(), GruntError> {
+        let addon = &self.addons[index];
+        let addon_dir = self.addon_dir(addon);
+        for dir in addon.dirs() {
+            if self.addons.iter().enumerate().any(|(other_index, other)| {
+                !removing.contains(&other_index)
+                    && other.install_root() == addon.install_root()
+                    && other.dirs().contains(dir)
+            }) {
+                continue;
+            }
+            if let Some(path) = wine::find_ignore_case(&addon_dir, dir) {
+                guard.remove_dir_all(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes an addon, found by name or identifier, the only owner of `dir`
+    /// Returns the conflicts left afterwards
+    pub fn reassign_dir(&mut self, dir: &str, addon: &str) -> Result<Vec<Conflict>, GruntError> {
+        let index = self.find_addon(addon)?;
+        if !self.root_dir.join(dir).is_dir() {
+            return Err(GruntError::MissingDir(dir.to_string()));
+        }
+        self.set_dir_owner(dir, index);
+        Ok(self.check_conflicts())
+    }
+
+    /// Removes saved data and leftover files that are no longer needed
+    /// Temp dirs are only removed once they're a day old so running installs aren't affected
+    pub fn gc(&mut self) -> Result<GcReport, GruntError> {
+        let mut report = GcReport::default();
+
+        // Cached info of projects that aren't installed anymore
+        let curse_ids: HashSet<&String> = self
+            .addons
+            .iter()
+            .filter(|addon| addon.addon_type() == &AddonType::Curse)
+            .map(|addon| addon.addon_id())
+            .collect();
+        let before = self.curse_cache.projects.len();
+        self.curse_cache
+            .projects
+            .retain(|id, _| curse_ids.contains(id));
+        report.cache_entries = before - self.curse_cache.projects.len();
+
+        // Fingerprints of deleted dirs
+        let root_dir = &self.root_dir;
+        let before = self.unresolved.len();
+        self.unresolved.retain(|dir, _| root_dir.join(dir).is_dir());
+        report.fingerprints = before - self.unresolved.len();
+
+        // Temp dirs left by installs that were killed
+        let max_age = Duration::from_secs(24 * 60 * 60);
+        for entry in std::fs::read_dir(std::env::temp_dir())? {
+            let entry = entry?;
+            let is_stale = entry
+                .metadata()
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > max_age);
+            if !entry.file_name().to_string_lossy().starts_with("grunt")
+                || !entry.path().is_dir()
+                || !is_stale
+            {
+                continue;
+            }
+            report.temp_bytes += walkdir::WalkDir::new(entry.path())
+                .into_iter()
+                .filter_map(|entry| entry.ok()?.metadata().ok())
+                .filter(|metadata| metadata.is_file())
+                .map(|metadata| metadata.len())
+                .sum::<u64>();
+            std::fs::remove_dir_all(entry.path())?;
+            report.temp_dirs += 1;
+        }
+
+        // Lockfile left behind after switching storage backends
+        self.save_lockfile()?;
+        let old_lockfile = storage::path(self.storage_backend.other(), &self.root_dir);
+        if old_lockfile.exists() {
+            std::fs::remove_file(&old_lockfile)?;
+            report.old_lockfile = Some(old_lockfile);
+        }
+
+        Ok(report)
+    }
+
+    /// Sets or clears the notes of the addon at `index`
+    pub fn set_notes(&mut self, index: usize, notes: Option<String>) {
+        self.addons[index].set_notes(notes);
+        self.note_changes();
+    }
+
+    /// Checks if an addon failed to update too many times in a row to be retried
+    /// automatically
+    pub fn is_paused(&self, addon: &Addon) -> bool {
+        self.max_update_failures > 0
+            && addon
+                .failures()
+                .as_ref()
+                .is_some_and(|failures| failures.count >= self.max_update_failures)
+    }
+
+    /// Clears the update failures of an addon so it's retried on the next update
+    pub fn clear_failures(&mut self, index: usize) {
+        self.addons[index].set_failures(None);
+        self.note_changes();
+    }
+
+    /// Sets how often the addon at `index` is checked for updates
+    pub fn set_frequency(&mut self, index: usize, frequency: UpdateFrequency) {
+        let addon = &mut self.addons[index];
+        addon.set_frequency(frequency);
+        addon.set_last_checked(None);
+        self.note_changes();
+    }
+
+    /// Sets whether the addon at `index` is kept at its installed version
+    pub fn set_pinned(&mut self, index: usize, pinned: bool) {
+        self.addons[index].set_pinned(pinned);
+        self.note_changes();
+    }
+
+    /// Sets whether the addon at `index` is updated before other addons
+    pub fn set_priority(&mut self, index: usize, priority: bool) {
+        self.addons[index].set_priority(priority);
+        self.note_changes();
+    }
+
+    /// Makes the addon at `index` the only owner of `dir`
+    pub fn set_dir_owner(&mut self, dir: &str, index: usize) {
+        for (i, addon) in self.addons.iter_mut().enumerate() {
+            let has_dir = addon.dirs().iter().any(|d| d == dir);
+            if i == index && !has_dir {
+                let mut dirs = addon.dirs().clone();
+                dirs.push(dir.to_string());
+                addon.set_dirs(dirs);
+            } else if i != index && has_dir {
+                let dirs = addon.dirs().iter().filter(|d| *d != dir).cloned().collect();
+                let files = addon
+                    .files()
+                    .iter()
+                    .filter(|file| !in_dir(file, dir))
+                    .cloned()
+                    .collect();
+                addon.set_dirs(dirs);
+                addon.set_files(files);
+            }
+        }
+        self.note_changes();
+    }
+
+    /// Guesses which of two conflicting addons last wrote the shared dir
+    /// Picks the addon whose other dirs changed closest to when the shared dir did
+    pub fn last_writer(&self, conflict: &Conflict) -> Option<usize> {
+        let shared = last_modified(&self.root_dir.join(&conflict.dir))?;
+        let distance = |index: usize| {
+            self.addons[index]
+                .dirs()
+                .iter()
+                .filter(|dir| *dir != &conflict.dir)
+                .filter_map(|dir| last_modified(&self.root_dir.join(dir)))
+                .max()
+                .map(|time| match shared.duration_since(time) {
+                    Ok(diff) => diff,
+                    Err(err) => err.duration(),
+                })
+        };
+        match (
+            distance(conflict.addon_a_index),
+            distance(conflict.addon_b_index),
+        ) {
+            (Some(a), Some(b)) if a <= b => Some(conflict.addon_a_index),
+            (Some(_), Some(_)) => Some(conflict.addon_b_index),
+            (Some(_), None) => Some(conflict.addon_a_index),
+            (None, Some(_)) => Some(conflict.addon_b_index),
+            (None, None) => None,
+        }
+    }
+
+    /// Downloads and installs the latest version of an addon even if it is up to date
+    pub fn reinstall_addon(
+        &mut self,
+        index: usize,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> Result<Metrics, GruntError> {
+        // Make the addon look outdated then update only it
+        let old_version = self.addons[index].version().clone();
+        let outdated_version = match self.addons[index].addon_type() {
+            AddonType::Curse => "0",
+            AddonType::Tukui
+            | AddonType::TSM
+            | AddonType::Custom
+            | AddonType::Url
+            | AddonType::GitHub => "",
+        };
+        self.addons[index].set_version(outdated_version.to_string());
+        // URLs may not have a version to compare, so they're always downloaded again
+        let force_urls = std::mem::replace(&mut self.force_urls, true);
+        let result = self.update_addons(
+            |outdated| {
+                outdated
+                    .into_iter()
+                    .filter(|upd| upd.index == index)
+                    .collect()
+            },
+            tsm_email,
+            tsm_pass,
+        );
+        self.force_urls = force_urls;
+        if result.is_err() {
+            self.addons[index].set_version(old_version);
+            self.note_changes();
+        }
+        result
+    }
+
+    /// Dirs of an addon that changed since grunt installed them
+    pub fn modified_dirs(&self, addon: &Addon) -> Vec<String> {
+        let addon_dir = self.addon_dir(addon);
+        addon
+            .dirs()
+            .iter()
+            .filter(|dir| {
+                addon.fingerprints().get(*dir).is_some_and(|fingerprint| {
+                    *fingerprint != dir_fingerprint(&addon_dir.join(dir))
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Compares the dirs of an addon with the fingerprints and file list recorded when
+    /// it was installed, to check an AddOns dir that was moved or copied
+    pub fn check_adoption(&self, addon: &Addon) -> Adoption {
+        let addon_dir = self.addon_dir(addon);
+        let mut missing = Vec::new();
+        let mut changed = Vec::new();
+        let mut moved = false;
+        for dir in addon.dirs() {
+            let path = match wine::find_ignore_case(&addon_dir, dir) {
+                Some(path) => path,
+                None => {
+                    missing.push(dir.clone());
+                    continue;
+                }
+            };
+            if addon.fingerprints().get(dir) == Some(&dir_fingerprint(&path)) {
+                continue;
+            }
+            // Copies get new modified times, so the files they have are compared instead
+            // The dir may differ in case from the tracked one
+            let recorded: BTreeSet<String> = addon
+                .files()
+                .iter()
+                .filter(|file| in_dir(file, dir))
+                .map(|file| file.to_lowercase())
+                .collect();
+            let found_name = path.file_name().unwrap().to_string_lossy().to_string();
+            let found: BTreeSet<String> = diff::list_files(&addon_dir, &[found_name])
+                .into_iter()
+                .map(|file| file.to_lowercase())
+                .collect();
+            if !recorded.is_empty() && recorded == found {
+                moved = true;
+            } else {
+                changed.push(dir.clone());
+            }
+        }
+        if !missing.is_empty() {
+            Adoption::Missing(missing)
+        } else if !changed.is_empty() {
+            Adoption::Changed(changed)
+        } else if moved {
+            Adoption::Moved
+        } else {
+            Adoption::Unchanged
+        }
+    }
+
+    /// Stops tracking the addon at `index`, leaving its dirs in place
+    pub fn untrack_addon(&mut self, index: usize) {
+        self.addons.remove(index);
+        self.note_changes();
+    }
+
+    /// Takes the current files of the addon at `index` as installed, so local edits
+    /// are no longer reported
+    pub fn accept_modified(&mut self, index: usize) {
+        let addon = &self.addons[index];
+        let fingerprints = fingerprint_dirs(&self.addon_dir(addon), addon.dirs());
+        self.addons[index].set_fingerprints(fingerprints);
+        self.note_changes();
+    }
+
+    /// Fingerprints dirs that don't have one yet, like ones tracked before fingerprints
+    /// were kept, and forgets dirs the addon no longer owns
+    /// Addons tracked before file lists were kept get the files that are there now
+    fn fill_fingerprints(&mut self) {
+        for index in 0..self.addons.len() {
+            let addon = &self.addons[index];
+            let addon_dir = self.addon_dir(addon);
+            let mut fingerprints = addon.fingerprints().clone();
+            fingerprints.retain(|dir, _| addon.dirs().contains(dir));
+            for dir in addon.dirs() {
+                if !fingerprints.contains_key(dir) {
+                    fingerprints.insert(dir.clone(), dir_fingerprint(&addon_dir.join(dir)));
+                }
+            }
+            let mut files = addon.files().clone();
+            files.retain(|file| addon.dirs().iter().any(|dir| in_dir(file, dir)));
+            if files.is_empty() {
+                files = diff::list_files(&addon_dir, addon.dirs())
+                    .into_iter()
+                    .collect();
+            }
+            if &fingerprints != addon.fingerprints() || &files != addon.files() {
+                let addon = &mut self.addons[index];
+                addon.set_fingerprints(fingerprints);
+                addon.set_files(files);
+            }
+        }
+    }
+
+    /// Returns the changes made to tracked addons since the last call, oldest first
+    /// Frontends can use these to refresh only the addons that changed
+    pub fn take_changes(&mut self) -> Vec<LockfileChanged> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Compares the addons with the last reported ones and queues a `LockfileChanged`
+    /// if any were added, removed or changed
+    fn note_changes(&mut self) {
+        let key = |addon: &Addon| addon.key();
+        let mut change = LockfileChanged::default();
+        for addon in self.addons.iter() {
+            match self.reported.iter().find(|old| key(old) == key(addon)) {
+                None => change.added.push(addon.identifier()),
+                Some(old) if old != addon => change.updated.push(addon.identifier()),
+                Some(_) => (),
+            }
+        }
+        for old in self.reported.iter() {
+            if !self.addons.iter().any(|addon| key(addon) == key(old)) {
+                change.removed.push(old.identifier());
+            }
+        }
+        if change != LockfileChanged::default() {
+            self.changes.push(change);
+            self.reported = self.addons.clone();
+        }
+    }
+
+    /// Deletes top-level directories and their contents if they are untracked
+    /// Nothing is deleted if any of them is tracked
+    pub fn remove_dirs(&self, dirs: Vec<String>) -> Result<(), GruntError> {
+        let untracked = self.find_untracked()?;
+        if let Some(dir) = dirs.iter().find(|dir| !untracked.contains(dir)) {
+            return Err(GruntError::TrackedDir(dir.clone()));
+        }
+        let root = self.root_dir();
+        for dir in dirs {
+            std::fs::remove_dir_all(root.join(dir))?;
+        }
+        Ok(())
+    }
+
+    /// Updates the data in TradeSkillMaster_AppHelper by using the (undocumented) tsm api
+    pub fn update_tsm_data(
+        &self,
+        tsm_email: Option<&String>,
+        tsm_pass: Option<&String>,
+    ) -> Result<(), GruntError> {
+        self.check_source(AddonType::TSM)?;
+        // Get TSM AppHelper addon
+        let addon = self
+            .addons
+            .iter()
+            .find(|a| a.name() == "TradeSkillMaster_AppHelper")
+            .ok_or_else(|| GruntError::NotFound("TradeSkillMaster_AppHelper".to_string()))?;
+
+        // Read current data, dropping lines that can't be parsed since they're rewritten
+        let mut current_data: HashMap<(String, String), (String, u64)> = HashMap::new();
+        let path = self.root_dir.join(addon.name()).join("AppData.lua");
+        let f = File::open(&path)?;
+        for line in BufReader::new(f).lines() {
+            if let Some((key, value)) = parse_app_data(&line?) {
+                current_data.insert(key, value);
+            }
+        }
+
+        // Login to the tsm api
+        let (api, status) = self.tsm_login(tsm_email, tsm_pass)?;
+
+        // Update to latest data
+        let time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let addon_message_str = format!(
+            "{{id={},msg=\"{}\"}}",
+            status.addon_message.id, status.addon_message.msg
+        );
+        let new_data = format!(
+            "{{version={},lastSync={},message={},news={}}}",
+            tsm::APP_VERSION,
+            time,
+            addon_message_str,
+            status.addon_news
+        );
+        current_data.insert(("APP_INFO".into(), "Global".into()), (new_data, time));
+        for region in status.regions {
+            let data = api.auctiondb("region", region.id)?;
+            current_data.insert(
+                ("AUCTIONDB_MARKET_DATA".into(), region.name.clone()),
+                (data, region.last_modified),
+            );
+        }
+        for realm in status.realms {
+            let data = api.auctiondb("realm", realm.master_id)?;
+            current_data.insert(
+                ("AUCTIONDB_MARKET_DATA".into(), realm.name.clone()),
+                (data, realm.last_modified),
+            );
+        }
+
+        // Save
+        let mut f = File::create(&path)?;
+        for ((data_type, data_name), (data, time)) in current_data.iter() {
+            let line = format!(
+                "select(2, ...).LoadData(\"{}\",\"{}\",[[return {}]]) --<{},{},{}>\r\n",
+                data_type, data_name, data, data_type, data_name, time
+            );
+            f.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Gets the rules deciding which files Curse fingerprints
+    /// Falls back to the last fetched rules, then the builtin ones, if `game/1` can't be used
+    fn game_rules(&mut self) -> GameRules {
+        if self.builtin_rules {
+            return GameRules::builtin();
+        }
+        let fetched = self
+            .curse_api
+            .get_game_info(WOW_GAME_ID)
+            .ok()
+            .and_then(|info| GameRules::from_game_info(&info));
+        match fetched {
+            Some(rules) => {
+                self.curse_cache.rules = Some(rules.clone());
+                rules
+            }
+            None => self
+                .curse_cache
+                .rules
+                .clone()
+                .unwrap_or_else(GameRules::builtin),
+        }
+    }
+
+    /// Fingerprints the dirs of Curse addons whose project is gone, tracking the ones
+    /// Curse matches to another project as that project
+    fn find_moved_projects(
+        &mut self,
+        metrics: &mut Metrics,
+    ) -> Result<Vec<ProjectMove>, GruntError> {
+        let gone: Vec<(String, Vec<String>)> = self
+            .addons
+            .iter()
+            .filter(|addon| addon.install_root().is_none())
+            .filter(|addon| {
+                matches!(
+                    self.deprecation(addon),
+                    Some(Deprecation::Unavailable) | Some(Deprecation::Deleted)
+                )
+            })
+            .map(|addon| (addon.addon_id().clone(), addon.dirs().clone()))
+            .collect();
+        if gone.is_empty() {
+            return Ok(Vec::new());
+        }
+        let dirs = gone.iter().flat_map(|(_, dirs)| dirs.clone()).collect();
+        let matches = self.resolve_curse(dirs, metrics)?;
+        let mut moved = Vec::new();
+        for (id, dirs) in gone {
+            let found = matches
+                .iter()
+                .find(|found| dirs.contains(found.name()) && found.addon_id() != &id);
+            if let Some(found) = found {
+                let to = found.addon_id().clone();
+                moved.extend(self.move_curse_project(&id, to, found.slug().clone()));
+            }
+        }
+        Ok(moved)
+    }
+
+    fn resolve_curse(
+        &mut self,
+        untracked: Vec<String>,
+        metrics: &mut Metrics,
+    ) -> Result<Vec<Addon>, GruntError> {
+        let rules_start = Instant::now();
+        let rules = self.game_rules();
+        metrics.phases.metadata += rules_start.elapsed();
+
+        // Compile regexes, using the builtin rules if the fetched ones are invalid
+        let (initial_inclusion_regex, extra_inclusion_regex, file_parsing_regex) =
+            match compile_rules(&rules) {
+                Some(compiled) => compiled,
+                None => compile_rules(&GameRules::builtin()).expect("Builtin rules are invalid"),
+            };
+
+        // Fingerprint each untracked dir
+        let hash_start = Instant::now();
+        let hashed: Vec<(u32, Duration)> = untracked
+            .par_iter() // Easy parallelization
+            .map(|dir_name| -> Result<_, GruntError> {
+                let hash_start = Instant::now();
+                let addon_dir = self.root_dir.join(dir_name);
+                let mut to_fingerprint = HashSet::new();
+                let mut to_parse = VecDeque::new();
+
+                // Add initial files, any with a `.` in their name
+                for entry in walkdir::WalkDir::new(&addon_dir).follow_links(true) {
+                    let entry = entry.map_err(std::io::Error::from)?;
+                    let path = entry.path();
+                    if !path.is_file() || !entry.file_name().to_string_lossy().contains('.') {
+                        continue;
+                    }
+
+                    // Test relative path matches regexes
+                    // Matching that fails from backtracking too much counts as no match
+                    let relative_path = path
+                        .strip_prefix(&self.root_dir)
+                        .unwrap_or(path)
+                        .to_string_lossy()
+                        .to_ascii_lowercase()
+                        .replace("/", "\\"); // Convert to windows seperator
+                    if initial_inclusion_regex
+                        .is_match(&relative_path)
+                        .unwrap_or(false)
+                    {
+                        to_parse.push_back(path.to_path_buf());
+                    } else if extra_inclusion_regex
+                        .is_match(&relative_path)
+                        .unwrap_or(false)
+                    {
+                        to_fingerprint.insert(path.to_path_buf());
+                    }
+                }
+
+                // Parse additional files
+                while let Some(path) = to_parse.pop_front() {
+                    to_fingerprint.insert(path.clone());
+
+                    // Skip if no rules for extension
+                    let ext = format!(
+                        ".{}",
+                        path.extension().unwrap_or_default().to_string_lossy()
+                    );
+                    let (comment_strip_regex, inclusion_regex) = match file_parsing_regex.get(&ext)
+                    {
+                        Some(regexes) => regexes,
+                        None => continue,
+                    };
+
+                    // Parse file for matches
+                    // TODO: Parse line by line because regex is \n sensitive
+                    let text = toc::decode(&std::fs::read(&path)?);
+                    let text = comment_strip_regex.replace_all(&text, "");
+                    for line in text.split(&['\n', '\r'][..]) {
+                        let mut last_offset = 0;
+                        while let Ok(Some(inc_match)) =
+                            inclusion_regex.captures_from_pos(line, last_offset)
+                        {
+                            last_offset = inc_match.get(0).map_or(line.len(), |all| all.end());
+                            let path_match = match inc_match.get(1) {
+                                Some(path_match) => path_match.as_str(),
+                                None => continue,
+                            };
+                            // Path might be case insensitive and have windows separators. Find it
+                            // Files that don't exist are skipped
+                            let path_match = path_match.replace("\\", "/");
+                            let parent = path.parent().unwrap_or(&addon_dir);
+                            match find_file(parent.join(Path::new(&path_match))) {
+                                Some(real_path) if real_path.is_file() => {
+                                    to_parse.push_back(real_path)
+                                }
+                                _ => (),
+                            }
+                        }
+                    }
+                }
+
+                // Calculate fingerprints
+                let mut fingerprints: Vec<u32> = to_fingerprint
+                    .iter()
+                    .map(|path| -> Result<u32, GruntError> {
+                        // Read file, removing whitespace
+                        let data: Vec<u8> = std::fs::read(path)?
+                            .into_iter()
+                            .filter(|&b| b != b' ' && b != b'\n' && b != b'\r' && b != b'\t')
+                            .collect();
+                        Ok(murmur2::calculate_hash(&data, 1))
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                // Calculate overall fingerprint
+                fingerprints.sort();
+                let to_hash = fingerprints
+                    .iter()
+                    .map(|val| val.to_string())
+                    .collect::<Vec<String>>()
+                    .join("");
+                let fingerprint = murmur2::calculate_hash(to_hash.as_bytes(), 1);
+                Ok((fingerprint, hash_start.elapsed()))
+            })
+            .collect::<Result<_, _>>()?;
+        metrics.phases.hashing += hash_start.elapsed();
+        let fingerprints: Vec<u32> = hashed.iter().map(|(fingerprint, _)| *fingerprint).collect();
+        metrics.hash_times = untracked
+            .iter()
+            .cloned()
+            .zip(hashed.into_iter().map(|(_, time)| time))
+            .collect();
+
+        // Query api for fingerprint matches
+        let metadata_start = Instant::now();
+        let results = self.curse_api.fingerprint_search(&fingerprints)?;
+        if results.exact_matches.is_empty() {
+            metrics.phases.metadata += metadata_start.elapsed();
+            return Ok(Vec::new());
+        }
+
+        // Look up slugs for the matched projects
+        let ids: Vec<String> = results
+            .exact_matches
+            .iter()
+            .map(|mat| mat.id.to_string())
+            .collect();
+        let infos = self
+            .curse_api
+            .get_addons_info(&ids.iter().collect::<Vec<&String>>())?;
+        metrics.phases.metadata += metadata_start.elapsed();
+
+        // Matches that don't line up with a fingerprint that was sent are skipped
+        Ok(results
+            .exact_matches
+            .iter()
+            .filter_map(|mat| {
+                // Assumes last module is the main one
+                let main_fingerprint = mat.file.modules.last()?.fingerprint;
+                let index = fingerprints.iter().position(|&x| x == main_fingerprint)?;
+                let name = untracked[index].clone();
+                let mut addon = Addon::from_curse_info(name, mat);
+                let slug = infos
+                    .iter()
+                    .find(|info| info.id == mat.id)
+                    .map(|info| info.slug.clone());
+                addon.set_slug(slug);
+                Some(addon)
+            })
+            .collect())
+    }
+}
+
+impl Lockfile {
+    pub fn from_grunt(grunt: &Grunt) -> Self {
+        let addons = grunt.addons.iter().map(|addon| addon.to_info()).collect();
+        Lockfile {
+            addons,
+            curse_cache: grunt.curse_cache.clone(),
+            unresolved: grunt.unresolved.clone(),
+            stats: grunt.stats.clone(),
+            latest: grunt
+                .latest
+                .iter()
+                .filter(|(key, _)| grunt.addons.iter().any(|addon| &addon.key() == *key))
+                .map(|(key, version)| (key.clone(), version.clone()))
+                .collect(),
+            history: grunt.history.clone(),
+            game_version: grunt.game_version.clone(),
+            flavor: Some(grunt.flavor),
+        }
+    }
+}
+
+pub struct Conflict {
+    pub addon_a_index: usize,
+    pub addon_b_index: usize,
+    pub dir: String,
+    /// Files in the dir that both addons installed
+    /// Empty if they only add their own files to it, like SharedMedia packs, or if
+    /// either was tracked before file lists were kept
+    pub files: Vec<String>,
+}
+
+/// Files a Curse project has for a game flavor
+pub struct FlavorSupport {
+    /// Curse's name for the flavor, e.g. `wow_classic`
+    pub flavor: String,
+    /// `None` if grunt doesn't know the flavor
+    pub known: Option<Flavor>,
+    pub game_versions: Vec<String>,
+    /// Name of the newest file
+    pub latest_file: Option<String>,
+}
+
+/// An untracked dir with addon dirs inside it, found by `Grunt::find_nested`
+#[derive(Debug, PartialEq)]
+pub struct NestedDir {
+    /// Dir in `AddOns`
+    pub dir: String,
+    /// Paths of the addon dirs inside it, relative to `dir` and separated by `/`
+    pub inner: Vec<String>,
+}
+
+/// How the dirs of a tracked addon compare with what grunt installed, see
+/// `Grunt::check_adoption`
+#[derive(Debug, PartialEq)]
+pub enum Adoption {
+    Unchanged,
+    /// The dirs have the installed files but were copied or touched since
+    Moved,
+    /// Dirs with files added or removed, likely from another version of the addon
+    Changed(Vec<String>),
+    /// Dirs that aren't there
+    Missing(Vec<String>),
+}
+
+/// What `Grunt::gc` cleaned up
+#[derive(Default)]
+pub struct GcReport {
+    /// Cached Curse projects that aren't installed
+    pub cache_entries: usize,
+    /// Fingerprints of unresolved dirs that were deleted
+    pub fingerprints: usize,
+    pub temp_dirs: usize,
+    /// Total size of the removed temp dirs
+    pub temp_bytes: u64,
+    /// Lockfile of the storage backend that isn't used
+    pub old_lockfile: Option<PathBuf>,
+}
+
+/// A Curse project found by `search`
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct SearchResult {
+    /// `curse:<slug>` identifier that can be passed to `install_curse`
+    pub identifier: String,
+    /// Curse project ID
+    pub id: i64,
+    pub name: String,
+    pub summary: String,
+    pub downloads: u64,
+    /// Whether the project is already installed
+    pub installed: bool,
+}
+
+/// An addon TSM distributes, found by `tsm_addons`
+pub struct TsmAddon {
+    pub name: String,
+    /// Latest version
+    pub version: String,
+    /// Installed version, if it's installed
+    pub installed: Option<String>,
+}
+
+pub struct License {
+    pub index: usize,
+    /// The `X-License` field of the addon's .toc
+    pub license: Option<String>,
+    pub redistribution: Redistribution,
+}
+
+/// Whether an addon's files can be shared, e.g. in a UI pack
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Redistribution {
+    Allowed,
+    Forbidden,
+    Unknown,
+}
+
+impl Redistribution {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Redistribution::Allowed => "allowed",
+            Redistribution::Forbidden => "forbidden",
+            Redistribution::Unknown => "unknown",
+        }
+    }
+
+    /// Guesses from the name of a license
+    fn from_license(license: Option<&str>) -> Self {
+        let license = match license {
+            Some(license) => license.to_ascii_lowercase(),
+            None => return Redistribution::Unknown,
+        };
+        let open = [
+            "mit",
+            "gpl",
+            "bsd",
+            "apache",
+            "mpl",
+            "cc0",
+            "unlicense",
+            "public domain",
+        ];
+        if license.contains("all rights reserved") {
+            Redistribution::Forbidden
+        } else if open.iter().any(|name| license.contains(name)) {
+            Redistribution::Allowed
+        } else {
+            Redistribution::Unknown
+        }
+    }
+}
+
+/// Addons added to, removed from or changed in the lockfile by an operation, as
+/// `source:id` identifiers
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct LockfileChanged {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub updated: Vec<String>,
+}
+
+/// Result of `Grunt::dry_run_updates`
+pub struct DryRun {
+    pub plan: UpdatePlan,
+    /// Updates that would fail, like ones whose dirs clash with another addon's
+    pub errors: Vec<GruntError>,
+}
+
+/// Result of `Grunt::check_updates`
+pub struct UpdateCheck {
+    /// Addons with a newer version
+    pub outdated: Vec<Updateable>,
+    /// Addons that couldn't be checked, by index
+    pub(crate) failed: Vec<(usize, GruntError)>,
+    /// Sources that are down, whose addons weren't checked
+    pub(crate) unavailable: Vec<GruntError>,
+    /// Whether each addon was due a check and checked
+    pub(crate) due: Vec<bool>,
+    pub(crate) checked_at: chrono::DateTime<chrono::Utc>,
+    pub(crate) skipped_modified: Vec<String>,
+    pub(crate) moved_projects: Vec<ProjectMove>,
+    pub(crate) phases: PhaseTimes,
+    /// Logged in if there are TSM addons, to download their updates
+    pub(crate) tsm_api: tsm::TSMApi,
+}
+
+impl UpdateCheck {
+    /// Errors of addons and sources that couldn't be checked
+    pub fn errors(&self) -> impl Iterator<Item = &GruntError> {
+        self.unavailable
+            .iter()
+            .chain(self.failed.iter().map(|(_, err)| err))
+    }
+}
+
+/// Events of an update, the ones of each addon come from the thread downloading it
+pub enum UpdateProgress {
+    /// The high priority updates were installed, `remaining` updates are still to go
+    PriorityInstalled {
+        names: Vec<String>,
+        remaining: usize,
+    },
+    DownloadStarted {
+        name: String,
+    },
+    /// `bytes` of an update were downloaded, out of `total` if the server said
+    DownloadProgress {
+        name: String,
+        bytes: u64,
+        total: Option<u64>,
+    },
+    /// The update was downloaded and is being unpacked
+    Extracting {
+        name: String,
+    },
+    /// The addon's files were replaced with the new version
+    Installed {
+        name: String,
+    },
+}
+
+pub enum ResolveProgress {
+    NewAddon {
+        name: String,
+        desc: String,
+    },
+    /// `skipped` dirs weren't checked because they're unchanged since they last failed
+    Finished {
+        not_found: Vec<String>,
+        skipped: usize,
+    },
+}
+
+/// Called with the bytes downloaded so far and the total size, if it's known
+type DownloadProgress<'a> = &'a dyn Fn(u64, Option<u64>);
+
+/// Downloads `url` to `path`, limiting the speed if a throttle is given
+/// Failed or stalled downloads are resumed up to `retries` times with a growing wait in
+/// between, then the configured mirror and alternate Curse CDN URLs are tried
+fn download_file(
+    client: &Client,
+    options: &NetOptions,
+    url: &str,
+    path: &Path,
+    throttle: Option<&Throttle>,
+    progress: Option<DownloadProgress>,
+) -> Result<(), GruntError> {
+    let mut result = Ok(());
+    for url in curse::download_urls(url, options.curse_mirror.as_deref()) {
+        result = download_with_retries(client, options, &url, path, throttle, progress);
+        if result.is_ok() {
+            break;
+        }
+    }
+    result
+}
+
+fn download_with_retries(
+    client: &Client,
+    options: &NetOptions,
+    url: &str,
+    path: &Path,
+    throttle: Option<&Throttle>,
+    progress: Option<DownloadProgress>,
+) -> Result<(), GruntError> {
+    // A download of another URL may have been left in the file
+    File::create(path)?;
+    // Retries only resume if the file hasn't changed since the first response
+    let mut validator: Option<HeaderValue> = None;
+    let get = |offset: u64, validator: Option<&HeaderValue>| {
+        let mut request = client.get(url);
+        if offset > 0 {
+            // Ranges count bytes of the encoded body, so ask for it unencoded
+            request = request
+                .header(header::RANGE, format!("bytes={}-", offset))
+                .header(header::ACCEPT_ENCODING, "identity");
+            if let Some(validator) = validator {
+                request = request.header(header::IF_RANGE, validator.clone());
+            }
+        }
+        net::send(client, request).map_err(|source| GruntError::Request {
+            url: url.to_string(),
+            source,
+        })
+    };
+    net::with_retries(options, || {
+        let offset = std::fs::metadata(path)?.len();
+        let mut resp = get(offset, validator.as_ref())?;
+        if resp.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            resp = get(0, None)?;
+        }
+        let resp = error::check_status(resp)?;
+        let resumed = offset > 0 && resp.status() == StatusCode::PARTIAL_CONTENT;
+        let (mut file, start) = if resumed {
+            (OpenOptions::new().append(true).open(path)?, offset)
+        } else {
+            validator = resume_validator(resp.headers());
+            (File::create(path)?, 0)
+        };
+        let total = resp.content_length().map(|len| start + len);
+        let reader: Box<dyn std::io::Read> = match throttle {
+            Some(throttle) => Box::new(ThrottledReader::new(resp, throttle)),
+            None => Box::new(resp),
+        };
+        let reader: Box<dyn std::io::Read> = match progress {
+            Some(progress) => Box::new(ProgressReader {
+                inner: reader,
+                bytes: start,
+                total,
+                progress,
+            }),
+            None => reader,
+        };
+        std::io::copy(&mut fault::reader(reader), &mut file)?;
+        Ok(())
+    })
+}
+
+/// The `ETag` or `Last-Modified` a resumed download sends in `If-Range`
+/// Weak ETags can't be used, they change with the encoding
+fn resume_validator(headers: &HeaderMap) -> Option<HeaderValue> {
+    headers
+        .get(header::ETAG)
+        .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+        .or_else(|| headers.get(header::LAST_MODIFIED))
+        .cloned()
+}
+
+/// Wraps a reader to report how much has been read
+struct ProgressReader<'a, R> {
+    inner: R,
+    bytes: u64,
+    total: Option<u64>,
+    progress: DownloadProgress<'a>,
+}
+
+impl<'a, R: std::io::Read> std::io::Read for ProgressReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read > 0 {
+            self.bytes += read as u64;
+            (self.progress)(self.bytes, self.total);
+        }
+        Ok(read)
+    }
+}
+
+/// An identifier given to `install_many` and the name of the addon it installed
+pub type InstallResult = (String, Result<String, GruntError>);
+
+/// An addon found by `find_install`, to download and install
+struct PendingInstall {
+    /// Shown in errors until the addon is named after its dirs
+    name: String,
+    addon_type: AddonType,
+    addon_id: String,
+    /// Identifier used for extract excludes and the shared cache
+    identifier: String,
+    version: String,
+    url: String,
+    /// Makes the addon once its dirs are known
+    into_addon: Box<dyn FnOnce(Vec<String>) -> Addon + Send + Sync>,
+}
+
+impl PendingInstall {
+    /// The key the addon will have, see `Addon::key`
+    fn key(&self) -> String {
+        format!("{}:{}", self.addon_type.prefix(), self.addon_id)
+    }
+}
+
+/// How many levels down `find_nested` looks for addon dirs
+const NESTED_DEPTH: usize = 3;
+
+/// Whether a dir has a `.toc` file directly inside it
+fn has_toc(path: &Path) -> bool {
+    std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .any(|entry| {
+            let path = entry.path();
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("toc"))
+        })
+}
+
+/// Dirs under `path` with a `.toc` file, looking at most `depth` levels down and not
+/// inside the dirs that are found
+fn find_toc_dirs(path: &Path, depth: usize) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if has_toc(&path) {
+            found.push(path);
+        } else if depth > 1 {
+            found.extend(find_toc_dirs(&path, depth - 1));
+        }
+    }
+    found.sort();
+    found
+}
+
+/// The `AddOns` dir meant by a path given by the user, made absolute
+/// Paths to the WoW dir of a flavor, its `Interface` dir or the game's install dir with
+/// a single flavor get the missing part appended
+pub fn find_addons_dir(path: &Path) -> Result<PathBuf, GruntError> {
+    let invalid = |reason: String| GruntError::InvalidAddonDir {
+        path: path.display().to_string(),
+        reason,
+    };
+    if !path.is_dir() {
+        return Err(invalid("it doesn't exist or isn't a dir".to_string()));
+    }
+    let is_named = |path: &Path, name: &str| {
+        path.file_name()
+            .is_some_and(|file_name| file_name.to_string_lossy().eq_ignore_ascii_case(name))
+    };
+    let interface =
+        |dir: &Path| wine::find_ignore_case(dir, "Interface").filter(|path| path.is_dir());
+    let mut dir = path.to_path_buf();
+    if !is_named(&dir, "AddOns") && !is_named(&dir, "Interface") && interface(&dir).is_none() {
+        // The install dir has a dir like `_retail_` for each flavor
+        let flavors: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                name.len() > 2
+                    && name.starts_with('_')
+                    && name.ends_with('_')
+                    && interface(path).is_some()
+            })
+            .collect();
+        match flavors.as_slice() {
+            [flavor] => dir = flavor.clone(),
+            [] => (),
+            _ => {
+                let mut names: Vec<String> = flavors
+                    .iter()
+                    .map(|flavor| flavor.file_name().unwrap().to_string_lossy().to_string())
+                    .collect();
+                names.sort();
+                return Err(invalid(format!(
+                    "it has several game flavors, use the AddOns dir of one of {}",
+                    names.join(", ")
+                )));
+            }
+        }
+    }
+    if !is_named(&dir, "AddOns") && !is_named(&dir, "Interface") {
+        dir = interface(&dir).unwrap_or(dir);
+    }
+    if is_named(&dir, "Interface") {
+        dir = wine::find_ignore_case(&dir, "AddOns").unwrap_or_else(|| dir.join("AddOns"));
+    }
+    if !dir.is_dir() {
+        return Err(invalid(format!("{} doesn't exist", dir.display())));
+    }
+    Ok(std::path::absolute(&dir)?)
+}
+
+/// Whether an `AddOns` dir is inside a WoW install, with the game's `WTF` dir or
+/// `.build.info` file near it
+pub fn looks_like_wow(addons_dir: &Path) -> bool {
+    let wow_dir = match addons_dir.parent().and_then(Path::parent) {
+        Some(wow_dir) => wow_dir,
+        None => return false,
+    };
+    wow_dir.join("WTF").is_dir()
+        || wow_dir.join(".build.info").is_file()
+        || wow_dir
+            .parent()
+            .is_some_and(|install_dir| install_dir.join(".build.info").is_file())
+}
+
+/// A relative path with `/` separators on every platform
+pub(crate) fn path_to_slash(path: &Path) -> String {
+    path.components()
+        .map(|part| part.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// Extracts a zip archive into a new directory at `out_dir`, skipping excluded files
+/// Archives over `limits` are rejected before or while extracting them
+fn extract_zip(
+    path: &Path,
+    out_dir: &Path,
+    excludes: &[glob::Pattern],
+    limits: &ExtractLimits,
+) -> Result<(), GruntError> {
+    std::fs::create_dir(out_dir)?;
+    let file = File::open(path)?;
+    let archive_size = file.metadata()?.len();
+    if archive_size > limits.max_archive_mib * 1024 * 1024 {
+        return Err(GruntError::UnsafeArchive(format!(
+            "it's larger than {} MiB",
+            limits.max_archive_mib
+        )));
+    }
+    let reader = BufReader::new(file);
+    let mut zip = zip::ZipArchive::new(reader)?;
+    if zip.len() > limits.max_files {
+        return Err(GruntError::UnsafeArchive(format!(
+            "it contains {} files, more than {}",
+            zip.len(),
+            limits.max_files
+        )));
+    }
+    // Sizes in the archive can lie so count what is actually written
+    let max_size = archive_size.max(1).saturating_mul(limits.max_ratio);
+    let too_large = || {
+        GruntError::UnsafeArchive(format!(
+            "it expands to more than {} times its size",
+            limits.max_ratio
+        ))
+    };
+    let mut extracted: u64 = 0;
+    // Iterate through each entry in the zip
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let entry_path = entry.sanitized_name();
+        let out_path = out_dir.join(&entry_path);
+        if entry.is_dir() {
+            // Create empty dir
+            std::fs::create_dir_all(&out_path)?;
+        } else if !settings::is_excluded(excludes, &entry_path) {
+            // Extract file
+            std::fs::create_dir_all(out_path.parent().unwrap())?;
+            let limit = max_size - extracted;
+            if entry.size() > limit {
+                return Err(too_large());
+            }
+            let mut out_file = File::create(&out_path)?;
+            let mut limited = std::io::Read::take(&mut entry, limit.saturating_add(1));
+            extracted += std::io::copy(&mut limited, &mut out_file)?;
+            if extracted > max_size {
+                return Err(too_large());
+            }
+            if let Some(modified) = zip_time(entry.last_modified()) {
+                out_file.set_modified(modified)?;
+            }
+            // Keep bundled binaries executable. Other bits come from the umask
+            if entry.unix_mode().is_some_and(|mode| mode & 0o111 != 0) {
+                perms::add_execute(&out_path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Names of the dirs in an unpacked archive, which shouldn't have any loose files
+fn top_level_dirs(unpacked_dir: &Path) -> Result<Vec<String>, GruntError> {
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(unpacked_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !entry.file_type()?.is_dir() {
+            return Err(GruntError::LooseFile(name));
+        }
+        dirs.push(name);
+    }
+    if dirs.is_empty() {
+        return Err(GruntError::InvalidArchive("no addon dirs".to_string()));
+    }
+    Ok(dirs)
+}
+
+/// Parses a line of TSM's `AppData.lua`, which is of the format
+/// `{data} --<{data_type},{realm},{time}>`
+fn parse_app_data(line: &str) -> Option<((String, String), (String, u64))> {
+    let mut split = line.split("--");
+    let data = split.next()?.trim_end_matches(' ').to_string();
+    let comment_data = split.next()?.trim_start_matches('<').trim_end_matches('>');
+    let mut comment_split = comment_data.split(',');
+    let data_type = comment_split.next()?.to_string();
+    let realm = comment_split.next()?.to_string();
+    let time = comment_split.next()?.parse().ok()?;
+    Some(((data_type, realm), (data, time)))
+}
+
+/// The backend the data of an `AddOns` dir is loaded from, `backend` unless the other
+/// one saved more recently, like after switching backends from another profile
+/// The other one is only used if it's built
+fn newest_backend(backend: StorageBackend, root_dir: &Path) -> StorageBackend {
+    let modified = |backend| {
+        let path = storage::path(backend, root_dir);
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    let other = backend.other();
+    if modified(other) > modified(backend) && storage::open(other, root_dir).is_ok() {
+        other
+    } else {
+        backend
+    }
+}
+
+/// Where the signature of a lockfile is kept, e.g. `grunt.lockfile.sig`
+#[cfg(feature = "crypto")]
+fn signature_path(lockfile_path: &Path) -> PathBuf {
+    let mut path = lockfile_path.as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+/// Converts a zip timestamp, which is in local time
+fn zip_time(time: zip::DateTime) -> Option<SystemTime> {
+    use chrono::TimeZone;
+    let time = chrono::NaiveDate::from_ymd_opt(
+        time.year().into(),
+        time.month().into(),
+        time.day().into(),
+    )?
+    .and_hms_opt(
+        time.hour().into(),
+        time.minute().into(),
+        time.second().into(),
+    )?;
+    Some(chrono::Local.from_local_datetime(&time).earliest()?.into())
+}
+
+/// Copies everything inside `from` into `to`, overwriting existing files
+/// Files keep their permissions and modified time
+fn copy_dir_contents(from: &Path, to: &Path) -> Result<(), GruntError> {
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry.map_err(std::io::Error::from)?;
+        let relative_path = entry.path().strip_prefix(from).unwrap();
+        let new_path = to.join(relative_path);
+        if entry.path().is_dir() {
+            std::fs::create_dir_all(new_path)?;
+        } else {
+            std::fs::create_dir_all(new_path.parent().unwrap())?;
+            std::fs::copy(entry.path(), &new_path)?;
+            let modified = entry.metadata().map_err(std::io::Error::from)?.modified()?;
+            File::options()
+                .write(true)
+                .open(&new_path)?
+                .set_modified(modified)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a `/` separated path from an addon's file list is inside `dir`
+fn in_dir(file: &str, dir: &str) -> bool {
+    file.strip_prefix(dir)
+        .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Copies the files in `from` that aren't in `to` over, keeping their paths
+fn copy_missing_files(from: &Path, to: &Path) -> Result<(), GruntError> {
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry.map_err(std::io::Error::from)?;
+        let new_path = to.join(entry.path().strip_prefix(from).unwrap());
+        if entry.file_type().is_file() && !new_path.exists() {
+            std::fs::create_dir_all(new_path.parent().unwrap())?;
+            std::fs::copy(entry.path(), &new_path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the names of the directories inside `dir`
+fn read_dir_names(dir: &Path) -> Result<Vec<String>, GruntError> {
+    let mut names = Vec::new();
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    Ok(names)
+}
+
+/// Cheap fingerprint of a dir's contents from the path, size and modified time of each file
+fn dir_fingerprint(path: &Path) -> u32 {
+    let mut entries: Vec<String> = walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata
+                .modified()
+                .ok()?
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .ok()?;
+            Some(format!(
+                "{}:{}:{}",
+                entry.path().strip_prefix(path).ok()?.to_string_lossy(),
+                metadata.len(),
+                modified.as_nanos()
+            ))
+        })
+        .collect();
+    entries.sort();
+    murmur2::calculate_hash(entries.join("\n").as_bytes(), 1)
+}
+
+/// Fingerprints of each of `dirs` inside `addon_dir`
+fn fingerprint_dirs(addon_dir: &Path, dirs: &[String]) -> BTreeMap<String, u32> {
+    dirs.iter()
+        .map(|dir| (dir.clone(), dir_fingerprint(&addon_dir.join(dir))))
+        .collect()
+}
+
+/// Returns the newest modification time of anything inside `path`
+fn last_modified(path: &Path) -> Option<SystemTime> {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .max()
+}
+
+/// Get the version string from a `.toc` file
+fn get_toc_version<P>(path: P) -> Result<String, GruntError>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    Toc::from_file(path)?
+        .field("Version")
+        .map(|version| version.to_string())
+        .ok_or_else(|| GruntError::NotFound(format!("Version in {}", path.display())))
+}
+
+/// Regexes of the files each file extension includes, and the comments stripped first
+type ParsingRegexes = HashMap<String, (regex::Regex, Regex)>;
+
+/// Compiles the patterns of `rules`. Returns `None` if any of them is invalid
+fn compile_rules(rules: &GameRules) -> Option<(Regex, Regex, ParsingRegexes)> {
+    let initial_inclusion_regex = Regex::new(&rules.initial_inclusion_pattern).ok()?;
+    let extra_inclusion_regex = Regex::new(&rules.extra_include_pattern).ok()?;
+    let file_parsing_regex = rules
+        .file_parsing_rules
+        .iter()
+        .map(|data| {
+            let comment_strip_regex = regex::Regex::new(&data.comment_strip_pattern).ok()?;
+            let inclusion_regex = Regex::new(&data.inclusion_pattern).ok()?;
+            Some((
+                data.file_extension.clone(),
+                (comment_strip_regex, inclusion_regex),
+            ))
+        })
+        .collect::<Option<_>>()?;
+    Some((
+        initial_inclusion_regex,
+        extra_inclusion_regex,
+        file_parsing_regex,
+    ))
+}
+
+/// The Curse project id of an addon
+fn curse_id(addon: &Addon) -> Result<i64, GruntError> {
+    addon
+        .addon_id()
+        .parse()
+        .map_err(|_| GruntError::InvalidAddonId(addon.name().clone()))
+}
+
+/// The dependencies listed in any of the .toc files of an addon dir
+fn dir_dependencies(path: &Path) -> Vec<String> {
+    let entries = match path.read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toc"))
+        .filter_map(|path| Toc::from_file(path).ok())
+        .flat_map(|toc| toc.dependencies())
+        .collect()
+}
+
+/// Reads a `## Field: value` line from a .toc, ignoring unreadable files
+fn get_toc_field<P>(path: P, field: &str) -> Option<String>
+where
+    P: AsRef<Path>,
+{
+    Some(Toc::from_file(path).ok()?.field(field)?.to_string())
+}
+
+/// The `<dir>.toc` file of an addon dir, in whatever case it's saved in
+fn main_toc(addon_dir: &Path, dir: &str) -> PathBuf {
+    let dir_path = wine::find_ignore_case(addon_dir, dir).unwrap_or_else(|| addon_dir.join(dir));
+    let toc = format!("{}.toc", dir);
+    wine::find_ignore_case(&dir_path, &toc).unwrap_or_else(|| dir_path.join(toc))
+}
+
+/// Finds a case sensitive path from an insensitive path
+/// Useful if, say, a WoW addon points to a local path in a different case but you're not on Windows
+/// Returns `None` if nothing matches
+fn find_file<P>(path: P) -> Option<PathBuf>
+where
+    P: AsRef<Path>,
+{
+    let mut current = path.as_ref();
+    let mut to_finds = Vec::new();
+
+    // Find first parent that exists
+    while !current.exists() {
+        to_finds.push(current.file_name()?);
+        current = current.parent()?;
+    }
+
+    // Match to finds
+    let mut current = current.to_path_buf();
+    to_finds.reverse();
+    for to_find in to_finds {
+        current = wine::find_ignore_case(&current, to_find.to_str()?)?;
+    }
+    Some(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("bomb.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for name in ["Addon/a.lua", "Addon/b.lua"].iter() {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(&[0; 1024 * 1024]).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let extract = |out: &str, limits: &ExtractLimits| {
+            extract_zip(&archive, &dir.path().join(out), &[], limits)
+        };
+        // 2 MiB of zeros compress far more than 100 times
+        assert!(matches!(
+            extract("default", &ExtractLimits::default()),
+            Err(GruntError::UnsafeArchive(_))
+        ));
+        let generous = ExtractLimits {
+            max_ratio: 10000,
+            ..Default::default()
+        };
+        assert!(extract("generous", &generous).is_ok());
+        let few_files = ExtractLimits {
+            max_files: 1,
+            ..generous
+        };
+        assert!(matches!(
+            extract("few_files", &few_files),
+            Err(GruntError::UnsafeArchive(_))
+        ));
+    }
+    #[test]
+    fn test_corrupt_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("grunt.lockfile"), "{").unwrap();
+        assert!(matches!(
+            Grunt::new(dir.path()),
+            Err(GruntError::Storage { .. })
+        ));
+    }
+    #[test]
+    fn test_find_addons_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let install_dir = dir.path().join("World of Warcraft");
+        let addons_dir = install_dir.join("_retail_/Interface/AddOns");
+        std::fs::create_dir_all(&addons_dir).unwrap();
+        for path in [
+            "_retail_/Interface/AddOns",
+            "_retail_/Interface",
+            "_retail_",
+            "",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                find_addons_dir(&install_dir.join(path)).unwrap(),
+                addons_dir
+            );
+        }
+        assert!(!looks_like_wow(&addons_dir));
+        std::fs::write(install_dir.join(".build.info"), "").unwrap();
+        assert!(looks_like_wow(&addons_dir));
+
+        // Which flavor is meant isn't known
+        std::fs::create_dir_all(install_dir.join("_classic_/Interface")).unwrap();
+        assert!(find_addons_dir(&install_dir).is_err());
+        assert!(find_addons_dir(&install_dir.join("_classic_")).is_err());
+        assert!(find_addons_dir(&install_dir.join("_missing_")).is_err());
+    }
+
+    #[test]
+    fn test_install_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let root_dir = dir.path().join("_retail_/Interface/AddOns");
+        std::fs::create_dir_all(&root_dir).unwrap();
+        let grunt = Grunt::new(&root_dir).unwrap();
+        let wow_dir = grunt.root_dir().parent().unwrap().parent().unwrap();
+        assert_eq!(&grunt.install_dir(None).unwrap(), grunt.root_dir());
+        assert_eq!(
+            grunt.install_dir(Some("Interface/Sounds")).unwrap(),
+            wow_dir.join("Interface/Sounds")
+        );
+        for install_root in ["../Other", "/Interface", "Interface/../..", ""].iter() {
+            assert!(matches!(
+                grunt.install_dir(Some(install_root)),
+                Err(GruntError::InvalidInstallRoot(_))
+            ));
+        }
+    }
+    #[test]
+    fn test_move_curse_project() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["A", "B"].iter() {
+            std::fs::create_dir_all(dir.path().join(name)).unwrap();
+        }
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let addon = |name: &str, id| {
+            Addon::from_curse_file(name.to_string(), id, None, 1, vec![name.to_string()])
+        };
+        grunt.import_addons(vec![addon("A", 1), addon("B", 2)]);
+        let moved = grunt.move_curse_project("1", "3".to_string(), Some("a".to_string()));
+        assert_eq!(
+            moved,
+            Some(ProjectMove {
+                addon: "A".to_string(),
+                from: "1".to_string(),
+                to: "3".to_string(),
+            })
+        );
+        assert_eq!(grunt.addons()[0].identifier(), "curse:a");
+        assert!(grunt.addons()[0]
+            .notes()
+            .as_ref()
+            .unwrap()
+            .starts_with("Curse project moved from 1 to 3"));
+        // B isn't merged into the project A already tracks
+        assert_eq!(grunt.move_curse_project("2", "3".to_string(), None), None);
+        assert_eq!(grunt.addons()[1].addon_id(), "2");
+    }
+    #[test]
+    fn test_install_many() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let results = grunt
+            .install_many(&["wago:abc", "custom:Missing"], None)
+            .unwrap();
+        assert_eq!(results[0].0, "wago:abc");
+        assert!(
+            matches!(&results[0].1, Err(GruntError::UnknownSource(source)) if source == "wago")
+        );
+        assert!(matches!(results[1].1, Err(GruntError::NotFound(_))));
+        assert!(grunt.addons().is_empty());
+    }
+    #[test]
+    #[cfg(feature = "tsm")]
+    fn test_source_unavailable() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["TradeSkillMaster", "Addon"].iter() {
+            std::fs::create_dir_all(dir.path().join(name)).unwrap();
+        }
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let url = "http://127.0.0.1:1/Addon.zip".to_string();
+        grunt.import_addons(vec![
+            Addon::init_tsm("1".to_string()),
+            Addon::from_url(url, vec!["Addon".to_string()], "1".to_string()),
+        ]);
+        // No login stops TSM without stopping the URL addon being checked
+        let errors = match grunt.update_addons(|updateable| updateable, None, None) {
+            Err(GruntError::Partial(errors)) => errors,
+            _ => panic!("Expected partial failure"),
+        };
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|err| matches!(
+            err,
+            GruntError::SourceUnavailable { name, .. } if name == "tsm"
+        )));
+        assert!(grunt.addons()[0].failures().is_none());
+        assert!(grunt.addons()[1].failures().is_some());
+    }
+    #[test]
+    fn test_plan_updates() {
+        use std::io::Read;
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["A", "B"].iter() {
+            std::fs::create_dir_all(dir.path().join(name)).unwrap();
+        }
+        // Serves the latest version of A
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let version_url = format!("http://{}/version", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf);
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nConnection: close\r\n\r\n2");
+        });
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let source = CustomSource {
+            version_url,
+            version_pointer: None,
+            version_regex: None,
+            download_url: "http://127.0.0.1:1/A-{version}.zip".to_string(),
+        };
+        grunt.set_custom_sources(vec![("A".to_string(), source)].into_iter().collect());
+        let url = "http://127.0.0.1:1/B.zip".to_string();
+        grunt.import_addons(vec![
+            Addon::from_custom("A".to_string(), vec!["A".to_string()], "1".to_string()),
+            Addon::from_url(url, vec!["B".to_string()], "1".to_string()),
+        ]);
+        grunt.take_changes();
+        // B failing leaves A in the plan, and isn't counted as a failure
+        let plan = grunt.plan_updates(None, None);
+        assert_eq!(plan.updates.len(), 1);
+        assert_eq!(plan.updates[0].identifier, "custom:A");
+        assert_eq!(plan.updates[0].new_version, "2");
+        assert_eq!(plan.warnings.len(), 1);
+        assert!(grunt.addons()[1].failures().is_none());
+        assert!(grunt.take_changes().is_empty());
+    }
+    #[test]
+    fn test_pinned() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("A")).unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let addon = Addon::from_custom("A".to_string(), vec!["A".to_string()], "1".to_string());
+        grunt.import_addons(vec![addon]);
+        grunt.set_frequency(0, UpdateFrequency::Weekly);
+        grunt.set_pinned(0, true);
+        assert_eq!(grunt.check_updates(None, None).due, vec![false]);
+        // Unpinning keeps the frequency it had
+        grunt.set_pinned(0, false);
+        assert_eq!(grunt.addons()[0].frequency(), &UpdateFrequency::Weekly);
+        assert_eq!(grunt.check_updates(None, None).due, vec![true]);
+    }
+    #[test]
+    fn test_check_adoption() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("A")).unwrap();
+        std::fs::write(dir.path().join("A/A.lua"), "print()").unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let addon = Addon::from_custom("A".to_string(), vec!["A".to_string()], "1".to_string());
+        grunt.import_addons(vec![addon]);
+        let check = |grunt: &Grunt| grunt.check_adoption(&grunt.addons()[0]);
+        assert_eq!(check(&grunt), Adoption::Unchanged);
+
+        // Copied files get a new modified time
+        let file = File::options()
+            .write(true)
+            .open(dir.path().join("A/A.lua"))
+            .unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(check(&grunt), Adoption::Moved);
+        std::fs::write(dir.path().join("A/New.lua"), "").unwrap();
+        assert_eq!(check(&grunt), Adoption::Changed(vec!["A".to_string()]));
+        std::fs::remove_dir_all(dir.path().join("A")).unwrap();
+        assert_eq!(check(&grunt), Adoption::Missing(vec!["A".to_string()]));
+    }
+    #[test]
+    fn test_dry_run() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["A", "B", "Shared"].iter() {
+            std::fs::create_dir_all(dir.path().join(name)).unwrap();
+        }
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let addon = |name: &str| {
+            let dirs = vec![name.to_string(), "Shared".to_string()];
+            Addon::from_custom(name.to_string(), dirs, "1".to_string())
+        };
+        grunt.addons = vec![addon("A"), addon("B")];
+        let dry_run = |queries: &[&str]| -> Vec<PathBuf> {
+            let queries: Vec<String> = queries.iter().map(|query| query.to_string()).collect();
+            grunt.dry_run_remove(&queries).unwrap()
+        };
+        assert_eq!(dry_run(&["custom:A"]), vec![dir.path().join("A")]);
+        assert_eq!(
+            dry_run(&["custom:A", "custom:B"]),
+            vec![
+                dir.path().join("A"),
+                dir.path().join("Shared"),
+                dir.path().join("B")
+            ]
+        );
+        assert!(dir.path().join("Shared").is_dir());
+        assert_eq!(grunt.addons().len(), 2);
+
+        let unpacked = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(unpacked.path().join("A2")).unwrap();
+        let mut guard = WriteGuard::dry_run();
+        let dirs = vec!["A".to_string()];
+        let new_dirs = grunt
+            .replace_dirs(dir.path(), &dirs, unpacked.path(), &mut guard)
+            .unwrap();
+        assert_eq!(new_dirs, vec!["A2".to_string()]);
+        assert_eq!(guard.deleted, vec![dir.path().join("A")]);
+        assert_eq!(guard.written, vec![dir.path().join("A2")]);
+        assert!(dir.path().join("A").is_dir());
+        assert!(!dir.path().join("A2").exists());
+    }
+    #[test]
+    fn test_lockfile_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["A", "B"].iter() {
+            std::fs::create_dir_all(dir.path().join(name)).unwrap();
+        }
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let addon = |name: &str| {
+            Addon::from_custom(name.to_string(), vec![name.to_string()], "1".to_string())
+        };
+        grunt.import_addons(vec![addon("A"), addon("B")]);
+        grunt.set_notes(0, Some("Notes".to_string()));
+        grunt.set_notes(0, Some("Notes".to_string()));
+        grunt.remove_addon(1).unwrap();
+        let identifiers = |names: &[&str]| -> Vec<String> {
+            names
+                .iter()
+                .map(|name| format!("custom:{}", name))
+                .collect()
+        };
+        assert_eq!(
+            grunt.take_changes(),
+            vec![
+                LockfileChanged {
+                    added: identifiers(&["A", "B"]),
+                    ..Default::default()
+                },
+                LockfileChanged {
+                    updated: identifiers(&["A"]),
+                    ..Default::default()
+                },
+                LockfileChanged {
+                    removed: identifiers(&["B"]),
+                    ..Default::default()
+                },
+            ]
+        );
+        assert!(grunt.take_changes().is_empty());
+    }
+    #[test]
+    fn test_modified_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("A")).unwrap();
+        std::fs::write(dir.path().join("A/A.lua"), "print()").unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let addon = Addon::from_custom("A".to_string(), vec!["A".to_string()], "1".to_string());
+        grunt.import_addons(vec![addon]);
+        assert!(grunt.modified_dirs(&grunt.addons()[0]).is_empty());
+        std::fs::write(dir.path().join("A/A.lua"), "print('edited')").unwrap();
+        assert_eq!(grunt.modified_dirs(&grunt.addons()[0]), vec!["A"]);
+        grunt.accept_modified(0);
+        assert!(grunt.modified_dirs(&grunt.addons()[0]).is_empty());
+    }
+
+    #[test]
+    fn test_unchecked_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("A")).unwrap();
+        std::fs::write(dir.path().join("A/A.toc"), "## Title: A").unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        grunt.set_disabled_sources(vec![AddonType::Curse, AddonType::TSM, AddonType::Tukui]);
+        grunt.resolve(false, |_| ()).unwrap();
+        // Nothing checked A so it's checked again once Curse is enabled
+        assert!(grunt.unresolved.is_empty());
+        assert_eq!(grunt.find_changed_unresolved().unwrap(), vec!["A"]);
+    }
+
+    #[test]
+    fn test_broken_addon_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("A/Sub")).unwrap();
+        std::fs::write(dir.path().join("A/Sub/Core.lua"), "").unwrap();
+        std::fs::write(dir.path().join("A/A.toc"), "## Title: A").unwrap();
+        assert_eq!(
+            find_file(dir.path().join("a/sub/core.LUA")),
+            Some(dir.path().join("A/Sub/Core.lua"))
+        );
+        assert_eq!(find_file(dir.path().join("A/Missing.lua")), None);
+        assert!(matches!(
+            get_toc_version(dir.path().join("A/A.toc")),
+            Err(GruntError::NotFound(_))
+        ));
+
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let mut info = Addon::from_curse_file("A".into(), 1, None, 1, vec!["A".into()]).to_info();
+        info.addon_id = "a".to_string();
+        grunt.import_addons(vec![Addon::from_info(info)]);
+        assert!(matches!(
+            grunt.description(0),
+            Err(GruntError::InvalidAddonId(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_storage_switch() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["A", "B"].iter() {
+            std::fs::create_dir_all(dir.path().join(name)).unwrap();
+        }
+        let addon = |name: &str| {
+            Addon::from_custom(name.to_string(), vec![name.to_string()], "1".to_string())
+        };
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        grunt.import_addons(vec![addon("A")]);
+        grunt.save_lockfile().unwrap();
+        let json_path = storage::path(StorageBackend::Json, dir.path());
+        let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        File::open(&json_path)
+            .unwrap()
+            .set_modified(hour_ago)
+            .unwrap();
+
+        // Another profile switched to SQLite and saved a change since
+        let mut grunt = Grunt::with_storage(dir.path(), StorageBackend::Sqlite).unwrap();
+        assert_eq!(grunt.addons().len(), 1);
+        grunt.import_addons(vec![addon("B")]);
+        grunt.save_lockfile().unwrap();
+
+        // The stale lockfile isn't used after switching back
+        let grunt = Grunt::new(dir.path()).unwrap();
+        assert_eq!(grunt.addons().len(), 2);
+    }
+
+    #[test]
+    fn test_flatten_nested() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in ["A/A", "B-main/B", "B-main/B_Options", "C"].iter() {
+            std::fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        std::fs::write(dir.path().join("A/A/A.toc"), "").unwrap();
+        std::fs::write(dir.path().join("B-main/README.md"), "").unwrap();
+        std::fs::write(dir.path().join("B-main/B/B.toc"), "").unwrap();
+        std::fs::write(dir.path().join("B-main/B_Options/B_Options.toc"), "").unwrap();
+        std::fs::write(dir.path().join("C/C.toc"), "").unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let mut nested = grunt.find_nested().unwrap();
+        nested.sort_by_key(|nested| nested.dir.clone());
+        assert_eq!(
+            nested,
+            vec![
+                NestedDir {
+                    dir: "A".to_string(),
+                    inner: vec!["A".to_string()],
+                },
+                NestedDir {
+                    dir: "B-main".to_string(),
+                    inner: vec!["B".to_string(), "B_Options".to_string()],
+                },
+            ]
+        );
+        for nested in nested.iter() {
+            grunt.flatten_nested(nested).unwrap();
+        }
+        assert!(dir.path().join("A/A.toc").exists());
+        assert!(dir.path().join("B_Options/B_Options.toc").exists());
+        assert!(!dir.path().join("B-main").exists());
+        assert!(grunt.find_nested().unwrap().is_empty());
+    }
+    #[test]
+    fn test_progress_reader() {
+        let reported = Mutex::new(Vec::new());
+        let progress = |bytes, total| reported.lock().unwrap().push((bytes, total));
+        let mut reader = ProgressReader {
+            inner: std::io::Cursor::new(vec![0; 10]),
+            bytes: 0,
+            total: Some(10),
+            progress: &progress,
+        };
+        let mut buf = [0; 4];
+        while std::io::Read::read(&mut reader, &mut buf).unwrap() > 0 {}
+        assert_eq!(
+            reported.into_inner().unwrap(),
+            vec![(4, Some(10)), (8, Some(10)), (10, Some(10))]
+        );
+    }
+    #[test]
+    fn test_keep_extra() {
+        let dir = tempfile::tempdir().unwrap();
+        let unpacked = tempfile::tempdir().unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        std::fs::create_dir_all(unpacked.path().join("A")).unwrap();
+        std::fs::write(unpacked.path().join("A/Core.lua"), "new").unwrap();
+        for keep_extra in [false, true].iter() {
+            std::fs::create_dir_all(dir.path().join("A/Locales")).unwrap();
+            std::fs::write(dir.path().join("A/Core.lua"), "old").unwrap();
+            std::fs::write(dir.path().join("A/Locales/deDE.lua"), "").unwrap();
+            grunt.set_keep_extra(*keep_extra);
+            let dirs = vec!["A".to_string()];
+            grunt
+                .replace_dirs(dir.path(), &dirs, unpacked.path(), &mut WriteGuard::new())
+                .unwrap();
+            let core = std::fs::read_to_string(dir.path().join("A/Core.lua")).unwrap();
+            assert_eq!(core, "new");
+            assert_eq!(dir.path().join("A/Locales/deDE.lua").exists(), *keep_extra);
+        }
+    }
+    #[test]
+    fn test_conflicting_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let addon = |id: &str, files: &[&str]| {
+            let dirs = vec![id.to_string(), "SharedMedia".to_string()];
+            let mut addon = Addon::from_custom(id.to_string(), dirs, "1".to_string());
+            addon.set_files(files.iter().map(|file| file.to_string()).collect());
+            addon
+        };
+        grunt.addons = vec![
+            addon(
+                "A",
+                &["A/A.toc", "SharedMedia/lib.lua", "SharedMedia/a.ogg"],
+            ),
+            addon(
+                "B",
+                &["B/B.toc", "SharedMedia/lib.lua", "SharedMedia/b.ogg"],
+            ),
+        ];
+        let conflicts = grunt.check_conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].files, vec!["SharedMedia/lib.lua"]);
+        grunt.set_dir_owner("SharedMedia", 0);
+        assert_eq!(grunt.addons[1].files(), &vec!["B/B.toc".to_string()]);
+    }
+    #[test]
+    fn test_same_names() {
+        let dir = tempfile::tempdir().unwrap();
+        for id in ["a", "b"].iter() {
+            std::fs::create_dir_all(dir.path().join(id)).unwrap();
+        }
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let addon = |id: &str| {
+            let mut addon = Addon::from_custom(id.to_string(), vec![id.to_string()], "1".into());
+            addon.set_name("Shared".to_string());
+            addon
+        };
+        grunt.import_addons(vec![addon("a"), addon("b")]);
+        assert!(matches!(
+            grunt.find_addon("Shared"),
+            Err(GruntError::Ambiguous { .. })
+        ));
+        assert_eq!(grunt.find_addon("custom:b").unwrap(), 1);
+        assert_eq!(grunt.get_addon("custom:b").unwrap().addon_id(), "b");
+        grunt.remove_addons(&["custom:a".to_string()]).unwrap();
+        assert_eq!(grunt.find_addon("Shared").unwrap(), 0);
+    }
+    #[test]
+    fn test_latest_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        grunt.addons = vec![
+            Addon::from_curse_file("DBM".to_string(), 3358, None, 100, vec!["DBM".to_string()]),
+            Addon::from_custom("loc".to_string(), vec!["loc".to_string()], "1".to_string()),
+        ];
+        grunt.curse_cache.projects.insert(
+            "3358".to_string(),
+            CachedProject {
+                date_modified: String::new(),
+                slug: "deadly-boss-mods".to_string(),
+                latest_file_id: 200,
+                download_url: String::new(),
+                file_length: None,
+                deprecation: None,
+            },
+        );
+        grunt
+            .latest
+            .insert("custom:loc".to_string(), "2".to_string());
+        let latest: Vec<Option<String>> = grunt
+            .addon_rows()
+            .into_iter()
+            .map(|row| row.latest)
+            .collect();
+        assert_eq!(latest, vec![Some("200".to_string()), Some("2".to_string())]);
+    }
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn test_untrusted_lockfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = Some("secret".to_string());
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        grunt.set_lockfile_secret(secret.clone());
+        grunt.save_lockfile().unwrap();
+
+        // Edited outside of grunt
+        let path = grunt.lockfile_path();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(
+            &path,
+            text.replace("\"addons\": []", "\"addons\": [], \"game_version\": \"1\""),
+        )
+        .unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        grunt.set_lockfile_secret(secret);
+        assert_eq!(grunt.verify_lockfile().unwrap(), Some(Signature::Invalid));
+        assert!(matches!(
+            grunt.save_lockfile(),
+            Err(GruntError::UntrustedLockfile(_))
+        ));
+
+        grunt.accept_lockfile();
+        grunt.save_lockfile().unwrap();
+        assert_eq!(grunt.verify_lockfile().unwrap(), Some(Signature::Valid));
+    }
+    #[test]
+    fn test_empty_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let unzip_dir = dir.path().join("unpacked");
+        std::fs::create_dir_all(&unzip_dir).unwrap();
+        std::fs::write(unzip_dir.join("loose.lua"), "").unwrap();
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let found = PendingInstall {
+            name: "Loose".to_string(),
+            addon_type: AddonType::Url,
+            addon_id: "loose".to_string(),
+            identifier: "url:loose".to_string(),
+            version: String::new(),
+            url: "https://example.com/loose.zip".to_string(),
+            into_addon: Box::new(|dirs| {
+                Addon::from_url(
+                    "https://example.com/loose.zip".to_string(),
+                    dirs,
+                    String::new(),
+                )
+            }),
+        };
+        let root_dir = grunt.root_dir.clone();
+        assert!(matches!(
+            grunt.finish_install(found, &unzip_dir, &root_dir, None),
+            Err(GruntError::InvalidArchive(_))
+        ));
+        assert!(grunt.addons.is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+use crate::rename::DirRename;
+use getset::Getters;
+use std::time::Duration;
+
+/// Timings and results collected while running an operation
+#[derive(Default, Debug, Clone, Getters)]
+#[getset(get = "pub")]
+pub struct Metrics {
+    /// How long the whole operation took
+    pub(crate) duration: Duration,
+    /// Time spent fingerprinting each directory
+    pub(crate) hash_times: Vec<(String, Duration)>,
+    pub(crate) downloads: Vec<DownloadMetrics>,
+    /// Dirs that updates renamed
+    pub(crate) renames: Vec<DirRename>,
+    /// Addons whose Curse project moved to a new id
+    pub(crate) moved_projects: Vec<ProjectMove>,
+    /// Addons that weren't updated because their files were edited locally
+    pub(crate) skipped_modified: Vec<String>,
+    pub(crate) phases: PhaseTimes,
+}
+
+/// Time spent in each phase of a resolve or update
+/// Downloads and extracting run in parallel so their times are summed across threads
+#[derive(Default, Debug, Clone, Copy, Getters)]
+#[getset(get = "pub")]
+pub struct PhaseTimes {
+    /// Fetching addon info and fingerprint matches from the sources
+    pub(crate) metadata: Duration,
+    /// Fingerprinting dirs
+    pub(crate) hashing: Duration,
+    pub(crate) download: Duration,
+    pub(crate) extract: Duration,
+    /// Replacing addon dirs with the new files
+    pub(crate) install: Duration,
+}
+
+/// A Curse project whose files were moved to a project with a new id
+#[derive(Debug, Clone, PartialEq, Getters)]
+#[getset(get = "pub")]
+pub struct ProjectMove {
+    pub(crate) addon: String,
+    pub(crate) from: String,
+    pub(crate) to: String,
+}
+
+/// Size and timing of a single addon download
+#[derive(Debug, Clone, Getters)]
+#[getset(get = "pub")]
+pub struct DownloadMetrics {
+    pub(crate) name: String,
+    pub(crate) bytes: u64,
+    pub(crate) duration: Duration,
+    /// Time spent extracting the archive after downloading it
+    pub(crate) extract_duration: Duration,
+}
+
+impl Metrics {
+    /// Total time spent fingerprinting directories across all threads
+    pub fn total_hash_time(&self) -> Duration {
+        self.hash_times.iter().map(|(_, time)| *time).sum()
+    }
+}
+
+impl DownloadMetrics {
+    /// Average download speed in bytes per second
+    pub fn throughput(&self) -> f64 {
+        self.bytes as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+}
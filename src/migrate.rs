@@ -0,0 +1,195 @@
+use crate::addon::Addon;
+use crate::error::GruntError;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Addons read from another addon manager
+pub struct Migration {
+    pub addons: Vec<Addon>,
+    /// Entries that couldn't be converted, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+/// Reads the addons installed by the CurseForge (Overwolf) client
+/// `path` is an instance json file or a dir searched for them, like the client's data dir
+pub fn from_curse_client(path: &Path) -> Result<Migration, GruntError> {
+    let files: Vec<_> = if path.is_dir() {
+        walkdir::WalkDir::new(path)
+            .max_depth(4)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry.file_type().is_file()
+                    && entry.path().extension().is_some_and(|ext| ext == "json")
+            })
+            .map(|entry| entry.into_path())
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut migration = Migration {
+        addons: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for file in files {
+        let instance: CurseInstance = match read_json(&file) {
+            Ok(instance) => instance,
+            // Other json files in the data dir aren't instances
+            Err(_) if path.is_dir() => continue,
+            Err(err) => return Err(err),
+        };
+        for installed in instance.installed_addons {
+            let dirs: Vec<String> = installed
+                .installed_file
+                .modules
+                .iter()
+                .map(|module| module.foldername.clone())
+                .collect();
+            if dirs.is_empty() {
+                migration.skipped.push((
+                    installed.installed_file.display_name,
+                    "No dirs recorded".to_string(),
+                ));
+                continue;
+            }
+            let name = installed.name.unwrap_or_else(|| dirs[0].clone());
+            migration.addons.push(Addon::from_curse_file(
+                name,
+                installed.addon_id,
+                None,
+                installed.installed_file.id,
+                dirs,
+            ));
+        }
+    }
+    Ok(migration)
+}
+
+/// Reads WowUp's addon store. `path` is the store file or WowUp's data dir
+pub fn from_wowup(path: &Path) -> Result<Migration, GruntError> {
+    let path = if path.is_dir() {
+        path.join("addon-store.json")
+    } else {
+        path.to_path_buf()
+    };
+    let store: HashMap<String, WowUpAddon> = read_json(&path)?;
+    let mut entries: Vec<WowUpAddon> = store.into_values().collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut migration = Migration {
+        addons: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for entry in entries {
+        match entry.to_addon() {
+            Ok(addon) => migration.addons.push(addon),
+            Err(reason) => migration.skipped.push((entry.name, reason)),
+        }
+    }
+    Ok(migration)
+}
+
+/// Deserializes a json file, wrapping errors with the path
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, GruntError> {
+    let import_err = |source: Box<dyn std::error::Error + Send + Sync>| GruntError::Import {
+        path: path.to_string_lossy().to_string(),
+        source,
+    };
+    let file = File::open(path).map_err(|err| import_err(err.into()))?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|err| import_err(err.into()))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CurseInstance {
+    installed_addons: Vec<CurseInstalledAddon>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CurseInstalledAddon {
+    #[serde(rename = "addonID")]
+    addon_id: i64,
+    name: Option<String>,
+    installed_file: CurseInstalledFile,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CurseInstalledFile {
+    id: i64,
+    #[serde(default)]
+    display_name: String,
+    #[serde(default)]
+    modules: Vec<CurseModule>,
+}
+
+#[derive(Deserialize)]
+struct CurseModule {
+    foldername: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WowUpAddon {
+    name: String,
+    provider_name: String,
+    external_id: String,
+    installed_version: Option<String>,
+    /// Curse file id of the installed version
+    installed_external_release_id: Option<String>,
+    /// Comma separated dirs
+    #[serde(default)]
+    installed_folders: String,
+}
+
+impl WowUpAddon {
+    /// Converts to a grunt addon, or returns why it can't be
+    fn to_addon(&self) -> Result<Addon, String> {
+        let dirs: Vec<String> = self
+            .installed_folders
+            .split(',')
+            .map(|dir| dir.trim().to_string())
+            .filter(|dir| !dir.is_empty())
+            .collect();
+        if dirs.is_empty() {
+            return Err("No dirs recorded".to_string());
+        }
+        let parse_id = |id: &str| {
+            id.parse::<i64>()
+                .map_err(|_| format!("Invalid {} id {}", self.provider_name, id))
+        };
+        match self.provider_name.as_str() {
+            "Curse" => {
+                let file_id = match &self.installed_external_release_id {
+                    Some(file_id) => parse_id(file_id)?,
+                    None => return Err("Installed Curse file isn't recorded".to_string()),
+                };
+                Ok(Addon::from_curse_file(
+                    dirs[0].clone(),
+                    parse_id(&self.external_id)?,
+                    None,
+                    file_id,
+                    dirs,
+                ))
+            }
+            "TukUI" => {
+                let version = match &self.installed_version {
+                    Some(version) => version.clone(),
+                    None => return Err("Installed version isn't recorded".to_string()),
+                };
+                Ok(Addon::from_tukui_info(
+                    dirs[0].clone(),
+                    parse_id(&self.external_id)?,
+                    dirs,
+                    version,
+                ))
+            }
+            provider => Err(format!("{} addons aren't supported", provider)),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+#[cfg(feature = "parallel")]
+pub(crate) use rayon::prelude::*;
+
+/// Stands in for rayon's `par_iter` without the `parallel` feature, running on one thread
+#[cfg(not(feature = "parallel"))]
+pub(crate) trait ParallelSlice<T> {
+    fn par_iter(&self) -> std::slice::Iter<'_, T>;
+}
+
+#[cfg(not(feature = "parallel"))]
+impl<T> ParallelSlice<T> for [T] {
+    fn par_iter(&self) -> std::slice::Iter<'_, T> {
+        self.iter()
+    }
+}
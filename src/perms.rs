@@ -0,0 +1,116 @@
+use std::path::Path;
+
+/// Permissions and owner of an addon dir that is about to be replaced
+/// Only used on unix where AddOns dirs can be shared between users
+pub struct Ownership {
+    #[cfg(unix)]
+    mode: u32,
+    #[cfg(unix)]
+    uid: u32,
+    #[cfg(unix)]
+    gid: u32,
+}
+
+impl Ownership {
+    /// Reads the ownership of `path` if it exists
+    #[cfg(unix)]
+    pub fn of(path: &Path) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Ownership {
+            mode: metadata.mode() & 0o7777,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn of(_path: &Path) -> Option<Self> {
+        None
+    }
+
+    /// Gives everything inside `path` the same owner and permissions as the replaced dir
+    /// Files get the dir's permissions without the execute bits
+    /// Errors are ignored since only root can give files to other users
+    #[cfg(unix)]
+    pub fn apply(&self, path: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+        for entry in walkdir::WalkDir::new(path).into_iter().flatten() {
+            let _ = std::os::unix::fs::chown(entry.path(), Some(self.uid), Some(self.gid));
+            let mode = if entry.file_type().is_dir() {
+                self.mode
+            } else {
+                // Executables keep the execute bits the dir allows
+                let executable = entry
+                    .metadata()
+                    .map(|metadata| metadata.permissions().mode() & 0o111)
+                    .unwrap_or(0);
+                self.mode & (0o666 | executable)
+            };
+            let _ = std::fs::set_permissions(entry.path(), std::fs::Permissions::from_mode(mode));
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply(&self, _path: &Path) {}
+}
+
+/// Sets the execute bits matching the read bits of a file
+#[cfg(unix)]
+pub fn add_execute(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = std::fs::metadata(path)?.permissions().mode();
+    std::fs::set_permissions(
+        path,
+        std::fs::Permissions::from_mode(mode | (mode & 0o444) >> 2),
+    )
+}
+
+#[cfg(not(unix))]
+pub fn add_execute(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Changes the group of everything inside `path`, ignoring errors
+#[cfg(unix)]
+pub fn apply_group(path: &Path, gid: u32) {
+    for entry in walkdir::WalkDir::new(path).into_iter().flatten() {
+        let _ = std::os::unix::fs::chown(entry.path(), None, Some(gid));
+    }
+}
+
+#[cfg(not(unix))]
+pub fn apply_group(_path: &Path, _gid: u32) {}
+
+/// Looks up a group by name or numeric id
+#[cfg(unix)]
+pub fn group_id(group: &str) -> Option<u32> {
+    if let Ok(gid) = group.parse() {
+        return Some(gid);
+    }
+    let name = std::ffi::CString::new(group).ok()?;
+    // Safety: getgrnam returns null or a pointer to a static group entry
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        None
+    } else {
+        Some(unsafe { (*entry).gr_gid })
+    }
+}
+
+#[cfg(not(unix))]
+pub fn group_id(_group: &str) -> Option<u32> {
+    None
+}
+
+/// Sets the umask used for every file grunt creates
+#[cfg(unix)]
+pub fn set_umask(mask: u32) {
+    // Safety: umask can't fail
+    unsafe {
+        libc::umask(mask as libc::mode_t);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn set_umask(_mask: u32) {}
@@ -0,0 +1,115 @@
+use crate::error::GruntError;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+fn plan_error(path: &Path, source: Box<dyn std::error::Error + Send + Sync>) -> GruntError {
+    GruntError::Plan {
+        path: path.display().to_string(),
+        source,
+    }
+}
+
+/// A reviewed set of updates that can be applied later with `grunt update --plan`
+#[derive(Serialize, Deserialize)]
+pub struct UpdatePlan {
+    pub created: DateTime<Local>,
+    /// The AddOns dir the plan was made for
+    pub root_dir: String,
+    pub updates: Vec<PlannedUpdate>,
+    /// Addons that weren't checked because they failed too many times in a row
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paused: Vec<PausedAddon>,
+    /// Addons and sources that couldn't be checked
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Dirs the updates delete, listed by dry runs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deleted_dirs: Vec<String>,
+    /// Dirs the updates write, listed by dry runs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub written_dirs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlannedUpdate {
+    pub name: String,
+    /// `source:id` identifier of the addon
+    pub identifier: String,
+    pub current_version: String,
+    pub new_version: String,
+    pub url: String,
+}
+
+/// An addon with a newer version available
+#[derive(Clone)]
+pub struct Updateable {
+    /// Index of the addon in the lockfile
+    pub index: usize,
+    pub name: String,
+    pub new_version: String,
+    pub url: String,
+    /// Size of the download in bytes, if the source says
+    pub size: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PausedAddon {
+    pub name: String,
+    pub identifier: String,
+    pub failures: u32,
+    pub last_error: String,
+}
+
+impl UpdatePlan {
+    /// Loads a plan from a file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, GruntError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| plan_error(path, err.into()))?;
+        let reader = BufReader::new(file);
+        serde_json::from_reader(reader).map_err(|err| plan_error(path, err.into()))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GruntError> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|err| plan_error(path, err.into()))?;
+        let writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(writer, self).map_err(|err| plan_error(path, err.into()))
+    }
+
+    pub fn from_json(text: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(text)
+    }
+
+    /// Indexes in `outdated` of the planned updates
+    /// `current` is the `source:id` identifier and version of every addon in the lockfile
+    /// Returns the names of planned addons whose update changed since the plan was made
+    pub fn pick(
+        &self,
+        current: &[(String, String)],
+        outdated: &[Updateable],
+    ) -> Result<Vec<usize>, Vec<String>> {
+        let mut picked = Vec::new();
+        let mut changed = Vec::new();
+        for planned in self.updates.iter() {
+            let upd = outdated.iter().position(|upd| {
+                let (identifier, version) = &current[upd.index];
+                identifier == &planned.identifier
+                    && version == &planned.current_version
+                    && upd.new_version == planned.new_version
+                    && upd.url == planned.url
+            });
+            match upd {
+                Some(index) => picked.push(index),
+                None => changed.push(planned.name.clone()),
+            }
+        }
+        if changed.is_empty() {
+            Ok(picked)
+        } else {
+            Err(changed)
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use getset::Getters;
+use std::path::{Path, PathBuf};
+
+/// A dir that an update replaced with a differently named one, like
+/// `Addon_Options` becoming `Addon_OptionsUI`
+#[derive(Debug, Clone, PartialEq, Getters)]
+#[getset(get = "pub")]
+pub struct DirRename {
+    pub(crate) addon: String,
+    pub(crate) from: String,
+    pub(crate) to: String,
+}
+
+/// Pairs the dirs an update removed with the ones it added
+/// A single removed and added dir are always a rename, otherwise names have to
+/// start with one another, ignoring case
+pub(crate) fn find_renames(old_dirs: &[String], new_dirs: &[String]) -> Vec<(String, String)> {
+    let contains = |dirs: &[String], dir: &String| dirs.iter().any(|other| other == dir);
+    let removed: Vec<&String> = old_dirs
+        .iter()
+        .filter(|dir| !contains(new_dirs, dir))
+        .collect();
+    let mut added: Vec<&String> = new_dirs
+        .iter()
+        .filter(|dir| !contains(old_dirs, dir))
+        .collect();
+    if removed.len() == 1 && added.len() == 1 {
+        return vec![(removed[0].clone(), added[0].clone())];
+    }
+    let mut renames = Vec::new();
+    for from in removed {
+        let from_lower = from.to_lowercase();
+        // Prefer the closest name when several match
+        let to = added
+            .iter()
+            .enumerate()
+            .filter(|(_, to)| {
+                let to_lower = to.to_lowercase();
+                to_lower.starts_with(&from_lower) || from_lower.starts_with(&to_lower)
+            })
+            .min_by_key(|(_, to)| (to.len() as isize - from.len() as isize).abs())
+            .map(|(index, _)| index);
+        if let Some(index) = to {
+            renames.push((from.clone(), added.remove(index).clone()));
+        }
+    }
+    renames
+}
+
+/// Moves the enabled or disabled state of renamed dirs in every `AddOns.txt` under
+/// the `WTF` dir of the install, so addons disabled in game stay disabled
+pub(crate) fn migrate_addons_txt(root_dir: &Path, renames: &[DirRename]) -> std::io::Result<()> {
+    let wtf_dir = match root_dir.parent().and_then(Path::parent) {
+        Some(install_dir) => install_dir.join("WTF"),
+        None => return Ok(()),
+    };
+    for path in addons_txt_files(&wtf_dir) {
+        let text = std::fs::read_to_string(&path)?;
+        let migrated = migrate_text(&text, renames);
+        if migrated != text {
+            std::fs::write(&path, migrated)?;
+        }
+    }
+    Ok(())
+}
+
+/// Account, realm and character `AddOns.txt` files
+fn addons_txt_files(wtf_dir: &Path) -> Vec<PathBuf> {
+    walkdir::WalkDir::new(wtf_dir.join("Account"))
+        .max_depth(3)
+        .into_iter()
+        .flatten()
+        .filter(|entry| {
+            entry.file_type().is_file() && entry.file_name().eq_ignore_ascii_case("AddOns.txt")
+        })
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Renames `Dir: enabled` lines, leaving any line the new dir already has
+fn migrate_text(text: &str, renames: &[DirRename]) -> String {
+    let dir_of = |line: &str| line.split(':').next().unwrap_or("").trim().to_lowercase();
+    let existing: Vec<String> = text.lines().map(dir_of).collect();
+    let mut migrated = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let dir = dir_of(line);
+        let rename = renames
+            .iter()
+            .find(|rename| rename.from.to_lowercase() == dir)
+            .filter(|rename| !existing.contains(&rename.to.to_lowercase()));
+        match (rename, line.find(':')) {
+            (Some(rename), Some(index)) => {
+                migrated.push_str(&rename.to);
+                migrated.push_str(&line[index..]);
+            }
+            _ => migrated.push_str(line),
+        }
+    }
+    migrated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dirs(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_find_renames() {
+        let old = dirs(&["Addon", "Addon_Options", "Addon_Raid"]);
+        let new = dirs(&[
+            "Addon",
+            "Addon_OptionsUI",
+            "Addon_RaidFrames",
+            "Addon_Extra",
+        ]);
+        assert_eq!(
+            find_renames(&old, &new),
+            vec![
+                ("Addon_Options".to_string(), "Addon_OptionsUI".to_string()),
+                ("Addon_Raid".to_string(), "Addon_RaidFrames".to_string()),
+            ]
+        );
+        assert_eq!(
+            find_renames(&dirs(&["Old"]), &dirs(&["New"])),
+            vec![("Old".to_string(), "New".to_string())]
+        );
+        assert!(find_renames(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_migrate_text() {
+        let renames = vec![DirRename {
+            addon: "Addon".to_string(),
+            from: "Addon_Options".to_string(),
+            to: "Addon_OptionsUI".to_string(),
+        }];
+        assert_eq!(
+            migrate_text("Addon: enabled\r\naddon_options: disabled\r\n", &renames),
+            "Addon: enabled\r\nAddon_OptionsUI: disabled\r\n"
+        );
+        let existing = "Addon_Options: disabled\nAddon_OptionsUI: enabled\n";
+        assert_eq!(migrate_text(existing, &renames), existing);
+    }
+}
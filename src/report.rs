@@ -0,0 +1,247 @@
+use crate::history::{AppliedUpdate, DiskUsage};
+use crate::table::format_size;
+use chrono::{DateTime, Utc};
+
+/// A format `grunt report weekly` writes in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub const NAMES: [&'static str; 2] = ["markdown", "html"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "markdown" => Some(ReportFormat::Markdown),
+            "html" => Some(ReportFormat::Html),
+            _ => None,
+        }
+    }
+
+    /// `Content-Type` the report is posted with
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "text/markdown; charset=utf-8",
+            ReportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+/// What happened to an `AddOns` dir over a period
+pub struct Report {
+    pub root_dir: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub updates: Vec<AppliedUpdate>,
+    /// Newer versions that haven't been installed yet
+    pub pending: Vec<PendingRelease>,
+    /// Addons that aren't maintained anymore, with why
+    pub abandoned: Vec<(String, String)>,
+    /// Sizes of the addons over the period, ending with the current one
+    pub disk_usage: Vec<DiskUsage>,
+}
+
+pub struct PendingRelease {
+    pub name: String,
+    pub current_version: String,
+    pub new_version: String,
+}
+
+/// A heading and its list
+struct Section {
+    title: String,
+    summary: Option<String>,
+    items: Vec<String>,
+}
+
+impl Report {
+    pub fn render(&self, format: ReportFormat) -> String {
+        let title = format!(
+            "Addon report {} to {}",
+            self.start.format("%Y-%m-%d"),
+            self.end.format("%Y-%m-%d")
+        );
+        let sections = self.sections();
+        match format {
+            ReportFormat::Markdown => {
+                let mut lines = vec![format!("# {}", title), String::new(), self.root_dir.clone()];
+                for section in sections {
+                    lines.push(String::new());
+                    lines.push(format!("## {}", section.title));
+                    lines.push(String::new());
+                    if let Some(summary) = section.summary {
+                        lines.push(summary);
+                        lines.push(String::new());
+                    }
+                    if section.items.is_empty() {
+                        lines.push("None".to_string());
+                    }
+                    lines.extend(section.items.iter().map(|item| format!("- {}", item)));
+                }
+                lines.join("\n")
+            }
+            ReportFormat::Html => {
+                let mut lines = vec![
+                    "<!DOCTYPE html>".to_string(),
+                    "<html>".to_string(),
+                    format!(
+                        "<head><meta charset=\"utf-8\"><title>{}</title></head>",
+                        title
+                    ),
+                    "<body>".to_string(),
+                    format!("<h1>{}</h1>", title),
+                    format!("<p>{}</p>", escape_html(&self.root_dir)),
+                ];
+                for section in sections {
+                    lines.push(format!("<h2>{}</h2>", escape_html(&section.title)));
+                    if let Some(summary) = section.summary {
+                        lines.push(format!("<p>{}</p>", escape_html(&summary)));
+                    }
+                    if section.items.is_empty() {
+                        lines.push("<p>None</p>".to_string());
+                        continue;
+                    }
+                    lines.push("<ul>".to_string());
+                    lines.extend(
+                        section
+                            .items
+                            .iter()
+                            .map(|item| format!("<li>{}</li>", escape_html(item))),
+                    );
+                    lines.push("</ul>".to_string());
+                }
+                lines.push("</body>".to_string());
+                lines.push("</html>".to_string());
+                lines.join("\n")
+            }
+        }
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        let updates = Section {
+            title: format!("Updates applied ({})", self.updates.len()),
+            summary: None,
+            items: self
+                .updates
+                .iter()
+                .map(|update| {
+                    format!(
+                        "{} {} → {} on {}",
+                        update.name,
+                        update.from,
+                        update.to,
+                        update.time.format("%Y-%m-%d")
+                    )
+                })
+                .collect(),
+        };
+        let pending = Section {
+            title: format!("Pending releases ({})", self.pending.len()),
+            summary: None,
+            items: self
+                .pending
+                .iter()
+                .map(|release| {
+                    format!(
+                        "{} {} → {}",
+                        release.name, release.current_version, release.new_version
+                    )
+                })
+                .collect(),
+        };
+        let abandoned = Section {
+            title: format!("Abandoned addons ({})", self.abandoned.len()),
+            summary: None,
+            items: self
+                .abandoned
+                .iter()
+                .map(|(name, reason)| format!("{} {}", name, reason))
+                .collect(),
+        };
+        let disk_usage = Section {
+            title: "Disk usage".to_string(),
+            summary: self.disk_usage_summary(),
+            items: self
+                .disk_usage
+                .iter()
+                .map(|usage| {
+                    format!(
+                        "{}: {}",
+                        usage.time.format("%Y-%m-%d"),
+                        format_size(usage.bytes)
+                    )
+                })
+                .collect(),
+        };
+        vec![updates, pending, abandoned, disk_usage]
+    }
+
+    /// The current size and how much it changed over the period
+    fn disk_usage_summary(&self) -> Option<String> {
+        let first = self.disk_usage.first()?;
+        let last = self.disk_usage.last()?;
+        let change = match last.bytes.cmp(&first.bytes) {
+            std::cmp::Ordering::Greater => format!("up {}", format_size(last.bytes - first.bytes)),
+            std::cmp::Ordering::Less => format!("down {}", format_size(first.bytes - last.bytes)),
+            std::cmp::Ordering::Equal => "unchanged".to_string(),
+        };
+        Some(format!(
+            "Addons use {}, {} since {}",
+            format_size(last.bytes),
+            change,
+            first.time.format("%Y-%m-%d")
+        ))
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_render() {
+        let day = |day| Utc.with_ymd_and_hms(2026, 1, day, 12, 0, 0).unwrap();
+        let report = Report {
+            root_dir: "/wow/_retail_/Interface/AddOns".to_string(),
+            start: day(1),
+            end: day(8),
+            updates: vec![AppliedUpdate {
+                time: day(3),
+                name: "Details".to_string(),
+                identifier: "curse:61284".to_string(),
+                from: "3120000".to_string(),
+                to: "3130000".to_string(),
+            }],
+            pending: vec![PendingRelease {
+                name: "WeakAuras".to_string(),
+                current_version: "5.1".to_string(),
+                new_version: "5.2".to_string(),
+            }],
+            abandoned: Vec::new(),
+            disk_usage: vec![
+                DiskUsage {
+                    time: day(1),
+                    bytes: 100 * 1024 * 1024,
+                },
+                DiskUsage {
+                    time: day(8),
+                    bytes: 102 * 1024 * 1024,
+                },
+            ],
+        };
+        for name in ReportFormat::NAMES.iter() {
+            let format = ReportFormat::from_name(name).unwrap();
+            insta::assert_snapshot!(format!("report_{}", name), report.render(format));
+        }
+    }
+}
@@ -1,17 +1,94 @@
+use crate::addon::AddonType;
+use crate::custom::CustomSource;
+use crate::error::GruntError;
+use crate::flavor::Flavor;
+use crate::net::NetOptions;
+use crate::starter::StarterPack;
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
 use getset::{Getters, Setters};
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::path::Path;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-static CURRENT_VERSION: u32 = 1;
+/// Changes the json of settings saved by an older version into the next version
+type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[0]` turns version 1 settings into version 2 and so on
+const MIGRATIONS: &[Migration] = &[];
+
+static CURRENT_VERSION: u32 = 1 + MIGRATIONS.len() as u32;
 
 #[derive(Serialize, Deserialize, Getters, Setters)]
 #[getset(get = "pub", set = "pub")]
+#[serde(default)]
 pub struct Settings {
     version: u32,
+    /// `AddOns` dir used by commands. `~` and environment variables like `$HOME` or
+    /// `%APPDATA%` are expanded, as in the other paths
     default_dir: Option<String>,
     tsm_email: Option<String>,
     tsm_pass: Option<String>,
+    /// Settings file of the TSM desktop app. Its session is used when `tsm_email` and
+    /// `tsm_pass` aren't set, so the password isn't needed
+    tsm_app_config: Option<String>,
+    /// Times the daemon is allowed to install updates. Any time if empty
+    update_windows: Vec<UpdateWindow>,
+    /// Download speed limit in KiB/s used by the daemon. 0 doesn't limit it
+    daemon_max_bandwidth: Option<u64>,
+    /// Minutes a daemon run can take before it's treated as stuck and cancelled
+    daemon_run_timeout: u64,
+    /// Times a stuck daemon run is retried
+    daemon_retries: u32,
+    /// Save a redacted report when grunt crashes
+    crash_reports: bool,
+    /// How to treat untracked dirs matching a glob. The first matching rule is used
+    untracked_rules: Vec<UntrackedRule>,
+    /// Octal umask for files grunt creates, e.g. `002` to keep them group writable
+    umask: Option<String>,
+    /// Group given to installed addon files on shared installs
+    file_group: Option<String>,
+    /// Seconds to wait for a connection
+    connect_timeout: u64,
+    /// Seconds a download can go without receiving data before it's restarted
+    stall_timeout: u64,
+    /// Times a failed download is retried
+    download_retries: u32,
+    /// Seconds to wait before retrying a failed download, doubled after each retry
+    retry_backoff: u64,
+    /// Base URL tried before the Curse CDN for downloads, e.g. a self-hosted proxy
+    curse_mirror: Option<String>,
+    /// Where addon data is saved in each `AddOns` dir
+    storage: StorageBackend,
+    /// Files left out when installing addons
+    extract_excludes: ExtractExcludes,
+    /// Limits that reject suspicious archives
+    extract_limits: ExtractLimits,
+    /// Extra packs for `grunt starter`, by name
+    starter_packs: HashMap<String, StarterPack>,
+    /// `AddOns` dirs by name, e.g. retail and classic, used by `--all-profiles`
+    profiles: BTreeMap<String, String>,
+    /// Game flavor of `AddOns` dirs by path, used instead of the one in their lockfile
+    flavors: BTreeMap<String, Flavor>,
+    /// Paths used on a machine by its host name, for a settings file synced between
+    /// machines that have the game in different places
+    hosts: BTreeMap<String, HostSettings>,
+    /// Sources for addons not on Curse or Tukui, by name
+    custom_sources: HashMap<String, CustomSource>,
+    /// Sources grunt doesn't use, e.g. `TSM` or `Tukui`
+    disabled_sources: Vec<AddonType>,
+    /// Secret the lockfile is signed with on shared installs, so changes made
+    /// outside of grunt are noticed
+    lockfile_secret: Option<String>,
+    /// Updates of an addon that can fail in a row before it's no longer retried
+    /// automatically. 0 keeps retrying
+    max_update_failures: u32,
+    /// Don't update addons whose files were edited since grunt installed them
+    skip_modified_addons: bool,
+    /// URL `grunt report weekly --post` sends the report to
+    report_webhook: Option<String>,
 }
 
 impl Default for Settings {
@@ -21,6 +98,33 @@ impl Default for Settings {
             default_dir: None,
             tsm_email: None,
             tsm_pass: None,
+            tsm_app_config: None,
+            update_windows: Vec::new(),
+            daemon_max_bandwidth: None,
+            daemon_run_timeout: 30,
+            daemon_retries: 2,
+            crash_reports: false,
+            untracked_rules: Vec::new(),
+            umask: None,
+            file_group: None,
+            connect_timeout: 10,
+            stall_timeout: 30,
+            download_retries: 3,
+            retry_backoff: 1,
+            curse_mirror: None,
+            storage: StorageBackend::Json,
+            extract_excludes: ExtractExcludes::default(),
+            extract_limits: ExtractLimits::default(),
+            starter_packs: HashMap::new(),
+            profiles: BTreeMap::new(),
+            flavors: BTreeMap::new(),
+            hosts: BTreeMap::new(),
+            custom_sources: HashMap::new(),
+            disabled_sources: Vec::new(),
+            lockfile_secret: None,
+            max_update_failures: 5,
+            skip_modified_addons: false,
+            report_webhook: None,
         }
     }
 }
@@ -31,26 +135,595 @@ impl Settings {
         Default::default()
     }
 
-    /// Loads settings from a file
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Self {
-        let file = File::open(path).expect("Error opening settings file");
-        let reader = std::io::BufReader::new(file);
-        serde_json::from_reader::<_, Settings>(reader).expect("Error reading settings as json")
+    /// Loads settings from a file, migrating ones saved by older versions of grunt
+    /// A file that can't be parsed is moved to a backup next to it, so the defaults can
+    /// be used and saved without losing it
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, GruntError> {
+        let path = path.as_ref();
+        let error = |source: Box<dyn std::error::Error + Send + Sync>| GruntError::Settings {
+            path: path.display().to_string(),
+            source,
+        };
+        let text = std::fs::read_to_string(path).map_err(|err| error(err.into()))?;
+        let value = serde_json::from_str(&text).map_err(|err| corrupt(path, err))?;
+        let value = migrate(value, MIGRATIONS).map_err(|err| error(err.into()))?;
+        let settings: Settings = serde_json::from_value(value).map_err(|err| corrupt(path, err))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Checks values that parse but can't be used, like update window times
+    fn validate(&self) -> Result<(), GruntError> {
+        for window in self.update_windows.iter() {
+            window.times()?;
+        }
+        self.umask_bits()?;
+        Ok(())
     }
 
     /// Loads settings from a file if it exists or uses default values
-    pub fn from_file_or_new<P: AsRef<Path>>(path: P) -> Self {
+    pub fn from_file_or_new<P: AsRef<Path>>(path: P) -> Result<Self, GruntError> {
         let path = path.as_ref();
         if path.exists() {
             Self::from_file(path)
         } else {
-            Self::new()
+            Ok(Self::new())
+        }
+    }
+
+    /// Saves to a temporary file that replaces `path` once it's complete, so an
+    /// interrupted save leaves the old settings in place
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GruntError> {
+        let path = path.as_ref();
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        let write = || -> std::io::Result<()> {
+            let mut file = std::fs::File::create(&tmp_path)?;
+            serde_json::to_writer_pretty(&mut file, self)?;
+            file.flush()?;
+            file.sync_all()?;
+            std::fs::rename(&tmp_path, path)
+        };
+        write().map_err(|source| {
+            let _ = std::fs::remove_file(&tmp_path);
+            GruntError::Settings {
+                path: path.display().to_string(),
+                source: source.into(),
+            }
+        })
+    }
+
+    /// Timeouts and retries to use for requests
+    pub fn net_options(&self) -> NetOptions {
+        NetOptions {
+            connect_timeout: Duration::from_secs(self.connect_timeout),
+            stall_timeout: Duration::from_secs(self.stall_timeout),
+            retries: self.download_retries,
+            retry_backoff: Duration::from_secs(self.retry_backoff),
+            curse_mirror: self.curse_mirror.clone(),
+        }
+    }
+
+    /// Overrides for this machine, if it has any
+    fn host(&self) -> Option<&HostSettings> {
+        let host_name = host_name()?;
+        self.hosts
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&host_name))
+            .map(|(_, host)| host)
+    }
+
+    /// The `AddOns` dir used on this machine, expanded
+    pub fn addon_dir(&self) -> Option<String> {
+        self.host()
+            .and_then(|host| host.default_dir.as_ref())
+            .or(self.default_dir.as_ref())
+            .map(|dir| expand_path(dir))
+    }
+
+    /// Sets the `AddOns` dir for this machine if it has its own, or `default_dir`
+    pub fn set_addon_dir(&mut self, dir: String) {
+        let host_name = host_name().unwrap_or_default();
+        match self.hosts.iter_mut().find(|(name, host)| {
+            name.eq_ignore_ascii_case(&host_name) && host.default_dir.is_some()
+        }) {
+            Some((_, host)) => host.default_dir = Some(dir),
+            None => self.default_dir = Some(dir),
         }
     }
 
-    pub fn save<P: AsRef<Path>>(&self, path: P) {
-        let file = File::create(path).expect("Error creating settings file");
-        let writer = std::io::BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, self).expect("Error writing settings");
+    /// Profiles with this machine's overrides, their dirs expanded
+    pub fn profile_dirs(&self) -> BTreeMap<String, String> {
+        self.profiles
+            .iter()
+            .chain(self.host().into_iter().flat_map(|host| &host.profiles))
+            .map(|(name, dir)| (name.clone(), expand_path(dir)))
+            .collect()
+    }
+
+    /// The flavor set for an `AddOns` dir
+    pub fn flavor_of(&self, dir: &str) -> Option<Flavor> {
+        self.flavors
+            .iter()
+            .find(|(path, _)| expand_path(path) == dir)
+            .map(|(_, flavor)| *flavor)
+    }
+
+    /// Checks the source isn't in `disabled_sources`
+    pub fn source_enabled(&self, source: &AddonType) -> bool {
+        !self.disabled_sources.contains(source)
+    }
+
+    /// The umask parsed from octal, if set
+    pub fn umask_bits(&self) -> Result<Option<u32>, GruntError> {
+        let umask = match &self.umask {
+            Some(umask) => umask,
+            None => return Ok(None),
+        };
+        match u32::from_str_radix(umask, 8) {
+            Ok(bits) if bits <= 0o777 => Ok(Some(bits)),
+            _ => Err(GruntError::InvalidSetting {
+                name: "umask".to_string(),
+                reason: format!("{} isn't an octal umask like 002", umask),
+            }),
+        }
+    }
+
+    /// Returns true if the daemon may install updates at the given local time
+    pub fn update_allowed_at(&self, time: NaiveDateTime) -> bool {
+        self.update_windows.is_empty()
+            || self
+                .update_windows
+                .iter()
+                .any(|window| window.contains(time))
+    }
+}
+
+/// Paths that apply to one machine
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct HostSettings {
+    /// Used instead of `default_dir`
+    pub default_dir: Option<String>,
+    /// Added to `profiles`, replacing ones with the same name
+    pub profiles: BTreeMap<String, String>,
+}
+
+/// A period of local time during which updates can be installed
+#[derive(Serialize, Deserialize, Getters, Clone, Debug, PartialEq)]
+#[getset(get = "pub")]
+pub struct UpdateWindow {
+    /// Days the window starts on. Every day if empty
+    #[serde(default)]
+    days: Vec<Weekday>,
+    /// Start time formatted as `HH:MM`
+    start: String,
+    /// End time formatted as `HH:MM`. Can be before `start` to run past midnight
+    end: String,
+}
+
+impl UpdateWindow {
+    /// Fails if `start` or `end` isn't formatted as `HH:MM`
+    pub fn new(days: Vec<Weekday>, start: String, end: String) -> Result<Self, GruntError> {
+        let window = UpdateWindow { days, start, end };
+        window.times()?;
+        Ok(window)
+    }
+
+    /// The start and end times of the window
+    fn times(&self) -> Result<(NaiveTime, NaiveTime), GruntError> {
+        Ok((
+            parse_window_time(&self.start)?,
+            parse_window_time(&self.end)?,
+        ))
+    }
+
+    /// Checks if `time` falls inside the window. An invalid window contains no times
+    pub fn contains(&self, time: NaiveDateTime) -> bool {
+        let (start, end) = match self.times() {
+            Ok(times) => times,
+            Err(_) => return false,
+        };
+        let starts_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        let day = time.weekday();
+        let time = time.time();
+        if start <= end {
+            starts_on(day) && start <= time && time < end
+        } else {
+            // Window started yesterday and runs past midnight
+            (starts_on(day) && start <= time) || (starts_on(day.pred()) && time < end)
+        }
+    }
+}
+
+/// Applies an action to every untracked dir whose name matches `pattern`
+#[derive(Serialize, Deserialize, Getters, Clone, Debug, PartialEq)]
+#[getset(get = "pub")]
+pub struct UntrackedRule {
+    /// Glob matched case-insensitively against the dir name, e.g. `Dev_*`
+    pattern: String,
+    action: UntrackedAction,
+}
+
+impl UntrackedRule {
+    pub fn new(pattern: String, action: UntrackedAction) -> Self {
+        UntrackedRule { pattern, action }
+    }
+
+    /// Checks if the rule applies to a dir
+    /// A rule with an invalid pattern applies to nothing
+    pub fn matches(&self, dir: &str) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        glob::Pattern::new(&self.pattern)
+            .map(|pattern| pattern.matches_with(dir, options))
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UntrackedAction {
+    /// Hide the dir completely
+    Ignore,
+    /// The dir is a local addon that shouldn't be resolved
+    TreatAsLocal,
+    /// The dir is junk that `rmdir --candidates` can delete
+    AutoRemoveCandidates,
+}
+
+/// Globs of files to skip when extracting addons, matched case-insensitively against paths
+/// inside the addon's dir. `*` also matches `/` so `*.md` skips them in every subdir
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct ExtractExcludes {
+    /// Globs applied to every addon, e.g. `*.psd` or `Screenshots/*`
+    pub global: Vec<String>,
+    /// Extra globs by addon identifier, e.g. `curse:deadly-boss-mods`
+    pub addons: HashMap<String, Vec<String>>,
+}
+
+impl ExtractExcludes {
+    /// Compiles the globs used for an addon
+    pub fn patterns(&self, identifier: &str) -> Result<Vec<glob::Pattern>, GruntError> {
+        self.global
+            .iter()
+            .chain(self.addons.get(identifier).into_iter().flatten())
+            .map(|pattern| {
+                glob::Pattern::new(pattern).map_err(|source| GruntError::InvalidPattern {
+                    pattern: pattern.clone(),
+                    source,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Limits checked while extracting, so zip bombs and broken archives can't fill the disk
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ExtractLimits {
+    /// Largest archive that is extracted, in MiB
+    pub max_archive_mib: u64,
+    /// How many times larger than the archive its files can be once extracted
+    pub max_ratio: u64,
+    /// Most files an archive can contain
+    pub max_files: usize,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        ExtractLimits {
+            max_archive_mib: 512,
+            max_ratio: 100,
+            max_files: 20000,
+        }
+    }
+}
+
+/// Checks if an archive path like `Addon/Screenshots/a.png` matches any exclude pattern
+pub fn is_excluded(patterns: &[glob::Pattern], path: &Path) -> bool {
+    let options = glob::MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: false,
+        require_literal_leading_dot: false,
+    };
+    // Match inside the addon's dir
+    let inner: PathBuf = match path.components().count() {
+        0 | 1 => path.to_path_buf(),
+        _ => path.components().skip(1).collect(),
+    };
+    patterns
+        .iter()
+        .any(|pattern| pattern.matches_path_with(&inner, options))
+}
+
+/// Formats addon data can be saved in
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum StorageBackend {
+    /// `grunt.lockfile`
+    Json,
+    /// `grunt.db`, better for large installs
+    Sqlite,
+}
+
+impl StorageBackend {
+    /// The backend that isn't this one
+    pub fn other(self) -> Self {
+        match self {
+            StorageBackend::Json => StorageBackend::Sqlite,
+            StorageBackend::Sqlite => StorageBackend::Json,
+        }
+    }
+}
+
+/// Expands a leading `~` and `$VAR`, `${VAR}` or `%VAR%` environment variables in a path
+/// Variables that aren't set are left as they are
+pub fn expand_path(path: &str) -> String {
+    let path = match (path.strip_prefix('~'), home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            format!("{}{}", home, rest)
+        }
+        _ => path.to_string(),
+    };
+    let variable = regex::Regex::new(r"\$\{(\w+)\}|\$(\w+)|%(\w+)%").unwrap();
+    variable
+        .replace_all(&path, |captures: &regex::Captures| {
+            let name = captures.iter().skip(1).flatten().next().unwrap().as_str();
+            std::env::var(name).unwrap_or_else(|_| captures[0].to_string())
+        })
+        .to_string()
+}
+
+/// A path as it's saved in the settings, with the home dir as `~` and `/` separators
+/// so it works on other machines
+pub fn portable_path(path: &Path) -> String {
+    let path = match home_dir().and_then(|home| path.strip_prefix(home).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => PathBuf::from("~"),
+        Some(rest) => Path::new("~").join(rest),
+        None => path.to_path_buf(),
+    };
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    }
+}
+
+fn home_dir() -> Option<String> {
+    std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .ok()
+        .filter(|home| !home.is_empty())
+}
+
+/// Name of this machine, used to find its entry in `hosts`
+fn host_name() -> Option<String> {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Applies the migrations a settings file needs to reach the current version
+fn migrate(mut value: Value, migrations: &[Migration]) -> Result<Value, String> {
+    let current = migrations.len() as u64 + 1;
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version > current {
+        return Err(format!(
+            "Saved by a newer version of grunt (settings version {}, this one reads up to {})",
+            version, current
+        ));
+    }
+    if let Some(settings) = value.as_object_mut() {
+        for migration in &migrations[version.saturating_sub(1) as usize..] {
+            migration(settings);
+        }
+        settings.insert("version".to_string(), current.into());
+    }
+    Ok(value)
+}
+
+/// Moves a settings file that can't be parsed to `<name>.corrupt-<time>`
+fn corrupt(path: &Path, source: serde_json::Error) -> GruntError {
+    let mut backup_name = path.file_name().unwrap_or_default().to_os_string();
+    backup_name.push(
+        chrono::Local::now()
+            .format(".corrupt-%Y%m%d%H%M%S")
+            .to_string(),
+    );
+    let backup = path.with_file_name(backup_name);
+    match std::fs::rename(path, &backup) {
+        Ok(()) => GruntError::CorruptSettings {
+            path: path.display().to_string(),
+            backup: backup.display().to_string(),
+            source,
+        },
+        Err(_) => GruntError::Settings {
+            path: path.display().to_string(),
+            source: source.into(),
+        },
+    }
+}
+
+fn parse_window_time(time: &str) -> Result<NaiveTime, GruntError> {
+    NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| GruntError::InvalidSetting {
+        name: "update_windows".to_string(),
+        reason: format!("{} isn't a time formatted as HH:MM", time),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32, min: u32) -> NaiveDateTime {
+        // 2020-06-01 is a Monday
+        NaiveDate::from_ymd_opt(2020, 6, day)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_update_window() {
+        let window = UpdateWindow::new(vec![Weekday::Mon], "03:00".into(), "06:00".into()).unwrap();
+        assert!(window.contains(at(1, 3, 0)));
+        assert!(window.contains(at(1, 5, 59)));
+        assert!(!window.contains(at(1, 6, 0)));
+        assert!(!window.contains(at(2, 4, 0)));
+
+        // Wraps past midnight into Tuesday
+        let window = UpdateWindow::new(vec![Weekday::Mon], "23:00".into(), "02:00".into()).unwrap();
+        assert!(window.contains(at(1, 23, 30)));
+        assert!(window.contains(at(2, 1, 0)));
+        assert!(!window.contains(at(1, 1, 0)));
+
+        let mut settings = Settings::new();
+        assert!(settings.update_allowed_at(at(3, 12, 0)));
+        settings.set_update_windows(vec![window]);
+        assert!(!settings.update_allowed_at(at(3, 12, 0)));
+
+        assert!(matches!(
+            UpdateWindow::new(Vec::new(), "25:00".into(), "02:00".into()),
+            Err(GruntError::InvalidSetting { .. })
+        ));
+    }
+
+    #[test]
+    fn test_settings_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        let mut settings = Settings::new();
+        settings.set_default_dir(Some("/wow".into()));
+        settings.save(&path).unwrap();
+        let loaded = Settings::from_file(&path).unwrap();
+        assert_eq!(loaded.default_dir(), &Some("/wow".to_string()));
+        assert!(!dir.path().join("config.json.tmp").exists());
+
+        std::fs::write(&path, r#"{"version": 99}"#).unwrap();
+        assert!(matches!(
+            Settings::from_file(&path),
+            Err(GruntError::Settings { .. })
+        ));
+
+        // Corrupt files are moved out of the way
+        std::fs::write(&path, "{\"default_dir\": ").unwrap();
+        match Settings::from_file(&path) {
+            Err(GruntError::CorruptSettings { backup, .. }) => {
+                assert_eq!(
+                    std::fs::read_to_string(backup).unwrap(),
+                    "{\"default_dir\": "
+                );
+            }
+            _ => panic!("Corrupt settings weren't backed up"),
+        }
+        assert!(Settings::from_file_or_new(&path).is_ok());
+
+        // Invalid values are reported without moving the file
+        let windows = r#"{"update_windows": [{"start": "3am", "end": "06:00"}]}"#;
+        std::fs::write(&path, windows).unwrap();
+        assert!(matches!(
+            Settings::from_file(&path),
+            Err(GruntError::InvalidSetting { .. })
+        ));
+        assert!(path.exists());
+        std::fs::write(&path, r#"{"umask": "0x2"}"#).unwrap();
+        assert!(matches!(
+            Settings::from_file(&path),
+            Err(GruntError::InvalidSetting { .. })
+        ));
+
+        let rename: Migration = |settings| {
+            let dir = settings.remove("wow_dir").unwrap();
+            settings.insert("default_dir".to_string(), dir);
+        };
+        let value = serde_json::json!({"version": 1, "wow_dir": "/wow"});
+        let value = migrate(value, &[rename]).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({"version": 2, "default_dir": "/wow"})
+        );
+    }
+
+    #[test]
+    fn test_expand_path() {
+        std::env::set_var("GRUNT_TEST_GAMES", "/games");
+        std::env::remove_var("GRUNT_TEST_UNSET");
+        let home = home_dir().unwrap();
+        assert_eq!(expand_path("~/wow"), format!("{}/wow", home));
+        assert_eq!(expand_path("~other/wow"), "~other/wow");
+        assert_eq!(expand_path("$GRUNT_TEST_GAMES/wow"), "/games/wow");
+        assert_eq!(expand_path("${GRUNT_TEST_GAMES}/wow"), "/games/wow");
+        assert_eq!(expand_path("%GRUNT_TEST_GAMES%/wow"), "/games/wow");
+        assert_eq!(
+            expand_path("%GRUNT_TEST_UNSET%/wow"),
+            "%GRUNT_TEST_UNSET%/wow"
+        );
+        assert_eq!(portable_path(&Path::new(&home).join("wow")), "~/wow");
+        assert_eq!(expand_path(&portable_path(Path::new("/games"))), "/games");
+
+        let mut settings = Settings::new();
+        settings.set_default_dir(Some("/synced".into()));
+        if let Some(host_name) = host_name() {
+            let host = HostSettings {
+                default_dir: Some("$GRUNT_TEST_GAMES/wow".into()),
+                profiles: BTreeMap::new(),
+            };
+            settings.hosts.insert(host_name.to_uppercase(), host);
+            assert_eq!(settings.addon_dir(), Some("/games/wow".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_untracked_rule() {
+        let rule = UntrackedRule::new("Dev_*".into(), UntrackedAction::TreatAsLocal);
+        assert!(rule.matches("Dev_Tools"));
+        assert!(rule.matches("dev_tools"));
+        assert!(!rule.matches("MyDev_Tools"));
+        let rule = UntrackedRule::new("Dev_[".into(), UntrackedAction::Ignore);
+        assert!(!rule.matches("Dev_["));
+
+        let rule: UntrackedRule =
+            serde_json::from_str(r#"{"pattern": "*_Old", "action": "auto-remove-candidates"}"#)
+                .unwrap();
+        assert_eq!(rule.action(), &UntrackedAction::AutoRemoveCandidates);
+    }
+
+    #[test]
+    fn test_extract_excludes() {
+        let excludes: ExtractExcludes = serde_json::from_str(
+            r#"{"global": ["*.md"], "addons": {"curse:dbm": ["Screenshots/*"]}}"#,
+        )
+        .unwrap();
+        let patterns = excludes.patterns("curse:dbm").unwrap();
+        assert!(is_excluded(&patterns, Path::new("DBM/README.MD")));
+        assert!(is_excluded(&patterns, Path::new("DBM/Docs/Guide.md")));
+        assert!(is_excluded(&patterns, Path::new("DBM/Screenshots/a.png")));
+        assert!(!is_excluded(&patterns, Path::new("DBM/DBM.toc")));
+        assert!(!is_excluded(&patterns, Path::new("Screenshots/DBM.toc")));
+
+        let patterns = excludes.patterns("curse:other").unwrap();
+        assert!(!is_excluded(
+            &patterns,
+            Path::new("Other/Screenshots/a.png")
+        ));
+
+        let excludes = ExtractExcludes {
+            global: vec!["[".to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            excludes.patterns("curse:dbm"),
+            Err(GruntError::InvalidPattern { .. })
+        ));
     }
 }
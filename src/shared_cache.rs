@@ -0,0 +1,71 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Metadata and archives fetched for one `AddOns` dir, kept so other dirs updated
+/// in the same run don't fetch or download them again
+/// Entries are keyed by source and project id, archives also by version
+#[derive(Clone, Debug)]
+pub struct SharedCache {
+    dir: PathBuf,
+}
+
+impl SharedCache {
+    /// Starts an empty cache in `dir`, deleting anything left by an earlier run
+    pub fn create<P: AsRef<Path>>(dir: P) -> std::io::Result<Self> {
+        let cache = SharedCache {
+            dir: dir.as_ref().to_path_buf(),
+        };
+        cache.clear()?;
+        std::fs::create_dir_all(cache.dir.join("metadata"))?;
+        std::fs::create_dir_all(cache.dir.join("archives"))?;
+        Ok(cache)
+    }
+
+    /// Deletes everything cached
+    pub fn clear(&self) -> std::io::Result<()> {
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn load<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let text = std::fs::read_to_string(self.metadata_path(key)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    /// Saves metadata. Failing to save only means it's fetched again
+    pub(crate) fn store<T: Serialize>(&self, key: &str, value: &T) {
+        if let Ok(text) = serde_json::to_string(value) {
+            let _ = std::fs::write(self.metadata_path(key), text);
+        }
+    }
+
+    /// Where the archive of an addon version is kept
+    pub(crate) fn archive_path(&self, identifier: &str, version: &str) -> PathBuf {
+        self.dir.join("archives").join(format!(
+            "{}-{}.zip",
+            file_key(identifier),
+            file_key(version)
+        ))
+    }
+
+    fn metadata_path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join("metadata")
+            .join(format!("{}.json", file_key(key)))
+    }
+}
+
+/// Replaces chars that can't be in file names, like the `:` of identifiers
+fn file_key(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
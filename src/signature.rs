@@ -0,0 +1,57 @@
+use crate::lockfile::Lockfile;
+use data_encoding::HEXLOWER;
+use ring::hmac;
+
+/// Result of checking the lockfile against its signature
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signature {
+    Valid,
+    /// The lockfile was saved without a secret set
+    Missing,
+    /// The lockfile was changed by something other than grunt
+    Invalid,
+}
+
+/// HMAC-SHA256 of the lockfile data, as hex
+/// The data is signed instead of the file so the signature doesn't depend on the
+/// storage backend or key order
+pub fn sign(secret: &str, lockfile: &Lockfile) -> String {
+    let tag = hmac::sign(&key(secret), &signed_data(lockfile));
+    HEXLOWER.encode(tag.as_ref())
+}
+
+pub fn verify(secret: &str, lockfile: &Lockfile, signature: &str) -> Signature {
+    let tag = match HEXLOWER.decode(signature.trim().as_bytes()) {
+        Ok(tag) => tag,
+        Err(_) => return Signature::Invalid,
+    };
+    match hmac::verify(&key(secret), &signed_data(lockfile), &tag) {
+        Ok(()) => Signature::Valid,
+        Err(_) => Signature::Invalid,
+    }
+}
+
+fn key(secret: &str) -> hmac::Key {
+    hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes())
+}
+
+/// The lockfile as json with sorted keys
+fn signed_data(lockfile: &Lockfile) -> Vec<u8> {
+    let value = serde_json::to_value(lockfile).expect("Error serializing lockfile");
+    serde_json::to_vec(&value).expect("Error serializing lockfile")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let mut lockfile = Lockfile::from_json(include_str!("fixtures/lockfile.json")).unwrap();
+        let signature = sign("secret", &lockfile);
+        assert_eq!(verify("secret", &lockfile, &signature), Signature::Valid);
+        assert_eq!(verify("other", &lockfile, &signature), Signature::Invalid);
+        lockfile.addons[0].version = "1".to_string();
+        assert_eq!(verify("secret", &lockfile, &signature), Signature::Invalid);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::net::execute;
+use data_encoding::HEXLOWER;
+use reqwest::blocking::{Client, Request, Response};
+use reqwest::ResponseBuilderExt;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Where responses are recorded to or replayed from
+enum Snapshot {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+static SNAPSHOT: OnceLock<Snapshot> = OnceLock::new();
+
+/// Saves every HTTP response to `dir` from now on, to be replayed with `replay_from`
+pub fn record_to<P: AsRef<Path>>(dir: P) -> std::io::Result<()> {
+    std::fs::create_dir_all(&dir)?;
+    let _ = SNAPSHOT.set(Snapshot::Record(dir.as_ref().to_path_buf()));
+    Ok(())
+}
+
+/// Serves every HTTP response from a snapshot made with `record_to` instead of the network
+/// Requests that weren't recorded get a 404
+pub fn replay_from<P: AsRef<Path>>(dir: P) {
+    let _ = SNAPSHOT.set(Snapshot::Replay(dir.as_ref().to_path_buf()));
+}
+
+/// A recorded response, saved as `<key>.json` next to its body in `<key>.body`
+#[derive(Serialize, Deserialize)]
+struct Recorded {
+    /// Only for reading the snapshot, the key is what's matched
+    url: String,
+    status: u16,
+    content_type: Option<String>,
+}
+
+/// Sends a request, through the snapshot if `record_to` or `replay_from` was called
+pub(crate) fn send(client: &Client, request: Request) -> reqwest::Result<Response> {
+    match SNAPSHOT.get() {
+        Some(Snapshot::Record(dir)) => {
+            let key = snapshot_key(&request);
+            let resp = execute(client, request)?;
+            Ok(record(dir, &key, resp)?)
+        }
+        Some(Snapshot::Replay(dir)) => Ok(replay(dir, &snapshot_key(&request), &request)),
+        None => execute(client, request),
+    }
+}
+
+/// Saves a response and returns a copy of it, since the body can only be read once
+fn record(dir: &Path, key: &str, resp: Response) -> reqwest::Result<Response> {
+    let url = resp.url().clone();
+    let status = resp.status();
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let mut body = resp.bytes()?.to_vec();
+    // The session would let anyone replaying the snapshot use the account
+    if url.path().starts_with("/v2/login") {
+        if let Ok(mut json) = serde_json::from_slice::<serde_json::Value>(&body) {
+            if let Some(session) = json.get_mut("session") {
+                *session = "<redacted>".into();
+                body = serde_json::to_vec(&json).expect("Error serializing response");
+            }
+        }
+    }
+    let recorded = Recorded {
+        url: snapshot_url(url.clone()),
+        status: status.as_u16(),
+        content_type,
+    };
+    // Recording is only for debugging, it never fails a request
+    let text = serde_json::to_string_pretty(&recorded).expect("Error serializing response");
+    let _ = std::fs::write(dir.join(format!("{}.json", key)), text);
+    let _ = std::fs::write(dir.join(format!("{}.body", key)), &body);
+    Ok(build_response(
+        url,
+        status.as_u16(),
+        recorded.content_type,
+        body,
+    ))
+}
+
+fn replay(dir: &Path, key: &str, request: &Request) -> Response {
+    let url = request.url().clone();
+    let recorded = std::fs::read_to_string(dir.join(format!("{}.json", key)))
+        .ok()
+        .and_then(|text| serde_json::from_str::<Recorded>(&text).ok());
+    let body = std::fs::read(dir.join(format!("{}.body", key)));
+    match (recorded, body) {
+        (Some(recorded), Ok(body)) => {
+            build_response(url, recorded.status, recorded.content_type, body)
+        }
+        _ => build_response(url, 404, None, b"Not in the snapshot".to_vec()),
+    }
+}
+
+fn build_response(
+    url: reqwest::Url,
+    status: u16,
+    content_type: Option<String>,
+    body: Vec<u8>,
+) -> Response {
+    let mut builder = http::Response::builder().status(status).url(url);
+    if let Some(content_type) = content_type {
+        builder = builder.header(reqwest::header::CONTENT_TYPE, content_type);
+    }
+    builder.body(body).expect("Error building response").into()
+}
+
+/// Identifies a request in a snapshot
+/// Values that change every run or identify the user are left out, so a snapshot
+/// still matches when replayed later or by someone else
+fn snapshot_key(request: &Request) -> String {
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    context.update(request.method().as_str().as_bytes());
+    context.update(b" ");
+    context.update(snapshot_url(request.url().clone()).as_bytes());
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        context.update(b"\n");
+        context.update(body);
+    }
+    HEXLOWER.encode(&context.finish().as_ref()[..8])
+}
+
+/// The URL without credentials, TSM's login hashes or per run query values
+fn snapshot_url(mut url: reqwest::Url) -> String {
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    if url.path().starts_with("/v2/login/") {
+        url.set_path("/v2/login");
+    }
+    let query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !["session", "time", "token"].contains(&name.as_ref()))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_url() {
+        let snapshot_url = |url| snapshot_url(reqwest::Url::parse(url).unwrap());
+        assert_eq!(
+            snapshot_url("http://app-server.tradeskillmaster.com/v2/login/abc/def?session=&time=1&channel=release"),
+            "http://app-server.tradeskillmaster.com/v2/login?channel=release"
+        );
+        assert_eq!(
+            snapshot_url("https://example.com/api.php?addons=all"),
+            "https://example.com/api.php?addons=all"
+        );
+        assert_eq!(
+            snapshot_url("https://example.com/v2/status?time=2&token=abc"),
+            "https://example.com/v2/status"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+use crate::flavor::Flavor;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// A list of addons to install on a fresh setup
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StarterPack {
+    pub description: String,
+    /// Identifiers like `curse:details` for each flavor
+    pub addons: HashMap<Flavor, Vec<String>>,
+}
+
+impl StarterPack {
+    /// The addons to install in an AddOns dir of `flavor`
+    pub fn addons_for(&self, flavor: Flavor) -> &[String] {
+        self.addons.get(&flavor).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+/// The packs shipped with grunt plus the user's own, which replace built-in packs of the same name
+pub fn packs(user_packs: &HashMap<String, StarterPack>) -> BTreeMap<String, StarterPack> {
+    let mut packs: BTreeMap<String, StarterPack> =
+        serde_json::from_str(include_str!("starter_packs.json"))
+            .expect("Error reading built-in starter packs");
+    packs.extend(
+        user_packs
+            .iter()
+            .map(|(name, pack)| (name.clone(), pack.clone())),
+    );
+    packs
+}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Usage counters kept with the lockfile of an `AddOns` dir
+/// They're only shown by `grunt stats` and never sent anywhere
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    /// When counting started
+    pub since: Option<DateTime<Utc>>,
+    /// Times each command was run, counting only runs that saved the lockfile
+    pub commands: BTreeMap<String, u64>,
+    pub installed: u64,
+    pub removed: u64,
+    /// Addon updates applied
+    pub updated: u64,
+}
+
+impl Stats {
+    pub fn record_command(&mut self, name: &str) {
+        self.since.get_or_insert_with(Utc::now);
+        *self.commands.entry(name.to_string()).or_default() += 1;
+    }
+}
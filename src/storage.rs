@@ -0,0 +1,453 @@
+use crate::error::GruntError;
+use crate::lockfile::Lockfile;
+use crate::settings::StorageBackend;
+use std::path::{Path, PathBuf};
+
+/// Somewhere the lockfile data of an `AddOns` dir is kept
+pub trait Storage {
+    /// Reads the saved data, or `None` if nothing has been saved yet
+    fn load(&self) -> Result<Option<Lockfile>, GruntError>;
+
+    fn save(&self, lockfile: &Lockfile) -> Result<(), GruntError>;
+}
+
+/// The file the data of an `AddOns` dir is kept in by `backend`
+pub fn path(backend: StorageBackend, root_dir: &Path) -> PathBuf {
+    match backend {
+        StorageBackend::Json => root_dir.join("grunt.lockfile"),
+        StorageBackend::Sqlite => root_dir.join("grunt.db"),
+    }
+}
+
+/// Opens the storage of an `AddOns` dir
+pub fn open(backend: StorageBackend, root_dir: &Path) -> Result<Box<dyn Storage>, GruntError> {
+    let path = path(backend, root_dir);
+    match backend {
+        StorageBackend::Json => Ok(Box::new(JsonStorage { path })),
+        #[cfg(feature = "sqlite")]
+        StorageBackend::Sqlite => Ok(Box::new(sqlite::SqliteStorage { path })),
+        #[cfg(not(feature = "sqlite"))]
+        StorageBackend::Sqlite => Err(GruntError::Storage {
+            path: path.display().to_string(),
+            source: "grunt was built without SQLite support".into(),
+        }),
+    }
+}
+
+/// The original `grunt.lockfile`
+struct JsonStorage {
+    path: PathBuf,
+}
+
+impl Storage for JsonStorage {
+    fn load(&self) -> Result<Option<Lockfile>, GruntError> {
+        if self.path.exists() {
+            Lockfile::from_file(&self.path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn save(&self, lockfile: &Lockfile) -> Result<(), GruntError> {
+        lockfile.save(&self.path)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::Storage;
+    use crate::curse::CurseCache;
+    use crate::error::GruntError;
+    use crate::lockfile::{AddonInfo, Lockfile, UnresolvedDir};
+    use crate::stats::Stats;
+    use rusqlite::types::Type;
+    use rusqlite::{params, Connection, OptionalExtension};
+    use std::path::PathBuf;
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS addons (
+            position INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            addon_type TEXT NOT NULL,
+            addon_id TEXT NOT NULL,
+            slug TEXT,
+            version TEXT NOT NULL,
+            dirs TEXT NOT NULL,
+            notes TEXT,
+            frequency TEXT,
+            last_checked TEXT,
+            failures TEXT,
+            install_root TEXT,
+            fingerprints TEXT,
+            files TEXT,
+            priority TEXT,
+            pinned TEXT
+        );
+        CREATE TABLE IF NOT EXISTS curse_projects (
+            id TEXT PRIMARY KEY,
+            data TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS fingerprints (
+            dir TEXT PRIMARY KEY,
+            fingerprint INTEGER NOT NULL,
+            attempted TEXT,
+            reason TEXT
+        );
+        CREATE TABLE IF NOT EXISTS meta (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS stats (
+            name TEXT PRIMARY KEY,
+            count INTEGER NOT NULL
+        );";
+
+    /// A SQLite database, which copes better with large installs
+    /// Enum and list values are stored as json
+    pub struct SqliteStorage {
+        pub path: PathBuf,
+    }
+
+    impl SqliteStorage {
+        fn connect(&self) -> rusqlite::Result<Connection> {
+            let conn = Connection::open(&self.path)?;
+            conn.execute_batch(SCHEMA)?;
+            // Columns added after the tables were first created
+            let added = [
+                ("addons", "frequency"),
+                ("addons", "last_checked"),
+                ("addons", "failures"),
+                ("addons", "install_root"),
+                ("addons", "fingerprints"),
+                ("addons", "files"),
+                ("addons", "priority"),
+                ("addons", "pinned"),
+                ("fingerprints", "attempted"),
+                ("fingerprints", "reason"),
+            ];
+            for (table, column) in added.iter() {
+                let mut stmt =
+                    conn.prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))?;
+                let columns: Vec<String> = stmt
+                    .query_map([], |row| row.get(0))
+                    .and_then(|rows| rows.collect())?;
+                drop(stmt);
+                if !columns.iter().any(|existing| existing == column) {
+                    conn.execute_batch(&format!(
+                        "ALTER TABLE {} ADD COLUMN {} TEXT",
+                        table, column
+                    ))?;
+                }
+            }
+            Ok(conn)
+        }
+
+        fn error(&self, source: rusqlite::Error) -> GruntError {
+            GruntError::Storage {
+                path: self.path.display().to_string(),
+                source: source.into(),
+            }
+        }
+
+        fn read(&self) -> rusqlite::Result<Lockfile> {
+            let conn = self.connect()?;
+
+            let mut stmt = conn
+                .prepare("SELECT name, addon_type, addon_id, slug, version, dirs, notes, frequency, last_checked, failures, install_root, fingerprints, files, priority, pinned FROM addons ORDER BY position")?;
+            let addons = stmt
+                .query_map([], |row| {
+                    Ok(AddonInfo {
+                        name: row.get(0)?,
+                        addon_type: from_json(row.get(1)?)?,
+                        addon_id: row.get(2)?,
+                        slug: row.get(3)?,
+                        version: row.get(4)?,
+                        dirs: from_json(row.get(5)?)?,
+                        notes: row.get(6)?,
+                        frequency: row
+                            .get::<_, Option<String>>(7)?
+                            .map(from_json)
+                            .transpose()?
+                            .unwrap_or_default(),
+                        last_checked: row
+                            .get::<_, Option<String>>(8)?
+                            .map(from_json)
+                            .transpose()?,
+                        failures: row
+                            .get::<_, Option<String>>(9)?
+                            .map(from_json)
+                            .transpose()?,
+                        install_root: row.get(10)?,
+                        fingerprints: row
+                            .get::<_, Option<String>>(11)?
+                            .map(from_json)
+                            .transpose()?
+                            .unwrap_or_default(),
+                        files: row
+                            .get::<_, Option<String>>(12)?
+                            .map(from_json)
+                            .transpose()?
+                            .unwrap_or_default(),
+                        priority: row
+                            .get::<_, Option<String>>(13)?
+                            .map(from_json)
+                            .transpose()?
+                            .unwrap_or_default(),
+                        pinned: row
+                            .get::<_, Option<String>>(14)?
+                            .map(from_json)
+                            .transpose()?
+                            .unwrap_or_default(),
+                    })
+                })
+                .and_then(|rows| rows.collect())?;
+
+            let mut stmt = conn.prepare("SELECT id, data FROM curse_projects")?;
+            let projects = stmt
+                .query_map([], |row| Ok((row.get(0)?, from_json(row.get(1)?)?)))
+                .and_then(|rows| rows.collect())?;
+            let meta = |key: &str| -> rusqlite::Result<Option<String>> {
+                conn.query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                    row.get(0)
+                })
+                .optional()
+            };
+            let curse_cache = CurseCache {
+                timestamp: meta("curse_timestamp")?,
+                projects,
+                rules: meta("curse_rules")?.map(from_json).transpose()?,
+            };
+
+            let mut stmt =
+                conn.prepare("SELECT dir, fingerprint, attempted, reason FROM fingerprints")?;
+            let unresolved = stmt
+                .query_map([], |row| {
+                    let dir = UnresolvedDir {
+                        fingerprint: row.get(1)?,
+                        attempted: row
+                            .get::<_, Option<String>>(2)?
+                            .map(from_json)
+                            .transpose()?,
+                        reason: row.get(3)?,
+                    };
+                    Ok((row.get(0)?, dir))
+                })
+                .and_then(|rows| rows.collect())?;
+
+            // Commands are stored as `command:<name>`
+            let mut stmt = conn.prepare("SELECT name, count FROM stats")?;
+            let counts: Vec<(String, u64)> = stmt
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .and_then(|rows| rows.collect())?;
+            let mut stats = Stats {
+                since: meta("stats_since")?.map(from_json).transpose()?,
+                ..Default::default()
+            };
+            for (name, count) in counts {
+                match name.as_str() {
+                    "installed" => stats.installed = count,
+                    "removed" => stats.removed = count,
+                    "updated" => stats.updated = count,
+                    _ => {
+                        if let Some(command) = name.strip_prefix("command:") {
+                            stats.commands.insert(command.to_string(), count);
+                        }
+                    }
+                }
+            }
+
+            Ok(Lockfile {
+                addons,
+                curse_cache,
+                unresolved,
+                stats,
+                latest: meta("latest")?
+                    .map(from_json)
+                    .transpose()?
+                    .unwrap_or_default(),
+                history: meta("history")?
+                    .map(from_json)
+                    .transpose()?
+                    .unwrap_or_default(),
+                game_version: meta("game_version")?,
+                flavor: meta("flavor")?.map(from_json).transpose()?,
+            })
+        }
+
+        fn write(&self, lockfile: &Lockfile) -> rusqlite::Result<()> {
+            let mut conn = self.connect()?;
+            let tx = conn.transaction()?;
+            tx.execute_batch(
+                "DELETE FROM addons; DELETE FROM curse_projects; DELETE FROM fingerprints; DELETE FROM meta; DELETE FROM stats;",
+            )?;
+            for (position, addon) in lockfile.addons.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO addons VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+                    params![
+                        position as i64,
+                        addon.name,
+                        to_json(&addon.addon_type),
+                        addon.addon_id,
+                        addon.slug,
+                        addon.version,
+                        to_json(&addon.dirs),
+                        addon.notes,
+                        to_json(&addon.frequency),
+                        addon.last_checked.as_ref().map(to_json),
+                        addon.failures.as_ref().map(to_json),
+                        addon.install_root,
+                        to_json(&addon.fingerprints),
+                        to_json(&addon.files),
+                        to_json(&addon.priority),
+                        to_json(&addon.pinned),
+                    ],
+                )?;
+            }
+            for (id, project) in lockfile.curse_cache.projects.iter() {
+                tx.execute(
+                    "INSERT INTO curse_projects VALUES (?1, ?2)",
+                    params![id, to_json(project)],
+                )?;
+            }
+            let meta = [
+                ("curse_timestamp", lockfile.curse_cache.timestamp.clone()),
+                (
+                    "curse_rules",
+                    lockfile.curse_cache.rules.as_ref().map(to_json),
+                ),
+                ("stats_since", lockfile.stats.since.as_ref().map(to_json)),
+                ("latest", Some(to_json(&lockfile.latest))),
+                ("history", Some(to_json(&lockfile.history))),
+                ("game_version", lockfile.game_version.clone()),
+                ("flavor", lockfile.flavor.as_ref().map(to_json)),
+            ];
+            for (key, value) in meta.iter() {
+                if let Some(value) = value {
+                    tx.execute("INSERT INTO meta VALUES (?1, ?2)", params![key, value])?;
+                }
+            }
+            for (dir, unresolved) in lockfile.unresolved.iter() {
+                tx.execute(
+                    "INSERT INTO fingerprints VALUES (?1, ?2, ?3, ?4)",
+                    params![
+                        dir,
+                        unresolved.fingerprint,
+                        unresolved.attempted.as_ref().map(to_json),
+                        unresolved.reason,
+                    ],
+                )?;
+            }
+            let stats = &lockfile.stats;
+            let counts = vec![
+                ("installed".to_string(), stats.installed),
+                ("removed".to_string(), stats.removed),
+                ("updated".to_string(), stats.updated),
+            ];
+            let commands = stats
+                .commands
+                .iter()
+                .map(|(command, count)| (format!("command:{}", command), *count));
+            for (name, count) in counts.into_iter().chain(commands) {
+                tx.execute("INSERT INTO stats VALUES (?1, ?2)", params![name, count])?;
+            }
+            tx.commit()
+        }
+    }
+
+    impl Storage for SqliteStorage {
+        fn load(&self) -> Result<Option<Lockfile>, GruntError> {
+            if !self.path.exists() {
+                return Ok(None);
+            }
+            self.read().map(Some).map_err(|source| self.error(source))
+        }
+
+        fn save(&self, lockfile: &Lockfile) -> Result<(), GruntError> {
+            self.write(lockfile).map_err(|source| self.error(source))
+        }
+    }
+
+    /// Json values are decoded while reading rows, so errors are returned as SQLite ones
+    fn from_json<T: serde::de::DeserializeOwned>(text: String) -> rusqlite::Result<T> {
+        serde_json::from_str(&text)
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, err.into()))
+    }
+
+    fn to_json<T: serde::Serialize>(value: &T) -> String {
+        serde_json::to_string(value).expect("Error serializing value for database")
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "sqlite")]
+mod tests {
+    use super::*;
+    use crate::addon::Addon;
+    use crate::curse::CachedProject;
+    use crate::flavor::Flavor;
+    use crate::lockfile::UnresolvedDir;
+    use crate::Grunt;
+
+    #[test]
+    fn test_sqlite_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["A", "A_Options", "B"].iter() {
+            std::fs::create_dir_all(dir.path().join(name)).unwrap();
+        }
+        let mut grunt = Grunt::new(dir.path()).unwrap();
+        let curse = Addon::from_curse_file(
+            "A".to_string(),
+            1,
+            Some("a".to_string()),
+            2,
+            vec!["A".to_string(), "A_Options".to_string()],
+        );
+        let custom = Addon::from_custom("B".to_string(), vec!["B".to_string()], "1".to_string());
+        grunt.import_addons(vec![curse, custom]);
+        grunt.set_pinned(1, true);
+        grunt.record_command("update");
+        let mut lockfile = Lockfile::from_grunt(&grunt);
+        lockfile.curse_cache.timestamp = Some("2021-01-01T00:00:00Z".to_string());
+        lockfile.curse_cache.projects.insert(
+            "1".to_string(),
+            CachedProject {
+                date_modified: "2021-01-01T00:00:00Z".to_string(),
+                slug: "a".to_string(),
+                latest_file_id: 3,
+                download_url: "https://example.com/a.zip".to_string(),
+                file_length: Some(100),
+                deprecation: None,
+            },
+        );
+        lockfile.unresolved.insert(
+            "C".to_string(),
+            UnresolvedDir {
+                fingerprint: 1,
+                attempted: Some(chrono::Utc::now()),
+                reason: Some("No Curse fingerprint match".to_string()),
+            },
+        );
+        lockfile
+            .latest
+            .insert(grunt.addons()[1].key(), "2".to_string());
+        lockfile.game_version = Some("9.0.2.37474".to_string());
+        lockfile.flavor = Some(Flavor::ClassicEra);
+
+        let storage = open(StorageBackend::Sqlite, dir.path()).unwrap();
+        assert!(storage.load().unwrap().is_none());
+        storage.save(&lockfile).unwrap();
+        assert_eq!(
+            storage.load().unwrap().unwrap().to_json(),
+            lockfile.to_json()
+        );
+
+        // Saving again replaces everything
+        lockfile.addons.pop();
+        lockfile.unresolved.clear();
+        storage.save(&lockfile).unwrap();
+        assert_eq!(
+            storage.load().unwrap().unwrap().to_json(),
+            lockfile.to_json()
+        );
+    }
+}
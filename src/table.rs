@@ -0,0 +1,198 @@
+use crate::addon::Deprecation;
+use chrono::{DateTime, Local};
+use comfy_table::presets::NOTHING;
+use comfy_table::{Attribute, Cell, ContentArrangement, Row, Table};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// A column that can be shown when listing addons
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Column {
+    Name,
+    Source,
+    Version,
+    Latest,
+    Size,
+    Updated,
+    Notes,
+}
+
+impl Column {
+    /// Columns shown when none are picked
+    pub const DEFAULT: [Column; 2] = [Column::Name, Column::Source];
+    /// Columns shown by `list --long`
+    pub const LONG: [Column; 7] = [
+        Column::Name,
+        Column::Source,
+        Column::Version,
+        Column::Latest,
+        Column::Size,
+        Column::Updated,
+        Column::Notes,
+    ];
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            Column::Name => "Name",
+            Column::Source => "Source",
+            Column::Version => "Version",
+            Column::Latest => "Latest",
+            Column::Size => "Size",
+            Column::Updated => "Updated",
+            Column::Notes => "Notes",
+        }
+    }
+
+    /// Parses a comma separated list of columns like `name,version,size`
+    pub fn parse_list(list: &str) -> Result<Vec<Column>, String> {
+        list.split(',').map(|name| name.trim().parse()).collect()
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "name" => Ok(Column::Name),
+            "source" => Ok(Column::Source),
+            "version" => Ok(Column::Version),
+            "latest" => Ok(Column::Latest),
+            "size" => Ok(Column::Size),
+            "updated" => Ok(Column::Updated),
+            "notes" => Ok(Column::Notes),
+            _ => Err(format!(
+                "Unknown column {}. Expected name, source, version, latest, size, updated or notes",
+                s
+            )),
+        }
+    }
+}
+
+/// Everything that can be shown about an addon in a table
+pub struct AddonRow {
+    pub name: String,
+    /// `source:id` identifier
+    pub source: String,
+    pub version: String,
+    /// Newest version available, if it has been checked
+    pub latest: Option<String>,
+    /// Total size of the addon's dirs in bytes
+    pub size: u64,
+    /// When the addon's files last changed
+    pub updated: Option<DateTime<Local>>,
+    pub notes: Option<String>,
+    /// Set if the addon won't get updates anymore
+    pub deprecation: Option<Deprecation>,
+    /// Kept at its installed version by `grunt pin`
+    pub pinned: bool,
+}
+
+impl AddonRow {
+    fn cell(&self, column: Column) -> String {
+        match column {
+            Column::Name if self.pinned => format!("{} (pinned)", self.name),
+            Column::Name => self.name.clone(),
+            Column::Source => self.source.clone(),
+            Column::Version => self.version.clone(),
+            Column::Latest => self.latest.clone().unwrap_or_else(|| "-".to_string()),
+            Column::Size => format_size(self.size),
+            Column::Updated => match self.updated {
+                Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+                None => "-".to_string(),
+            },
+            Column::Notes => self.notes.clone().unwrap_or_default(),
+        }
+    }
+
+    fn compare(&self, other: &AddonRow, column: Column) -> Ordering {
+        match column {
+            Column::Name => self.name.to_lowercase().cmp(&other.name.to_lowercase()),
+            Column::Source => self.source.cmp(&other.source),
+            Column::Version => self.version.cmp(&other.version),
+            Column::Latest => self.latest.cmp(&other.latest),
+            Column::Size => self.size.cmp(&other.size),
+            Column::Updated => self.updated.cmp(&other.updated),
+            Column::Notes => self.notes.cmp(&other.notes),
+        }
+    }
+}
+
+/// Creates an empty table that fits the terminal width
+/// The header row is left out if `headers` is empty
+pub fn new_table(headers: &[&str]) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(NOTHING)
+        .set_content_arrangement(ContentArrangement::Dynamic);
+    if !headers.is_empty() {
+        table.set_header(
+            headers
+                .iter()
+                .map(|header| Cell::new(header).add_attribute(Attribute::Bold)),
+        );
+    }
+    table
+}
+
+/// Renders addons as a table sorted by `sort`. Cells too wide for the terminal are truncated
+pub fn render_addons(rows: &mut [AddonRow], columns: &[Column], sort: Column) -> String {
+    rows.sort_by(|a, b| a.compare(b, sort).then_with(|| a.compare(b, Column::Name)));
+    let headers: Vec<&str> = columns.iter().map(|column| column.header()).collect();
+    let mut table = new_table(&headers);
+    for row in rows.iter() {
+        let mut cells = Row::from(columns.iter().map(|&column| row.cell(column)));
+        cells.max_height(1);
+        table.add_row(cells);
+    }
+    table.to_string()
+}
+
+/// Formats a number of bytes like `1.5 MiB`
+pub fn format_size(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, units[0])
+    } else {
+        format!("{:.1} {}", size, units[unit])
+    }
+}
+
+/// How long ago something happened, like `3 days ago`, in the largest whole unit
+pub fn format_age(age: chrono::Duration) -> String {
+    let units = [
+        ("day", age.num_days()),
+        ("hour", age.num_hours()),
+        ("minute", age.num_minutes()),
+    ];
+    match units.iter().find(|(_, count)| *count > 0) {
+        Some((unit, 1)) => format!("1 {} ago", unit),
+        Some((unit, count)) => format!("{} {}s ago", count, unit),
+        None => "just now".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_columns() {
+        assert_eq!(
+            Column::parse_list("name, Size,updated"),
+            Ok(vec![Column::Name, Column::Size, Column::Updated])
+        );
+        assert!(Column::parse_list("name,colour").is_err());
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536 * 1024), "1.5 MiB");
+        assert_eq!(format_age(chrono::Duration::hours(80)), "3 days ago");
+        assert_eq!(format_age(chrono::Duration::minutes(61)), "1 hour ago");
+        assert_eq!(format_age(chrono::Duration::seconds(5)), "just now");
+    }
+}
@@ -0,0 +1,75 @@
+use std::io::Read;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Limits the combined transfer rate of every reader sharing it
+/// A limit of 0 doesn't throttle
+pub struct Throttle {
+    bytes_per_sec: u64,
+    /// When throttling started and the bytes transferred since
+    state: Mutex<(Instant, u64)>,
+}
+
+impl Throttle {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Throttle {
+            bytes_per_sec,
+            state: Mutex::new((Instant::now(), 0)),
+        }
+    }
+
+    /// Records transferred bytes, sleeping until the average rate is back under the limit
+    fn consume(&self, bytes: usize) {
+        if self.bytes_per_sec == 0 {
+            return;
+        }
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            state.1 += bytes as u64;
+            let expected = Duration::from_secs_f64(state.1 as f64 / self.bytes_per_sec as f64);
+            expected.checked_sub(state.0.elapsed())
+        };
+        if let Some(wait) = wait {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+/// Wraps a reader so reads are limited by a `Throttle`
+pub struct ThrottledReader<'a, R> {
+    inner: R,
+    throttle: &'a Throttle,
+}
+
+impl<'a, R: Read> ThrottledReader<'a, R> {
+    pub fn new(inner: R, throttle: &'a Throttle) -> Self {
+        ThrottledReader { inner, throttle }
+    }
+}
+
+impl<'a, R: Read> Read for ThrottledReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.throttle.consume(read);
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle() {
+        let data = vec![0; 10_000];
+        let read = |throttle: &Throttle| {
+            let start = Instant::now();
+            let mut reader = ThrottledReader::new(data.as_slice(), throttle);
+            std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+            start.elapsed()
+        };
+        assert!(read(&Throttle::new(100_000)) >= Duration::from_millis(100));
+        // No limit
+        assert!(read(&Throttle::new(0)) < Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,99 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The `## Field: value` lines of an addon's `.toc`
+pub struct Toc {
+    /// Values by lowercase field name
+    fields: HashMap<String, String>,
+}
+
+impl Toc {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let data = std::fs::read(path)?;
+        Ok(Toc::parse(&decode(&data)))
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let fields = text
+            .lines()
+            .filter_map(|line| {
+                let (name, value) = line.strip_prefix("##")?.split_once(':')?;
+                Some((name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            })
+            .filter(|(_, value)| !value.is_empty())
+            .collect();
+        Toc { fields }
+    }
+
+    /// Gets a field by name, ignoring case
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Lowercase dirs from `Dependencies`, `RequiredDeps`, `OptionalDeps` and other `Dep*` fields
+    pub fn dependencies(&self) -> Vec<String> {
+        self.fields
+            .iter()
+            .filter(|(name, _)| {
+                name.starts_with("dep") || *name == "requireddeps" || *name == "optionaldeps"
+            })
+            .flat_map(|(_, value)| value.split(','))
+            .map(|dep| dep.trim().to_ascii_lowercase())
+            .filter(|dep| !dep.is_empty())
+            .collect()
+    }
+}
+
+/// Decodes text files written by addon authors
+/// Uses the BOM if there is one, then UTF-8, then falls back to latin-1 which never fails
+pub fn decode(data: &[u8]) -> String {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(data) {
+        let (text, _) = encoding.decode_without_bom_handling(&data[bom_len..]);
+        return text.into_owned();
+    }
+    // UTF-16 without a BOM has a zero byte in every ascii char
+    let encoding = match data {
+        [first, 0, ..] if *first != 0 => UTF_16LE,
+        [0, second, ..] if *second != 0 => UTF_16BE,
+        _ if std::str::from_utf8(data).is_ok() => UTF_8,
+        _ => WINDOWS_1252,
+    };
+    let (text, _) = encoding.decode_without_bom_handling(data);
+    text.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let expected = "## Title: Caf\u{e9}\n";
+        assert_eq!(decode(b"\xEF\xBB\xBF## Title: Caf\xC3\xA9\n"), expected);
+        assert_eq!(decode(b"## Title: Caf\xE9\n"), expected);
+        let utf16: Vec<u8> = expected.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode(&[&[0xFF, 0xFE], &utf16[..]].concat()), expected);
+        assert_eq!(decode(&utf16), expected);
+    }
+
+    #[test]
+    fn test_parse() {
+        let toc = Toc::parse("## Interface: 90001\r\n##Version : 1.2 \n## Notes:\nCore.lua\n");
+        assert_eq!(toc.field("version"), Some("1.2"));
+        assert_eq!(toc.field("Interface"), Some("90001"));
+        assert_eq!(toc.field("Notes"), None);
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let toc = Toc::parse(
+            "## Dependencies: Ace3, LibStub\n## OptionalDeps: LibSharedMedia-3.0\n## X-Deps: No\n",
+        );
+        let mut deps = toc.dependencies();
+        deps.sort();
+        assert_eq!(deps, vec!["ace3", "libsharedmedia-3.0", "libstub"]);
+    }
+}
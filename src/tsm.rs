@@ -1,23 +1,36 @@
-use data_encoding::HEXLOWER;
-use reqwest::blocking::{Client, ClientBuilder};
-use ring::digest::{Algorithm, Context, SHA256, SHA512};
+use crate::error::{check_status, GruntError};
+use crate::flavor::Flavor;
+use crate::net::{self, NetOptions};
+use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 const PASSWORD_SALT: &str = "f2f618c502a975825e5da6f8650ba8fb";
 const TOKEN_SALT: &str = "6e8fd9d5da4f1cd0e64ad4d082be477c";
 pub const APP_VERSION: u32 = 403;
 
+/// How grunt logs in to TSM
+#[derive(Debug, Clone)]
+pub enum TsmLogin {
+    Password {
+        email: String,
+        pass: String,
+    },
+    /// Reuses the session the TSM desktop app saved in its settings file
+    AppSession(PathBuf),
+}
+
 pub struct TSMApi {
     clients: HashMap<String, Client>,
     session: String,
     subdomains: HashMap<String, String>,
+    options: NetOptions,
 }
 
 impl TSMApi {
-    pub fn new() -> TSMApi {
+    pub fn new(options: &NetOptions) -> TSMApi {
         let mut subdomains: HashMap<String, String> = HashMap::new();
         subdomains.insert("login".into(), "app-server".into());
         subdomains.insert("log".into(), "app-server".into());
@@ -25,56 +38,92 @@ impl TSMApi {
             clients: HashMap::new(),
             session: "".into(),
             subdomains,
+            options: options.clone(),
+        }
+    }
+
+    /// Logs in with a password or the desktop app's session
+    pub fn sign_in(&mut self, login: &TsmLogin) -> Result<(), GruntError> {
+        match login {
+            TsmLogin::Password { email, pass } => self.login(email, pass),
+            TsmLogin::AppSession(path) => {
+                self.use_session(read_app_session(path)?);
+                Ok(())
+            }
         }
     }
 
     /// Login to the TSM Api
-    pub fn login(&mut self, email: &str, password: &str) {
+    pub fn login(&mut self, email: &str, password: &str) -> Result<(), GruntError> {
         self.create_clients();
-        let email_hash = hash_string(&email.to_ascii_lowercase(), &SHA256);
-        let initial_pass_hash = hash_string(password, &SHA512);
-        let pass_hash = hash_string(&format!("{}{}", initial_pass_hash, PASSWORD_SALT), &SHA512);
-        let user_info = self.make_request::<LoginRespData>(vec!["login", &email_hash, &pass_hash]);
-        self.session = user_info.session;
-        self.subdomains.extend(user_info.endpoint_subdomains);
+        let email_hash = hash_string(&email.to_ascii_lowercase(), Hash::Sha256)?;
+        let initial_pass_hash = hash_string(password, Hash::Sha512)?;
+        let pass_hash = hash_string(
+            &format!("{}{}", initial_pass_hash, PASSWORD_SALT),
+            Hash::Sha512,
+        )?;
+        let session = self.make_request::<Session>(vec!["login", &email_hash, &pass_hash])?;
+        self.use_session(session);
+        Ok(())
+    }
+
+    fn use_session(&mut self, session: Session) {
+        self.session = session.session;
+        self.subdomains.extend(session.endpoint_subdomains);
         self.create_clients();
     }
 
-    pub fn get_status(&self) -> StatusRespData {
+    pub fn get_status(&self) -> Result<StatusRespData, GruntError> {
         self.make_request::<StatusRespData>(vec!["status"])
     }
 
-    pub fn auctiondb(&self, data_type: &str, id: i64) -> String {
+    pub fn auctiondb(&self, data_type: &str, id: i64) -> Result<String, GruntError> {
         let resp =
-            self.make_request::<AuctionDBRespData>(vec!["auctiondb", data_type, &id.to_string()]);
-        resp.data
+            self.make_request::<AuctionDBRespData>(vec!["auctiondb", data_type, &id.to_string()])?;
+        Ok(resp.data)
     }
 
-    /// Downloads a TSM addon the the specified path
-    pub fn addon<P>(&self, addon_name: &str, path: P)
+    /// Downloads a TSM addon the the specified path, retrying if it fails
+    pub fn addon<P>(&self, addon_name: &str, path: P) -> Result<(), GruntError>
     where
         P: AsRef<Path>,
     {
-        let mut resp = self.make_request_raw(vec!["addon", addon_name]);
-        let file = std::fs::File::create(path).unwrap();
-        let mut writer = std::io::BufWriter::new(file);
-        resp.copy_to(&mut writer).unwrap();
+        net::with_retries(&self.options, || {
+            let mut resp = self.make_request_raw(vec!["addon", addon_name])?;
+            let file = std::fs::File::create(path.as_ref())?;
+            let mut writer = std::io::BufWriter::new(file);
+            let url = resp.url().to_string();
+            resp.copy_to(&mut writer)
+                .map_err(|source| GruntError::Request { url, source })?;
+            Ok(())
+        })
     }
 
     fn create_clients(&mut self) {
         for (_, subdomain) in self.subdomains.iter() {
+            let options = &self.options;
             self.clients
                 .entry(subdomain.into())
-                .or_insert_with(|| ClientBuilder::new().build().unwrap());
+                .or_insert_with(|| options.client());
         }
     }
 
-    fn make_request<T: serde::de::DeserializeOwned>(&self, endpoint: Vec<&str>) -> T {
-        let resp = self.make_request_raw(endpoint);
-        resp.json::<T>().unwrap()
+    fn make_request<T: serde::de::DeserializeOwned>(
+        &self,
+        endpoint: Vec<&str>,
+    ) -> Result<T, GruntError> {
+        let resp = self.make_request_raw(endpoint)?;
+        let url = resp.url().to_string();
+        resp.json::<T>().map_err(|source| GruntError::Decode {
+            url,
+            source: source.into(),
+        })
     }
 
-    fn make_request_raw(&self, endpoint: Vec<&str>) -> reqwest::blocking::Response {
+    fn make_request_raw(
+        &self,
+        endpoint: Vec<&str>,
+    ) -> Result<reqwest::blocking::Response, GruntError> {
         // Setup params
         let session = &self.session;
         let version = APP_VERSION.to_string();
@@ -83,7 +132,10 @@ impl TSMApi {
             .unwrap()
             .as_secs()
             .to_string();
-        let token = hash_string(&format!("{}:{}:{}", APP_VERSION, time, TOKEN_SALT), &SHA256);
+        let token = hash_string(
+            &format!("{}:{}:{}", APP_VERSION, time, TOKEN_SALT),
+            Hash::Sha256,
+        )?;
         let channel = "release";
         let tsm_version = "";
         let mut params: HashMap<&str, &str> = HashMap::new();
@@ -112,16 +164,36 @@ impl TSMApi {
             subdomain,
             endpoint.join("/")
         );
-        client.get(&url).query(&params).send().unwrap()
+        let resp = net::send(client, client.get(&url).query(&params))
+            .map_err(|source| GruntError::Request { url, source })?;
+        check_status(resp)
     }
 }
 
-fn hash_string(data: &str, algorithm: &'static Algorithm) -> String {
-    let mut context = Context::new(algorithm);
+#[derive(Clone, Copy)]
+enum Hash {
+    Sha256,
+    Sha512,
+}
+
+/// Hex digest of `data`
+#[cfg(feature = "tsm")]
+fn hash_string(data: &str, hash: Hash) -> Result<String, GruntError> {
+    use ring::digest::{Context, SHA256, SHA512};
+    let mut context = Context::new(match hash {
+        Hash::Sha256 => &SHA256,
+        Hash::Sha512 => &SHA512,
+    });
     let bytes = data.as_bytes();
-    context.update(&bytes);
+    context.update(bytes);
     let digest = context.finish();
-    HEXLOWER.encode(digest.as_ref())
+    Ok(data_encoding::HEXLOWER.encode(digest.as_ref()))
+}
+
+/// Logging in and signing requests both need hashes from ring, which the `tsm` feature adds
+#[cfg(not(feature = "tsm"))]
+fn hash_string(_data: &str, _hash: Hash) -> Result<String, GruntError> {
+    Err(GruntError::SourceNotBuilt(crate::addon::AddonType::TSM))
 }
 
 #[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -131,13 +203,56 @@ struct AuctionDBRespData {
     pub data: String,
 }
 
+/// A logged in session and the subdomains of the endpoints it uses
 #[derive(Default, Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct LoginRespData {
+struct Session {
     pub session: String,
     pub endpoint_subdomains: HashMap<String, String>,
 }
 
+/// Reads the session from the desktop app's settings file, an ini file with a `session`
+/// key and an `endpointSubdomains` group
+fn read_app_session(path: &Path) -> Result<Session, GruntError> {
+    let error = |source: Box<dyn std::error::Error + Send + Sync>| GruntError::Import {
+        path: path.display().to_string(),
+        source,
+    };
+    let text = std::fs::read_to_string(path).map_err(|err| error(err.into()))?;
+    let mut session = Session::default();
+    let mut group = String::new();
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            group = if name == "General" {
+                String::new()
+            } else {
+                format!("{}\\", name)
+            };
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (format!("{}{}", group, key.trim()), value.trim()),
+            None => continue,
+        };
+        let value = value.trim_matches('"').to_string();
+        match key.replace('/', "\\").strip_prefix("endpointSubdomains\\") {
+            Some(endpoint) => {
+                session
+                    .endpoint_subdomains
+                    .insert(endpoint.to_string(), value);
+            }
+            None if key == "session" => session.session = value,
+            None => (),
+        }
+    }
+    if session.session.is_empty() || session.endpoint_subdomains.is_empty() {
+        return Err(error(
+            "No session saved. Log in with the TSM app first".into(),
+        ));
+    }
+    Ok(session)
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StatusRespData {
@@ -154,10 +269,21 @@ pub struct StatusRespData {
     pub regions_classic: Vec<::serde_json::Value>,
     pub addons: Vec<Addon>,
     #[serde(rename = "addons-Classic")]
-    pub addons_classic: Vec<AddonsClassic>,
+    pub addons_classic: Vec<Addon>,
     pub channels: Channels,
 }
 
+impl StatusRespData {
+    /// Addons TSM distributes for a flavor, classic flavors get their own builds
+    pub fn addons_for(&self, flavor: Flavor) -> &[Addon] {
+        if flavor.is_classic() {
+            &self.addons_classic
+        } else {
+            &self.addons
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppInfo {
@@ -199,14 +325,6 @@ pub struct Addon {
     pub version_str: String,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct AddonsClassic {
-    pub name: String,
-    #[serde(rename = "version_str")]
-    pub version_str: String,
-}
-
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Channels {
@@ -222,7 +340,53 @@ mod tests {
         dotenv::dotenv().ok();
         let email = env::var("TSM_TEST_EMAIL").unwrap();
         let password = env::var("TSM_TEST_PASSWORD").unwrap();
-        let mut api = TSMApi::new();
-        api.login(&email, &password);
+        let mut api = TSMApi::new(&NetOptions::default());
+        api.login(&email, &password).unwrap();
+    }
+
+    #[test]
+    fn test_read_app_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("TSMApplication.ini");
+        std::fs::write(
+            &path,
+            "[General]\r\nsession=\"abc123\"\r\nendpointSubdomains\\status=app-server-2\r\n\r\n\
+             [endpointSubdomains]\r\nauctiondb=data-server\r\n",
+        )
+        .unwrap();
+        let session = read_app_session(&path).unwrap();
+        assert_eq!(session.session, "abc123");
+        assert_eq!(session.endpoint_subdomains["status"], "app-server-2");
+        assert_eq!(session.endpoint_subdomains["auctiondb"], "data-server");
+
+        std::fs::write(&path, "[General]\r\nsession=abc123\r\n").unwrap();
+        assert!(read_app_session(&path).is_err());
+    }
+
+    #[test]
+    fn test_addons_for() {
+        let addon = |name: &str, version: &str| Addon {
+            name: name.to_string(),
+            version_str: version.to_string(),
+        };
+        let status = StatusRespData {
+            addons: vec![addon("TradeSkillMaster_AppHelper", "v4.1")],
+            addons_classic: vec![addon("TradeSkillMaster_AppHelper", "v4.0-classic")],
+            ..Default::default()
+        };
+        assert_eq!(status.addons_for(Flavor::Retail)[0].version_str, "v4.1");
+        assert_eq!(
+            status.addons_for(Flavor::ClassicWrath)[0].version_str,
+            "v4.0-classic"
+        );
+        let helper = crate::addon::Addon::from_tsm(
+            "TradeSkillMaster_AppHelper".to_string(),
+            "v4.1".to_string(),
+            Vec::new(),
+        );
+        assert_eq!(
+            helper.key(),
+            crate::addon::Addon::init_tsm_helper(String::new()).key()
+        );
     }
 }
@@ -1,25 +1,46 @@
+use crate::addon::AddonType;
+use crate::error::{check_status, GruntError};
+use crate::flavor::Flavor;
+use crate::net::{self, NetOptions};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::BufReader;
 
-pub fn get_addon_infos() -> Vec<AddonInfo> {
-    make_request("client-api.php?addons=all")
+/// Gets every addon hosted on Tukui for a game flavor
+pub fn get_addon_infos(flavor: Flavor, options: &NetOptions) -> Result<Vec<AddonInfo>, GruntError> {
+    let endpoint = match flavor {
+        Flavor::Retail => "client-api.php?addons=all",
+        Flavor::ClassicEra => "client-api.php?classic-addons=all",
+        Flavor::ClassicTbc => "client-api.php?classic-tbc-addons=all",
+        Flavor::ClassicWrath => "client-api.php?classic-wotlk-addons=all",
+    };
+    make_request(endpoint, options)
 }
 
-pub fn get_elvui_info() -> ElvUIInfo {
-    make_request("client-api.php?ui=elvui")
+pub fn get_elvui_info(options: &NetOptions) -> Result<ElvUIInfo, GruntError> {
+    make_request("client-api.php?ui=elvui", options)
 }
 
 /// Makes a request to a Tukui API endpoint, decoding the response as json
-fn make_request<Q>(endpoint: &str) -> Q
+fn make_request<Q>(endpoint: &str, options: &NetOptions) -> Result<Q, GruntError>
 where
     Q: DeserializeOwned,
 {
+    if !AddonType::Tukui.is_built() {
+        return Err(GruntError::SourceNotBuilt(AddonType::Tukui));
+    }
     let url = format!("https://www.tukui.org/{}", endpoint);
 
-    let resp = reqwest::blocking::get(&url).expect("Error making tukui api request");
-    let resp = resp
-        .error_for_status()
-        .expect("Error sending tukui api request");
-    resp.json().expect("Error decoding curse api response")
+    let client = options.client();
+    let resp = net::send(&client, client.get(&url)).map_err(|source| GruntError::Request {
+        url: url.clone(),
+        source,
+    })?;
+    let resp = check_status(resp)?;
+    // Parse while downloading instead of buffering the whole (large) response first
+    serde_json::from_reader(BufReader::new(resp)).map_err(|source| GruntError::Decode {
+        url,
+        source: source.into(),
+    })
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
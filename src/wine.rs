@@ -0,0 +1,133 @@
+use std::path::{Path, PathBuf};
+
+/// Where WoW is installed inside a prefix by the Battle.net installer
+const WOW_DIRS: [&str; 2] = [
+    "drive_c/Program Files (x86)/World of Warcraft",
+    "drive_c/Program Files/World of Warcraft",
+];
+
+/// Wine prefixes in the places Wine, Lutris and Steam's Proton put them
+/// `$WINEPREFIX` and `~/.wine`, Lutris' `~/Games/<game>` and Proton's `compatdata/<id>/pfx`
+pub fn find_prefixes() -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = std::env::var_os("WINEPREFIX")
+        .map(PathBuf::from)
+        .into_iter()
+        .collect();
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        candidates.push(home.join(".wine"));
+        candidates.extend(subdirs(&home.join("Games")));
+        for steam in [".steam/steam", ".local/share/Steam"].iter() {
+            let compat_data = home.join(steam).join("steamapps/compatdata");
+            candidates.extend(subdirs(&compat_data).map(|dir| dir.join("pfx")));
+        }
+    }
+    let mut prefixes: Vec<PathBuf> = Vec::new();
+    for prefix in candidates {
+        // Steam links `~/.steam/steam` to its real dir
+        let prefix = prefix.canonicalize().unwrap_or(prefix);
+        if prefix.join("drive_c").is_dir() && !prefixes.contains(&prefix) {
+            prefixes.push(prefix);
+        }
+    }
+    prefixes
+}
+
+/// `AddOns` dirs of every flavor of WoW installed in the prefixes
+pub fn find_addons_dirs(prefixes: &[PathBuf]) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for prefix in prefixes {
+        for wow_dir in WOW_DIRS.iter() {
+            let wow_dir = match resolve_ignore_case(prefix, Path::new(wow_dir)) {
+                Some(wow_dir) => wow_dir,
+                None => continue,
+            };
+            let mut flavors: Vec<PathBuf> = subdirs(&wow_dir)
+                .filter_map(|flavor| resolve_ignore_case(&flavor, Path::new("Interface/AddOns")))
+                .collect();
+            flavors.sort();
+            found.extend(flavors);
+        }
+    }
+    found
+}
+
+/// Translates a Windows path like `C:\Games\World of Warcraft` into the first prefix it
+/// exists in, matching each part case-insensitively like Windows does
+pub fn translate_path(path: &str, prefixes: &[PathBuf]) -> Option<PathBuf> {
+    let mut chars = path.chars();
+    let drive = chars.next().filter(char::is_ascii_alphabetic)?;
+    let rest = chars.as_str().strip_prefix(':')?.replace('\\', "/");
+    let rest = Path::new(rest.trim_start_matches('/'));
+    prefixes.iter().find_map(|prefix| {
+        // Drives are links in `dosdevices`, `C:` is `drive_c` if they're missing
+        let drive = format!("{}:", drive.to_ascii_lowercase());
+        let root = match prefix.join("dosdevices").join(&drive) {
+            root if root.exists() => root,
+            _ if drive == "c:" => prefix.join("drive_c"),
+            _ => return None,
+        };
+        resolve_ignore_case(&root, rest)
+    })
+}
+
+/// `name` inside `parent`, matching it case-insensitively if it isn't there as is
+/// Wine prefixes and NTFS mounts on Linux can have a dir in a different case than
+/// grunt expects while the game still loads it
+pub(crate) fn find_ignore_case(parent: &Path, name: &str) -> Option<PathBuf> {
+    let path = parent.join(name);
+    if path.exists() {
+        return Some(path);
+    }
+    std::fs::read_dir(parent)
+        .ok()?
+        .flatten()
+        .find(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .eq_ignore_ascii_case(name)
+        })
+        .map(|entry| entry.path())
+}
+
+/// Follows a relative path from `root` one part at a time with `find_ignore_case`
+fn resolve_ignore_case(root: &Path, path: &Path) -> Option<PathBuf> {
+    path.iter().try_fold(root.to_path_buf(), |dir, part| {
+        find_ignore_case(&dir, &part.to_string_lossy())
+    })
+}
+
+fn subdirs(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let prefix = dir.path().join("pfx");
+        let addons_dir =
+            prefix.join("drive_c/Program Files (x86)/World of Warcraft/_retail_/Interface/AddOns");
+        std::fs::create_dir_all(&addons_dir).unwrap();
+        let prefixes = vec![prefix];
+
+        assert_eq!(find_addons_dirs(&prefixes), vec![addons_dir.clone()]);
+        assert_eq!(
+            translate_path(
+                r"C:\program files (x86)\World of Warcraft\_retail_\Interface\AddOns",
+                &prefixes
+            ),
+            Some(addons_dir)
+        );
+        assert_eq!(translate_path(r"D:\Games", &prefixes), None);
+        assert_eq!(translate_path("/home/wow", &prefixes), None);
+    }
+}